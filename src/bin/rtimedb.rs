@@ -1,16 +1,23 @@
-use std::{fs::File, io::BufReader};
-use std::io;
+use std::io::{self, Write};
+use std::path::Path;
 
-use rtimedb::executors::physical_plan::PhysicalOperator;
 use tracing::info;
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
 
-use csv::ReaderBuilder;
-use clap::{Arg, Command};
-use rtimedb::tsf::tsf_reader::TSFReader;
+use clap::{Arg, ArgAction, Command};
+use rtimedb::ingest::csv_ingest::{ingest_csv, ingest_csv_reader, IngestOptions};
+use rtimedb::tsf::error::TsfError;
+use rtimedb::tsf::async_tsf_reader::{AsyncTSFReader, DataRow as AsyncDataRow, SegmentRowStream};
+use rtimedb::tsf::segments::segment_column_data::SegmentColumnData;
+use rtimedb::tsf::segments::types::{ColumnSchema, EnumColumnData, EnumDataComp, EnumDataEnc, EnumDataType, Schema, SchemaMismatch, CSV_DELIMITER};
 use rtimedb::tsf::tsf_writer::TSFWriter;
-use rtimedb::tsf::segments::types::{EnumDataType,EnumDataEnc,EnumDataComp};
-use rtimedb::executors::{executor::Executor, physical_plan::PhysicalPlan};
+use rtimedb::tsf::tsf_reader::{DataRow, TSFReader};
+use tokio_stream::StreamExt;
+use rtimedb::executors::{executor::{Executor, RowStream}, physical_plan::{check_row_window, PhysicalOperator, PhysicalPlan}};
+use rtimedb::parsers::sql::parse_query;
+
+// Input file name that reads the CSV from stdin
+const STDIN_INPUT: &str = "-";
 
 #[tokio::main]
 async fn main() -> Result<(), String> {
@@ -39,8 +46,42 @@ async fn main() -> Result<(), String> {
                     .short('i')
                     .long("input-file")
                     .value_name("INPUT FILE")
-                    .help("CSV file to ingest data")
-                    .required(true)),
+                    .help("CSV file to ingest data, - reads from stdin")
+                    .required_unless_present("stdin"))
+                .arg(Arg::new("stdin")
+                    .long("stdin")
+                    .help("Read the CSV from stdin, same as -i -")
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with("input_file"))
+                .arg(Arg::new("null_timestamp")
+                    .long("null-timestamp")
+                    .value_name("VALUE")
+                    .help("Timestamp value treated as missing when computing the segment date range")
+                    .value_parser(clap::value_parser!(i64))
+                    .allow_negative_numbers(true))
+                .arg(Arg::new("timestamp_col_index")
+                    .long("timestamp-col-index")
+                    .value_name("N")
+                    .help("CSV column holding the timestamp")
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("0")
+                    .conflicts_with("no_timestamp"))
+                .arg(Arg::new("no_timestamp")
+                    .long("no-timestamp")
+                    .help("Ingest every CSV column as a value column without a timestamp column")
+                    .action(ArgAction::SetTrue))
+                .arg(Arg::new("timestamp_type")
+                    .long("timestamp-type")
+                    .value_name("TYPE")
+                    .help("Store the timestamp as datetime32 (epoch seconds) or datetime64 (epoch milliseconds)")
+                    .value_parser(["datetime32", "datetime64"])
+                    .conflicts_with("no_timestamp"))
+                .arg(Arg::new("max_errors")
+                    .long("max-errors")
+                    .value_name("N")
+                    .help("Log and skip up to N rows that fail to parse, 0 stops at the first bad row")
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("0")),
         )
         .subcommand(
             Command::new("read")
@@ -56,15 +97,108 @@ async fn main() -> Result<(), String> {
                 .arg(Arg::new("FILE")
                     .help("The file path of the database to read from")
                     .required(true)
+                    .index(1))
+                .arg(Arg::new("limit")
+                    .long("limit")
+                    .value_name("N")
+                    .help("Emit at most N rows")
+                    .value_parser(clap::value_parser!(usize)))
+                .arg(Arg::new("offset")
+                    .long("offset")
+                    .value_name("M")
+                    .help("Skip the first M rows")
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("0")),
+        )
+        .subcommand(
+            Command::new("cat")
+                .about("Prints the first rows of a time series database as CSV")
+                .arg(Arg::new("FILE")
+                    .help("The file path of the database to read from")
+                    .required(true)
+                    .index(1))
+                .arg(Arg::new("limit")
+                    .short('n')
+                    .long("limit")
+                    .value_name("N")
+                    .help("Number of rows to print")
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("20")),
+        )
+        .subcommand(
+            Command::new("convert")
+                .about("Rewrites a time series database with a different encoding and compression")
+                .arg(Arg::new("IN")
+                    .help("The file path of the database to read from")
+                    .required(true)
+                    .index(1))
+                .arg(Arg::new("OUT")
+                    .help("The file path of the database to create")
+                    .required(true)
+                    .index(2))
+                .arg(Arg::new("encoding")
+                    .long("encoding")
+                    .value_name("ENC")
+                    .help("Column encoding")
+                    .value_parser(["none", "delta", "double-delta"])
+                    .default_value("none"))
+                .arg(Arg::new("compression")
+                    .long("compression")
+                    .value_name("COMP")
+                    .help("Column compression")
+                    .value_parser(["none", "zstd"])
+                    .default_value("none")),
+        )
+        .subcommand(
+            Command::new("info")
+                .about("Prints the row count and each column's type, encoding, compression and compression ratio")
+                .arg(Arg::new("FILE")
+                    .help("The file path of the database to inspect")
+                    .required(true)
                     .index(1)),
         )
+        .subcommand(
+            Command::new("diff")
+                .about("Compares two time series databases by schema and then row by row")
+                .arg(Arg::new("A")
+                    .help("The file path of the first database")
+                    .required(true)
+                    .index(1))
+                .arg(Arg::new("B")
+                    .help("The file path of the second database")
+                    .required(true)
+                    .index(2)),
+        )
         .subcommand(
             Command::new("astream")
                 .about("Async streams data from a time series database")
                 .arg(Arg::new("FILE")
                     .help("The file path of the database to read from")
                     .required(true)
-                    .index(1)),
+                    .index(1))
+                .arg(Arg::new("limit")
+                    .long("limit")
+                    .value_name("N")
+                    .help("Emit at most N rows")
+                    .value_parser(clap::value_parser!(usize)))
+                .arg(Arg::new("offset")
+                    .long("offset")
+                    .value_name("M")
+                    .help("Skip the first M rows")
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("0")),
+        )
+        .subcommand(
+            Command::new("query")
+                .about("Runs a SELECT ... FROM FILE [WHERE ...] [LIMIT n] query and prints the rows as CSV")
+                .arg(Arg::new("FILE")
+                    .help("The file path of the database to query, the query's FROM has to name it")
+                    .required(true)
+                    .index(1))
+                .arg(Arg::new("SQL")
+                    .help("The query to run")
+                    .required(true)
+                    .index(2)),
         );
 
     let matches: clap::ArgMatches = app.get_matches();
@@ -74,10 +208,27 @@ async fn main() -> Result<(), String> {
             let file_path: &String = sub_matches
                 .get_one::<String>("FILE")
                 .expect("FILE argument missing");
-            let input_file: &String = sub_matches
-                .get_one::<String>("input_file")
-                .expect("input_file missing");
-            return create_time_series_db(file_path, input_file);
+            let input_file: &str = match sub_matches.get_flag("stdin") {
+                true => STDIN_INPUT,
+                false => sub_matches
+                    .get_one::<String>("input_file")
+                    .expect("input_file missing"),
+            };
+            let options: IngestOptions = IngestOptions {
+                null_timestamp: sub_matches.get_one::<i64>("null_timestamp").copied(),
+                timestamp_col_index: *sub_matches
+                    .get_one::<usize>("timestamp_col_index")
+                    .expect("timestamp_col_index has a default"),
+                no_timestamp: sub_matches.get_flag("no_timestamp"),
+                timestamp_type: sub_matches.get_one::<String>("timestamp_type").map(|name: &String| match name.as_str() {
+                    "datetime64" => EnumDataType::DateTime64,
+                    _ => EnumDataType::DateTime32,
+                }),
+                max_errors: *sub_matches
+                    .get_one::<usize>("max_errors")
+                    .expect("max_errors has a default"),
+            };
+            return create_time_series_db(file_path, input_file, &options);
         },
         Some(("read", sub_matches)) => {
             let file_path: &String = sub_matches
@@ -89,57 +240,83 @@ async fn main() -> Result<(), String> {
             let file_path: &String = sub_matches
                 .get_one::<String>("FILE")
                 .expect("FILE argument missing");
-            return stream_time_series_db(file_path).await;
+            let offset: usize = *sub_matches
+                .get_one::<usize>("offset")
+                .expect("offset has a default");
+            let limit: Option<usize> = sub_matches.get_one::<usize>("limit").copied();
+            return stream_time_series_db(file_path, offset, limit, &mut io::stdout().lock()).await;
+        },
+        Some(("cat", sub_matches)) => {
+            let file_path: &String = sub_matches
+                .get_one::<String>("FILE")
+                .expect("FILE argument missing");
+            let limit: usize = *sub_matches
+                .get_one::<usize>("limit")
+                .expect("limit has a default");
+            return cat_time_series_db(file_path, limit, &mut io::stdout().lock()).await;
+        },
+        Some(("convert", sub_matches)) => {
+            let input_file: &String = sub_matches
+                .get_one::<String>("IN")
+                .expect("IN argument missing");
+            let output_file: &String = sub_matches
+                .get_one::<String>("OUT")
+                .expect("OUT argument missing");
+            let encoding: EnumDataEnc = sub_matches
+                .get_one::<String>("encoding")
+                .and_then(|name: &String| EnumDataEnc::from_name(name))
+                .expect("encoding has a default");
+            let compression: EnumDataComp = sub_matches
+                .get_one::<String>("compression")
+                .and_then(|name: &String| EnumDataComp::from_name(name))
+                .expect("compression has a default");
+            return convert_time_series_db(input_file, output_file, encoding, compression);
+        },
+        Some(("info", sub_matches)) => {
+            let file_path: &String = sub_matches
+                .get_one::<String>("FILE")
+                .expect("FILE argument missing");
+            return info_time_series_db(file_path, &mut io::stdout().lock());
+        },
+        Some(("diff", sub_matches)) => {
+            let left_file: &String = sub_matches
+                .get_one::<String>("A")
+                .expect("A argument missing");
+            let right_file: &String = sub_matches
+                .get_one::<String>("B")
+                .expect("B argument missing");
+            return diff_time_series_db(left_file, right_file, &mut io::stdout().lock()).await;
         },
         Some(("astream", sub_matches)) => {
             let file_path: &String = sub_matches
                 .get_one::<String>("FILE")
                 .expect("FILE argument missing");
-            return astream_time_series_db(file_path).await;
+            let offset: usize = *sub_matches
+                .get_one::<usize>("offset")
+                .expect("offset has a default");
+            let limit: Option<usize> = sub_matches.get_one::<usize>("limit").copied();
+            return astream_time_series_db(file_path, offset, limit, &mut io::stdout().lock()).await;
+        },
+        Some(("query", sub_matches)) => {
+            let file_path: &String = sub_matches
+                .get_one::<String>("FILE")
+                .expect("FILE argument missing");
+            let sql: &String = sub_matches
+                .get_one::<String>("SQL")
+                .expect("SQL argument missing");
+            return query_time_series_db(file_path, sql, &mut io::stdout().lock()).await;
         },
         _ => Ok(()),
     }
 }
 
-fn create_time_series_db(file_path: &str, input_file: &str) -> Result<(), String> {
-    // Open the input CSV file
-    let csv_file: File = File::open(input_file).map_err(|e| e.to_string())?;
-    let mut rdr: csv::Reader<BufReader<File>> = ReaderBuilder::new()
-        .has_headers(false)
-        .from_reader(BufReader::new(csv_file));
-
-    let mut tsf_writer: TSFWriter = TSFWriter::new(file_path).map_err(|e| e.to_string())?;
-    tsf_writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)?;
-    tsf_writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false)?;
-
-    let mut metric_time: Vec<i32> = Vec::new();
-    let mut temperatures: Vec<i8> = Vec::new();
-
-    for result in rdr.records() {
-        let record: csv::StringRecord = result.map_err(|e: csv::Error| e.to_string())?;
-    
-        let time: i32 = record.get(0)
-            .ok_or("Missing metric_time value".to_string())
-            .and_then(|t: &str| t.parse::<i32>().map_err(|e: std::num::ParseIntError| e.to_string()))?;
-    
-        let temp: i8 = record.get(1)
-            .ok_or("Missing temperature value".to_string())
-            .and_then(|t: &str| t.parse::<i8>().map_err(|e: std::num::ParseIntError| e.to_string()))?;
-    
-        metric_time.push(time);
-        temperatures.push(temp);
+fn create_time_series_db(file_path: &str, input_file: &str, options: &IngestOptions) -> Result<(), String> {
+    if input_file == STDIN_INPUT {
+        ingest_csv_reader(file_path, io::stdin().lock(), options)?;
+    } else {
+        ingest_csv(file_path, input_file, options)?;
     }
 
-    let min_date: i32 = *metric_time.iter().min().expect("Timestamp data should not be empty");
-    let max_date: i32 = *metric_time.iter().max().expect("Timestamp data should not be empty");
-
-    tsf_writer.add_column_data(metric_time, EnumDataEnc::None, EnumDataComp::None)?;
-    tsf_writer.add_column_data(temperatures, EnumDataEnc::None, EnumDataComp::None)?;
-
-    tsf_writer.update_segment_dates(min_date as i64, max_date as i64);
-
-    tsf_writer.try_save().map_err(|e: io::Error| e.to_string())?;
-
     println!("Created TimeSeriesFile");
     Ok(())
 }
@@ -155,46 +332,453 @@ fn read_time_series_db(file_path: &str) -> Result<(), String> {
     Ok(())
 }
 
-async fn stream_time_series_db(file_path: &str) -> Result<(), String> {
+async fn stream_time_series_db<W: Write>(file_path: &str, offset: usize, limit: Option<usize>, out: &mut W) -> Result<(), String> {
     info!("Reading from the database at: {}", file_path);
 
-    let plan: PhysicalPlan = PhysicalPlan{
-        root_operator: PhysicalOperator::Scan {
-            columns: vec!("metric_time".to_string(), "temperature".to_string()),
-            table_name: file_path.to_string(),
-            time_range: None
-        }
-    };
+    let plan: PhysicalPlan = PhysicalPlan::scan_all(file_path).map_err(|e: io::Error| e.to_string())?;
+    let plan: PhysicalPlan = PhysicalPlan { root_operator: PhysicalOperator::limit(plan.root_operator, offset, limit)? };
     
+    // Rows are printed as the executor reads them instead of after the whole scan
     let tsf_executor: Executor = Executor{};
-    let result: Vec<Vec<rtimedb::tsf::segments::types::EnumDataValue>> = tsf_executor.execute(plan).await?;
+    let mut rows: RowStream = tsf_executor.execute_stream(plan).await?;
 
-    for row in result {
-        println!("{},{}", row[0], row[1]);
+    while let Some(row_result) = rows.next().await {
+        let row: DataRow = row_result?;
+        let values: Vec<String> = row.values.iter().map(|value| value.to_string()).collect();
+        writeln!(out, "{}", values.join(CSV_DELIMITER)).map_err(|e: io::Error| e.to_string())?;
     }
 
     info!("Data read successfully.");
     Ok(())
 }
 
-async fn astream_time_series_db(file_path: &str) -> Result<(), String> {
-    info!("Reading from the database at: {}", file_path);
+async fn cat_time_series_db<W: Write>(file_path: &str, limit: usize, out: &mut W) -> Result<(), String> {
+    let mut tsf_reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await.map_err(|e: io::Error| e.to_string())?;
+    let schema: Vec<ColumnSchema> = tsf_reader.read_schema().await.map_err(|e: io::Error| e.to_string())?;
 
-    let plan: PhysicalPlan = PhysicalPlan{
-        root_operator: PhysicalOperator::Scan {
-            columns: vec!("metric_time".to_string(), "temperature".to_string()),
-            table_name: file_path.to_string(),
-            time_range: None
+    let column_names: Vec<String> = schema.into_iter().map(|column: ColumnSchema| column.name).collect();
+    writeln!(out, "{}", column_names.join(CSV_DELIMITER)).map_err(|e: io::Error| e.to_string())?;
+
+    // Rows come one segment at a time from every segment, reading stops once limit rows are printed
+    let mut stream = tsf_reader.stream_segments().take(limit);
+    while let Some(row_result) = stream.next().await {
+        let row: AsyncDataRow = row_result.map_err(|e: io::Error| e.to_string())?;
+        let values: Vec<String> = row.values.iter().map(|value| value.to_string()).collect();
+        writeln!(out, "{}", values.join(CSV_DELIMITER)).map_err(|e: io::Error| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+fn convert_time_series_db(input_file: &str, output_file: &str, encoding: EnumDataEnc, compression: EnumDataComp) -> Result<(), String> {
+    info!("Converting {} to {} with {:?} encoding and {:?} compression", input_file, output_file, encoding, compression);
+
+    // The writer appends to existing files, converting into one would mix old and new segments
+    if Path::new(output_file).exists() {
+        return Err(TsfError::FileExists { path: output_file.to_string() }.into());
+    }
+
+    let mut tsf_reader: TSFReader = TSFReader::new(input_file).map_err(|e: io::Error| e.to_string())?;
+    let segment_offsets: Vec<u64> = tsf_reader.segment_offsets().map_err(|e: io::Error| e.to_string())?;
+
+    // One output segment per input segment, so segment metadata and date ranges carry over as they are
+    for segment_pos in &segment_offsets {
+        tsf_reader.read_segment_at(*segment_pos).map_err(|e: io::Error| e.to_string())?;
+
+        let mut tsf_writer: TSFWriter = TSFWriter::new(output_file).map_err(|e: io::Error| e.to_string())?;
+        let mut index: usize = 0;
+        while let Some(header) = tsf_reader.get_column_header(index) {
+            let is_ts_column: bool = tsf_reader.get_ts_column() == Some(index);
+            tsf_writer.add_column_header_with_meta(&header.column_name, header.column_type, encoding, compression, is_ts_column, header.get_column_meta().clone())?;
+
+            let data: EnumColumnData = tsf_reader.get_column_data(index)
+                .ok_or_else(|| format!("Column {} has no data", header.column_name))?
+                .clone();
+            tsf_writer.add_segment_column_data(SegmentColumnData::from_column_data(data, encoding, compression))?;
+            index += 1;
         }
-    };
-    
-    let tsf_executor: Executor = Executor{};
-    let result: Vec<Vec<rtimedb::tsf::segments::types::EnumDataValue>> = tsf_executor.execute_async(plan).await?;
 
-    for row in result {
-        println!("{},{}", row[0], row[1]);
+        tsf_writer.set_metadata(tsf_reader.metadata().clone())?;
+        if let Some((date_start, date_end)) = tsf_reader.date_range() {
+            tsf_writer.update_segment_dates(date_start, date_end);
+        }
+        tsf_writer.try_save().map_err(|e: io::Error| e.to_string())?;
+    }
+
+    info!("Converted {} segments", segment_offsets.len());
+    Ok(())
+}
+
+// Only the headers are read, the column sizes come from the layout and the raw sizes from type and row count
+fn info_time_series_db<W: Write>(file_path: &str, out: &mut W) -> Result<(), String> {
+    let mut tsf_reader: TSFReader = TSFReader::new(file_path).map_err(|e: io::Error| e.to_string())?;
+
+    let row_count: u64 = tsf_reader.row_count().map_err(|e: io::Error| e.to_string())?;
+    let segment_offsets: Vec<u64> = tsf_reader.segment_offsets().map_err(|e: io::Error| e.to_string())?;
+    writeln!(out, "rows: {}", row_count).map_err(|e: io::Error| e.to_string())?;
+    writeln!(out, "segments: {}", segment_offsets.len()).map_err(|e: io::Error| e.to_string())?;
+
+    // Only segment headers are read, the layout doesn't need any column data
+    for (index, segment_pos) in segment_offsets.into_iter().enumerate() {
+        tsf_reader.read_segment_header_at(segment_pos).map_err(|e: io::Error| e.to_string())?;
+        let segment_rows: usize = tsf_reader.get_row_count();
+        writeln!(out, "segment {} at {}: {} rows", index, segment_pos, segment_rows).map_err(|e: io::Error| e.to_string())?;
+        for layout in tsf_reader.column_layout() {
+            writeln!(out, "  {}: {:?} encoding={:?} compression={:?} size={} ratio={:.3}",
+                layout.name, layout.column_type, layout.encoding, layout.compression, layout.column_size, layout.compression_ratio(segment_rows))
+                .map_err(|e: io::Error| e.to_string())?;
+        }
+    }
+
+    Ok(())
+}
+
+// Prints the first schema or row difference between two files, or "identical". Only column names, types
+// and values are compared, so segment layout and per write fields like uuid_txid don't count
+async fn diff_time_series_db<W: Write>(left_file: &str, right_file: &str, out: &mut W) -> Result<(), String> {
+    let left_schema: Vec<ColumnSchema> = TSFReader::new(left_file).and_then(|mut reader: TSFReader| reader.read_schema())
+        .map_err(|e: io::Error| format!("{}: {}", left_file, e))?;
+    let right_schema: Vec<ColumnSchema> = TSFReader::new(right_file).and_then(|mut reader: TSFReader| reader.read_schema())
+        .map_err(|e: io::Error| format!("{}: {}", right_file, e))?;
+
+    let left_schema: Schema = Schema::from(left_schema);
+    let right_schema: Schema = Schema::from(right_schema);
+    match left_schema.is_compatible_with(&right_schema) {
+        Ok(()) => {},
+        Err(SchemaMismatch::ColumnCount { expected, found }) => {
+            writeln!(out, "schema differs: {} columns vs {} columns", expected, found).map_err(|e: io::Error| e.to_string())?;
+            return Ok(());
+        },
+        Err(mismatch) => {
+            let (left, right): (&ColumnSchema, &ColumnSchema) = (&left_schema.columns[mismatch.index()], &right_schema.columns[mismatch.index()]);
+            writeln!(out, "schema differs at column {}: {} ({:?}) vs {} ({:?})", mismatch.index(), left.name, left.data_type, right.name, right.data_type)
+                .map_err(|e: io::Error| e.to_string())?;
+            return Ok(());
+        },
+    }
+
+    let mut left_rows: SegmentRowStream = AsyncTSFReader::new(left_file).await.map_err(|e: io::Error| e.to_string())?.stream_segments();
+    let mut right_rows: SegmentRowStream = AsyncTSFReader::new(right_file).await.map_err(|e: io::Error| e.to_string())?.stream_segments();
+    let mut row_index: usize = 0;
+    loop {
+        let left: Option<AsyncDataRow> = left_rows.next().await.transpose().map_err(|e: io::Error| e.to_string())?;
+        let right: Option<AsyncDataRow> = right_rows.next().await.transpose().map_err(|e: io::Error| e.to_string())?;
+        let (left, right) = match (left, right) {
+            (None, None) => break,
+            (Some(_), None) => {
+                writeln!(out, "row {} only in {}", row_index, left_file).map_err(|e: io::Error| e.to_string())?;
+                return Ok(());
+            },
+            (None, Some(_)) => {
+                writeln!(out, "row {} only in {}", row_index, right_file).map_err(|e: io::Error| e.to_string())?;
+                return Ok(());
+            },
+            (Some(left), Some(right)) => (left, right),
+        };
+
+        // Display round trips every value exactly, floats included, so equal text means equal values
+        let differences: Vec<String> = left_schema.columns.iter()
+            .zip(left.values.iter().zip(&right.values))
+            .filter(|(_, (left_value, right_value))| left_value.to_string() != right_value.to_string())
+            .map(|(column, (left_value, right_value))| format!("  {}: {} vs {}", column.name, left_value, right_value))
+            .collect();
+        if !differences.is_empty() {
+            writeln!(out, "row {} differs", row_index).map_err(|e: io::Error| e.to_string())?;
+            writeln!(out, "{}", differences.join("\n")).map_err(|e: io::Error| e.to_string())?;
+            return Ok(());
+        }
+        row_index += 1;
+    }
+
+    writeln!(out, "identical").map_err(|e: io::Error| e.to_string())
+}
+
+async fn astream_time_series_db<W: Write>(file_path: &str, offset: usize, limit: Option<usize>, out: &mut W) -> Result<(), String> {
+    info!("Reading from the database at: {}", file_path);
+    check_row_window(offset, limit)?;
+
+    // Segments are decoded one at a time so memory stays bounded on large files, and reading stops once the window is full
+    let tsf_reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await.map_err(|e: io::Error| e.to_string())?;
+    let mut stream = tsf_reader.stream_segments().skip(offset).take(limit.unwrap_or(usize::MAX));
+
+    while let Some(row_result) = stream.next().await {
+        let row: AsyncDataRow = row_result.map_err(|e: io::Error| e.to_string())?;
+        let values: Vec<String> = row.values.iter().map(|value| value.to_string()).collect();
+        writeln!(out, "{}", values.join(CSV_DELIMITER)).map_err(|e: io::Error| e.to_string())?;
     }
 
     info!("Data read successfully.");
     Ok(())
 }
+
+// Column names are resolved against FILE's schema, so the query can only read FILE
+async fn query_time_series_db<W: Write>(file_path: &str, sql: &str, out: &mut W) -> Result<(), String> {
+    let schema: Vec<ColumnSchema> = TSFReader::new(file_path).and_then(|mut reader: TSFReader| reader.read_schema())
+        .map_err(|e: io::Error| e.to_string())?;
+    let plan: PhysicalPlan = parse_query(sql, &schema)?;
+    match scan_table(&plan.root_operator) {
+        Some(table_name) if table_name == file_path => {},
+        table_name => return Err(format!("Query reads {}, expected FROM {}", table_name.unwrap_or("no table"), file_path)),
+    }
+
+    let tsf_executor: Executor = Executor::new();
+    let mut rows: RowStream = tsf_executor.execute_stream(plan).await?;
+    while let Some(row_result) = rows.next().await {
+        let row: DataRow = row_result?;
+        let values: Vec<String> = row.values.iter().map(|value| value.to_string()).collect();
+        writeln!(out, "{}", values.join(CSV_DELIMITER)).map_err(|e: io::Error| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+fn scan_table(operator: &PhysicalOperator) -> Option<&str> {
+    match operator {
+        PhysicalOperator::Scan { table_name, .. } => Some(table_name),
+        PhysicalOperator::Filter { input, .. } | PhysicalOperator::Limit { input, .. } => scan_table(input),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::{tempdir, NamedTempFile, TempDir};
+
+    #[tokio::test]
+    async fn test_cat_prints_header_and_limited_rows() -> Result<(), String> {
+        let temp_file: NamedTempFile = NamedTempFile::new().map_err(|e: io::Error| e.to_string())?;
+        let file_path: &str = temp_file.path().to_str().unwrap();
+
+        {
+            let mut writer: TSFWriter = TSFWriter::new(file_path).map_err(|e: io::Error| e.to_string())?;
+            writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)?;
+            writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false)?;
+            writer.add_column_data((0..2).collect::<Vec<i32>>(), EnumDataEnc::None, EnumDataComp::None)?;
+            writer.add_column_data((0..2).collect::<Vec<i8>>(), EnumDataEnc::None, EnumDataComp::None)?;
+            writer.update_segment_dates(0, 1);
+            writer.try_save().map_err(|e: io::Error| e.to_string())?;
+        }
+
+        // The limit runs past the first segment into the second
+        let mut writer: TSFWriter = TSFWriter::new(file_path).map_err(|e: io::Error| e.to_string())?;
+        writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)?;
+        writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false)?;
+        writer.add_column_data((2..30).collect::<Vec<i32>>(), EnumDataEnc::None, EnumDataComp::None)?;
+        writer.add_column_data((2..30).collect::<Vec<i8>>(), EnumDataEnc::None, EnumDataComp::None)?;
+        writer.update_segment_dates(2, 29);
+        writer.try_save().map_err(|e: io::Error| e.to_string())?;
+
+        let mut out: Vec<u8> = Vec::new();
+        cat_time_series_db(file_path, 3, &mut out).await?;
+
+        let output: String = String::from_utf8(out).unwrap();
+        assert_eq!(output, "metric_time,temperature\n0,0\n1,1\n2,2\n");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_offset_and_limit_page_rows() -> Result<(), String> {
+        let temp_file: NamedTempFile = NamedTempFile::new().map_err(|e: io::Error| e.to_string())?;
+        let file_path: &str = temp_file.path().to_str().unwrap();
+
+        let mut writer: TSFWriter = TSFWriter::new(file_path).map_err(|e: io::Error| e.to_string())?;
+        writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)?;
+        writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false)?;
+        writer.add_column_data((0..5).collect::<Vec<i32>>(), EnumDataEnc::None, EnumDataComp::None)?;
+        writer.add_column_data((20..25).collect::<Vec<i8>>(), EnumDataEnc::None, EnumDataComp::None)?;
+        writer.update_segment_dates(0, 4);
+        writer.try_save().map_err(|e: io::Error| e.to_string())?;
+
+        let mut out: Vec<u8> = Vec::new();
+        stream_time_series_db(file_path, 2, Some(2), &mut out).await?;
+        assert_eq!(String::from_utf8(out).unwrap(), "2,22\n3,23\n");
+
+        let mut out: Vec<u8> = Vec::new();
+        astream_time_series_db(file_path, 2, Some(2), &mut out).await?;
+        assert_eq!(String::from_utf8(out).unwrap(), "2,22\n3,23\n");
+
+        assert_eq!(
+            stream_time_series_db(file_path, usize::MAX, Some(1), &mut Vec::new()).await,
+            Err(format!("Offset {} plus limit 1 overflows", usize::MAX))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_to_zstd_keeps_data_and_shrinks_file() -> Result<(), String> {
+        let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+        let input_file: String = dir.path().join("raw.tsf").to_str().unwrap().to_string();
+        let output_file: String = dir.path().join("zstd.tsf").to_str().unwrap().to_string();
+
+        let metric_time: Vec<i32> = (1710555318..1710555318 + 1000).collect();
+        let temperatures: Vec<i8> = (0..1000).map(|index: i32| (index % 8) as i8).collect();
+        let mut writer: TSFWriter = TSFWriter::new(&input_file).map_err(|e: io::Error| e.to_string())?;
+        writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)?;
+        writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false)?;
+        writer.add_column_data(metric_time.clone(), EnumDataEnc::None, EnumDataComp::None)?;
+        writer.add_column_data(temperatures.clone(), EnumDataEnc::None, EnumDataComp::None)?;
+        writer.update_segment_dates(1710555318, 1710555318 + 999);
+        writer.try_save().map_err(|e: io::Error| e.to_string())?;
+
+        convert_time_series_db(&input_file, &output_file, EnumDataEnc::None, EnumDataComp::ZStd)?;
+
+        let mut reader: TSFReader = TSFReader::new(&output_file).map_err(|e: io::Error| e.to_string())?;
+        reader.read_all().map_err(|e: io::Error| e.to_string())?;
+        assert_eq!(reader.get_column_header(0).unwrap().column_comp, EnumDataComp::ZStd);
+        assert_eq!(reader.get_ts_column(), Some(0));
+        assert_eq!(reader.date_range(), Some((1710555318, 1710555318 + 999)));
+        assert_eq!(reader.column_slice::<i32>(0).unwrap(), &metric_time[..]);
+        assert_eq!(reader.column_slice::<i8>(1).unwrap(), &temperatures[..]);
+
+        let input_size: u64 = std::fs::metadata(&input_file).map_err(|e: io::Error| e.to_string())?.len();
+        let output_size: u64 = std::fs::metadata(&output_file).map_err(|e: io::Error| e.to_string())?.len();
+        assert!(output_size < input_size);
+
+        // Converting onto an existing file is refused
+        assert!(convert_time_series_db(&input_file, &output_file, EnumDataEnc::None, EnumDataComp::ZStd).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_keeps_every_segment_with_delta_zstd() -> Result<(), String> {
+        let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+        let input_file: String = dir.path().join("raw.tsf").to_str().unwrap().to_string();
+        let output_file: String = dir.path().join("delta.tsf").to_str().unwrap().to_string();
+
+        for segment in 0..2i32 {
+            let metric_time: Vec<i32> = (0..10).map(|row: i32| 1710555318 + segment * 10 + row).collect();
+            let mut writer: TSFWriter = TSFWriter::new(&input_file).map_err(|e: io::Error| e.to_string())?;
+            writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)?;
+            writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false)?;
+            writer.add_column_data(metric_time.clone(), EnumDataEnc::None, EnumDataComp::None)?;
+            writer.add_column_data(vec![segment as i8; 10], EnumDataEnc::None, EnumDataComp::None)?;
+            writer.update_segment_dates(metric_time[0] as i64, metric_time[9] as i64);
+            writer.try_save().map_err(|e: io::Error| e.to_string())?;
+        }
+
+        convert_time_series_db(&input_file, &output_file, EnumDataEnc::Delta, EnumDataComp::ZStd)?;
+
+        let mut reader: TSFReader = TSFReader::new(&output_file).map_err(|e: io::Error| e.to_string())?;
+        assert_eq!(reader.row_count().map_err(|e: io::Error| e.to_string())?, 20);
+        let segment_offsets: Vec<u64> = reader.segment_offsets().map_err(|e: io::Error| e.to_string())?;
+        assert_eq!(segment_offsets.len(), 2);
+        for (segment, segment_pos) in segment_offsets.into_iter().enumerate() {
+            reader.read_segment_at(segment_pos).map_err(|e: io::Error| e.to_string())?;
+            let start: i32 = 1710555318 + segment as i32 * 10;
+            assert_eq!(reader.get_column_header(0).unwrap().column_enc, EnumDataEnc::Delta);
+            assert_eq!(reader.get_column_header(0).unwrap().column_comp, EnumDataComp::ZStd);
+            assert_eq!(reader.date_range(), Some((start as i64, start as i64 + 9)));
+            assert_eq!(reader.column_slice::<i32>(0).unwrap(), &(start..start + 10).collect::<Vec<i32>>()[..]);
+            assert_eq!(reader.column_slice::<i8>(1).unwrap(), &[segment as i8; 10]);
+        }
+
+        Ok(())
+    }
+
+    fn write_diff_file(file_path: &str, temperatures: Vec<i8>) -> Result<(), String> {
+        let mut writer: TSFWriter = TSFWriter::new(file_path).map_err(|e: io::Error| e.to_string())?;
+        writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)?;
+        writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false)?;
+        writer.add_column_data((0..temperatures.len() as i32).collect::<Vec<i32>>(), EnumDataEnc::None, EnumDataComp::None)?;
+        writer.add_column_data(temperatures.clone(), EnumDataEnc::None, EnumDataComp::None)?;
+        writer.update_segment_dates(0, temperatures.len() as i64 - 1);
+        writer.try_save().map_err(|e: io::Error| e.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_diff_reports_identical_and_first_differing_cell() -> Result<(), String> {
+        let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+        let left_file: String = dir.path().join("left.tsf").to_str().unwrap().to_string();
+        let same_file: String = dir.path().join("same.tsf").to_str().unwrap().to_string();
+        let changed_file: String = dir.path().join("changed.tsf").to_str().unwrap().to_string();
+
+        // Each write gets its own uuid_txid, which the diff ignores
+        write_diff_file(&left_file, vec![20, 21, 22, 23])?;
+        write_diff_file(&same_file, vec![20, 21, 22, 23])?;
+        write_diff_file(&changed_file, vec![20, 21, 25, 23])?;
+
+        let mut out: Vec<u8> = Vec::new();
+        diff_time_series_db(&left_file, &same_file, &mut out).await?;
+        assert_eq!(String::from_utf8(out).unwrap(), "identical\n");
+
+        let mut out: Vec<u8> = Vec::new();
+        diff_time_series_db(&left_file, &changed_file, &mut out).await?;
+        assert_eq!(String::from_utf8(out).unwrap(), "row 2 differs\n  temperature: 22 vs 25\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_info_lists_every_segment_with_zstd_ratio_below_one() -> Result<(), String> {
+        let temp_file: NamedTempFile = NamedTempFile::new().map_err(|e: io::Error| e.to_string())?;
+        let file_path: &str = temp_file.path().to_str().unwrap();
+
+        {
+            let mut writer: TSFWriter = TSFWriter::new(file_path).map_err(|e: io::Error| e.to_string())?;
+            writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)?;
+            writer.add_column_header("temperature", EnumDataType::Int16, EnumDataEnc::None, EnumDataComp::ZStd, false)?;
+            writer.add_column_data((0..1000).collect::<Vec<i32>>(), EnumDataEnc::None, EnumDataComp::None)?;
+            writer.add_column_data(vec![21i16; 1000], EnumDataEnc::None, EnumDataComp::ZStd)?;
+            writer.update_segment_dates(0, 999);
+            writer.try_save().map_err(|e: io::Error| e.to_string())?;
+        }
+
+        let mut writer: TSFWriter = TSFWriter::new(file_path).map_err(|e: io::Error| e.to_string())?;
+        writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)?;
+        writer.add_column_header("temperature", EnumDataType::Int16, EnumDataEnc::None, EnumDataComp::None, false)?;
+        writer.add_column_data((1000..1010).collect::<Vec<i32>>(), EnumDataEnc::None, EnumDataComp::None)?;
+        writer.add_column_data(vec![22i16; 10], EnumDataEnc::None, EnumDataComp::None)?;
+        writer.update_segment_dates(1000, 1009);
+        writer.try_save().map_err(|e: io::Error| e.to_string())?;
+
+        let mut out: Vec<u8> = Vec::new();
+        info_time_series_db(file_path, &mut out)?;
+        let output: String = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines[0], "rows: 1010");
+        assert_eq!(lines[1], "segments: 2");
+        assert!(lines[2].starts_with("segment 0 at ") && lines[2].ends_with(": 1000 rows"));
+        assert_eq!(lines[3], "  metric_time: Int32 encoding=None compression=None size=4000 ratio=1.000");
+        assert!(lines[4].starts_with("  temperature: Int16 encoding=None compression=ZStd size="));
+        let ratio: f64 = lines[4].rsplit("ratio=").next().unwrap().parse().unwrap();
+        assert!(ratio < 1.0);
+
+        assert!(lines[5].starts_with("segment 1 at ") && lines[5].ends_with(": 10 rows"));
+        assert_eq!(lines[6], "  metric_time: Int32 encoding=None compression=None size=40 ratio=1.000");
+        assert_eq!(lines[7], "  temperature: Int16 encoding=None compression=None size=20 ratio=1.000");
+        assert_eq!(lines.len(), 8);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_filters_and_limits_rows() -> Result<(), String> {
+        let temp_file: NamedTempFile = NamedTempFile::new().map_err(|e: io::Error| e.to_string())?;
+        let file_path: &str = temp_file.path().to_str().unwrap();
+
+        let mut writer: TSFWriter = TSFWriter::new(file_path).map_err(|e: io::Error| e.to_string())?;
+        writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)?;
+        writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false)?;
+        writer.add_column_data((0..10).collect::<Vec<i32>>(), EnumDataEnc::None, EnumDataComp::None)?;
+        writer.add_column_data((20..30).collect::<Vec<i8>>(), EnumDataEnc::None, EnumDataComp::None)?;
+        writer.update_segment_dates(0, 9);
+        writer.try_save().map_err(|e: io::Error| e.to_string())?;
+
+        let mut out: Vec<u8> = Vec::new();
+        let sql: String = format!("SELECT temperature FROM '{}' WHERE time BETWEEN 2 AND 8 AND temperature > 23 LIMIT 2", file_path);
+        query_time_series_db(file_path, &sql, &mut out).await?;
+        assert_eq!(String::from_utf8(out).unwrap(), "24\n25\n");
+
+        assert_eq!(
+            query_time_series_db(file_path, "SELECT * FROM other.tsf", &mut Vec::new()).await,
+            Err(format!("Query reads other.tsf, expected FROM {}", file_path))
+        );
+
+        Ok(())
+    }
+}
@@ -1,16 +1,22 @@
+use std::cmp::Reverse;
+use std::collections::{btree_map::Entry, BTreeMap, BinaryHeap};
 use std::io;
 use std::pin::Pin;
 
-use chrono::{DateTime, Utc};
-use futures::Stream;
+use chrono::{DateTime, Duration, Utc};
+use futures::{future::BoxFuture, stream::BoxStream, FutureExt, Stream};
 use tokio_stream::StreamExt;
 
-use crate::tsf::{segments::types::EnumDataValue, tsf_reader::{DataRow, TSFReader}};
+use crate::tsf::{async_tsf_reader::{AsyncTSFReader, DataRow as AsyncDataRow}, segments::types::{ColumnSchema, EnumColumnData, EnumDataValue, EnumTimeResolution}, tsf_reader::{DataRow, TSFReader}};
 
-use super::physical_plan::{PhysicalOperator, PhysicalPlan};
+use super::physical_plan::{check_row_window, AggregationFunction, FilterPredicate, GapfillMethod, PhysicalOperator, PhysicalPlan};
+use super::query_result::QueryResult;
 
 pub struct Executor {}
 
+// Rows of a plan handed out one at a time by Executor::execute_stream
+pub type RowStream = BoxStream<'static, Result<DataRow, String>>;
+
 impl Executor {
   pub fn new() -> Self {
     Executor{}
@@ -24,13 +30,118 @@ impl Executor {
     self.async_execute_operator(&plan.root_operator).await
   }
   
+  // Same plan as a lazy stream. Scan, Filter and Limit pass rows on as each segment is read, any other
+  // operator needs its whole input first so it is executed up front and its rows streamed afterwards
+  pub async fn execute_stream(&self, plan: PhysicalPlan) -> Result<RowStream, String> {
+    self.operator_stream(plan.root_operator).await
+  }
+
+  fn operator_stream(&self, operator: PhysicalOperator) -> BoxFuture<'_, Result<RowStream, String>> {
+    async move {
+      let rows: RowStream = match operator {
+        PhysicalOperator::Scan { columns, table_name, time_range } => {
+          scan_stream(&table_name, &columns, &time_range).await?
+        },
+        PhysicalOperator::Filter { input, column, predicate } => {
+          let rows: RowStream = self.operator_stream(*input).await?;
+          Box::pin(rows.filter_map(move |row_result: Result<DataRow, String>| {
+            let matches: Result<bool, String> = row_result.as_ref().map_err(String::clone).and_then(|row: &DataRow| {
+              let value: &EnumDataValue = row.values.get(column)
+                .ok_or_else(|| format!("Filter column {} is out of range", column))?;
+              predicate.matches(value)
+            });
+            match matches {
+              Ok(true) => Some(row_result),
+              Ok(false) => None,
+              Err(e) => Some(Err(e)),
+            }
+          }))
+        },
+        PhysicalOperator::Limit { input, offset, limit } => {
+          check_row_window(offset, limit)?;
+          let rows: RowStream = self.operator_stream(*input).await?;
+          Box::pin(rows.skip(offset).take(limit.unwrap_or(usize::MAX)))
+        },
+        operator => {
+          let rows: Vec<Vec<EnumDataValue>> = self.async_execute_operator(&operator).await?;
+          Box::pin(tokio_stream::iter(rows.into_iter().map(|values: Vec<EnumDataValue>| Ok(DataRow { values }))))
+        },
+      };
+
+      Ok(rows)
+    }.boxed()
+  }
+
+  // Same plan evaluated into a column major QueryResult, a bare scan is built from the reader's columns without going through rows
+  pub async fn execute_columnar(&self, plan: PhysicalPlan) -> Result<QueryResult, String> {
+    if let PhysicalOperator::Scan { columns, table_name, time_range } = &plan.root_operator {
+      return self.scan_columns(table_name, columns, time_range);
+    }
+
+    let (table_name, columns): (&String, &[String]) = output_scan(&plan.root_operator)?;
+    let schema: Vec<ColumnSchema> = table_schema(table_name, columns)?;
+    QueryResult::from_rows(schema, self.execute_operator(&plan.root_operator).await?)
+  }
+
+  pub async fn execute_columnar_async(&self, plan: PhysicalPlan) -> Result<QueryResult, String> {
+    if let PhysicalOperator::Scan { columns, table_name, time_range } = &plan.root_operator {
+      return self.async_scan_columns(table_name, columns, time_range).await;
+    }
+
+    let (table_name, columns): (&String, &[String]) = output_scan(&plan.root_operator)?;
+    let schema: Vec<ColumnSchema> = async_table_schema(table_name, columns).await?;
+    QueryResult::from_rows(schema, self.async_execute_operator(&plan.root_operator).await?)
+  }
+
   pub async fn execute_operator(&self, operator: &PhysicalOperator) -> Result<Vec<Vec<EnumDataValue>>, String> {
     match operator {
       PhysicalOperator::Scan { columns, table_name, time_range } => {
         self.execute_scan(columns, table_name, time_range).await
       },
-      PhysicalOperator::Aggregate { input, columns, function, time_bucket } => {Err("Not Implemented".to_string())},
-      PhysicalOperator::Join { join_type, left, right, condition } => {Err("Not Implemented".to_string())}
+      PhysicalOperator::UnionScan { tables } => {
+        self.execute_union_scan(tables).await
+      },
+      PhysicalOperator::Filter { input, column, predicate } => {
+        let rows: Vec<Vec<EnumDataValue>> = Box::pin(self.execute_operator(input)).await?;
+        filter_rows(rows, *column, predicate)
+      },
+      PhysicalOperator::Sample { input, stride } => {
+        let rows: Vec<Vec<EnumDataValue>> = Box::pin(self.execute_operator(input)).await?;
+        sample_rows(rows, *stride)
+      },
+      PhysicalOperator::Limit { input, offset, limit } => {
+        let rows: Vec<Vec<EnumDataValue>> = Box::pin(self.execute_operator(input)).await?;
+        limit_rows(rows, *offset, *limit)
+      },
+      PhysicalOperator::Gapfill { input, ts_column, bucket, method } => {
+        let bucket: i64 = bucket_units(input, *ts_column, *bucket)?;
+        let rows: Vec<Vec<EnumDataValue>> = Box::pin(self.execute_operator(input)).await?;
+        gapfill_rows(rows, *ts_column, bucket, method)
+      },
+      PhysicalOperator::Aggregate { input, ts_column, columns, function, time_bucket } => {
+        let bucket: i64 = bucket_units(input, *ts_column, *time_bucket)?;
+        let mut aggregate: StreamingAggregate = StreamingAggregate::new(*ts_column, columns, function, bucket)?;
+        // Scans feed the aggregate row by row instead of materializing the table first
+        if let PhysicalOperator::Scan { columns: scan_columns, table_name, time_range } = input.as_ref() {
+          self.scan_rows(table_name, scan_columns, time_range, |row: Vec<EnumDataValue>| aggregate.push(&row)).await?;
+        } else if let PhysicalOperator::UnionScan { tables } = input.as_ref() {
+          // Order does not matter to an aggregate, so each table is aggregated on its own and merged
+          for table_name in tables {
+            let mut partial: StreamingAggregate = StreamingAggregate::new(*ts_column, columns, function, bucket)?;
+            self.scan_rows(table_name, &[], &None, |row: Vec<EnumDataValue>| partial.push(&row)).await?;
+            aggregate.merge(partial);
+          }
+        } else {
+          for row in Box::pin(self.execute_operator(input)).await? {
+            aggregate.push(&row)?;
+          }
+        }
+        aggregate.finish()
+      },
+      PhysicalOperator::Join { join_type: _, left, right, condition } => {
+        condition.resolve(&scan_schema(left)?, &scan_schema(right)?)?;
+        Err("Not Implemented".to_string())
+      },
     }
   }
 
@@ -39,50 +150,1160 @@ impl Executor {
       PhysicalOperator::Scan { columns, table_name, time_range } => {
         self.async_execute_scan(columns, table_name, time_range).await
       },
-      PhysicalOperator::Aggregate { input, columns, function, time_bucket } => {Err("Not Implemented".to_string())},
-      PhysicalOperator::Join { join_type, left, right, condition } => {Err("Not Implemented".to_string())}
+      PhysicalOperator::UnionScan { tables } => {
+        self.async_execute_union_scan(tables).await
+      },
+      PhysicalOperator::Filter { input, column, predicate } => {
+        let rows: Vec<Vec<EnumDataValue>> = Box::pin(self.async_execute_operator(input)).await?;
+        filter_rows(rows, *column, predicate)
+      },
+      PhysicalOperator::Sample { input, stride } => {
+        let rows: Vec<Vec<EnumDataValue>> = Box::pin(self.async_execute_operator(input)).await?;
+        sample_rows(rows, *stride)
+      },
+      PhysicalOperator::Limit { input, offset, limit } => {
+        let rows: Vec<Vec<EnumDataValue>> = Box::pin(self.async_execute_operator(input)).await?;
+        limit_rows(rows, *offset, *limit)
+      },
+      PhysicalOperator::Gapfill { input, ts_column, bucket, method } => {
+        let bucket: i64 = async_bucket_units(input, *ts_column, *bucket).await?;
+        let rows: Vec<Vec<EnumDataValue>> = Box::pin(self.async_execute_operator(input)).await?;
+        gapfill_rows(rows, *ts_column, bucket, method)
+      },
+      PhysicalOperator::Aggregate { input, ts_column, columns, function, time_bucket } => {
+        let bucket: i64 = async_bucket_units(input, *ts_column, *time_bucket).await?;
+        let mut aggregate: StreamingAggregate = StreamingAggregate::new(*ts_column, columns, function, bucket)?;
+        // Scans feed the aggregate row by row instead of materializing the table first
+        if let PhysicalOperator::Scan { columns: scan_columns, table_name, time_range } = input.as_ref() {
+          self.async_scan_rows(table_name, scan_columns, time_range, |row: Vec<EnumDataValue>| aggregate.push(&row)).await?;
+        } else if let PhysicalOperator::UnionScan { tables } = input.as_ref() {
+          // Order does not matter to an aggregate, so each table is aggregated on its own and merged
+          for table_name in tables {
+            let mut partial: StreamingAggregate = StreamingAggregate::new(*ts_column, columns, function, bucket)?;
+            self.async_scan_rows(table_name, &[], &None, |row: Vec<EnumDataValue>| partial.push(&row)).await?;
+            aggregate.merge(partial);
+          }
+        } else {
+          for row in Box::pin(self.async_execute_operator(input)).await? {
+            aggregate.push(&row)?;
+          }
+        }
+        aggregate.finish()
+      },
+      PhysicalOperator::Join { join_type: _, left, right, condition } => {
+        condition.resolve(&async_scan_schema(left).await?, &async_scan_schema(right).await?)?;
+        Err("Not Implemented".to_string())
+      },
     }
   }
 
-  async fn execute_scan(&self, _columns: &Vec<String>, _table_name: &String, _time_range: &Option<(DateTime<Utc>, DateTime<Utc>)>) -> Result<Vec<Vec<EnumDataValue>>, String> {
+  async fn execute_scan(&self, columns: &[String], _table_name: &String, time_range: &Option<(DateTime<Utc>, DateTime<Utc>)>) -> Result<Vec<Vec<EnumDataValue>>, String> {
+    let mut result: Vec<Vec<EnumDataValue>> = vec![];
+    self.scan_rows(_table_name, columns, time_range, |row: Vec<EnumDataValue>| {
+      result.push(row);
+      Ok(())
+    }).await?;
+
+    Ok(result)
+  }
+
+  // Loads one segment at a time and hands its rows to sink before moving on to the next
+  async fn scan_rows(&self, _table_name: &String, columns: &[String], time_range: &Option<(DateTime<Utc>, DateTime<Utc>)>, mut sink: impl FnMut(Vec<EnumDataValue>) -> Result<(), String>) -> Result<(), String> {
     let mut reader: TSFReader = TSFReader::new(_table_name)
       .map_err(|_| "Failed to read table_name".to_string())?;
 
-    reader.read_all().map_err(|e: io::Error| e.to_string())?;
+    let segment_offsets: Vec<u64> = reader.segment_offsets().map_err(|e: io::Error| e.to_string())?;
+    for segment_pos in segment_offsets {
+      reader.read_segment_at(segment_pos).map_err(|e: io::Error| e.to_string())?;
 
-    let mut stream: Pin<Box<dyn Stream<Item = Result<DataRow, io::Error>> + Send>> = reader.stream_rows();
-    let mut result: Vec<Vec<EnumDataValue>> = vec![];
-    while let Some(row_result) = stream.next().await {
-      match row_result {
-        Ok(data_row) => {
-          let row: Vec<EnumDataValue> = data_row.values;
-          result.push(row);
-        },
-        Err(_) => return Err("Failed to fetch row".to_string()),
+      let ts_column: Option<usize> = reader.get_ts_column();
+      let ts_resolution: Option<EnumTimeResolution> = ts_column.and_then(|index: usize| reader.get_column_header(index))
+        .and_then(|header| header.get_column_meta().time_resolution(header.column_type));
+      let time_filter: Option<TimeFilter> = scan_time_filter(_table_name, ts_column, ts_resolution, time_range)?;
+      let projection: Option<Vec<usize>> = scan_projection(_table_name, &reader.schema(), columns)?;
+
+      let mut stream: Pin<Box<dyn Stream<Item = Result<DataRow, io::Error>> + Send>> = reader.stream_rows();
+      while let Some(row_result) = stream.next().await {
+        match row_result {
+          Ok(data_row) => {
+            let row: Vec<EnumDataValue> = data_row.values;
+            if time_filter.as_ref().is_none_or(|filter: &TimeFilter| filter.contains(&row)) {
+              sink(project(row, projection.as_deref()))?;
+            }
+          },
+          Err(_) => return Err("Failed to fetch row".to_string()),
+        }
       }
     }
 
+    Ok(())
+  }
+
+  async fn async_execute_scan(&self, columns: &[String], _table_name: &String, time_range: &Option<(DateTime<Utc>, DateTime<Utc>)>) -> Result<Vec<Vec<EnumDataValue>>, String> {
+    let mut result: Vec<Vec<EnumDataValue>> = vec![];
+    self.async_scan_rows(_table_name, columns, time_range, |row: Vec<EnumDataValue>| {
+      result.push(row);
+      Ok(())
+    }).await?;
+
     Ok(result)
   }
 
-  async fn async_execute_scan(&self, _columns: &Vec<String>, _table_name: &String, _time_range: &Option<(DateTime<Utc>, DateTime<Utc>)>) -> Result<Vec<Vec<EnumDataValue>>, String> {
-    let mut reader: crate::tsf::async_tsf_reader::AsyncTSFReader = crate::tsf::async_tsf_reader::AsyncTSFReader::new(_table_name).await
+  // Rows come from scan_stream, so only the segment being decoded is held in memory
+  async fn async_scan_rows(&self, _table_name: &String, columns: &[String], time_range: &Option<(DateTime<Utc>, DateTime<Utc>)>, mut sink: impl FnMut(Vec<EnumDataValue>) -> Result<(), String>) -> Result<(), String> {
+    let mut stream: RowStream = scan_stream(_table_name, columns, time_range).await?;
+    while let Some(row_result) = stream.next().await {
+      sink(row_result?.values)?;
+    }
+
+    Ok(())
+  }
+
+  fn scan_columns(&self, table_name: &str, columns: &[String], time_range: &Option<(DateTime<Utc>, DateTime<Utc>)>) -> Result<QueryResult, String> {
+    let mut reader: TSFReader = TSFReader::new(table_name)
+      .map_err(|_| "Failed to read table_name".to_string())?;
+
+    reader.read_all().map_err(|e: io::Error| e.to_string())?;
+
+    let ts_column: Option<usize> = reader.get_ts_column();
+    let ts_resolution: Option<EnumTimeResolution> = ts_column.and_then(|index: usize| reader.get_column_header(index))
+      .and_then(|header| header.get_column_meta().time_resolution(header.column_type));
+    let time_filter: Option<TimeFilter> = scan_time_filter(table_name, ts_column, ts_resolution, time_range)?;
+    let projection: Option<Vec<usize>> = scan_projection(table_name, &reader.schema(), columns)?;
+    let data: Vec<EnumColumnData> = reader.to_columns().into_iter().map(|(_, data)| data).collect();
+
+    let data: Vec<EnumColumnData> = project(filter_columns(data, time_filter.as_ref()), projection.as_deref());
+    QueryResult::new(project(reader.schema(), projection.as_deref()), data)
+  }
+
+  async fn async_scan_columns(&self, table_name: &str, columns: &[String], time_range: &Option<(DateTime<Utc>, DateTime<Utc>)>) -> Result<QueryResult, String> {
+    let mut reader: crate::tsf::async_tsf_reader::AsyncTSFReader = crate::tsf::async_tsf_reader::AsyncTSFReader::new(table_name).await
       .map_err(|_| "Failed to read table_name".to_string())?;
 
     reader.read_all().await.map_err(|e: io::Error| e.to_string())?;
 
-    let mut stream: Pin<Box<dyn Stream<Item = Result<crate::tsf::async_tsf_reader::DataRow, io::Error>> + Send>> = reader.stream_rows();
+    let ts_column: Option<usize> = reader.get_ts_column();
+    let ts_resolution: Option<EnumTimeResolution> = ts_column.and_then(|index: usize| reader.get_column_header(index))
+      .and_then(|header| header.get_column_meta().time_resolution(header.column_type));
+    let time_filter: Option<TimeFilter> = scan_time_filter(table_name, ts_column, ts_resolution, time_range)?;
+    let projection: Option<Vec<usize>> = scan_projection(table_name, &reader.schema(), columns)?;
+    let data: Vec<EnumColumnData> = reader.to_columns().into_iter().map(|(_, data)| data).collect();
+
+    let data: Vec<EnumColumnData> = project(filter_columns(data, time_filter.as_ref()), projection.as_deref());
+    QueryResult::new(project(reader.schema(), projection.as_deref()), data)
+  }
+
+  async fn execute_union_scan(&self, tables: &[String]) -> Result<Vec<Vec<EnumDataValue>>, String> {
     let mut result: Vec<Vec<EnumDataValue>> = vec![];
-    while let Some(row_result) = stream.next().await {
-      match row_result {
-        Ok(data_row) => {
-          let row: Vec<EnumDataValue> = data_row.values;
-          result.push(row);
+    self.union_scan_rows(tables, |row: Vec<EnumDataValue>| {
+      result.push(row);
+      Ok(())
+    }).await?;
+
+    Ok(result)
+  }
+
+  // Rows of every table merged by timestamp into sink. Each table is read a segment at a time, so the merge
+  // only holds the segment every input is on
+  async fn union_scan_rows(&self, tables: &[String], sink: impl FnMut(Vec<EnumDataValue>) -> Result<(), String>) -> Result<(), String> {
+    let mut inputs: Vec<(RowStream, usize)> = Vec::new();
+    for table_name in tables {
+      let mut reader: TSFReader = TSFReader::new(table_name)
+        .map_err(|_| "Failed to read table_name".to_string())?;
+
+      reader.read_schema().map_err(|e: io::Error| e.to_string())?;
+      let ts_column: usize = reader.get_ts_column()
+        .ok_or_else(|| format!("Table {} has no timestamp column", table_name))?;
+      inputs.push((segment_rows(reader)?, ts_column));
+    }
+
+    merge_by_timestamp(inputs, sink).await
+  }
+
+  async fn async_execute_union_scan(&self, tables: &[String]) -> Result<Vec<Vec<EnumDataValue>>, String> {
+    let mut result: Vec<Vec<EnumDataValue>> = vec![];
+    self.async_union_scan_rows(tables, |row: Vec<EnumDataValue>| {
+      result.push(row);
+      Ok(())
+    }).await?;
+
+    Ok(result)
+  }
+
+  async fn async_union_scan_rows(&self, tables: &[String], sink: impl FnMut(Vec<EnumDataValue>) -> Result<(), String>) -> Result<(), String> {
+    let mut inputs: Vec<(RowStream, usize)> = Vec::new();
+    for table_name in tables {
+      let mut reader: AsyncTSFReader = AsyncTSFReader::new(table_name).await
+        .map_err(|_| "Failed to read table_name".to_string())?;
+
+      reader.read_schema().await.map_err(|e: io::Error| e.to_string())?;
+      let ts_column: usize = reader.get_ts_column()
+        .ok_or_else(|| format!("Table {} has no timestamp column", table_name))?;
+      inputs.push((Box::pin(reader.stream_segments().map(|row_result: io::Result<AsyncDataRow>| row_result
+        .map(|data_row: AsyncDataRow| DataRow { values: data_row.values })
+        .map_err(|_| "Failed to fetch row".to_string()))), ts_column));
+    }
+
+    merge_by_timestamp(inputs, sink).await
+  }
+}
+
+// Every segment of a sync reader as one stream, the next segment is only loaded once the current one is drained
+fn segment_rows(mut reader: TSFReader) -> Result<RowStream, String> {
+  let segment_offsets: Vec<u64> = reader.segment_offsets().map_err(|e: io::Error| e.to_string())?;
+
+  let segments = futures::stream::iter(segment_offsets).map(move |segment_pos: u64| -> RowStream {
+    match reader.read_segment_at(segment_pos) {
+      Ok(()) => Box::pin(reader.stream_rows().map(|row_result: io::Result<DataRow>| row_result.map_err(|_| "Failed to fetch row".to_string()))),
+      Err(e) => Box::pin(tokio_stream::once(Err(e.to_string()))),
+    }
+  });
+  Ok(Box::pin(futures::StreamExt::flatten(segments)))
+}
+
+// Every segment of the table, read one at a time as the stream is polled
+async fn scan_stream(table_name: &str, columns: &[String], time_range: &Option<(DateTime<Utc>, DateTime<Utc>)>) -> Result<RowStream, String> {
+  let mut reader: AsyncTSFReader = AsyncTSFReader::new(table_name).await
+    .map_err(|_| "Failed to read table_name".to_string())?;
+
+  let schema: Vec<ColumnSchema> = reader.read_schema().await.map_err(|e: io::Error| e.to_string())?;
+  let projection: Option<Vec<usize>> = scan_projection(table_name, &schema, columns)?;
+
+  let ts_column: Option<usize> = reader.get_ts_column();
+  let ts_resolution: Option<EnumTimeResolution> = ts_column.and_then(|index: usize| reader.get_column_header(index))
+    .and_then(|header| header.get_column_meta().time_resolution(header.column_type));
+  let time_filter: Option<TimeFilter> = scan_time_filter(table_name, ts_column, ts_resolution, time_range)?;
+
+  Ok(Box::pin(reader.stream_segments().filter_map(move |row_result: io::Result<AsyncDataRow>| match row_result {
+    Ok(data_row) => time_filter.as_ref().is_none_or(|filter: &TimeFilter| filter.contains(&data_row.values))
+      .then_some(Ok(DataRow { values: project(data_row.values, projection.as_deref()) })),
+    Err(_) => Some(Err("Failed to fetch row".to_string())),
+  })))
+}
+
+// Table positions of a scan's columns by name, None when the scan keeps every column in file order
+fn scan_projection(table_name: &str, schema: &[ColumnSchema], columns: &[String]) -> Result<Option<Vec<usize>>, String> {
+  if columns.is_empty() {
+    return Ok(None);
+  }
+
+  let indices: Vec<usize> = columns.iter()
+    .map(|name: &String| schema.iter().position(|column: &ColumnSchema| &column.name == name)
+      .ok_or_else(|| format!("Column {} not found in table {}", name, table_name)))
+    .collect::<Result<Vec<usize>, String>>()?;
+  Ok(Some(indices).filter(|indices: &Vec<usize>| !indices.iter().copied().eq(0..schema.len())))
+}
+
+// Picks the projected entries of a row, column list or schema. Scans apply the time filter to the full
+// row first, so the ts column is decoded once and shared whether or not it is projected
+fn project<T: Clone>(values: Vec<T>, projection: Option<&[usize]>) -> Vec<T> {
+  match projection {
+    Some(indices) => indices.iter().map(|index: &usize| values[*index].clone()).collect(),
+    None => values,
+  }
+}
+
+// Inclusive time range check against a scan's timestamp column
+struct TimeFilter {
+  ts_column: usize,
+  ts_resolution: EnumTimeResolution,
+  start: DateTime<Utc>,
+  end: DateTime<Utc>,
+}
+
+impl TimeFilter {
+  fn contains(&self, row: &[EnumDataValue]) -> bool {
+    row.get(self.ts_column).is_some_and(|value: &EnumDataValue| self.contains_value(value))
+  }
+
+  fn contains_value(&self, value: &EnumDataValue) -> bool {
+    value.as_i64()
+      .and_then(|epoch: i64| self.ts_resolution.to_utc(epoch))
+      .is_some_and(|timestamp: DateTime<Utc>| self.start <= timestamp && timestamp <= self.end)
+  }
+}
+
+fn scan_time_filter(table_name: &str, ts_column: Option<usize>, ts_resolution: Option<EnumTimeResolution>, time_range: &Option<(DateTime<Utc>, DateTime<Utc>)>) -> Result<Option<TimeFilter>, String> {
+  let (start, end) = match time_range {
+    Some(range) => *range,
+    None => return Ok(None),
+  };
+  match (ts_column, ts_resolution) {
+    (Some(ts_column), Some(ts_resolution)) => Ok(Some(TimeFilter { ts_column, ts_resolution, start, end })),
+    _ => Err(format!("Table {} has no timestamp column to apply time_range", table_name)),
+  }
+}
+
+// Keeps the rows inside the time filter with one take per column, the columns are never turned into rows
+fn filter_columns(columns: Vec<EnumColumnData>, time_filter: Option<&TimeFilter>) -> Vec<EnumColumnData> {
+  let Some(filter) = time_filter else {
+    return columns;
+  };
+  let Some(ts_data) = columns.get(filter.ts_column) else {
+    return columns;
+  };
+
+  let indices: Vec<usize> = (0..ts_data.len())
+    .filter(|index: &usize| ts_data.value_at(*index).is_some_and(|value: EnumDataValue| filter.contains_value(&value)))
+    .collect();
+  columns.iter().map(|column: &EnumColumnData| column.take(&indices)).collect()
+}
+
+// Table and projection whose columns an operator's rows carry, an empty projection is every column.
+// Aggregate and Join reshape their rows and have none
+fn output_scan(operator: &PhysicalOperator) -> Result<(&String, &[String]), String> {
+  match operator {
+    PhysicalOperator::Scan { columns, table_name, .. } => Ok((table_name, columns)),
+    PhysicalOperator::UnionScan { tables } => tables.first().map(|table_name: &String| (table_name, &[][..])).ok_or_else(|| "UnionScan has no tables".to_string()),
+    PhysicalOperator::Filter { input, .. }
+    | PhysicalOperator::Sample { input, .. }
+    | PhysicalOperator::Limit { input, .. }
+    | PhysicalOperator::Gapfill { input, .. } => output_scan(input),
+    PhysicalOperator::Aggregate { .. } | PhysicalOperator::Join { .. } => Err("Columnar results need an operator that keeps its input's columns".to_string()),
+  }
+}
+
+fn table_schema(table_name: &str, columns: &[String]) -> Result<Vec<ColumnSchema>, String> {
+  let mut reader: TSFReader = TSFReader::new(table_name)
+    .map_err(|_| "Failed to read table_name".to_string())?;
+  reader.read_all().map_err(|e: io::Error| e.to_string())?;
+
+  let schema: Vec<ColumnSchema> = reader.schema();
+  let projection: Option<Vec<usize>> = scan_projection(table_name, &schema, columns)?;
+  Ok(project(schema, projection.as_deref()))
+}
+
+async fn async_table_schema(table_name: &str, columns: &[String]) -> Result<Vec<ColumnSchema>, String> {
+  let mut reader: crate::tsf::async_tsf_reader::AsyncTSFReader = crate::tsf::async_tsf_reader::AsyncTSFReader::new(table_name).await
+    .map_err(|_| "Failed to read table_name".to_string())?;
+  reader.read_all().await.map_err(|e: io::Error| e.to_string())?;
+
+  let schema: Vec<ColumnSchema> = reader.schema();
+  let projection: Option<Vec<usize>> = scan_projection(table_name, &schema, columns)?;
+  Ok(project(schema, projection.as_deref()))
+}
+
+// A time bucket counted in the raw units of the input's ts column, e.g. milliseconds for a DateTime64 column
+fn bucket_units(input: &PhysicalOperator, ts_column: usize, bucket: Duration) -> Result<i64, String> {
+  let (table_name, columns): (&String, &[String]) = output_scan(input)
+    .map_err(|_| "Time buckets need an input whose rows come from a table".to_string())?;
+  let mut reader: TSFReader = TSFReader::new(table_name)
+    .map_err(|_| "Failed to read table_name".to_string())?;
+
+  let schema: Vec<ColumnSchema> = reader.read_schema().map_err(|e: io::Error| e.to_string())?;
+  let resolutions: Vec<Option<EnumTimeResolution>> = (0..schema.len())
+    .map(|index: usize| reader.get_column_header(index)
+      .and_then(|header| header.get_column_meta().time_resolution(header.column_type)))
+    .collect();
+  units_of_bucket(table_name, &schema, resolutions, columns, ts_column, bucket)
+}
+
+async fn async_bucket_units(input: &PhysicalOperator, ts_column: usize, bucket: Duration) -> Result<i64, String> {
+  let (table_name, columns): (&String, &[String]) = output_scan(input)
+    .map_err(|_| "Time buckets need an input whose rows come from a table".to_string())?;
+  let mut reader: AsyncTSFReader = AsyncTSFReader::new(table_name).await
+    .map_err(|_| "Failed to read table_name".to_string())?;
+
+  let schema: Vec<ColumnSchema> = reader.read_schema().await.map_err(|e: io::Error| e.to_string())?;
+  let resolutions: Vec<Option<EnumTimeResolution>> = (0..schema.len())
+    .map(|index: usize| reader.get_column_header(index)
+      .and_then(|header| header.get_column_meta().time_resolution(header.column_type)))
+    .collect();
+  units_of_bucket(table_name, &schema, resolutions, columns, ts_column, bucket)
+}
+
+fn units_of_bucket(table_name: &str, schema: &[ColumnSchema], resolutions: Vec<Option<EnumTimeResolution>>, columns: &[String], ts_column: usize, bucket: Duration) -> Result<i64, String> {
+  let projection: Option<Vec<usize>> = scan_projection(table_name, schema, columns)?;
+  let resolution: EnumTimeResolution = project(resolutions, projection.as_deref()).get(ts_column).copied().flatten()
+    .ok_or_else(|| format!("Column {} of table {} is not a timestamp column", ts_column, table_name))?;
+
+  resolution.units_in(bucket).ok_or_else(|| format!("Time bucket {} does not fit the timestamp column of table {}", bucket, table_name))
+}
+
+// Column names and types of a join input, so a bad condition fails before any rows are read
+fn scan_schema(operator: &PhysicalOperator) -> Result<Vec<ColumnSchema>, String> {
+  let PhysicalOperator::Scan { columns, table_name, .. } = operator else {
+    return Err("Join inputs must be scans".to_string());
+  };
+
+  table_schema(table_name, columns)
+}
+
+async fn async_scan_schema(operator: &PhysicalOperator) -> Result<Vec<ColumnSchema>, String> {
+  let PhysicalOperator::Scan { columns, table_name, .. } = operator else {
+    return Err("Join inputs must be scans".to_string());
+  };
+
+  async_table_schema(table_name, columns).await
+}
+
+fn filter_rows(rows: Vec<Vec<EnumDataValue>>, column: usize, predicate: &FilterPredicate) -> Result<Vec<Vec<EnumDataValue>>, String> {
+  let mut result: Vec<Vec<EnumDataValue>> = Vec::new();
+  for row in rows {
+    let value: &EnumDataValue = row.get(column)
+      .ok_or_else(|| format!("Filter column {} is out of range", column))?;
+    if predicate.matches(value)? {
+      result.push(row);
+    }
+  }
+
+  Ok(result)
+}
+
+fn sample_rows(rows: Vec<Vec<EnumDataValue>>, stride: usize) -> Result<Vec<Vec<EnumDataValue>>, String> {
+  if stride == 0 {
+    return Err("Sample stride must be greater than zero".to_string());
+  }
+
+  Ok(rows.into_iter().step_by(stride).collect())
+}
+
+fn limit_rows(rows: Vec<Vec<EnumDataValue>>, offset: usize, limit: Option<usize>) -> Result<Vec<Vec<EnumDataValue>>, String> {
+  check_row_window(offset, limit)?;
+
+  Ok(rows.into_iter().skip(offset).take(limit.unwrap_or(usize::MAX)).collect())
+}
+
+// Timestamps are in seconds, the last row of a bucket represents it and carries the bucket start as its timestamp
+fn gapfill_rows(rows: Vec<Vec<EnumDataValue>>, ts_column: usize, bucket: i64, method: &GapfillMethod) -> Result<Vec<Vec<EnumDataValue>>, String> {
+  if bucket <= 0 {
+    return Err("Gapfill bucket must be at least one unit of the timestamp column".to_string());
+  }
+
+  let mut buckets: Vec<(i64, Vec<EnumDataValue>)> = Vec::new();
+  for row in rows {
+    let timestamp: i64 = row.get(ts_column)
+      .and_then(|value: &EnumDataValue| value.as_i64())
+      .ok_or_else(|| "Timestamp column value is not an integer".to_string())?;
+    let bucket_start: i64 = timestamp - timestamp.rem_euclid(bucket);
+
+    match buckets.last_mut() {
+      Some((last_start, last_row)) if *last_start == bucket_start => *last_row = row,
+      Some((last_start, _)) if *last_start > bucket_start => {
+        return Err("Gapfill input is not sorted by timestamp".to_string());
+      },
+      _ => buckets.push((bucket_start, row)),
+    }
+  }
+
+  let mut result: Vec<Vec<EnumDataValue>> = Vec::new();
+  for (index, (bucket_start, row)) in buckets.iter().enumerate() {
+    result.push(with_timestamp(row, ts_column, *bucket_start)?);
+
+    let next: Option<&(i64, Vec<EnumDataValue>)> = buckets.get(index + 1);
+    let next_start: i64 = next.map_or(*bucket_start, |(next_start, _)| *next_start);
+
+    let mut missing_start: i64 = bucket_start + bucket;
+    while missing_start < next_start {
+      let filled: Vec<EnumDataValue> = match (method, next) {
+        (GapfillMethod::Linear, Some((_, next_row))) => {
+          let fraction: f64 = (missing_start - bucket_start) as f64 / (next_start - bucket_start) as f64;
+          interpolate_row(row, next_row, fraction)
+        },
+        _ => row.clone(),
+      };
+      result.push(with_timestamp(&filled, ts_column, missing_start)?);
+      missing_start += bucket;
+    }
+  }
+
+  Ok(result)
+}
+
+// Groups rows into time buckets, the bucket start keeps the timestamp column's type. Only one running
+// state per bucket and column is kept, so memory grows with the number of buckets and not with the rows
+struct StreamingAggregate<'a> {
+  ts_column: usize,
+  columns: &'a [usize],
+  function: &'a AggregationFunction,
+  bucket: i64,
+  buckets: BTreeMap<i64, (EnumDataValue, Vec<AggregateState>)>,
+}
+
+impl<'a> StreamingAggregate<'a> {
+  fn new(ts_column: usize, columns: &'a [usize], function: &'a AggregationFunction, bucket: i64) -> Result<Self, String> {
+    if bucket <= 0 {
+      return Err("Aggregate time_bucket must be at least one unit of the timestamp column".to_string());
+    }
+
+    Ok(StreamingAggregate { ts_column, columns, function, bucket, buckets: BTreeMap::new() })
+  }
+
+  fn push(&mut self, row: &[EnumDataValue]) -> Result<(), String> {
+    let ts_value: &EnumDataValue = row.get(self.ts_column)
+      .ok_or_else(|| "Timestamp column is out of range".to_string())?;
+    let timestamp: i64 = ts_value.as_i64()
+      .ok_or_else(|| "Timestamp column value is not an integer".to_string())?;
+    let bucket_start: i64 = timestamp - timestamp.rem_euclid(self.bucket);
+
+    let (_, states) = match self.buckets.entry(bucket_start) {
+      Entry::Occupied(entry) => entry.into_mut(),
+      Entry::Vacant(entry) => {
+        let start_value: EnumDataValue = ts_value.with_f64(bucket_start as f64)
+          .ok_or_else(|| "Timestamp column value is not an integer".to_string())?;
+        entry.insert((start_value, vec![AggregateState::default(); self.columns.len()]))
+      },
+    };
+    for (state, column) in states.iter_mut().zip(self.columns) {
+      let value: &EnumDataValue = row.get(*column)
+        .ok_or_else(|| format!("Aggregate column {} is out of range", column))?;
+      state.push(value, self.function)?;
+    }
+
+    Ok(())
+  }
+
+  // Folds in a partial aggregate over other rows with the same plan, bucket by bucket
+  fn merge(&mut self, other: StreamingAggregate) {
+    for (bucket_start, (start_value, states)) in other.buckets {
+      match self.buckets.entry(bucket_start) {
+        Entry::Occupied(mut entry) => {
+          for (state, other_state) in entry.get_mut().1.iter_mut().zip(states) {
+            state.merge(other_state);
+          }
+        },
+        Entry::Vacant(entry) => {
+          entry.insert((start_value, states));
         },
-        Err(_) => return Err("Failed to fetch row".to_string()),
       }
     }
+  }
 
-    Ok(result)
+  #[cfg(test)]
+  fn bucket_count(&self) -> usize {
+    self.buckets.len()
+  }
+
+  fn finish(self) -> Result<Vec<Vec<EnumDataValue>>, String> {
+    let function: &AggregationFunction = self.function;
+    Ok(self.buckets.into_values()
+      .map(|(start_value, states)| {
+        let mut row: Vec<EnumDataValue> = vec![start_value];
+        row.extend(states.into_iter().map(|state: AggregateState| state.finish(function)));
+        row
+      })
+      .collect())
+  }
+}
+
+// Running aggregate of one column, partial states built over separate segments merge into the state of
+// all of them. Count is a UInt64, Sum and Avg are Float64, Min and Max keep the column's type
+#[derive(Clone, Default)]
+pub struct AggregateState {
+  count: u64,
+  sum: f64,
+  min: Option<(f64, EnumDataValue)>,
+  max: Option<(f64, EnumDataValue)>,
+}
+
+impl AggregateState {
+  pub fn push(&mut self, value: &EnumDataValue, function: &AggregationFunction) -> Result<(), String> {
+    self.count += 1;
+    if let AggregationFunction::Count = function {
+      return Ok(());
+    }
+
+    let number: f64 = value.as_f64()
+      .ok_or_else(|| format!("Cannot aggregate non numeric value {}", value))?;
+    self.sum += number;
+    if self.min.as_ref().is_none_or(|(min, _)| number < *min) {
+      self.min = Some((number, value.clone()));
+    }
+    if self.max.as_ref().is_none_or(|(max, _)| number > *max) {
+      self.max = Some((number, value.clone()));
+    }
+
+    Ok(())
+  }
+
+  // Avg merges through the summed totals and counts, ties on Min and Max keep self's value
+  pub fn merge(&mut self, other: AggregateState) {
+    self.count += other.count;
+    self.sum += other.sum;
+    if let Some((number, value)) = other.min {
+      if self.min.as_ref().is_none_or(|(min, _)| number < *min) {
+        self.min = Some((number, value));
+      }
+    }
+    if let Some((number, value)) = other.max {
+      if self.max.as_ref().is_none_or(|(max, _)| number > *max) {
+        self.max = Some((number, value));
+      }
+    }
+  }
+
+  // Only called on states that received at least one value
+  pub fn finish(self, function: &AggregationFunction) -> EnumDataValue {
+    match function {
+      AggregationFunction::Count => EnumDataValue::UInt64Value(self.count),
+      AggregationFunction::Sum => EnumDataValue::Float64Value(self.sum),
+      AggregationFunction::Avg => EnumDataValue::Float64Value(self.sum / self.count as f64),
+      AggregationFunction::Max => self.max.map(|(_, value)| value).unwrap_or(EnumDataValue::Float64Value(f64::NAN)),
+      AggregationFunction::Min => self.min.map(|(_, value)| value).unwrap_or(EnumDataValue::Float64Value(f64::NAN)),
+    }
+  }
+}
+
+fn with_timestamp(row: &[EnumDataValue], ts_column: usize, timestamp: i64) -> Result<Vec<EnumDataValue>, String> {
+  let mut row: Vec<EnumDataValue> = row.to_vec();
+  row[ts_column] = row[ts_column].with_f64(timestamp as f64)
+    .ok_or_else(|| "Timestamp column value is not an integer".to_string())?;
+  Ok(row)
+}
+
+// Non numeric columns are carried forward from the earlier row
+fn interpolate_row(previous: &[EnumDataValue], next: &[EnumDataValue], fraction: f64) -> Vec<EnumDataValue> {
+  previous.iter().zip(next)
+    .map(|(previous_value, next_value)| {
+      match (previous_value.as_f64(), next_value.as_f64()) {
+        (Some(start), Some(end)) => previous_value.with_f64(start + (end - start) * fraction)
+          .unwrap_or_else(|| previous_value.clone()),
+        _ => previous_value.clone(),
+      }
+    })
+    .collect()
+}
+
+// K-way merge of timestamp ordered inputs into sink, ties go to the earlier input. Only the next row of
+// every input is held
+async fn merge_by_timestamp(mut inputs: Vec<(RowStream, usize)>, mut sink: impl FnMut(Vec<EnumDataValue>) -> Result<(), String>) -> Result<(), String> {
+  let mut heads: Vec<Option<Vec<EnumDataValue>>> = vec![None; inputs.len()];
+  let mut heap: BinaryHeap<Reverse<(i64, usize)>> = BinaryHeap::new();
+  for (input, (rows, ts_column)) in inputs.iter_mut().enumerate() {
+    if let Some((timestamp, row)) = next_timestamped(rows, *ts_column).await? {
+      heads[input] = Some(row);
+      heap.push(Reverse((timestamp, input)));
+    }
+  }
+
+  while let Some(Reverse((_, input))) = heap.pop() {
+    if let Some(row) = heads[input].take() {
+      sink(row)?;
+    }
+
+    let (rows, ts_column): &mut (RowStream, usize) = &mut inputs[input];
+    if let Some((timestamp, row)) = next_timestamped(rows, *ts_column).await? {
+      heads[input] = Some(row);
+      heap.push(Reverse((timestamp, input)));
+    }
+  }
+
+  Ok(())
+}
+
+async fn next_timestamped(rows: &mut RowStream, ts_column: usize) -> Result<Option<(i64, Vec<EnumDataValue>)>, String> {
+  let row: Vec<EnumDataValue> = match rows.next().await {
+    Some(row_result) => row_result?.values,
+    None => return Ok(None),
+  };
+  let timestamp: i64 = row.get(ts_column)
+    .and_then(|value: &EnumDataValue| value.as_i64())
+    .ok_or_else(|| "Timestamp column value is not an integer".to_string())?;
+
+  Ok(Some((timestamp, row)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::{tempdir, TempDir};
+  use crate::tsf::segments::segment_column_data::SegmentColumnData;
+  use crate::tsf::segments::types::{EnumDataComp, EnumDataEnc, EnumDataType};
+  use crate::tsf::tsf_writer::TSFWriter;
+  use crate::test_support::append_segment;
+  use crate::executors::physical_plan::{FilterPredicate, JoinCondition, JoinType};
+
+  fn write_table(dir: &TempDir, name: &str, metric_time: Vec<i32>, temperatures: Vec<i8>) -> Result<String, String> {
+    let file_path: String = dir.path().join(name).to_str().unwrap().to_string();
+    append_segment(&file_path, metric_time, temperatures).map_err(|e: io::Error| e.to_string())?;
+
+    Ok(file_path)
+  }
+
+  // Same layout as write_table with a DateTime64 ts column, so timestamps are milliseconds
+  fn write_millis_table(dir: &TempDir, name: &str, metric_time: Vec<i64>, temperatures: Vec<i8>) -> Result<String, String> {
+    let file_path: String = dir.path().join(name).to_str().unwrap().to_string();
+    let mut writer: TSFWriter = TSFWriter::new(&file_path).map_err(|e: io::Error| e.to_string())?;
+    writer.add_column_header("metric_time", EnumDataType::DateTime64, EnumDataEnc::None, EnumDataComp::None, true)?;
+    writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false)?;
+    writer.update_segment_dates(*metric_time.first().unwrap(), *metric_time.last().unwrap());
+    writer.add_segment_column_data(SegmentColumnData::from_column_data(EnumColumnData::DateTime64Vec(metric_time), EnumDataEnc::None, EnumDataComp::None))?;
+    writer.add_column_data(temperatures, EnumDataEnc::None, EnumDataComp::None)?;
+    writer.try_save().map_err(|e: io::Error| e.to_string())?;
+
+    Ok(file_path)
+  }
+
+  #[tokio::test]
+  async fn test_union_scan_merges_by_timestamp() -> Result<(), String> {
+    let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+    let day_one: String = write_table(&dir, "day_one.tsf", vec![1, 4, 5, 9], vec![10, 40, 50, 90])?;
+    let day_two: String = write_table(&dir, "day_two.tsf", vec![2, 3], vec![20, 30])?;
+    append_segment(&day_two, vec![6, 7], vec![60, 70]).map_err(|e: io::Error| e.to_string())?;
+    let union = || PhysicalPlan {
+      root_operator: PhysicalOperator::UnionScan { tables: vec![day_one.clone(), day_two.clone()] }
+    };
+
+    let executor: Executor = Executor::new();
+    for result in [executor.execute(union()).await?, executor.execute_async(union()).await?] {
+      let timestamps: Vec<i64> = result.iter().map(|row| row[0].as_i64().unwrap()).collect();
+      let temperatures: Vec<i64> = result.iter().map(|row| row[1].as_i64().unwrap()).collect();
+      assert_eq!(timestamps, vec![1, 2, 3, 4, 5, 6, 7, 9]);
+      assert_eq!(temperatures, vec![10, 20, 30, 40, 50, 60, 70, 90]);
+    }
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_execute_stream_yields_rows_before_the_scan_finishes() -> Result<(), String> {
+    let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+    let table: String = write_table(&dir, "streamed.tsf", vec![1, 2, 3], vec![10, 20, 30])?;
+    append_segment(&table, vec![4, 5, 6], vec![40, 50, 60]).map_err(|e: io::Error| e.to_string())?;
+    // Garbage where a third segment would start, only reached once the first two are drained
+    let mut file: std::fs::File = std::fs::OpenOptions::new().append(true).open(&table).map_err(|e: io::Error| e.to_string())?;
+    std::io::Write::write_all(&mut file, &[0xFF; 64]).map_err(|e: io::Error| e.to_string())?;
+
+    let plan: PhysicalPlan = PhysicalPlan {
+      root_operator: PhysicalOperator::limit(PhysicalOperator::Filter {
+        input: Box::new(PhysicalOperator::Scan { columns: vec![], table_name: table.clone(), time_range: None }),
+        column: 1,
+        predicate: FilterPredicate::Gt(EnumDataValue::Int8Value(10)),
+      }, 1, None)?,
+    };
+    let mut rows: RowStream = Executor::new().execute_stream(plan).await?;
+
+    let mut timestamps: Vec<i64> = Vec::new();
+    while let Some(Ok(row)) = rows.next().await {
+      timestamps.push(row.values[0].as_i64().unwrap());
+    }
+    assert_eq!(timestamps, vec![3, 4, 5, 6]);
+
+    // The bad bytes only surface after every good row has been handed out
+    let plan: PhysicalPlan = PhysicalPlan::scan_all(&table).map_err(|e: io::Error| e.to_string())?;
+    let results: Vec<Result<DataRow, String>> = Executor::new().execute_stream(plan).await?.collect().await;
+    assert_eq!(results.len(), 7);
+    assert!(results[..6].iter().all(|row: &Result<DataRow, String>| row.is_ok()));
+    assert_eq!(results[6].as_ref().err(), Some(&"Failed to fetch row".to_string()));
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_filter_between_on_timestamp_and_value_columns() -> Result<(), String> {
+    let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+    let table: String = write_table(&dir, "between.tsf", vec![10, 20, 30, 40, 50], vec![-5, 0, 5, 10, 15])?;
+
+    let filter = |column: usize, predicate: FilterPredicate| PhysicalPlan {
+      root_operator: PhysicalOperator::Filter {
+        input: Box::new(PhysicalOperator::Scan { columns: vec![], table_name: table.clone(), time_range: None }),
+        column,
+        predicate,
+      }
+    };
+
+    let executor: Executor = Executor::new();
+    let timestamps = |rows: Vec<Vec<EnumDataValue>>| -> Vec<i64> { rows.iter().map(|row| row[0].as_i64().unwrap()).collect() };
+
+    // Bounds are inclusive and need not share the column's type
+    let on_timestamp: Vec<Vec<EnumDataValue>> = executor.execute(filter(0, FilterPredicate::Between(EnumDataValue::Int64Value(20), EnumDataValue::Int64Value(40)))).await?;
+    assert_eq!(timestamps(on_timestamp), vec![20, 30, 40]);
+
+    let on_value: Vec<Vec<EnumDataValue>> = executor.execute_async(filter(1, FilterPredicate::Between(EnumDataValue::Float64Value(-0.5), EnumDataValue::Int8Value(10)))).await?;
+    assert_eq!(timestamps(on_value), vec![20, 30, 40]);
+
+    let empty: Vec<Vec<EnumDataValue>> = executor.execute(filter(1, FilterPredicate::Between(EnumDataValue::Int8Value(10), EnumDataValue::Int8Value(0)))).await?;
+    assert!(empty.is_empty());
+
+    let err: String = executor.execute(filter(1, FilterPredicate::Between(EnumDataValue::BooleanValue(false), EnumDataValue::BooleanValue(true)))).await.unwrap_err();
+    assert_eq!(err, "Cannot compare -5 with false");
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_projected_scan_with_time_range() -> Result<(), String> {
+    let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+    let table: String = write_table(&dir, "projected.tsf", vec![10, 20, 30, 40], vec![1, 2, 3, 4])?;
+    let scan = |columns: &[&str]| -> Result<PhysicalPlan, String> {
+      let columns: Vec<String> = columns.iter().map(|name: &&str| name.to_string()).collect();
+      Ok(PhysicalPlan { root_operator: PhysicalOperator::scan_epochs(columns, table.clone(), Some((20, 30)))? })
+    };
+
+    let executor: Executor = Executor::new();
+    let text = |rows: Vec<Vec<EnumDataValue>>| -> Vec<String> {
+      rows.iter().map(|row| row.iter().map(|value: &EnumDataValue| value.to_string()).collect::<Vec<String>>().join(",")).collect()
+    };
+
+    let rows: Vec<Vec<EnumDataValue>> = executor.execute(scan(&["temperature", "metric_time"])?).await?;
+    assert_eq!(text(rows), vec!["2,20", "3,30"]);
+
+    // The range still applies when the ts column isn't projected
+    let rows: Vec<Vec<EnumDataValue>> = executor.execute_async(scan(&["temperature"])?).await?;
+    assert_eq!(text(rows), vec!["2", "3"]);
+
+    let result: QueryResult = executor.execute_columnar(scan(&["metric_time"])?).await?;
+    assert_eq!(result.schema.len(), 1);
+    assert!(matches!(result.column("metric_time"), Some(EnumColumnData::Int32Vec(values)) if *values == vec![20, 30]));
+
+    let err: String = executor.execute(scan(&["humidity"])?).await.unwrap_err();
+    assert_eq!(err, format!("Column humidity not found in table {}", table));
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_scan_covers_every_segment() -> Result<(), String> {
+    let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+    let table: String = write_table(&dir, "segments.tsf", vec![10, 20], vec![1, 2])?;
+    append_segment(&table, vec![30, 40], vec![3, 4]).map_err(|e: io::Error| e.to_string())?;
+    append_segment(&table, vec![50, 60], vec![5, 6]).map_err(|e: io::Error| e.to_string())?;
+    let scan = || -> Result<PhysicalPlan, String> {
+      Ok(PhysicalPlan { root_operator: PhysicalOperator::scan_epochs(vec!["temperature".to_string()], table.clone(), Some((20, 50)))? })
+    };
+
+    let executor: Executor = Executor::new();
+    let temperatures = |rows: Vec<Vec<EnumDataValue>>| -> Vec<i64> { rows.iter().map(|row| row[0].as_i64().unwrap()).collect() };
+
+    let rows: Vec<Vec<EnumDataValue>> = executor.execute(scan()?).await?;
+    assert_eq!(temperatures(rows), vec![2, 3, 4, 5]);
+
+    let rows: Vec<Vec<EnumDataValue>> = executor.execute_async(scan()?).await?;
+    assert_eq!(temperatures(rows), vec![2, 3, 4, 5]);
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_columnar_result_matches_table_columns() -> Result<(), String> {
+    let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+    let table: String = write_table(&dir, "columnar.tsf", vec![10, 20, 30, 40], vec![1, 2, 3, 4])?;
+    let scan = |time_range: Option<(i64, i64)>| -> Result<PhysicalPlan, String> {
+      Ok(PhysicalPlan { root_operator: PhysicalOperator::scan_epochs(vec![], table.clone(), time_range)? })
+    };
+
+    let executor: Executor = Executor::new();
+    let result: QueryResult = executor.execute_columnar(scan(None)?).await?;
+    let names: Vec<&str> = result.schema.iter().map(|column: &ColumnSchema| column.name.as_str()).collect();
+    assert_eq!(names, vec!["metric_time", "temperature"]);
+    assert!(matches!(result.column("metric_time"), Some(EnumColumnData::Int32Vec(values)) if *values == vec![10, 20, 30, 40]));
+    assert!(matches!(result.column("temperature"), Some(EnumColumnData::Int8Vec(values)) if *values == vec![1, 2, 3, 4]));
+    assert!(result.column("humidity").is_none());
+
+    // The time range is applied to the columns directly
+    let ranged: QueryResult = executor.execute_columnar_async(scan(Some((20, 30)))?).await?;
+    assert!(matches!(ranged.column("temperature"), Some(EnumColumnData::Int8Vec(values)) if *values == vec![2, 3]));
+
+    // Anything above a scan is transposed from its rows, and iterating rows gives them back
+    let sampled: QueryResult = executor.execute_columnar(PhysicalPlan {
+      root_operator: PhysicalOperator::Sample { input: Box::new(scan(None)?.root_operator), stride: 2 }
+    }).await?;
+    assert_eq!(sampled.row_count(), 2);
+    assert!(matches!(sampled.column("temperature"), Some(EnumColumnData::Int8Vec(values)) if *values == vec![1, 3]));
+    let rows: Vec<Vec<EnumDataValue>> = sampled.rows().collect();
+    assert!(matches!(rows[1].as_slice(), [EnumDataValue::Int32Value(30), EnumDataValue::Int8Value(3)]));
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_sample_every_third_row() -> Result<(), String> {
+    let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+    let table: String = write_table(&dir, "sample.tsf", (0..10).collect(), (0..10).collect())?;
+
+    let plan: PhysicalPlan = PhysicalPlan {
+      root_operator: PhysicalOperator::Sample {
+        input: Box::new(PhysicalOperator::Scan {
+          columns: vec!["metric_time".to_string(), "temperature".to_string()],
+          table_name: table,
+          time_range: None,
+        }),
+        stride: 3,
+      }
+    };
+
+    let executor: Executor = Executor::new();
+    let result: Vec<Vec<EnumDataValue>> = executor.execute(plan).await?;
+
+    let timestamps: Vec<i64> = result.iter().map(|row| row[0].as_i64().unwrap()).collect();
+    assert_eq!(timestamps, vec![0, 3, 6, 9]);
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_gapfill_carries_previous_value_forward() -> Result<(), String> {
+    let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+    // The 120 bucket has no readings
+    let table: String = write_table(&dir, "gaps.tsf", vec![0, 65, 185], vec![10, 20, 40])?;
+
+    let gapfill = |method: GapfillMethod| PhysicalPlan {
+      root_operator: PhysicalOperator::Gapfill {
+        input: Box::new(PhysicalOperator::Scan {
+          columns: vec!["metric_time".to_string(), "temperature".to_string()],
+          table_name: table.clone(),
+          time_range: None,
+        }),
+        ts_column: 0,
+        bucket: chrono::Duration::seconds(60),
+        method,
+      }
+    };
+
+    let executor: Executor = Executor::new();
+    let result: Vec<Vec<EnumDataValue>> = executor.execute(gapfill(GapfillMethod::Previous)).await?;
+
+    let timestamps: Vec<i64> = result.iter().map(|row| row[0].as_i64().unwrap()).collect();
+    let temperatures: Vec<i64> = result.iter().map(|row| row[1].as_i64().unwrap()).collect();
+    assert_eq!(timestamps, vec![0, 60, 120, 180]);
+    assert_eq!(temperatures, vec![10, 20, 20, 40]);
+
+    let result: Vec<Vec<EnumDataValue>> = executor.execute(gapfill(GapfillMethod::Linear)).await?;
+    let temperatures: Vec<i64> = result.iter().map(|row| row[1].as_i64().unwrap()).collect();
+    assert_eq!(temperatures, vec![10, 20, 30, 40]);
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_gapfill_buckets_datetime64_in_milliseconds() -> Result<(), String> {
+    let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+    let table: String = write_millis_table(&dir, "millis_gaps.tsf", vec![0, 65_000, 185_000], vec![10, 20, 40])?;
+
+    let gapfill = || PhysicalPlan {
+      root_operator: PhysicalOperator::Gapfill {
+        input: Box::new(PhysicalOperator::Scan { columns: vec![], table_name: table.clone(), time_range: None }),
+        ts_column: 0,
+        bucket: chrono::Duration::seconds(60),
+        method: GapfillMethod::Previous,
+      }
+    };
+
+    let executor: Executor = Executor::new();
+    for result in [executor.execute(gapfill()).await?, executor.execute_async(gapfill()).await?] {
+      let timestamps: Vec<i64> = result.iter().map(|row| row[0].as_i64().unwrap()).collect();
+      let temperatures: Vec<i64> = result.iter().map(|row| row[1].as_i64().unwrap()).collect();
+      assert_eq!(timestamps, vec![0, 60_000, 120_000, 180_000]);
+      assert_eq!(temperatures, vec![10, 20, 20, 40]);
+    }
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_scan_with_epoch_time_range() -> Result<(), String> {
+    let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+    let table: String = write_table(&dir, "range.tsf", vec![1710555318, 1710555378, 1710555438, 1710555498], vec![10, 20, 30, 40])?;
+
+    let scan = || PhysicalOperator::scan_epochs(
+      vec!["metric_time".to_string(), "temperature".to_string()],
+      table.clone(),
+      Some((1710555378, 1710555438)),
+    );
+
+    let executor: Executor = Executor::new();
+    for result in [
+      executor.execute(PhysicalPlan { root_operator: scan()? }).await?,
+      executor.execute_async(PhysicalPlan { root_operator: scan()? }).await?,
+    ] {
+      let temperatures: Vec<i64> = result.iter().map(|row| row[1].as_i64().unwrap()).collect();
+      assert_eq!(temperatures, vec![20, 30]);
+    }
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_aggregate_avg_per_minute_with_bucket_start() -> Result<(), String> {
+    let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+    let table: String = write_table(&dir, "minutes.tsf", vec![0, 30, 60, 90, 150], vec![10, 20, 30, 50, 7])?;
+
+    let plan: PhysicalPlan = PhysicalPlan {
+      root_operator: PhysicalOperator::Aggregate {
+        input: Box::new(PhysicalOperator::Scan {
+          columns: vec!["metric_time".to_string(), "temperature".to_string()],
+          table_name: table,
+          time_range: None,
+        }),
+        ts_column: 0,
+        columns: vec![1],
+        function: AggregationFunction::Avg,
+        time_bucket: chrono::Duration::minutes(1),
+      }
+    };
+
+    let executor: Executor = Executor::new();
+    let result: Vec<Vec<EnumDataValue>> = executor.execute(plan).await?;
+
+    let rows: Vec<(i64, f64)> = result.iter().map(|row| (row[0].as_i64().unwrap(), row[1].as_f64().unwrap())).collect();
+    assert_eq!(rows, vec![(0, 15.0), (60, 40.0), (120, 7.0)]);
+    assert!(matches!(result[0][0], EnumDataValue::Int32Value(0)));
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_aggregate_buckets_datetime64_in_milliseconds() -> Result<(), String> {
+    let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+    let table: String = write_millis_table(&dir, "millis.tsf", vec![0, 30_000, 60_000, 90_000, 150_000], vec![10, 20, 30, 50, 7])?;
+
+    let aggregate = || PhysicalPlan {
+      root_operator: PhysicalOperator::Aggregate {
+        input: Box::new(PhysicalOperator::Scan { columns: vec![], table_name: table.clone(), time_range: None }),
+        ts_column: 0,
+        columns: vec![1],
+        function: AggregationFunction::Avg,
+        time_bucket: chrono::Duration::minutes(1),
+      }
+    };
+
+    let executor: Executor = Executor::new();
+    for result in [executor.execute(aggregate()).await?, executor.execute_async(aggregate()).await?] {
+      let rows: Vec<(i64, f64)> = result.iter().map(|row| (row[0].as_i64().unwrap(), row[1].as_f64().unwrap())).collect();
+      assert_eq!(rows, vec![(0, 15.0), (60_000, 40.0), (120_000, 7.0)]);
+      assert!(matches!(result[0][0], EnumDataValue::DateTime64Value(0)));
+    }
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_scan_without_timestamp_column() -> Result<(), String> {
+    let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+    let table: String = dir.path().join("no_ts.tsf").to_str().unwrap().to_string();
+
+    let mut writer: TSFWriter = TSFWriter::new(&table).map_err(|e: io::Error| e.to_string())?;
+    writer.add_column_header("sensor", EnumDataType::Int16, EnumDataEnc::None, EnumDataComp::None, false)?;
+    writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false)?;
+    writer.add_column_data(vec![1i16, 2, 3], EnumDataEnc::None, EnumDataComp::None)?;
+    writer.add_column_data(vec![10i8, 20, 30], EnumDataEnc::None, EnumDataComp::None)?;
+    writer.update_segment_dates(0, 0);
+    writer.try_save().map_err(|e: io::Error| e.to_string())?;
+
+    let scan = |time_range: Option<(i64, i64)>| PhysicalOperator::scan_epochs(
+      vec!["sensor".to_string(), "temperature".to_string()],
+      table.clone(),
+      time_range,
+    );
+
+    let executor: Executor = Executor::new();
+    for result in [
+      executor.execute(PhysicalPlan { root_operator: scan(None)? }).await?,
+      executor.execute_async(PhysicalPlan { root_operator: scan(None)? }).await?,
+    ] {
+      let temperatures: Vec<i64> = result.iter().map(|row| row[1].as_i64().unwrap()).collect();
+      assert_eq!(temperatures, vec![10, 20, 30]);
+    }
+
+    // A time filter still needs somewhere to look
+    assert!(executor.execute(PhysicalPlan { root_operator: scan(Some((0, 1)))? }).await.is_err());
+
+    Ok(())
+  }
+
+  fn join_plan(left: &str, right: &str, condition: JoinCondition) -> PhysicalPlan {
+    let scan = |table_name: &str| Box::new(PhysicalOperator::Scan { columns: vec![], table_name: table_name.to_string(), time_range: None });
+    PhysicalPlan {
+      root_operator: PhysicalOperator::Join { join_type: JoinType::Inner, left: scan(left), right: scan(right), condition }
+    }
+  }
+
+  #[tokio::test]
+  async fn test_join_rejects_missing_column() -> Result<(), String> {
+    let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+    let left: String = write_table(&dir, "left.tsf", vec![1, 2], vec![10, 20])?;
+    let right: String = write_table(&dir, "right.tsf", vec![1, 2], vec![30, 40])?;
+
+    let executor: Executor = Executor::new();
+    let err: String = executor.execute(join_plan(&left, &right, JoinCondition::new("metric_time", "humidity"))).await.unwrap_err();
+    assert!(err.contains("humidity not found in right input"), "{}", err);
+    let err: String = executor.execute_async(join_plan(&left, &right, JoinCondition::new("sensor", "metric_time"))).await.unwrap_err();
+    assert!(err.contains("sensor not found in left input"), "{}", err);
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_join_rejects_incomparable_types() -> Result<(), String> {
+    let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+    let left: String = write_table(&dir, "left.tsf", vec![1, 2], vec![10, 20])?;
+
+    // There is no string column type yet, booleans are the other type integers cannot be compared with
+    let right: String = dir.path().join("flags.tsf").to_str().unwrap().to_string();
+    let mut writer: TSFWriter = TSFWriter::new(&right).map_err(|e: io::Error| e.to_string())?;
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)?;
+    writer.add_column_header("active", EnumDataType::Boolean, EnumDataEnc::None, EnumDataComp::None, false)?;
+    writer.add_column_data(vec![1i32, 2], EnumDataEnc::None, EnumDataComp::None)?;
+    writer.add_segment_column_data(SegmentColumnData::from_column_data(EnumColumnData::BooleanVec(vec![true, false]), EnumDataEnc::None, EnumDataComp::None))?;
+    writer.update_segment_dates(1, 2);
+    writer.try_save().map_err(|e: io::Error| e.to_string())?;
+
+    let executor: Executor = Executor::new();
+    let err: String = executor.execute(join_plan(&left, &right, JoinCondition::new("metric_time", "active"))).await.unwrap_err();
+    assert!(err.contains("Cannot join metric_time (Int32) with active (Boolean)"), "{}", err);
+
+    // Comparable columns get past validation
+    let err: String = executor.execute(join_plan(&left, &right, JoinCondition::new("metric_time", "metric_time"))).await.unwrap_err();
+    assert_eq!(err, "Not Implemented");
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_streaming_aggregate_keeps_one_state_per_bucket() -> Result<(), String> {
+    let columns: Vec<usize> = vec![1];
+    for function in [AggregationFunction::Count, AggregationFunction::Sum, AggregationFunction::Avg, AggregationFunction::Max, AggregationFunction::Min] {
+      // One bucket per hour over a million rows one second apart
+      let mut aggregate: StreamingAggregate = StreamingAggregate::new(0, &columns, &function, 3600)?;
+      for timestamp in 0..1_000_000i64 {
+        aggregate.push(&[EnumDataValue::Int64Value(timestamp), EnumDataValue::Int16Value((timestamp % 100) as i16)])?;
+        assert!(aggregate.bucket_count() as i64 <= timestamp / 3600 + 1);
+      }
+      assert_eq!(aggregate.bucket_count(), 278);
+
+      let result: Vec<Vec<EnumDataValue>> = aggregate.finish()?;
+      let first: f64 = result[0][1].as_f64().unwrap();
+      let expected: f64 = match function {
+        AggregationFunction::Count => 3600.0,
+        AggregationFunction::Sum => 36.0 * 4950.0,
+        AggregationFunction::Avg => 49.5,
+        AggregationFunction::Max => 99.0,
+        AggregationFunction::Min => 0.0,
+      };
+      assert_eq!(first, expected);
+      assert_eq!(result[277][0].as_i64(), Some(277 * 3600));
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_merged_segment_aggregates_match_single_pass() -> Result<(), String> {
+    let row = |timestamp: i32, value: i16| vec![EnumDataValue::Int32Value(timestamp), EnumDataValue::Int16Value(value)];
+    // The segments share the bucket starting at 60
+    let first: Vec<Vec<EnumDataValue>> = vec![row(0, 5), row(30, -2), row(60, 9)];
+    let second: Vec<Vec<EnumDataValue>> = vec![row(90, 9), row(100, 1), row(130, 4)];
+
+    let columns: Vec<usize> = vec![1];
+    for function in [AggregationFunction::Count, AggregationFunction::Sum, AggregationFunction::Avg, AggregationFunction::Max, AggregationFunction::Min] {
+      let aggregate = |rows: &[Vec<EnumDataValue>]| -> Result<StreamingAggregate, String> {
+        let mut aggregate: StreamingAggregate = StreamingAggregate::new(0, &columns, &function, 60)?;
+        for row in rows {
+          aggregate.push(row)?;
+        }
+        Ok(aggregate)
+      };
+
+      let mut merged: StreamingAggregate = aggregate(&first)?;
+      merged.merge(aggregate(&second)?);
+      let together: StreamingAggregate = aggregate(&[first.clone(), second.clone()].concat())?;
+
+      let render = |rows: Vec<Vec<EnumDataValue>>| -> Vec<String> {
+        rows.iter().map(|row| format!("{:?}", row)).collect()
+      };
+      assert_eq!(render(merged.finish()?), render(together.finish()?));
+    }
+
+    Ok(())
   }
 }
@@ -1,2 +1,3 @@
 pub mod executor;
 pub mod physical_plan;
+pub mod query_result;
@@ -1,5 +1,8 @@
-use chrono::{DateTime, Duration, Utc};
+use std::{cmp::Ordering, io};
 
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+use crate::tsf::{segments::types::{ColumnSchema, EnumDataType, EnumDataValue}, tsf_reader::TSFReader};
 
 pub enum PhysicalOperator {
   Scan {
@@ -7,9 +10,39 @@ pub enum PhysicalOperator {
     table_name: String,
     time_range: Option<(DateTime<Utc>, DateTime<Utc>)>,
   },
+  // Merges several timestamp sorted tables into one timestamp sorted stream
+  UnionScan {
+    tables: Vec<String>,
+  },
+  // Emits the input rows whose value in column satisfies the predicate
+  Filter {
+    input: Box<PhysicalOperator>,
+    column: usize,
+    predicate: FilterPredicate,
+  },
+  // Emits every stride-th row of the input, starting with the first
+  Sample {
+    input: Box<PhysicalOperator>,
+    stride: usize,
+  },
+  // Skips the first offset rows of the input and emits up to limit of the rest, all of them when None
+  Limit {
+    input: Box<PhysicalOperator>,
+    offset: usize,
+    limit: Option<usize>,
+  },
+  // Emits one row per bucket between the first and last timestamp, filling empty buckets
+  Gapfill {
+    input: Box<PhysicalOperator>,
+    ts_column: usize,
+    bucket: Duration,
+    method: GapfillMethod,
+  },
+  // Emits one (bucket_start, aggregated column...) row per non empty time bucket
   Aggregate {
     input: Box<PhysicalOperator>,
-    columns: Vec<String>,
+    ts_column: usize,
+    columns: Vec<usize>,
     function: AggregationFunction,
     time_bucket: Duration,
   },
@@ -21,6 +54,37 @@ pub enum PhysicalOperator {
   }
 }
 
+impl PhysicalOperator {
+  // Scan bounded by epoch seconds, for callers like the CLI that only have raw timestamps
+  pub fn scan_epochs(columns: Vec<String>, table_name: String, time_range: Option<(i64, i64)>) -> Result<Self, String> {
+    let time_range: Option<(DateTime<Utc>, DateTime<Utc>)> = match time_range {
+      Some((start, end)) => Some((epoch_seconds_to_utc(start)?, epoch_seconds_to_utc(end)?)),
+      None => None,
+    };
+
+    Ok(PhysicalOperator::Scan { columns, table_name, time_range })
+  }
+
+  pub fn limit(input: PhysicalOperator, offset: usize, limit: Option<usize>) -> Result<Self, String> {
+    check_row_window(offset, limit)?;
+
+    Ok(PhysicalOperator::Limit { input: Box::new(input), offset, limit })
+  }
+}
+
+// A row window has to end within usize, for paging callers that take offset and limit from the user
+pub fn check_row_window(offset: usize, limit: Option<usize>) -> Result<(), String> {
+  match limit {
+    Some(limit) if offset.checked_add(limit).is_none() => Err(format!("Offset {} plus limit {} overflows", offset, limit)),
+    _ => Ok(()),
+  }
+}
+
+fn epoch_seconds_to_utc(epoch: i64) -> Result<DateTime<Utc>, String> {
+  Utc.timestamp_opt(epoch, 0).single()
+    .ok_or_else(|| format!("Epoch {} is out of range", epoch))
+}
+
 pub enum AggregationFunction {
   Count,
   Sum,
@@ -29,6 +93,43 @@ pub enum AggregationFunction {
   Min,
 }
 
+// Compared with EnumDataValue::compare, so integer and timestamp columns take bounds of any numeric type
+pub enum FilterPredicate {
+  Eq(EnumDataValue),
+  NotEq(EnumDataValue),
+  Lt(EnumDataValue),
+  LtEq(EnumDataValue),
+  Gt(EnumDataValue),
+  GtEq(EnumDataValue),
+  // Inclusive on both ends, like SQL BETWEEN
+  Between(EnumDataValue, EnumDataValue),
+}
+
+impl FilterPredicate {
+  pub fn matches(&self, value: &EnumDataValue) -> Result<bool, String> {
+    let compare = |bound: &EnumDataValue| -> Result<Ordering, String> {
+      value.compare(bound).ok_or_else(|| format!("Cannot compare {} with {}", value, bound))
+    };
+
+    Ok(match self {
+      FilterPredicate::Eq(bound) => compare(bound)? == Ordering::Equal,
+      FilterPredicate::NotEq(bound) => compare(bound)? != Ordering::Equal,
+      FilterPredicate::Lt(bound) => compare(bound)? == Ordering::Less,
+      FilterPredicate::LtEq(bound) => compare(bound)? != Ordering::Greater,
+      FilterPredicate::Gt(bound) => compare(bound)? == Ordering::Greater,
+      FilterPredicate::GtEq(bound) => compare(bound)? != Ordering::Less,
+      FilterPredicate::Between(low, high) => compare(low)? != Ordering::Less && compare(high)? != Ordering::Greater,
+    })
+  }
+}
+
+pub enum GapfillMethod {
+  // Carry the previous bucket's row forward
+  Previous,
+  // Interpolate numeric columns between the surrounding buckets
+  Linear,
+}
+
 pub enum JoinType {
   Inner,
   LeftOuter,
@@ -41,6 +142,71 @@ pub struct JoinCondition {
   right_column: String,
 }
 
+impl JoinCondition {
+  pub fn new(left_column: &str, right_column: &str) -> Self {
+    JoinCondition { left_column: left_column.to_string(), right_column: right_column.to_string() }
+  }
+
+  // Index of the join column on each side, checked before any rows are read
+  pub fn resolve(&self, left: &[ColumnSchema], right: &[ColumnSchema]) -> Result<(usize, usize), String> {
+    let left_index: usize = find_column(left, &self.left_column, "left")?;
+    let right_index: usize = find_column(right, &self.right_column, "right")?;
+
+    let left_type: EnumDataType = left[left_index].data_type;
+    let right_type: EnumDataType = right[right_index].data_type;
+    if !left_type.is_comparable_with(right_type) {
+      return Err(format!("Cannot join {} ({:?}) with {} ({:?})", self.left_column, left_type, self.right_column, right_type));
+    }
+
+    Ok((left_index, right_index))
+  }
+}
+
+fn find_column(schema: &[ColumnSchema], column_name: &str, side: &str) -> Result<usize, String> {
+  schema.iter().position(|column: &ColumnSchema| column.name == column_name)
+    .ok_or_else(|| format!("Join column {} not found in {} input", column_name, side))
+}
+
 pub struct PhysicalPlan {
   pub root_operator: PhysicalOperator,
 }
+
+impl PhysicalPlan {
+  // Scan selecting every column of the file in its own order, only the headers are read to find them
+  pub fn scan_all(path: &str) -> io::Result<PhysicalPlan> {
+    let mut reader: TSFReader = TSFReader::new(path)?;
+    let columns: Vec<String> = reader.read_schema()?.into_iter()
+      .map(|column: ColumnSchema| column.name)
+      .collect();
+
+    Ok(PhysicalPlan {
+      root_operator: PhysicalOperator::Scan { columns, table_name: path.to_string(), time_range: None },
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::NamedTempFile;
+  use crate::test_support::append_segment;
+
+  #[test]
+  fn test_scan_all_lists_file_columns_in_order() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    append_segment(file_path, vec![1710555318, 1710555319], vec![21, 22])?;
+
+    let plan: PhysicalPlan = PhysicalPlan::scan_all(file_path)?;
+    let PhysicalOperator::Scan { columns, table_name, time_range } = plan.root_operator else {
+      panic!("scan_all should build a Scan");
+    };
+    assert_eq!(columns, vec!["metric_time".to_string(), "temperature".to_string()]);
+    assert_eq!(table_name, file_path);
+    assert!(time_range.is_none());
+
+    assert!(PhysicalPlan::scan_all("/nonexistent/missing.tsf").is_err());
+
+    Ok(())
+  }
+}
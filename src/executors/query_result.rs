@@ -0,0 +1,63 @@
+use crate::tsf::segments::types::{ColumnSchema, EnumColumnData, EnumDataValue};
+
+// Column major query output, columns[i] holds every value of schema[i] and all columns have the same length
+#[derive(Debug, Clone)]
+pub struct QueryResult {
+  pub schema: Vec<ColumnSchema>,
+  pub columns: Vec<EnumColumnData>,
+}
+
+impl QueryResult {
+  pub fn new(schema: Vec<ColumnSchema>, columns: Vec<EnumColumnData>) -> Result<Self, String> {
+    if schema.len() != columns.len() {
+      return Err(format!("Schema has {} columns but {} were given", schema.len(), columns.len()));
+    }
+    for (column_schema, column) in schema.iter().zip(&columns) {
+      if column.data_type() != column_schema.data_type {
+        return Err(format!("Column {} is {:?} but holds {:?} data", column_schema.name, column_schema.data_type, column.data_type()));
+      }
+    }
+    if columns.windows(2).any(|pair: &[EnumColumnData]| pair[0].len() != pair[1].len()) {
+      return Err("Columns have different lengths".to_string());
+    }
+
+    Ok(QueryResult { schema, columns })
+  }
+
+  // Transposes row major executor output, every value has to match its column's type
+  pub fn from_rows(schema: Vec<ColumnSchema>, rows: Vec<Vec<EnumDataValue>>) -> Result<Self, String> {
+    let mut columns: Vec<EnumColumnData> = schema.iter()
+      .map(|column_schema: &ColumnSchema| EnumColumnData::from_enum_data_type(column_schema.data_type))
+      .collect();
+
+    for row in rows {
+      if row.len() != schema.len() {
+        return Err(format!("Row has {} values but the schema has {} columns", row.len(), schema.len()));
+      }
+      for ((column, column_schema), value) in columns.iter_mut().zip(&schema).zip(&row) {
+        if !column.push_value(value) {
+          return Err(format!("Value {} does not match column {} ({:?})", value, column_schema.name, column_schema.data_type));
+        }
+      }
+    }
+
+    Ok(QueryResult { schema, columns })
+  }
+
+  pub fn row_count(&self) -> usize {
+    self.columns.first().map_or(0, EnumColumnData::len)
+  }
+
+  pub fn column(&self, name: &str) -> Option<&EnumColumnData> {
+    self.schema.iter()
+      .position(|column_schema: &ColumnSchema| column_schema.name == name)
+      .and_then(|index: usize| self.columns.get(index))
+  }
+
+  // Rows built on demand, for callers that still want the row major view
+  pub fn rows(&self) -> impl Iterator<Item = Vec<EnumDataValue>> + '_ {
+    (0..self.row_count()).map(move |index: usize| {
+      self.columns.iter().filter_map(|column: &EnumColumnData| column.value_at(index)).collect()
+    })
+  }
+}
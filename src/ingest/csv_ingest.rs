@@ -0,0 +1,368 @@
+use std::{fmt::Display, fs::File, io::{self, BufReader, Read}, str::FromStr};
+
+use csv::ReaderBuilder;
+use tracing::{trace, warn};
+
+use crate::tsf::tsf_writer::TSFWriter;
+use crate::tsf::segments::segment_column_data::SegmentColumnData;
+use crate::tsf::segments::types::{EnumColumnData, EnumDataEnc, EnumDataComp, EnumDataType};
+
+#[derive(Debug, Clone, Default)]
+pub struct IngestOptions {
+  // Timestamp value the source uses for a missing timestamp
+  pub null_timestamp: Option<i64>,
+  // CSV column holding the timestamp, ignored when no_timestamp is set
+  pub timestamp_col_index: usize,
+  // Ingest every column as a value column and leave the segment without a timestamp column
+  pub no_timestamp: bool,
+  // DateTime32 for epoch seconds or DateTime64 for epoch milliseconds, None keeps a plain Int32 column
+  pub timestamp_type: Option<EnumDataType>,
+  // Rows that fail to parse are logged and skipped, up to this many. 0 fails on the first bad row
+  pub max_errors: usize,
+}
+
+// Min and max timestamp, skipping the null sentinel. None when nothing real is left
+pub fn timestamp_range<T: Copy + Ord + Into<i64>>(timestamps: &[T], null_timestamp: Option<T>) -> Option<(i64, i64)> {
+  trace!("csv_ingest::timestamp_range");
+
+  let mut real_timestamps = timestamps.iter()
+    .copied()
+    .filter(|timestamp: &T| Some(*timestamp) != null_timestamp);
+
+  let first: T = real_timestamps.next()?;
+  let (min_date, max_date): (T, T) = real_timestamps
+    .fold((first, first), |(min, max), timestamp| (min.min(timestamp), max.max(timestamp)));
+
+  Some((min_date.into(), max_date.into()))
+}
+
+// Ingests a headerless CSV into a new TSF file. The timestamp column becomes metric_time and every other
+// column is a temperature reading, named temperature for the first and temperature_<csv index> after that
+pub fn ingest_csv(file_path: &str, input_file: &str, options: &IngestOptions) -> Result<(), String> {
+  trace!("csv_ingest::ingest_csv");
+
+  // Open the input CSV file
+  let csv_file: File = File::open(input_file).map_err(|e| e.to_string())?;
+  ingest_csv_reader(file_path, BufReader::new(csv_file), options)
+}
+
+// Same as ingest_csv but reads the CSV from any source, such as stdin in a pipeline
+pub fn ingest_csv_reader<R: Read>(file_path: &str, input: R, options: &IngestOptions) -> Result<(), String> {
+  trace!("csv_ingest::ingest_csv_reader");
+
+  let mut rdr: csv::Reader<R> = ReaderBuilder::new()
+    .has_headers(false)
+    .from_reader(input);
+
+  let ts_index: Option<usize> = if options.no_timestamp { None } else { Some(options.timestamp_col_index) };
+  let ts_type: EnumDataType = match options.timestamp_type {
+    None => EnumDataType::Int32,
+    Some(data_type @ (EnumDataType::DateTime32 | EnumDataType::DateTime64)) => data_type,
+    Some(data_type) => return Err(format!("Timestamp type must be DateTime32 or DateTime64, not {:?}", data_type)),
+  };
+
+  let mut metric_time: Vec<i64> = Vec::new();
+  // Value columns keyed by their CSV index, in CSV order
+  let mut temperatures: Vec<(usize, Vec<i8>)> = Vec::new();
+
+  let mut rejected_rows: usize = 0;
+
+  for (row_index, result) in rdr.records().enumerate() {
+    let record: csv::StringRecord = match result {
+      Ok(record) => record,
+      Err(e) => {
+        reject_row(format!("Row {}: {}", row_index, e), &mut rejected_rows, options.max_errors)?;
+        continue;
+      },
+    };
+
+    if temperatures.is_empty() && metric_time.is_empty() {
+      if let Some(ts_index) = ts_index.filter(|ts_index: &usize| *ts_index >= record.len()) {
+        return Err(format!("Timestamp column {} is out of range for {} CSV columns", ts_index, record.len()));
+      }
+      temperatures = (0..record.len())
+        .filter(|index: &usize| Some(*index) != ts_index)
+        .map(|index: usize| (index, Vec::new()))
+        .collect();
+    }
+
+    // Parse the whole row before pushing anything, so a skipped row leaves every column the same length
+    let time: Option<i64> = match ts_index.map(|ts_index: usize| parse_timestamp(&record, row_index, ts_index, ts_type)).transpose() {
+      Ok(time) => time,
+      Err(e) => {
+        reject_row(e, &mut rejected_rows, options.max_errors)?;
+        continue;
+      },
+    };
+    let values: Result<Vec<i8>, String> = temperatures.iter()
+      .enumerate()
+      .map(|(value_index, (csv_index, _)): (usize, &(usize, Vec<i8>))| parse_field(&record, row_index, *csv_index, &value_column_name(value_index, *csv_index)))
+      .collect();
+    let values: Vec<i8> = match values {
+      Ok(values) => values,
+      Err(e) => {
+        reject_row(e, &mut rejected_rows, options.max_errors)?;
+        continue;
+      },
+    };
+
+    metric_time.extend(time);
+    for ((_, column), value) in temperatures.iter_mut().zip(values) {
+      column.push(value);
+    }
+  }
+
+  let (min_date, max_date): (i64, i64) = match ts_index {
+    Some(_) => timestamp_range(&metric_time, options.null_timestamp)
+      .ok_or("Timestamp data should not be empty".to_string())?,
+    None if temperatures.first().is_some_and(|(_, column)| !column.is_empty()) => (0, 0),
+    None => return Err("CSV data should not be empty".to_string()),
+  };
+
+  let mut columns: Vec<(String, EnumColumnData)> = temperatures.into_iter()
+    .enumerate()
+    .map(|(value_index, (csv_index, column)): (usize, (usize, Vec<i8>))| (value_column_name(value_index, csv_index), EnumColumnData::Int8Vec(column)))
+    .collect();
+  if let Some(ts_index) = ts_index {
+    // Every value was checked against the type's range as it was parsed
+    let ts_data: EnumColumnData = match ts_type {
+      EnumDataType::DateTime64 => EnumColumnData::DateTime64Vec(metric_time),
+      EnumDataType::DateTime32 => EnumColumnData::DateTime32Vec(metric_time.into_iter().map(|time: i64| time as i32).collect()),
+      _ => EnumColumnData::Int32Vec(metric_time.into_iter().map(|time: i64| time as i32).collect()),
+    };
+    columns.insert(ts_index, ("metric_time".to_string(), ts_data));
+  }
+
+  let mut tsf_writer: TSFWriter = TSFWriter::new(file_path).map_err(|e| e.to_string())?;
+  for (index, (column_name, data)) in columns.into_iter().enumerate() {
+    tsf_writer.add_column_header(&column_name, data.data_type(), EnumDataEnc::None, EnumDataComp::None, Some(index) == ts_index)?;
+    tsf_writer.add_segment_column_data(SegmentColumnData::from_column_data(data, EnumDataEnc::None, EnumDataComp::None))?;
+  }
+
+  tsf_writer.update_segment_dates(min_date, max_date);
+
+  tsf_writer.try_save().map_err(|e: io::Error| e.to_string())?;
+
+  Ok(())
+}
+
+// Logs a row that failed to parse, or returns the error once more than max_errors rows were rejected
+fn reject_row(reason: String, rejected_rows: &mut usize, max_errors: usize) -> Result<(), String> {
+  *rejected_rows += 1;
+  if *rejected_rows > max_errors {
+    return Err(match max_errors {
+      0 => reason,
+      _ => format!("{} (more than {} rejected rows)", reason, max_errors),
+    });
+  }
+
+  warn!("Skipping rejected row, {}", reason);
+  Ok(())
+}
+
+// The timestamp has to fit the column type, DateTime64 takes any i64
+fn parse_timestamp(record: &csv::StringRecord, row_index: usize, ts_index: usize, ts_type: EnumDataType) -> Result<i64, String> {
+  let time: i64 = parse_field(record, row_index, ts_index, "metric_time")?;
+  if ts_type != EnumDataType::DateTime64 && i32::try_from(time).is_err() {
+    return Err(format!(
+      "Row {}, column metric_time: {} does not fit in {:?} (epoch seconds), millisecond timestamps need DateTime64",
+      row_index, time, ts_type,
+    ));
+  }
+
+  Ok(time)
+}
+
+fn value_column_name(value_index: usize, csv_index: usize) -> String {
+  if value_index == 0 { "temperature".to_string() } else { format!("temperature_{}", csv_index) }
+}
+
+// Parses one CSV field into the column's type, errors name the row, column and offending text
+fn parse_field<T>(record: &csv::StringRecord, row_index: usize, csv_index: usize, column_name: &str) -> Result<T, String>
+where
+  T: FromStr,
+  T::Err: Display,
+{
+  let text: &str = record.get(csv_index)
+    .ok_or_else(|| format!("Row {}: missing {} value", row_index, column_name))?;
+
+  text.parse::<T>()
+    .map_err(|e: T::Err| format!("Row {}, column {}: cannot parse {:?} as {}: {}", row_index, column_name, text, std::any::type_name::<T>(), e))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::tsf::tsf_reader::TSFReader;
+  use tempfile::{tempdir, TempDir};
+
+  fn ingest(csv: &str, options: &IngestOptions) -> Result<TSFReader, String> {
+    let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+    let input_file: String = dir.path().join("input.csv").to_str().unwrap().to_string();
+    let file_path: String = dir.path().join("output.tsf").to_str().unwrap().to_string();
+    std::fs::write(&input_file, csv).map_err(|e: io::Error| e.to_string())?;
+
+    ingest_csv(&file_path, &input_file, options)?;
+
+    let mut reader: TSFReader = TSFReader::new(&file_path).map_err(|e: io::Error| e.to_string())?;
+    reader.read_all().map_err(|e: io::Error| e.to_string())?;
+    Ok(reader)
+  }
+
+  #[test]
+  fn test_ingest_with_timestamp_in_last_column() -> Result<(), String> {
+    let options: IngestOptions = IngestOptions { timestamp_col_index: 1, ..Default::default() };
+    let reader: TSFReader = ingest("21,1710555318\n22,1710555319\n", &options)?;
+
+    assert_eq!(reader.get_ts_column(), Some(1));
+    assert_eq!(reader.get_column_header(0).unwrap().column_name, "temperature");
+    assert_eq!(reader.get_column_header(1).unwrap().column_name, "metric_time");
+    assert_eq!(reader.column_slice::<i8>(0).unwrap(), &[21, 22]);
+    assert_eq!(reader.column_slice::<i32>(1).unwrap(), &[1710555318, 1710555319]);
+    assert_eq!(reader.date_range(), Some((1710555318, 1710555319)));
+
+    // The timestamp index has to point at a CSV column
+    let options: IngestOptions = IngestOptions { timestamp_col_index: 2, ..Default::default() };
+    assert!(ingest("21,1710555318\n", &options).is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_ingest_without_timestamp() -> Result<(), String> {
+    let options: IngestOptions = IngestOptions { no_timestamp: true, ..Default::default() };
+    let reader: TSFReader = ingest("21,-5\n22,-6\n", &options)?;
+
+    assert_eq!(reader.get_ts_column(), None);
+    assert_eq!(reader.get_column_header(0).unwrap().column_name, "temperature");
+    assert_eq!(reader.get_column_header(1).unwrap().column_name, "temperature_1");
+    assert_eq!(reader.column_slice::<i8>(0).unwrap(), &[21, 22]);
+    assert_eq!(reader.column_slice::<i8>(1).unwrap(), &[-5, -6]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_ingest_from_reader() -> Result<(), String> {
+    let dir: TempDir = tempdir().map_err(|e: io::Error| e.to_string())?;
+    let file_path: String = dir.path().join("output.tsf").to_str().unwrap().to_string();
+    let stdin: io::Cursor<&[u8]> = io::Cursor::new(b"1710555318,21\n1710555319,22\n");
+
+    ingest_csv_reader(&file_path, stdin, &IngestOptions::default())?;
+
+    let mut reader: TSFReader = TSFReader::new(&file_path).map_err(|e: io::Error| e.to_string())?;
+    reader.read_all().map_err(|e: io::Error| e.to_string())?;
+    assert_eq!(reader.column_slice::<i32>(0).unwrap(), &[1710555318, 1710555319]);
+    assert_eq!(reader.column_slice::<i8>(1).unwrap(), &[21, 22]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_parse_error_names_row_and_column() {
+    let err: String = ingest("1710555318,21,5\n1710555319,22,300\n", &IngestOptions::default()).err().unwrap();
+    assert_eq!(err, "Row 1, column temperature_2: cannot parse \"300\" as i8: number too large to fit in target type");
+
+    let err: String = ingest("1710555318,21\nnow,22\n", &IngestOptions::default()).err().unwrap();
+    assert!(err.starts_with("Row 1, column metric_time: cannot parse \"now\" as i64"));
+  }
+
+  #[derive(Clone, Default)]
+  struct LogBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+  impl io::Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      self.0.lock().unwrap().extend_from_slice(buf);
+      Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn test_max_errors_skips_and_logs_bad_rows() -> Result<(), String> {
+    let csv: &str = "1710555318,21\nnow,22\n1710555320,300\n1710555321,24\n";
+    let logs: LogBuffer = LogBuffer::default();
+    let writer: LogBuffer = logs.clone();
+    let subscriber = tracing_subscriber::fmt().with_writer(move || writer.clone()).with_ansi(false).finish();
+
+    let options: IngestOptions = IngestOptions { max_errors: 5, ..Default::default() };
+    let reader: TSFReader = tracing::subscriber::with_default(subscriber, || ingest(csv, &options))?;
+    assert_eq!(reader.column_slice::<i32>(0).unwrap(), &[1710555318, 1710555321]);
+    assert_eq!(reader.column_slice::<i8>(1).unwrap(), &[21, 24]);
+    assert_eq!(reader.date_range(), Some((1710555318, 1710555321)));
+
+    let logs: String = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    assert_eq!(logs.matches("WARN").count(), 2);
+    assert!(logs.contains("Row 1, column metric_time: cannot parse \"now\""));
+    assert!(logs.contains("Row 2, column temperature: cannot parse \"300\""));
+
+    // One more bad row than allowed aborts the ingest
+    let options: IngestOptions = IngestOptions { max_errors: 1, ..Default::default() };
+    let err: String = ingest(csv, &options).err().unwrap();
+    assert!(err.starts_with("Row 2, column temperature"));
+    assert!(err.ends_with("(more than 1 rejected rows)"));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_timestamp_type_range_is_enforced() -> Result<(), String> {
+    let millis: &str = "1710555318000,21\n1710555319000,22\n";
+
+    let options: IngestOptions = IngestOptions { timestamp_type: Some(EnumDataType::DateTime32), ..Default::default() };
+    let err: String = ingest(millis, &options).err().unwrap();
+    assert_eq!(err, "Row 0, column metric_time: 1710555318000 does not fit in DateTime32 (epoch seconds), millisecond timestamps need DateTime64");
+
+    let options: IngestOptions = IngestOptions { timestamp_type: Some(EnumDataType::DateTime64), ..Default::default() };
+    let reader: TSFReader = ingest(millis, &options)?;
+    assert_eq!(reader.get_column_header(0).unwrap().column_type, EnumDataType::DateTime64);
+    assert_eq!(reader.column_slice::<i64>(0).unwrap(), &[1710555318000, 1710555319000]);
+    assert_eq!(reader.date_range(), Some((1710555318000, 1710555319000)));
+
+    let options: IngestOptions = IngestOptions { timestamp_type: Some(EnumDataType::DateTime32), ..Default::default() };
+    let reader: TSFReader = ingest("1710555318,21\n", &options)?;
+    assert_eq!(reader.get_column_header(0).unwrap().column_type, EnumDataType::DateTime32);
+
+    let options: IngestOptions = IngestOptions { timestamp_type: Some(EnumDataType::Float64), ..Default::default() };
+    assert!(ingest("1710555318,21\n", &options).is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_null_timestamps_are_ingested_but_left_out_of_the_range() -> Result<(), String> {
+    // A DateTime64 sentinel that doesn't fit in an i32
+    let csv: &str = "-9223372036854775808,20\n1710555318000,21\n1710555319000,22\n";
+    let options: IngestOptions = IngestOptions {
+      null_timestamp: Some(i64::MIN),
+      timestamp_type: Some(EnumDataType::DateTime64),
+      ..Default::default()
+    };
+    let reader: TSFReader = ingest(csv, &options)?;
+
+    assert_eq!(reader.column_slice::<i64>(0).unwrap(), &[i64::MIN, 1710555318000, 1710555319000]);
+    assert_eq!(reader.column_slice::<i8>(1).unwrap(), &[20, 21, 22]);
+    assert_eq!(reader.date_range(), Some((1710555318000, 1710555319000)));
+
+    let options: IngestOptions = IngestOptions { null_timestamp: Some(0), ..Default::default() };
+    let reader: TSFReader = ingest("0,20\n0,21\n1710555320,22\n", &options)?;
+    assert_eq!(reader.column_slice::<i32>(0).unwrap(), &[0, 0, 1710555320]);
+    assert_eq!(reader.column_slice::<i8>(1).unwrap(), &[20, 21, 22]);
+    assert_eq!(reader.date_range(), Some((1710555320, 1710555320)));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_null_timestamp_excluded_from_range() {
+    let timestamps: Vec<i32> = vec![1710555319, i32::MIN, 1710555318, 1710555320];
+
+    assert_eq!(timestamp_range(&timestamps, None), Some((i32::MIN as i64, 1710555320)));
+    assert_eq!(timestamp_range(&timestamps, Some(i32::MIN)), Some((1710555318, 1710555320)));
+    assert_eq!(timestamp_range(&[i32::MIN, i32::MIN], Some(i32::MIN)), None);
+  }
+}
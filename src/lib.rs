@@ -2,3 +2,8 @@
 
 pub mod tsf;
 pub mod executors;
+pub mod ingest;
+pub mod parsers;
+
+#[cfg(test)]
+pub(crate) mod test_support;
@@ -0,0 +1,347 @@
+use std::{iter::Peekable, str::Chars};
+
+use tracing::trace;
+
+use crate::executors::physical_plan::{FilterPredicate, PhysicalOperator, PhysicalPlan};
+use crate::tsf::segments::types::{ColumnSchema, EnumDataValue};
+
+// Parses the small SQL subset the executor can run into a Scan, one Filter per condition and a Limit:
+//
+//   SELECT * | col [, col...] FROM table
+//     [WHERE cond [AND cond...]] [LIMIT n [OFFSET m]]
+//
+// where cond is `time BETWEEN a AND b` on the timestamp column (epoch seconds, pushed into the scan),
+// `col BETWEEN a AND b` or `col op value` with op one of = != <> < <= > >=. Keywords are case insensitive,
+// table names with characters other than letters, digits, _ . / are single quoted. schema is the table's
+// columns, filter columns are resolved against it and have to be selected
+pub fn parse_query(sql: &str, schema: &[ColumnSchema]) -> Result<PhysicalPlan, String> {
+  trace!("parse_query");
+
+  let mut parser: Parser = Parser { tokens: tokenize(sql)?, pos: 0 };
+  parser.expect_keyword("SELECT")?;
+  let columns: Vec<String> = parser.parse_select_list()?;
+  for column_name in &columns {
+    if !schema.iter().any(|column: &ColumnSchema| &column.name == column_name) {
+      return Err(format!("Column {} not found", column_name));
+    }
+  }
+
+  parser.expect_keyword("FROM")?;
+  let table_name: String = match parser.next() {
+    Some(Token::Word(name)) | Some(Token::Quoted(name)) => name,
+    token => return Err(format!("Expected a table name after FROM, found {}", describe(token.as_ref()))),
+  };
+
+  let mut time_range: Option<(i64, i64)> = None;
+  let mut filters: Vec<(usize, FilterPredicate)> = Vec::new();
+  if parser.next_keyword("WHERE") {
+    loop {
+      let column_name: String = parser.expect_word()?;
+      if column_name.eq_ignore_ascii_case("time") && parser.peek_keyword("BETWEEN") {
+        parser.expect_keyword("BETWEEN")?;
+        let start: i64 = parser.expect_integer()?;
+        parser.expect_keyword("AND")?;
+        let end: i64 = parser.expect_integer()?;
+        if time_range.replace((start, end)).is_some() {
+          return Err("time BETWEEN is given more than once".to_string());
+        }
+      } else {
+        let column: usize = filter_column(&column_name, &columns, schema)?;
+        filters.push((column, parser.parse_predicate()?));
+      }
+
+      if !parser.next_keyword("AND") {
+        break;
+      }
+    }
+  }
+
+  let mut limit: Option<usize> = None;
+  let mut offset: usize = 0;
+  if parser.next_keyword("LIMIT") {
+    limit = Some(parser.expect_count()?);
+    if parser.next_keyword("OFFSET") {
+      offset = parser.expect_count()?;
+    }
+  }
+
+  parser.next_symbol(";");
+  if let Some(token) = parser.next() {
+    return Err(format!("Unexpected {} after the query", describe(Some(&token))));
+  }
+
+  let mut root_operator: PhysicalOperator = PhysicalOperator::scan_epochs(columns, table_name, time_range)?;
+  for (column, predicate) in filters {
+    root_operator = PhysicalOperator::Filter { input: Box::new(root_operator), column, predicate };
+  }
+  if limit.is_some() || offset > 0 {
+    root_operator = PhysicalOperator::limit(root_operator, offset, limit)?;
+  }
+
+  Ok(PhysicalPlan { root_operator })
+}
+
+// Index of the column in the rows the scan emits, every column for SELECT * or else the selected ones
+fn filter_column(column_name: &str, columns: &[String], schema: &[ColumnSchema]) -> Result<usize, String> {
+  let index: Option<usize> = match columns.is_empty() {
+    true => schema.iter().position(|column: &ColumnSchema| column.name == column_name),
+    false => columns.iter().position(|name: &String| name == column_name),
+  };
+
+  index.ok_or_else(|| match schema.iter().any(|column: &ColumnSchema| column.name == column_name) {
+    true => format!("Filter column {} has to be selected", column_name),
+    false => format!("Column {} not found", column_name),
+  })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  Word(String),
+  Quoted(String),
+  Number(String),
+  Symbol(&'static str),
+}
+
+// Reserved, so a missing column name is reported instead of the keyword being read as one
+const KEYWORDS: &[&str] = &["SELECT", "FROM", "WHERE", "BETWEEN", "AND", "LIMIT", "OFFSET"];
+
+const SYMBOLS: &[&str] = &["<=", ">=", "<>", "!=", "=", "<", ">", ",", "*", ";"];
+
+fn tokenize(sql: &str) -> Result<Vec<Token>, String> {
+  let mut tokens: Vec<Token> = Vec::new();
+  let mut chars: Peekable<Chars> = sql.chars().peekable();
+
+  while let Some(&c) = chars.peek() {
+    if c.is_whitespace() {
+      chars.next();
+    } else if c == '\'' {
+      chars.next();
+      let mut quoted: String = String::new();
+      loop {
+        match chars.next() {
+          Some('\'') => break,
+          Some(c) => quoted.push(c),
+          None => return Err("Unterminated quoted name".to_string()),
+        }
+      }
+      tokens.push(Token::Quoted(quoted));
+    } else if c.is_ascii_digit() || (c == '-' && tokens.last().is_none_or(|token: &Token| matches!(token, Token::Symbol(_) | Token::Word(_)))) {
+      let mut number: String = String::from(c);
+      chars.next();
+      while let Some(&c) = chars.peek().filter(|c: &&char| c.is_ascii_digit() || **c == '.') {
+        number.push(c);
+        chars.next();
+      }
+      tokens.push(Token::Number(number));
+    } else if c.is_alphanumeric() || c == '_' || c == '.' || c == '/' {
+      let mut word: String = String::new();
+      while let Some(&c) = chars.peek().filter(|c: &&char| c.is_alphanumeric() || **c == '_' || **c == '.' || **c == '/') {
+        word.push(c);
+        chars.next();
+      }
+      tokens.push(Token::Word(word));
+    } else {
+      let rest: String = chars.clone().take(2).collect();
+      let symbol: &'static str = SYMBOLS.iter().copied()
+        .find(|symbol: &&str| rest.starts_with(symbol))
+        .ok_or_else(|| format!("Unexpected character {}", c))?;
+      for _ in 0..symbol.len() {
+        chars.next();
+      }
+      tokens.push(Token::Symbol(symbol));
+    }
+  }
+
+  Ok(tokens)
+}
+
+fn describe(token: Option<&Token>) -> String {
+  match token {
+    Some(Token::Word(word)) => word.clone(),
+    Some(Token::Quoted(quoted)) => format!("'{}'", quoted),
+    Some(Token::Number(number)) => number.clone(),
+    Some(Token::Symbol(symbol)) => symbol.to_string(),
+    None => "end of query".to_string(),
+  }
+}
+
+struct Parser {
+  tokens: Vec<Token>,
+  pos: usize,
+}
+
+impl Parser {
+  fn next(&mut self) -> Option<Token> {
+    let token: Option<Token> = self.tokens.get(self.pos).cloned();
+    self.pos += 1;
+    token
+  }
+
+  fn peek_keyword(&self, keyword: &str) -> bool {
+    matches!(self.tokens.get(self.pos), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+  }
+
+  fn next_keyword(&mut self, keyword: &str) -> bool {
+    let found: bool = self.peek_keyword(keyword);
+    if found {
+      self.pos += 1;
+    }
+    found
+  }
+
+  fn next_symbol(&mut self, symbol: &str) -> bool {
+    let found: bool = matches!(self.tokens.get(self.pos), Some(Token::Symbol(next)) if *next == symbol);
+    if found {
+      self.pos += 1;
+    }
+    found
+  }
+
+  fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+    match self.next_keyword(keyword) {
+      true => Ok(()),
+      false => Err(format!("Expected {}, found {}", keyword, describe(self.tokens.get(self.pos)))),
+    }
+  }
+
+  fn expect_word(&mut self) -> Result<String, String> {
+    match self.next() {
+      Some(Token::Word(word)) if !KEYWORDS.iter().any(|keyword: &&str| word.eq_ignore_ascii_case(keyword)) => Ok(word),
+      token => Err(format!("Expected a column name, found {}", describe(token.as_ref()))),
+    }
+  }
+
+  fn expect_integer(&mut self) -> Result<i64, String> {
+    match self.next() {
+      Some(Token::Number(number)) => number.parse::<i64>().map_err(|_| format!("Expected an integer, found {}", number)),
+      token => Err(format!("Expected an integer, found {}", describe(token.as_ref()))),
+    }
+  }
+
+  fn expect_count(&mut self) -> Result<usize, String> {
+    match self.next() {
+      Some(Token::Number(number)) => number.parse::<usize>().map_err(|_| format!("Expected a row count, found {}", number)),
+      token => Err(format!("Expected a row count, found {}", describe(token.as_ref()))),
+    }
+  }
+
+  fn expect_value(&mut self) -> Result<EnumDataValue, String> {
+    match self.next() {
+      Some(Token::Number(number)) => match number.contains('.') {
+        true => number.parse::<f64>().map(EnumDataValue::Float64Value).ok(),
+        false => number.parse::<i64>().map(EnumDataValue::Int64Value).ok(),
+      }.ok_or_else(|| format!("Invalid number {}", number)),
+      Some(Token::Word(word)) if word.eq_ignore_ascii_case("true") => Ok(EnumDataValue::BooleanValue(true)),
+      Some(Token::Word(word)) if word.eq_ignore_ascii_case("false") => Ok(EnumDataValue::BooleanValue(false)),
+      token => Err(format!("Expected a value, found {}", describe(token.as_ref()))),
+    }
+  }
+
+  fn parse_select_list(&mut self) -> Result<Vec<String>, String> {
+    if self.next_symbol("*") {
+      return Ok(Vec::new());
+    }
+
+    let mut columns: Vec<String> = vec![self.expect_word()?];
+    while self.next_symbol(",") {
+      columns.push(self.expect_word()?);
+    }
+    Ok(columns)
+  }
+
+  fn parse_predicate(&mut self) -> Result<FilterPredicate, String> {
+    if self.next_keyword("BETWEEN") {
+      let low: EnumDataValue = self.expect_value()?;
+      self.expect_keyword("AND")?;
+      return Ok(FilterPredicate::Between(low, self.expect_value()?));
+    }
+
+    let symbol: &'static str = match self.next() {
+      Some(Token::Symbol(symbol)) if !matches!(symbol, "," | "*" | ";") => symbol,
+      token => return Err(format!("Expected a comparison, found {}", describe(token.as_ref()))),
+    };
+    let value: EnumDataValue = self.expect_value()?;
+
+    Ok(match symbol {
+      "=" => FilterPredicate::Eq(value),
+      "!=" | "<>" => FilterPredicate::NotEq(value),
+      "<" => FilterPredicate::Lt(value),
+      "<=" => FilterPredicate::LtEq(value),
+      ">" => FilterPredicate::Gt(value),
+      _ => FilterPredicate::GtEq(value),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::{DateTime, Utc};
+  use crate::tsf::segments::types::EnumDataType;
+
+  fn schema() -> Vec<ColumnSchema> {
+    ["metric_time", "temperature", "humidity"].iter()
+      .map(|name: &&str| ColumnSchema { name: name.to_string(), data_type: EnumDataType::Int32, description: None })
+      .collect()
+  }
+
+  #[test]
+  fn test_parse_select_with_time_range_and_limit() {
+    let plan: PhysicalPlan = parse_query("select metric_time, temperature FROM data.tsf WHERE time BETWEEN 10 AND 20 LIMIT 5", &schema()).unwrap();
+
+    let PhysicalOperator::Limit { input, offset: 0, limit: Some(5) } = plan.root_operator else {
+      panic!("expected a Limit at the root");
+    };
+    let PhysicalOperator::Scan { columns, table_name, time_range: Some((start, end)) } = *input else {
+      panic!("expected a Scan with a time range under the Limit");
+    };
+    assert_eq!(columns, vec!["metric_time".to_string(), "temperature".to_string()]);
+    assert_eq!(table_name, "data.tsf");
+    assert_eq!((start.timestamp(), end.timestamp()), (10, 20));
+  }
+
+  #[test]
+  fn test_parse_filters_resolve_against_selected_columns() {
+    let plan: PhysicalPlan = parse_query("SELECT * FROM 'my-data.tsf' WHERE humidity >= 40 AND temperature BETWEEN -5 AND 30.5 LIMIT 10 OFFSET 3;", &schema()).unwrap();
+
+    let PhysicalOperator::Limit { input, offset: 3, limit: Some(10) } = plan.root_operator else {
+      panic!("expected a Limit at the root");
+    };
+    let PhysicalOperator::Filter { input, column: 1, predicate: FilterPredicate::Between(low, high) } = *input else {
+      panic!("expected the temperature Filter under the Limit");
+    };
+    assert!(matches!(low, EnumDataValue::Int64Value(-5)));
+    assert!(matches!(high, EnumDataValue::Float64Value(value) if value == 30.5));
+    let PhysicalOperator::Filter { input, column: 2, predicate: FilterPredicate::GtEq(EnumDataValue::Int64Value(40)) } = *input else {
+      panic!("expected the humidity Filter under the temperature Filter");
+    };
+    let time_range: Option<(DateTime<Utc>, DateTime<Utc>)> = None;
+    assert!(matches!(*input, PhysicalOperator::Scan { ref columns, ref table_name, time_range: range } if columns.is_empty() && table_name == "my-data.tsf" && range == time_range));
+
+    // With a column list the filter indexes the selected columns
+    let plan: PhysicalPlan = parse_query("SELECT humidity FROM data.tsf WHERE humidity <> 0", &schema()).unwrap();
+    assert!(matches!(plan.root_operator, PhysicalOperator::Filter { column: 0, predicate: FilterPredicate::NotEq(_), .. }));
+  }
+
+  #[test]
+  fn test_parse_rejects_malformed_queries() {
+    let errors: Vec<(&str, &str)> = vec![
+      ("FROM data.tsf", "Expected SELECT, found FROM"),
+      ("SELECT FROM data.tsf", "Expected a column name, found FROM"),
+      ("SELECT metric_time data.tsf", "Expected FROM, found data.tsf"),
+      ("SELECT pressure FROM data.tsf", "Column pressure not found"),
+      ("SELECT metric_time FROM data.tsf WHERE humidity > 3", "Filter column humidity has to be selected"),
+      ("SELECT * FROM data.tsf WHERE time BETWEEN 1 AND", "Expected an integer, found end of query"),
+      ("SELECT * FROM data.tsf WHERE time BETWEEN 1 AND 2 AND time BETWEEN 3 AND 4", "time BETWEEN is given more than once"),
+      ("SELECT * FROM data.tsf WHERE humidity , 3", "Expected a comparison, found ,"),
+      ("SELECT * FROM data.tsf LIMIT -1", "Expected a row count, found -1"),
+      ("SELECT * FROM data.tsf LIMIT 5 ORDER BY humidity", "Unexpected ORDER after the query"),
+      ("SELECT * FROM 'data.tsf", "Unterminated quoted name"),
+      ("SELECT * FROM data.tsf WHERE humidity ~ 3", "Unexpected character ~"),
+    ];
+
+    for (sql, error) in errors {
+      assert_eq!(parse_query(sql, &schema()).err(), Some(error.to_string()), "{}", sql);
+    }
+  }
+}
@@ -0,0 +1,64 @@
+// Builders shared by tests across modules, so a new test doesn't have to hand-roll headers and temp files
+
+use std::io;
+
+use tempfile::NamedTempFile;
+
+use crate::tsf::header::FileHeader;
+use crate::tsf::segments::segment_column_data::SegmentColumnData;
+use crate::tsf::segments::segment_data::SegmentData;
+use crate::tsf::segments::segment_data_header::SegmentColumnHeader;
+use crate::tsf::segments::types::{EnumColumnData, EnumDataComp, EnumDataEnc, EnumDataType};
+use crate::tsf::tsf_writer::TSFWriter;
+
+// First metric_time in a sample segment, one second per row after that
+pub const SAMPLE_START: i32 = 1710555318;
+
+// Value of an Int16 sample column, distinct for every row and column
+pub fn sample_value(row: usize, column: usize) -> i16 {
+  (column * 100 + row) as i16
+}
+
+// A segment with a metric_time timestamp column followed by cols - 1 Int16 columns named column_<index>
+pub fn sample_segment(rows: usize, cols: usize) -> SegmentData {
+  let mut segment_data: SegmentData = SegmentData::new().start_tx();
+
+  for column in 0..cols {
+    let (column_name, data): (String, EnumColumnData) = match column {
+      0 => ("metric_time".to_string(), EnumColumnData::Int32Vec((0..rows).map(|row: usize| SAMPLE_START + row as i32).collect())),
+      _ => (format!("column_{}", column), EnumColumnData::Int16Vec((0..rows).map(|row: usize| sample_value(row, column)).collect())),
+    };
+    let header: SegmentColumnHeader = SegmentColumnHeader::new(column_name, data.data_type(), EnumDataEnc::None, EnumDataComp::None);
+    segment_data.add_column_header(header, column == 0).expect("sample column header");
+    segment_data.add_column_data(SegmentColumnData::from_column_data(data, EnumDataEnc::None, EnumDataComp::None)).expect("sample column data");
+  }
+
+  segment_data.update_header_dates(SAMPLE_START as i64, SAMPLE_START as i64 + rows.saturating_sub(1) as i64);
+  segment_data
+}
+
+// Writes a file header and the segment to a new temp file, removed when the handle is dropped
+pub fn write_temp_file(mut segment_data: SegmentData) -> io::Result<NamedTempFile> {
+  let mut temp_file: NamedTempFile = NamedTempFile::new()?;
+  FileHeader::new().write_header(temp_file.as_file_mut())?;
+  segment_data.write_to_file(temp_file.as_file_mut())?;
+  Ok(temp_file)
+}
+
+// Appends a metric_time,temperature segment, creating the file if needed
+pub fn append_segment(file_path: &str, metric_time: Vec<i32>, temperatures: Vec<i8>) -> io::Result<()> {
+  let date_start: i64 = *metric_time.first().unwrap() as i64;
+  let date_end: i64 = *metric_time.last().unwrap() as i64;
+
+  let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+  writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+    .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+  writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false)
+    .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+  writer.add_column_data(metric_time, EnumDataEnc::None, EnumDataComp::None)
+    .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+  writer.add_column_data(temperatures, EnumDataEnc::None, EnumDataComp::None)
+    .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+  writer.update_segment_dates(date_start, date_end);
+  writer.try_save()
+}
@@ -1,14 +1,20 @@
-use tokio::{fs::File, io::{self, AsyncReadExt, AsyncWriteExt}};
+use tokio::{fs::File, io::{self, AsyncWriteExt}};
+
+use super::error::TsfError;
+use super::read_util::async_read_exact_retry;
 
 // "TSFD" in hex Magic Number
 const TSFD_MAGIC_NUMBER: u32 = 0x54534644;
 // Version Number
 const TSFD_VERSION: u16 = 1;
+// Version 2 headers carry a u64 schema_id after the version, only written when a schema_id is set
+const TSFD_SCHEMA_VERSION: u16 = 2;
 
 #[repr(C)]
 pub struct FileHeader {
   magic_number: u32,
   version: u16,
+  schema_id: u64,
 }
 
 impl FileHeader {
@@ -16,13 +22,35 @@ impl FileHeader {
     FileHeader {
         magic_number: TSFD_MAGIC_NUMBER, 
         version: TSFD_VERSION,
+        schema_id: 0,
     }
   }
 
+  // User supplied id of the schema the file was written with, 0 means unset and keeps the version 1 header
+  pub fn set_schema_id(&mut self, schema_id: u64) {
+    self.schema_id = schema_id;
+    self.version = if schema_id == 0 { TSFD_VERSION } else { TSFD_SCHEMA_VERSION };
+  }
+
+  pub fn get_schema_id(&self) -> u64 {
+    self.schema_id
+  }
+
+  // An expected id of 0 accepts any file, otherwise the file must carry exactly that id
+  pub fn check_schema_id(&self, expected: u64) -> Result<(), TsfError> {
+    if expected != 0 && self.schema_id != expected {
+      return Err(TsfError::SchemaMismatch { expected, found: self.schema_id });
+    }
+    Ok(())
+  }
+
   pub async fn write_header(&self, file: &mut File) -> io::Result<()> {
     let mut bytes: Vec<u8> = Vec::new();
     bytes.extend_from_slice(&self.magic_number.to_le_bytes());
     bytes.extend_from_slice(&self.version.to_le_bytes());
+    if self.version == TSFD_SCHEMA_VERSION {
+      bytes.extend_from_slice(&self.schema_id.to_le_bytes());
+    }
 
     file.write_all(&bytes).await
   }
@@ -30,16 +58,23 @@ impl FileHeader {
   pub async fn read_header(&mut self, file: &mut File) -> io::Result<()> {
     let mut buffer: [u8; 6] = [0u8; 6];
     
-    file.read_exact(&mut buffer).await?;
+    async_read_exact_retry(file, &mut buffer).await?;
 
     self.magic_number = u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
     self.version = u16::from_le_bytes([buffer[4], buffer[5]]);
+    self.schema_id = 0;
+
+    if self.version == TSFD_SCHEMA_VERSION {
+      let mut schema_id: [u8; 8] = [0u8; 8];
+      async_read_exact_retry(file, &mut schema_id).await?;
+      self.schema_id = u64::from_le_bytes(schema_id);
+    }
     
     Ok(())
   }
 
   pub fn verify_header(&self) -> bool {
-    self.magic_number == TSFD_MAGIC_NUMBER && self.version == TSFD_VERSION
+    self.magic_number == TSFD_MAGIC_NUMBER && (self.version == TSFD_VERSION || self.version == TSFD_SCHEMA_VERSION)
   }
 }
 
@@ -98,4 +133,22 @@ mod tests {
     
     Ok(())
   }
+
+  #[tokio::test]
+  async fn test_schema_id_round_trip() -> io::Result<()> {
+    let mut temp_file: File = File::from_std(tempfile()?);
+    let mut header: FileHeader = FileHeader::new();
+    header.set_schema_id(42);
+    header.write_header(&mut temp_file).await?;
+
+    temp_file.seek(io::SeekFrom::Start(0)).await?;
+    let mut read_back: FileHeader = FileHeader::new();
+    read_back.read_header(&mut temp_file).await?;
+
+    assert!(read_back.verify_header());
+    assert_eq!(read_back.get_schema_id(), 42);
+    assert_eq!(temp_file.stream_position().await?, 14);
+
+    Ok(())
+  }
 }
@@ -1,30 +1,47 @@
-use std::{io, path::Path};
+use std::{collections::{BTreeMap, VecDeque}, io, path::PathBuf, pin::Pin, sync::{atomic::{AtomicUsize, Ordering}, Arc}, task::{Context, Poll}};
 
-use futures::stream::BoxStream;
+use futures::stream::{self, BoxStream, Stream};
 use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncSeekExt, SeekFrom};
 use tokio_stream::StreamExt;
 use tracing::trace;
 
 use super::async_header::FileHeader;
-use super::segments::{async_segment_data::SegmentData, types::{EnumColumnData, EnumDataValue}};
+use super::error::{check_path, open_error};
+use super::read_util::{async_chain_end, next_segment_pos};
+use super::segments::{async_column_data::SegmentColumnData, codec::{CodecRegistry, CustomColumn}, async_data_header::{SegmentColumnHeader, SegmentDataHeader, DEFAULT_MAX_COLUMN_COUNT}, async_segment_data::SegmentData, types::{ColumnLayout, ColumnSchema, ColumnSlice, EnumColumnData, EnumDataValue, UnsupportedColumn}};
 
 #[derive(Debug)]
 pub struct DataRow {
   pub values: Vec<EnumDataValue>
 }
 
+impl DataRow {
+  // Pairs each value with its column name from schema, in column order
+  pub fn named_iter<'a>(&'a self, schema: &'a [ColumnSchema]) -> impl Iterator<Item = (&'a str, &'a EnumDataValue)> + 'a {
+    schema.iter().map(|column: &ColumnSchema| column.name.as_str()).zip(self.values.iter())
+  }
+}
+
 pub struct AsyncTSFReader {
   file: File,
   file_header: FileHeader,
   segment_data: SegmentData,
+  reorder_by_name: bool,
+  skip_unsupported_columns: bool,
+  max_column_count: u16,
+  ignore_trailing_bytes: bool,
+  apply_scale: bool,
 }
 
 impl AsyncTSFReader {
   pub async fn new(file_path: &str) -> io::Result<Self> {
+    let path: PathBuf = check_path(file_path)?;
     let file: File = OpenOptions::new()
       .read(true)
-      .open(Path::new(file_path))
-      .await?;
+      .open(&path)
+      .await
+      .map_err(|e: io::Error| open_error(e, &path))?;
 
     let file_header: FileHeader = FileHeader::new();
     let segment_data: SegmentData = SegmentData::new();
@@ -33,61 +50,252 @@ impl AsyncTSFReader {
       file,
       file_header,
       segment_data,
+      reorder_by_name: false,
+      skip_unsupported_columns: false,
+      max_column_count: DEFAULT_MAX_COLUMN_COUNT,
+      ignore_trailing_bytes: false,
+      apply_scale: false,
     })
   }
 
+  // When set, stream_segments emits every segment's values in the first segment's column order,
+  // matched by name, instead of each segment's physical order
+  pub fn set_reorder_by_name(&mut self, reorder_by_name: bool) {
+    self.reorder_by_name = reorder_by_name;
+  }
+
+  // Read files with columns of unknown type, listing them in unsupported_columns instead of failing
+  pub fn set_skip_unsupported_columns(&mut self, skip_unsupported_columns: bool) {
+    self.skip_unsupported_columns = skip_unsupported_columns;
+    self.segment_data.set_skip_unsupported_columns(skip_unsupported_columns);
+  }
+
+  // Segments declaring more columns than this are rejected as corrupt, DEFAULT_MAX_COLUMN_COUNT unless set
+  pub fn set_max_column_count(&mut self, max_column_count: u16) {
+    self.max_column_count = max_column_count;
+    self.segment_data.set_max_column_count(max_column_count);
+  }
+
+  // When set, bytes where the last segment's next_offset points that do not parse as a segment header,
+  // e.g. the remains of an aborted append, end the segment stream instead of failing it. The first
+  // segment must still parse, and a segment whose header parses is part of the chain so a bad column
+  // in it still fails the stream
+  pub fn set_ignore_trailing_bytes(&mut self, ignore_trailing_bytes: bool) {
+    self.ignore_trailing_bytes = ignore_trailing_bytes;
+  }
+
+  // When set, rows present integer columns with ColumnMeta::Scale as floats, see TSFReader::set_apply_scale
+  pub fn set_apply_scale(&mut self, apply_scale: bool) {
+    self.apply_scale = apply_scale;
+  }
+
+  // When set, read_data decodes columns on tokio's blocking pool instead of the calling task
+  pub fn set_decode_in_background(&mut self, decode_in_background: bool) {
+    self.segment_data.set_decode_in_background(decode_in_background);
+  }
+
+  pub fn unsupported_columns(&self) -> &[UnsupportedColumn] {
+    self.segment_data.get_unsupported_columns()
+  }
+
+  // Columns stored with a type code registered in codecs are decoded by its codec, see TSFReader::set_codecs
+  pub fn set_codecs(&mut self, codecs: CodecRegistry) {
+    self.segment_data.set_codecs(codecs);
+  }
+
+  pub fn custom_columns(&self) -> &[CustomColumn] {
+    self.segment_data.get_custom_columns()
+  }
+
+  // Set when the segment has been marked deleted in place
+  pub fn is_tombstoned(&self) -> bool {
+    self.segment_data.is_tombstoned()
+  }
+
+  // Schema id from the file header, 0 when the writer did not set one. Valid after read_header
+  pub fn get_schema_id(&self) -> u64 {
+    self.file_header.get_schema_id()
+  }
+
+  // Rejects a file written with another schema, an expected id of 0 accepts any file
+  pub fn check_schema_id(&self, expected: u64) -> io::Result<()> {
+    self.file_header.check_schema_id(expected).map_err(io::Error::from)
+  }
+
+  pub fn get_ts_column(&self) -> Option<usize> {
+    self.segment_data.get_ts_column()
+  }
+
+  pub fn get_column_header(&self, index: usize) -> Option<&SegmentColumnHeader> {
+    self.segment_data.get_column_header(index)
+  }
+
+  pub fn get_column_data(&self, index: usize) -> Option<&EnumColumnData> {
+    self.segment_data.get_segment_data(index).and_then(|column| column.get_data())
+  }
+
+  // Decoded data of the column with this name, None if there is no such column or it wasn't read
+  pub fn get_column(&self, column_name: &str) -> Option<&EnumColumnData> {
+    (0..self.segment_data.get_column_count())
+      .find(|index: &usize| self.get_column_header(*index).is_some_and(|header: &SegmentColumnHeader| header.column_name == column_name))
+      .and_then(|index: usize| self.get_column_data(index))
+  }
+
+  // Every column's name and decoded data in column order, straight from the segment without building rows
+  pub fn to_columns(&self) -> Vec<(String, EnumColumnData)> {
+    (0..self.segment_data.get_column_count())
+      .filter_map(|index: usize| Some((self.get_column_header(index)?.column_name.clone(), self.get_column_data(index)?.clone())))
+      .collect()
+  }
+
+  pub fn schema(&self) -> Vec<ColumnSchema> {
+    (0..self.segment_data.get_column_count())
+      .filter_map(|index: usize| self.get_column_header(index))
+      .map(|header: &SegmentColumnHeader| ColumnSchema {
+        name: header.column_name.clone(),
+        data_type: header.column_type,
+        description: header.get_description().map(str::to_string),
+      })
+      .collect()
+  }
+
+  // Seconds since the epoch when the loaded segment was written, for retention by age
+  pub fn created_at(&self) -> i64 {
+    self.segment_data.get_created_at()
+  }
+
+  pub fn date_range(&self) -> Option<(i64, i64)> {
+    self.segment_data.get_date_range()
+  }
+
+  pub fn column_layout(&self) -> Vec<ColumnLayout> {
+    self.segment_data.column_layout()
+  }
+
+  pub fn metadata(&self) -> &BTreeMap<String, String> {
+    self.segment_data.get_metadata()
+  }
+
+  pub fn column_slice<T: ColumnSlice>(&self, index: usize) -> Option<&[T]> {
+    self.segment_data.column_slice(index)
+  }
+
+  // Rows are built as the stream is polled, so a consumer that stops early never builds the rest. The
+  // columns are copied out so the stream doesn't borrow the reader
   pub fn stream_rows(&self) -> BoxStream<'static, io::Result<DataRow>> {
     let num_rows: usize = self.segment_data.get_row_count();
-    
-    let mut rows: Vec<Result<DataRow, io::Error>> = Vec::new();
-
-    for row_index in 0..num_rows {
-      let mut row_values: Vec<EnumDataValue> = Vec::new();
-
-      // Assuming you have a way to iterate over each column index
-      for column_index in 0..self.segment_data.get_column_count() {
-        if let Some(column) = self.segment_data.get_segment_data(column_index) {
-          if let Some(data) = column.get_data() {
-            match data {
-              EnumColumnData::Int8Vec(v) => {
-                if row_index < v.len() {
-                    row_values.push(EnumDataValue::Int8Value(v[row_index]));
-                }
-              },
-              EnumColumnData::Int16Vec(v) => {
-                if row_index < v.len() {
-                    row_values.push(EnumDataValue::Int16Value(v[row_index]));
-                }
-              },
-              EnumColumnData::Int32Vec(v) => {
-                if row_index < v.len() {
-                    row_values.push(EnumDataValue::Int32Value(v[row_index]));
-                }
-              },
-              EnumColumnData::Int64Vec(v) => {
-                if row_index < v.len() {
-                    row_values.push(EnumDataValue::Int64Value(v[row_index]));
-                }
-              },
-              _ => return Box::pin(tokio_stream::iter(vec![Err(io::Error::new(io::ErrorKind::Other, "EnumColumnData not implemented"))])),
-            }
-          }
-        } else {
-          // Handle the case where column data is missing
-          return Box::pin(tokio_stream::iter(vec![Err(io::Error::new(io::ErrorKind::Other, "Column data missing"))]));
+    if num_rows == 0 {
+      return Box::pin(tokio_stream::empty());
+    }
+
+    let columns: Vec<Option<EnumColumnData>> = match segment_columns(&self.segment_data) {
+      Ok(columns) => columns.into_iter().map(|column: Option<&EnumColumnData>| column.cloned()).collect(),
+      Err(e) => return Box::pin(tokio_stream::iter(vec![Err(e)])),
+    };
+    let scales: Vec<Option<u32>> = column_scales(&self.segment_data, self.apply_scale);
+
+    Box::pin(tokio_stream::iter(0..num_rows)
+      .map(move |row_index: usize| Ok(row_at(columns.iter().map(Option::as_ref), &scales, row_index))))
+  }
+
+  // Rows the predicate accepts, errors are passed through so the caller still sees them
+  pub fn stream_rows_where<P>(&self, predicate: P) -> BoxStream<'static, io::Result<DataRow>>
+  where
+    P: Fn(&DataRow) -> bool + Send + 'static,
+  {
+    Box::pin(self.stream_rows().filter(move |row: &io::Result<DataRow>| row.as_ref().map_or(true, |row: &DataRow| predicate(row))))
+  }
+
+  // Streams every segment in the file, reading and decoding the next one only once the previous is drained
+  pub fn stream_segments(self) -> SegmentRowStream {
+    trace!("TSFReader::stream_segments");
+
+    self.segment_row_stream(false)
+  }
+
+  // Streams segments last to first with each segment's rows newest first, for "latest readings" queries.
+  // Segments only link forward, so their offsets are indexed from the headers before the first read
+  pub fn stream_segments_reverse(self) -> SegmentRowStream {
+    trace!("TSFReader::stream_segments_reverse");
+
+    self.segment_row_stream(true)
+  }
+
+  fn segment_row_stream(self, reverse: bool) -> SegmentRowStream {
+    let segments_decoded: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+    let state: LazySegmentState = LazySegmentState {
+      file: self.file,
+      file_header: self.file_header,
+      segment_pos: None,
+      rows: VecDeque::new(),
+      done: false,
+      segments_decoded: segments_decoded.clone(),
+      column_order: if self.reorder_by_name { Some(Vec::new()) } else { None },
+      reverse,
+      segment_offsets: None,
+      skip_unsupported_columns: self.skip_unsupported_columns,
+      max_column_count: self.max_column_count,
+      ignore_trailing_bytes: self.ignore_trailing_bytes,
+      apply_scale: self.apply_scale,
+    };
+
+    let inner: BoxStream<'static, io::Result<DataRow>> = Box::pin(stream::unfold(state, |mut state: LazySegmentState| async move {
+      loop {
+        if let Some(row) = state.rows.pop_front() {
+          return Some((Ok(row), state));
+        }
+
+        if state.done {
+          return None;
+        }
+
+        match state.read_next_segment().await {
+          Ok(true) => continue,
+          Ok(false) => return None,
+          Err(e) => {
+            state.done = true;
+            return Some((Err(e), state));
+          },
         }
       }
+    }));
 
-      // Create a DataRow for each row of values
-      rows.push(Ok(DataRow { values: row_values }));
+    SegmentRowStream {
+      inner,
+      segments_decoded,
     }
+  }
 
-    Box::pin(tokio_stream::iter(rows))
+  // Column schema from the file and segment headers alone, no column data is read. A file with no segments has none
+  pub async fn read_schema(&mut self) -> io::Result<Vec<ColumnSchema>> {
+    trace!("TSFReader::read_schema");
+
+    self.file.seek(SeekFrom::Start(0)).await?;
+    self.read_header().await?;
+
+    let segment_pos: u64 = self.file.stream_position().await?;
+    let (segments_end, _): (u64, Option<u64>) = async_chain_end(&mut self.file).await?;
+    if segment_pos >= segments_end {
+      return Ok(self.schema());
+    }
+    self.file.seek(SeekFrom::Start(segment_pos)).await?;
+    self.segment_data.read_header_from_file(&mut self.file).await?;
+
+    Ok(self.schema())
   }
 
+  // A file with a header and no segments reads as empty with 0 rows, see the sync TSFReader::read_all
   pub async fn read_all(&mut self) -> io::Result<()> {
     trace!("TSFReader::read_all");
     self.read_header().await?;
+
+    let segment_pos: u64 = self.file.stream_position().await?;
+    let (segments_end, _): (u64, Option<u64>) = async_chain_end(&mut self.file).await?;
+    if segment_pos >= segments_end {
+      return Ok(());
+    }
+    self.file.seek(SeekFrom::Start(segment_pos)).await?;
+
     self.read_data().await?;
     Ok(())
   }
@@ -109,3 +317,559 @@ impl AsyncTSFReader {
     Ok(())
   }
 }
+
+// Row stream returned by AsyncTSFReader::stream_segments
+pub struct SegmentRowStream {
+  inner: BoxStream<'static, io::Result<DataRow>>,
+  segments_decoded: Arc<AtomicUsize>,
+}
+
+impl SegmentRowStream {
+  // Number of segments read from disk so far
+  pub fn segments_decoded(&self) -> usize {
+    self.segments_decoded.load(Ordering::Relaxed)
+  }
+}
+
+impl Stream for SegmentRowStream {
+  type Item = io::Result<DataRow>;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    self.inner.as_mut().poll_next(cx)
+  }
+}
+
+struct LazySegmentState {
+  file: File,
+  file_header: FileHeader,
+  segment_pos: Option<u64>,
+  rows: VecDeque<DataRow>,
+  done: bool,
+  segments_decoded: Arc<AtomicUsize>,
+  // Canonical column names when reordering by name, empty until the first segment is read
+  column_order: Option<Vec<String>>,
+  reverse: bool,
+  // Segment starts found by the index pass, popped from the back when reading in reverse
+  segment_offsets: Option<Vec<u64>>,
+  skip_unsupported_columns: bool,
+  max_column_count: u16,
+  ignore_trailing_bytes: bool,
+  apply_scale: bool,
+}
+
+impl LazySegmentState {
+  // Loads the rows of the next segment, false once the end of the file is reached
+  async fn read_next_segment(&mut self) -> io::Result<bool> {
+    trace!("LazySegmentState::read_next_segment");
+
+    if self.reverse {
+      return self.read_previous_segment().await;
+    }
+
+    let segment_pos: u64 = match self.segment_pos {
+      Some(segment_pos) => segment_pos,
+      None => self.read_file_header().await?,
+    };
+
+    let (segments_end, _): (u64, Option<u64>) = async_chain_end(&mut self.file).await?;
+    if segment_pos >= segments_end {
+      self.done = true;
+      return Ok(false);
+    }
+
+    self.file.seek(SeekFrom::Start(segment_pos)).await?;
+    let mut segment_data: SegmentData = SegmentData::new();
+    segment_data.set_skip_unsupported_columns(self.skip_unsupported_columns);
+    segment_data.set_max_column_count(self.max_column_count);
+    if let Err(e) = segment_data.read_header_from_file(&mut self.file).await {
+      if self.is_trailing_bytes(self.segments_decoded.load(Ordering::Relaxed) > 0, &e) {
+        self.done = true;
+        return Ok(false);
+      }
+      return Err(e);
+    }
+    segment_data.read_segment_data(&mut self.file).await?;
+    self.segments_decoded.fetch_add(1, Ordering::Relaxed);
+    let segment_end: u64 = self.file.seek(SeekFrom::Current(0)).await?;
+
+    self.segment_pos = Some(next_segment_pos(segment_pos, segment_end, segments_end, segment_data.get_next_offset())?);
+    self.rows = self.ordered_rows(&segment_data)?.into();
+
+    Ok(true)
+  }
+
+  // Reverse counterpart of read_next_segment, indexing the segment offsets on the first call
+  async fn read_previous_segment(&mut self) -> io::Result<bool> {
+    trace!("LazySegmentState::read_previous_segment");
+
+    if self.segment_offsets.is_none() {
+      self.segment_offsets = Some(self.index_segments().await?);
+    }
+
+    let segment_pos: u64 = match self.segment_offsets.as_mut().and_then(|segment_offsets: &mut Vec<u64>| segment_offsets.pop()) {
+      Some(segment_pos) => segment_pos,
+      None => {
+        self.done = true;
+        return Ok(false);
+      },
+    };
+
+    self.file.seek(SeekFrom::Start(segment_pos)).await?;
+    let mut segment_data: SegmentData = SegmentData::new();
+    segment_data.set_skip_unsupported_columns(self.skip_unsupported_columns);
+    segment_data.set_max_column_count(self.max_column_count);
+    segment_data.read_segment_from_file(&mut self.file).await?;
+    self.segments_decoded.fetch_add(1, Ordering::Relaxed);
+
+    let mut rows: Vec<DataRow> = self.ordered_rows(&segment_data)?;
+    rows.reverse();
+    self.rows = rows.into();
+
+    Ok(true)
+  }
+
+  // Walks the segment chain reading only headers and returns each segment's start in file order
+  async fn index_segments(&mut self) -> io::Result<Vec<u64>> {
+    trace!("LazySegmentState::index_segments");
+
+    let mut segment_pos: u64 = self.read_file_header().await?;
+    let (segments_end, _): (u64, Option<u64>) = async_chain_end(&mut self.file).await?;
+    let mut segment_offsets: Vec<u64> = Vec::new();
+
+    while segment_pos < segments_end {
+      self.file.seek(SeekFrom::Start(segment_pos)).await?;
+      let mut data_header: SegmentDataHeader = SegmentDataHeader::new();
+      data_header.set_skip_unsupported_columns(self.skip_unsupported_columns);
+      data_header.set_max_column_count(self.max_column_count);
+      if let Err(e) = data_header.read_segment_header(&mut self.file).await {
+        if self.is_trailing_bytes(!segment_offsets.is_empty(), &e) {
+          break;
+        }
+        return Err(e);
+      }
+      let column_bytes: u64 = data_header.column_headers.iter()
+        .map(|header: &SegmentColumnHeader| header.column_size)
+        .chain(data_header.get_unsupported_columns().iter().map(|column: &UnsupportedColumn| column.column_size))
+        .sum();
+      let segment_end: u64 = self.file.seek(SeekFrom::Current(0)).await? + column_bytes;
+
+      segment_offsets.push(segment_pos);
+      segment_pos = next_segment_pos(segment_pos, segment_end, segments_end, data_header.next_offset)?;
+    }
+
+    Ok(segment_offsets)
+  }
+
+  // A segment header that fails to parse where a good segment's next_offset points is taken for trailing
+  // bytes when allowed, errors other than a short or invalid read still fail the stream
+  fn is_trailing_bytes(&self, after_first_segment: bool, err: &io::Error) -> bool {
+    self.ignore_trailing_bytes && after_first_segment
+      && matches!(err.kind(), io::ErrorKind::UnexpectedEof | io::ErrorKind::InvalidData)
+  }
+
+  // Reads and verifies the file header, returning where the first segment starts
+  async fn read_file_header(&mut self) -> io::Result<u64> {
+    self.file.seek(SeekFrom::Start(0)).await?;
+    self.file_header.read_header(&mut self.file).await?;
+    if !self.file_header.verify_header() {
+      return Err(io::Error::new(io::ErrorKind::InvalidData, "File header verification failed"));
+    }
+    self.file.seek(SeekFrom::Current(0)).await
+  }
+
+  fn ordered_rows(&mut self, segment_data: &SegmentData) -> io::Result<Vec<DataRow>> {
+    let mut rows: Vec<DataRow> = segment_rows(segment_data, self.apply_scale)?;
+    if let Some(permutation) = self.column_permutation(segment_data)? {
+      for row in rows.iter_mut() {
+        row.values = permutation.iter().map(|index: &usize| row.values[*index].clone()).collect();
+      }
+    }
+    Ok(rows)
+  }
+
+  // Physical column index for each canonical column, None when rows can be emitted as is
+  fn column_permutation(&mut self, segment_data: &SegmentData) -> io::Result<Option<Vec<usize>>> {
+    let column_order: &mut Vec<String> = match self.column_order.as_mut() {
+      Some(column_order) => column_order,
+      None => return Ok(None),
+    };
+
+    let names: Vec<&str> = (0..segment_data.get_column_count())
+      .filter_map(|index: usize| segment_data.get_column_header(index))
+      .map(|header: &SegmentColumnHeader| header.column_name.as_str())
+      .collect();
+
+    if column_order.is_empty() {
+      *column_order = names.iter().map(|name: &&str| name.to_string()).collect();
+      return Ok(None);
+    }
+
+    if names.len() != column_order.len() {
+      return Err(io::Error::new(io::ErrorKind::InvalidData, "Segment columns do not match the first segment"));
+    }
+
+    let permutation: Vec<usize> = column_order.iter()
+      .map(|name: &String| names.iter().position(|column_name: &&str| column_name == name)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Segment is missing column {}", name))))
+      .collect::<io::Result<Vec<usize>>>()?;
+
+    Ok(Some(permutation))
+  }
+}
+
+fn segment_rows(segment_data: &SegmentData, apply_scale: bool) -> io::Result<Vec<DataRow>> {
+  let num_rows: usize = segment_data.get_row_count();
+  if num_rows == 0 {
+    return Ok(Vec::new());
+  }
+
+  let columns: Vec<Option<&EnumColumnData>> = segment_columns(segment_data)?;
+  let scales: Vec<Option<u32>> = column_scales(segment_data, apply_scale);
+  Ok((0..num_rows)
+    .map(|row_index: usize| row_at(columns.iter().copied(), &scales, row_index))
+    .collect())
+}
+
+// Every column's decoded data, an error when a column the header declares has none
+fn segment_columns(segment_data: &SegmentData) -> io::Result<Vec<Option<&EnumColumnData>>> {
+  (0..segment_data.get_column_count())
+    .map(|column_index: usize| segment_data.get_segment_data(column_index)
+      .map(|column: &SegmentColumnData| column.get_data())
+      .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Column data missing")))
+    .collect()
+}
+
+fn column_scales(segment_data: &SegmentData, apply_scale: bool) -> Vec<Option<u32>> {
+  (0..segment_data.get_column_count())
+    .map(|column_index: usize| segment_data.get_column_header(column_index)
+      .and_then(|header: &SegmentColumnHeader| header.get_column_meta().scale())
+      .filter(|_| apply_scale))
+    .collect()
+}
+
+// One row's values across the columns, scaled where the column has a scale to apply
+fn row_at<'a>(columns: impl Iterator<Item = Option<&'a EnumColumnData>>, scales: &[Option<u32>], row_index: usize) -> DataRow {
+  let values: Vec<EnumDataValue> = columns.zip(scales)
+    .filter_map(|(column, scale): (Option<&EnumColumnData>, &Option<u32>)| {
+      let value: EnumDataValue = column?.value_at(row_index)?;
+      Some(match scale {
+        Some(scale) => value.with_scale(*scale),
+        None => value,
+      })
+    })
+    .collect();
+
+  DataRow { values }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::NamedTempFile;
+  use crate::tsf::segments::checksum::calculate_checksum;
+  use crate::tsf::segments::types::{EnumChecksumAlg, EnumDataComp, EnumDataEnc, EnumDataType};
+  use crate::tsf::tsf_reader::TSFReader;
+  use crate::tsf::tsf_writer::TSFWriter;
+  use crate::test_support::append_segment;
+  use crate::tsf::error::TsfError;
+
+  #[tokio::test]
+  async fn test_async_column_positions_across_segments() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    append_segment(file_path, vec![1, 2, 3], vec![10, 20, 30])?;
+    append_segment(file_path, vec![4, 5], vec![-40, -50])?;
+    let raw: Vec<u8> = std::fs::read(file_path)?;
+
+    let mut reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+    reader.read_header().await?;
+    reader.read_data().await?;
+    reader.read_data().await?;
+
+    // The second column of the second segment sits where the computed position says it does
+    let layout: Vec<ColumnLayout> = reader.column_layout();
+    let temperature_pos: usize = layout[1].file_pos as usize;
+    assert_eq!(layout[1].file_pos, layout[0].file_pos + layout[0].column_size);
+    assert_eq!(&raw[temperature_pos..temperature_pos + 2], &[-40i8 as u8, -50i8 as u8]);
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_async_stream_rows_where_stops_at_the_first_match() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    append_segment(file_path, vec![1, 2, 3, 4, 5], vec![20, 22, 21, 25, 23])?;
+
+    let mut reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+    reader.read_all().await?;
+
+    let checked: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+    let counter: Arc<AtomicUsize> = checked.clone();
+    let first: Vec<io::Result<DataRow>> = reader.stream_rows_where(move |row: &DataRow| {
+      counter.fetch_add(1, Ordering::SeqCst);
+      matches!(row.values[1], EnumDataValue::Int8Value(temperature) if temperature > 21)
+    }).take(1).collect().await;
+
+    assert!(matches!(first[0].as_ref().unwrap().values[0], EnumDataValue::Int32Value(2)));
+    assert_eq!(checked.load(Ordering::SeqCst), 2);
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_async_schema_and_column_by_name() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    append_segment(file_path, vec![1710555318, 1710555319], vec![21, 22])?;
+
+    // Headers alone give the schema, nothing is decoded yet
+    let mut reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+    let schema: Vec<ColumnSchema> = reader.read_schema().await?;
+    let names: Vec<(&str, EnumDataType)> = schema.iter().map(|column: &ColumnSchema| (column.name.as_str(), column.data_type)).collect();
+    assert_eq!(names, vec![("metric_time", EnumDataType::Int32), ("temperature", EnumDataType::Int8)]);
+    assert!(reader.get_column("temperature").is_none());
+
+    let mut reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+    reader.read_all().await?;
+    assert_eq!(reader.schema(), schema);
+    assert!(matches!(reader.get_column("temperature"), Some(EnumColumnData::Int8Vec(values)) if *values == vec![21, 22]));
+    assert!(reader.get_column("humidity").is_none());
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_stream_segments_reads_one_segment_at_a_time() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    append_segment(file_path, vec![1, 2], vec![10, 20])?;
+    append_segment(file_path, vec![3, 4, 5], vec![30, 40, 50])?;
+
+    let reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+    let mut stream: SegmentRowStream = reader.stream_segments();
+    assert_eq!(stream.segments_decoded(), 0);
+
+    let mut timestamps: Vec<i64> = Vec::new();
+    let mut segments_decoded: Vec<usize> = Vec::new();
+    while let Some(row) = stream.next().await {
+      let row: DataRow = row?;
+      timestamps.push(row.values[0].as_i64().unwrap());
+      segments_decoded.push(stream.segments_decoded());
+    }
+
+    assert_eq!(timestamps, vec![1, 2, 3, 4, 5]);
+    // The second segment is only read once the first one has been drained
+    assert_eq!(segments_decoded, vec![1, 1, 2, 2, 2]);
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_stream_segments_reorders_columns_by_name() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    append_segment(file_path, vec![1, 2], vec![10, 20])?;
+
+    // Same columns, physically swapped
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![30i8, 40], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![3i32, 4], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(3, 4);
+    writer.try_save()?;
+
+    let mut reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+    reader.set_reorder_by_name(true);
+    let mut stream: SegmentRowStream = reader.stream_segments();
+
+    let mut rows: Vec<(i64, i64)> = Vec::new();
+    while let Some(row) = stream.next().await {
+      let row: DataRow = row?;
+      rows.push((row.values[0].as_i64().unwrap(), row.values[1].as_i64().unwrap()));
+    }
+
+    assert_eq!(rows, vec![(1, 10), (2, 20), (3, 30), (4, 40)]);
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_stream_segments_reverse_yields_last_segment_first() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    append_segment(file_path, vec![1, 2], vec![10, 20])?;
+    append_segment(file_path, vec![3, 4, 5], vec![30, 40, 50])?;
+
+    let reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+    let mut stream: SegmentRowStream = reader.stream_segments_reverse();
+
+    let mut rows: Vec<(i64, i64)> = Vec::new();
+    let mut segments_decoded: Vec<usize> = Vec::new();
+    while let Some(row) = stream.next().await {
+      let row: DataRow = row?;
+      rows.push((row.values[0].as_i64().unwrap(), row.values[1].as_i64().unwrap()));
+      segments_decoded.push(stream.segments_decoded());
+    }
+
+    assert_eq!(rows, vec![(5, 50), (4, 40), (3, 30), (2, 20), (1, 10)]);
+    assert_eq!(segments_decoded, vec![1, 1, 1, 2, 2]);
+
+    Ok(())
+  }
+
+  // Rewrites the first segment's next_offset and re-signs its header so only the chain is broken
+  fn set_first_next_offset(file_path: &str, next_offset: u32) -> io::Result<()> {
+    let mut bytes: Vec<u8> = std::fs::read(file_path)?;
+    let segment_start: usize = 6;
+    let column_header_size: usize = u32::from_le_bytes(bytes[segment_start + 46..segment_start + 50].try_into().unwrap()) as usize;
+    let metadata_pos: usize = segment_start + 50 + column_header_size;
+    let metadata_size: usize = u16::from_le_bytes(bytes[metadata_pos..metadata_pos + 2].try_into().unwrap()) as usize;
+    let check_pos: usize = metadata_pos + 2 + metadata_size + 8;
+
+    bytes[segment_start + 2..segment_start + 6].copy_from_slice(&next_offset.to_le_bytes());
+    let checksum: [u8; 8] = calculate_checksum(EnumChecksumAlg::default(), &bytes[segment_start..check_pos]);
+    bytes[check_pos..check_pos + 8].copy_from_slice(&checksum);
+
+    std::fs::write(file_path, bytes)
+  }
+
+  #[tokio::test]
+  async fn test_stream_segments_rejects_next_offset_outside_file() -> io::Result<()> {
+    for next_offset in [1u32, 1_000_000] {
+      let temp_file: NamedTempFile = NamedTempFile::new()?;
+      let file_path: &str = temp_file.path().to_str().unwrap();
+
+      append_segment(file_path, vec![1, 2], vec![10, 20])?;
+      append_segment(file_path, vec![3, 4], vec![30, 40])?;
+      set_first_next_offset(file_path, next_offset)?;
+
+      let reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+      let results: Vec<io::Result<DataRow>> = reader.stream_segments().collect().await;
+
+      assert_eq!(results.len(), 1);
+      let err: &io::Error = results[0].as_ref().unwrap_err();
+      assert!(matches!(err.get_ref().and_then(|inner| inner.downcast_ref::<TsfError>()), Some(TsfError::Corruption { .. })));
+    }
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_trailing_bytes_after_last_segment_are_ignored() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    append_segment(file_path, vec![1, 2], vec![10, 20])?;
+    append_segment(file_path, vec![3, 4], vec![30, 40])?;
+    // What an append that died partway through its header would leave behind
+    let mut bytes: Vec<u8> = std::fs::read(file_path)?;
+    bytes.extend_from_slice(&[0u8, 0, 0xAB, 0xCD, 0xEF]);
+    std::fs::write(file_path, &bytes)?;
+
+    let reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+    let results: Vec<io::Result<DataRow>> = reader.stream_segments().collect().await;
+    assert!(results.last().unwrap().is_err());
+
+    for reverse in [false, true] {
+      let mut reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+      reader.set_ignore_trailing_bytes(true);
+      let stream: SegmentRowStream = match reverse {
+        true => reader.stream_segments_reverse(),
+        false => reader.stream_segments(),
+      };
+      let rows: Vec<DataRow> = stream.collect::<io::Result<Vec<DataRow>>>().await?;
+
+      let mut timestamps: Vec<i64> = rows.iter().map(|row: &DataRow| row.values[0].as_i64().unwrap()).collect();
+      if reverse {
+        timestamps.reverse();
+      }
+      assert_eq!(timestamps, vec![1, 2, 3, 4]);
+    }
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_corrupt_segment_inside_the_chain_is_not_trailing_bytes() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    append_segment(file_path, vec![1, 2], vec![10, 20])?;
+    append_segment(file_path, vec![3, 4], vec![30, 40])?;
+    append_segment(file_path, vec![5, 6], vec![50, 60])?;
+    // Flip a bit in the middle segment's temperature column, its header still parses
+    let mut sync_reader: TSFReader = TSFReader::new(file_path)?;
+    let segment_offsets: Vec<u64> = sync_reader.segment_offsets()?;
+    sync_reader.read_segment_header_at(segment_offsets[1])?;
+    let column_pos: usize = sync_reader.column_layout()[1].file_pos as usize;
+    let mut bytes: Vec<u8> = std::fs::read(file_path)?;
+    bytes[column_pos] ^= 0x01;
+    std::fs::write(file_path, &bytes)?;
+
+    let mut reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+    reader.set_ignore_trailing_bytes(true);
+    let results: Vec<io::Result<DataRow>> = reader.stream_segments().collect().await;
+    assert_eq!(results.len(), 3);
+    assert!(results[..2].iter().all(|row: &io::Result<DataRow>| row.is_ok()));
+    assert_eq!(results[2].as_ref().unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_background_decode_keeps_runtime_responsive() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    let row_count: i32 = 2_000_000;
+    append_segment(
+      file_path,
+      (0..row_count).collect::<Vec<i32>>(),
+      (0..row_count).map(|value: i32| (value % 100) as i8).collect::<Vec<i8>>(),
+    )?;
+
+    // Without background decode the columns are decoded on the runtime's thread
+    let mut inline_reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+    inline_reader.read_all().await?;
+    assert_eq!(inline_reader.segment_data.get_decode_thread(), Some(std::thread::current().id()));
+
+    let mut reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+    reader.set_decode_in_background(true);
+    reader.read_header().await?;
+
+    // The test runtime is single threaded, so this task only advances while read_data is parked
+    let read_task: tokio::task::JoinHandle<io::Result<AsyncTSFReader>> = tokio::spawn(async move {
+      reader.read_data().await?;
+      Ok(reader)
+    });
+    // A tick only counts when this task got the runtime back while read_data was still in flight
+    let mut ticks: usize = 0;
+    loop {
+      tokio::task::yield_now().await;
+      if read_task.is_finished() {
+        break;
+      }
+      ticks += 1;
+    }
+    let reader: AsyncTSFReader = read_task.await??;
+
+    assert!(ticks > 0);
+    // The decode ran on the blocking pool, not on the runtime's only thread
+    let runtime_thread: std::thread::ThreadId = std::thread::current().id();
+    assert!(reader.segment_data.get_decode_thread().is_some_and(|decode_thread: std::thread::ThreadId| decode_thread != runtime_thread));
+    let metric_time: &[i32] = reader.column_slice::<i32>(0).unwrap();
+    assert_eq!(metric_time.len(), row_count as usize);
+    assert_eq!(metric_time[row_count as usize - 1], row_count - 1);
+    assert_eq!(reader.column_slice::<i8>(1).unwrap()[199], 99);
+
+    Ok(())
+  }
+}
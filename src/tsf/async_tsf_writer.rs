@@ -0,0 +1,365 @@
+use std::{collections::BTreeMap, fs, io, path::PathBuf, sync::Arc};
+
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt, SeekFrom};
+
+use super::async_header::FileHeader;
+use super::error::{check_path, open_error};
+use super::read_util::{async_chain_end, encode_footer};
+use super::write_lock::WriteLock;
+use super::segments::{
+  async_column_data::{ColumnDataCreator, SegmentColumnData},
+  codec::ColumnCodec,
+  async_data_header::SegmentColumnHeader,
+  async_segment_data::SegmentData,
+  types::{ColumnMeta, EnumChecksumAlg, EnumColumnData, EnumDataType, EnumDataEnc, EnumDataComp}
+};
+
+pub struct AsyncTSFWriter {
+  file: File,
+  file_path: PathBuf,
+  file_exists: bool,
+  file_header: FileHeader,
+  segment_data: SegmentData,
+  // Write a footer pointing at the segment after appending it, see read_util::FOOTER_MAGIC
+  footer: bool,
+  cleanup: bool,
+  // Held for the writer's lifetime and released on drop, after any cleanup of a failed new file
+  _write_lock: WriteLock,
+}
+
+impl AsyncTSFWriter {
+  pub async fn new(path: &str) -> io::Result<Self> {
+    let path_buf: PathBuf = check_path(path)?;
+    let write_lock: WriteLock = WriteLock::acquire(&path_buf)
+      .map_err(|e: io::Error| open_error(e, &path_buf))?;
+    let file_exists: bool = path_buf.exists();
+
+    let file: File = if file_exists {
+      OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&path_buf)
+        .await
+        .map_err(|e: io::Error| open_error(e, &path_buf))?
+    } else {
+      OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .open(&path_buf)
+        .await
+        .map_err(|e: io::Error| open_error(e, &path_buf))?
+    };
+
+    let file_header: FileHeader = FileHeader::new();
+    let segment_data: SegmentData = SegmentData::new()
+      .start_tx();
+
+    Ok(AsyncTSFWriter {
+      file,
+      file_path: path_buf,
+      file_exists,
+      file_header,
+      segment_data,
+      footer: false,
+      cleanup: false,
+      _write_lock: write_lock,
+    })
+  }
+
+  pub fn add_column_header(&mut self, column_name: &str, column_type: EnumDataType, encoding: EnumDataEnc, compression: EnumDataComp, ts_column: bool) -> Result<(), String> {
+    self.add_column_header_with_meta(column_name, column_type, encoding, compression, ts_column, ColumnMeta::None)
+  }
+
+  pub fn add_column_header_with_meta(&mut self, column_name: &str, column_type: EnumDataType, encoding: EnumDataEnc, compression: EnumDataComp, ts_column: bool, column_meta: ColumnMeta) -> Result<(), String> {
+    let header: SegmentColumnHeader = SegmentColumnHeader::new(
+      column_name.to_string(),
+      column_type,
+      encoding,
+      compression,
+    ).with_meta(column_meta);
+
+    self.segment_data.add_column_header(header, ts_column)?;
+
+    Ok(())
+  }
+
+  // Stored with the column header, the column must already have been added
+  pub fn describe_column(&mut self, column_name: &str, description: &str) -> Result<(), String> {
+    self.segment_data.describe_column(column_name, description)
+  }
+
+  pub fn add_column_data<T>(&mut self, column: Vec<T>, encoding: EnumDataEnc, compression: EnumDataComp) -> Result<(), String>
+  where
+      T: ColumnDataCreator + Sized,
+  {
+      if column.is_empty() {
+          return Err("Column data empty".to_string());
+      }
+
+      // The file position is resolved once the segment is written
+      let data_segment: SegmentColumnData = T::create_segment_column_data(column, 0, encoding, compression);
+      self.segment_data.add_column_data(data_segment)?;
+
+      Ok(())
+  }
+
+  // Adds a column that was already built, e.g. with SegmentColumnData::from_column_data
+  pub fn add_segment_column_data(&mut self, data_segment: SegmentColumnData) -> Result<(), String> {
+    self.segment_data.add_column_data(data_segment)
+  }
+
+  // Adds both header and data of a column read back by a reader with codec registered under type_code.
+  // Add it once the columns before it have their data
+  pub fn add_custom_column(&mut self, column_name: &str, type_code: u16, codec: Arc<dyn ColumnCodec>, data: EnumColumnData) -> Result<(), String> {
+    self.segment_data.add_custom_column(column_name, type_code, codec, data)
+  }
+
+  // Written to the header of a new file, appending to an existing file leaves its schema_id as it is
+  pub fn set_schema_id(&mut self, schema_id: u64) {
+    self.file_header.set_schema_id(schema_id);
+  }
+
+  pub fn set_checksum_alg(&mut self, checksum_alg: EnumChecksumAlg) {
+    self.segment_data.set_checksum_alg(checksum_alg);
+  }
+
+  pub fn set_metadata(&mut self, metadata: BTreeMap<String, String>) -> Result<(), String> {
+    self.segment_data.set_metadata(metadata)
+  }
+
+  // Pads the segment so the one appended after it starts on a multiple of alignment bytes, e.g. 4096
+  // for direct I/O. The first segment still starts right after the file header
+  pub fn set_segment_alignment(&mut self, alignment: u64) -> Result<(), String> {
+    self.segment_data.set_alignment(alignment)
+  }
+
+  // Grows the file to the segment's full size before writing it, for fewer and more contiguous extents
+  pub fn set_presize(&mut self, presize: bool) {
+    self.segment_data.set_presize(presize);
+  }
+
+  // Keep a footer with the last segment's position so readers can reach it without walking the chain
+  pub fn set_footer(&mut self, footer: bool) {
+    self.footer = footer;
+  }
+
+  // Bytes each column of a streamed segment buffers before it is written out, see TSFWriter::with_flush_threshold
+  pub fn with_flush_threshold(mut self, flush_threshold: usize) -> Self {
+    self.segment_data.set_flush_threshold(flush_threshold);
+    self
+  }
+
+  // Writes the last finished stream made to its column spools
+  pub fn stream_flushes(&self) -> usize {
+    self.segment_data.get_stream_flushes()
+  }
+
+  pub fn set_auto_sort(&mut self, auto_sort: bool) {
+    self.segment_data.set_auto_sort(auto_sort);
+  }
+
+  pub fn update_segment_dates(&mut self, date_start: i64, date_end: i64) {
+    self.segment_data.update_header_dates(date_start, date_end);
+  }
+
+  // Starts a segment written in batches, see SegmentData::begin_stream
+  pub async fn begin_stream(&mut self) -> io::Result<()> {
+    self.seek_segment_start().await?;
+    self.segment_data.begin_stream(&mut self.file).await
+  }
+
+  pub async fn append_batch(&mut self, columns: &[EnumColumnData]) -> Result<(), String> {
+    self.segment_data.append_batch(columns).await
+  }
+
+  pub async fn finish_stream(&mut self) -> io::Result<()> {
+    self.segment_data.finish_stream(&mut self.file).await?;
+    self.write_footer().await?;
+    self.file.flush().await
+  }
+
+  pub async fn try_save(&mut self) -> io::Result<()> {
+    self.cleanup = false;
+    if let Err(e) = self.save().await {
+      self.cleanup = true;
+      return Err(e);
+    }
+    Ok(())
+  }
+
+  // Save the SegmentData to the file
+  async fn save(&mut self) -> io::Result<()> {
+    self.seek_segment_start().await?;
+    self.segment_data.write_to_file(&mut self.file).await?;
+    self.write_footer().await?;
+    // tokio writes are completed in the background, make sure they land before returning
+    self.file.flush().await?;
+    Ok(())
+  }
+
+  // Appending to an existing file adds another segment after the ones already there, a footer from the
+  // last append is cut off first. The file is not opened in append mode so a presized segment can be
+  // written into the space it reserved
+  async fn seek_segment_start(&mut self) -> io::Result<()> {
+    let (segments_end, footer): (u64, Option<u64>) = async_chain_end(&mut self.file).await?;
+    if footer.is_some() {
+      self.file.set_len(segments_end).await?;
+    }
+    if self.file.seek(SeekFrom::Start(segments_end)).await? == 0 {
+      self.file_header.write_header(&mut self.file).await?;
+    }
+    Ok(())
+  }
+
+  async fn write_footer(&mut self) -> io::Result<()> {
+    if self.footer {
+      self.file.seek(SeekFrom::End(0)).await?;
+      self.file.write_all(&encode_footer(self.segment_data.get_segment_pos())).await?;
+    }
+    Ok(())
+  }
+}
+
+impl Drop for AsyncTSFWriter {
+  fn drop(&mut self) {
+    if self.cleanup && !self.file_exists {
+      let _ = fs::remove_file(&self.file_path);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+  use tokio_stream::StreamExt;
+  use crate::tsf::async_tsf_reader::{AsyncTSFReader, DataRow};
+  use crate::tsf::segments::types::EnumDataValue;
+
+  #[tokio::test]
+  async fn test_async_tsf_writer_new() -> io::Result<()> {
+    let dir: tempfile::TempDir = tempdir()?;
+    let file_path: PathBuf = dir.path().join("test.tsf");
+
+    let writer_result: Result<AsyncTSFWriter, io::Error> = AsyncTSFWriter::new(file_path.to_str().unwrap()).await;
+    assert!(writer_result.is_ok());
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_async_write_and_read_back() -> io::Result<()> {
+    let dir: tempfile::TempDir = tempdir()?;
+    let file_path: PathBuf = dir.path().join("test.tsf");
+    let file_path: &str = file_path.to_str().unwrap();
+
+    let mut writer: AsyncTSFWriter = AsyncTSFWriter::new(file_path).await?;
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    writer.add_column_data(vec![1710555318i32, 1710555319, 1710555320], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![20i8, 22, 21], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    writer.update_segment_dates(1710555318, 1710555320);
+    writer.try_save().await?;
+    drop(writer);
+
+    let mut reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+    reader.read_all().await?;
+
+    let rows: Vec<DataRow> = reader.stream_rows()
+      .collect::<Vec<io::Result<DataRow>>>().await
+      .into_iter()
+      .collect::<io::Result<Vec<DataRow>>>()?;
+
+    assert_eq!(rows.len(), 3);
+    assert!(matches!(rows[0].values[0], EnumDataValue::Int32Value(1710555318)));
+    assert!(matches!(rows[0].values[1], EnumDataValue::Int8Value(20)));
+    assert!(matches!(rows[2].values[0], EnumDataValue::Int32Value(1710555320)));
+    assert!(matches!(rows[2].values[1], EnumDataValue::Int8Value(21)));
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_async_streamed_segment() -> io::Result<()> {
+    let dir: tempfile::TempDir = tempdir()?;
+    let file_path: PathBuf = dir.path().join("stream.tsf");
+    let file_path: &str = file_path.to_str().unwrap();
+    let to_io = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+
+    let mut writer: AsyncTSFWriter = AsyncTSFWriter::new(file_path).await?;
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true).map_err(to_io)?;
+    writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false).map_err(to_io)?;
+    writer.begin_stream().await?;
+    writer.append_batch(&[EnumColumnData::Int32Vec(vec![10, 11]), EnumColumnData::Int8Vec(vec![1, 2])]).await.map_err(to_io)?;
+    writer.append_batch(&[EnumColumnData::Int32Vec(vec![12]), EnumColumnData::Int8Vec(vec![3])]).await.map_err(to_io)?;
+    writer.finish_stream().await?;
+    drop(writer);
+
+    let mut reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+    reader.read_all().await?;
+    let rows: Vec<DataRow> = reader.stream_rows()
+      .collect::<Vec<io::Result<DataRow>>>().await
+      .into_iter()
+      .collect::<io::Result<Vec<DataRow>>>()?;
+
+    assert_eq!(rows.len(), 3);
+    assert!(matches!(rows[2].values[0], EnumDataValue::Int32Value(12)));
+    assert!(matches!(rows[2].values[1], EnumDataValue::Int8Value(3)));
+    assert_eq!(reader.date_range(), Some((10, 12)));
+
+    Ok(())
+  }
+
+  async fn stream_with_threshold(file_path: &str, flush_threshold: Option<usize>) -> io::Result<usize> {
+    let to_io = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+
+    let mut writer: AsyncTSFWriter = AsyncTSFWriter::new(file_path).await?;
+    if let Some(flush_threshold) = flush_threshold {
+      writer = writer.with_flush_threshold(flush_threshold);
+    }
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true).map_err(to_io)?;
+    writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false).map_err(to_io)?;
+    writer.begin_stream().await?;
+    for batch in 0..50 {
+      let metric_time: Vec<i32> = (batch * 4..batch * 4 + 4).collect();
+      let temperature: Vec<i8> = metric_time.iter().map(|value: &i32| (*value % 100) as i8).collect();
+      writer.append_batch(&[EnumColumnData::Int32Vec(metric_time), EnumColumnData::Int8Vec(temperature)]).await.map_err(to_io)?;
+    }
+    writer.finish_stream().await?;
+
+    Ok(writer.stream_flushes())
+  }
+
+  #[tokio::test]
+  async fn test_async_flush_threshold_changes_write_count_not_output() -> io::Result<()> {
+    let dir: tempfile::TempDir = tempdir()?;
+    let buffered_path: PathBuf = dir.path().join("buffered.tsf");
+    let small_path: PathBuf = dir.path().join("small.tsf");
+
+    // Same batches as the sync test, the default buffers hold all of them so each spool is written once
+    let buffered_flushes: usize = stream_with_threshold(buffered_path.to_str().unwrap(), None).await?;
+    let small_flushes: usize = stream_with_threshold(small_path.to_str().unwrap(), Some(32)).await?;
+    assert_eq!(buffered_flushes, 2);
+    assert!(small_flushes > buffered_flushes * 10);
+
+    assert_eq!(fs::read(&buffered_path)?.len(), fs::read(&small_path)?.len());
+    let mut buffered: AsyncTSFReader = AsyncTSFReader::new(buffered_path.to_str().unwrap()).await?;
+    buffered.read_all().await?;
+    let mut small: AsyncTSFReader = AsyncTSFReader::new(small_path.to_str().unwrap()).await?;
+    small.read_all().await?;
+    assert_eq!(buffered.column_slice::<i32>(0).unwrap(), small.column_slice::<i32>(0).unwrap());
+    assert_eq!(buffered.column_slice::<i8>(1).unwrap(), small.column_slice::<i8>(1).unwrap());
+    assert_eq!(buffered.column_slice::<i32>(0).unwrap().len(), 200);
+
+    Ok(())
+  }
+}
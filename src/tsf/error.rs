@@ -0,0 +1,99 @@
+use std::{error::Error, fmt, io, path::{Path, PathBuf}};
+
+use super::segments::types::{EnumDataEnc, EnumDataType, MAX_COLUMN_NAME_SIZE};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TsfError {
+  // The cast is not defined between the two types
+  InvalidCast { from: EnumDataType, to: EnumDataType },
+  // A value does not fit in the target type
+  CastOverflow { to: EnumDataType, value: String },
+  // The timestamp column is not in ascending order
+  TimestampNotSorted { column: String },
+  // The encoding cannot be applied to the column type
+  UnsupportedEncoding { column: String, encoding: EnumDataEnc, data_type: EnumDataType },
+  // The column name would break the CSV or JSON output it ends up in
+  InvalidColumnName { column: String },
+  // The column name doesn't fit the u16 column_name_length, only its byte length is kept
+  ColumnNameTooLong { length: usize },
+  // The file structure is inconsistent, e.g. a segment chain pointing outside the file
+  Corruption { reason: String },
+  // Another writer holds the file's write lock
+  AlreadyLocked { path: String },
+  // The file was written with a different schema_id than the caller expects
+  SchemaMismatch { expected: u64, found: u64 },
+  // Opening the file failed because it doesn't exist
+  FileNotFound { path: String },
+  // Opening the file failed because the process may not read or write it
+  PermissionDenied { path: String },
+  // The file exists but a new one had to be created
+  FileExists { path: String },
+  // The path can't name a TSF file, e.g. it is empty or a directory
+  InvalidPath { path: String, reason: String },
+  // A segment with no rows has no timestamps to give it a date range
+  EmptySegment,
+  // The segment's date_start is after its date_end, pruning would skip it for every query
+  InvalidDateRange { start: i64, end: i64 },
+}
+
+impl fmt::Display for TsfError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TsfError::InvalidCast { from, to } => write!(f, "Cannot cast {:?} to {:?}", from, to),
+      TsfError::CastOverflow { to, value } => write!(f, "Value {} does not fit in {:?}", value, to),
+      TsfError::TimestampNotSorted { column } => write!(f, "Timestamp column {} is not sorted ascending", column),
+      TsfError::UnsupportedEncoding { column, encoding, data_type } => write!(f, "Column {} cannot use {:?} encoding with {:?}", column, encoding, data_type),
+      TsfError::InvalidColumnName { column } => write!(f, "Column name {:?} contains a control character or the CSV delimiter", column),
+      TsfError::ColumnNameTooLong { length } => write!(f, "Column name is {} bytes, more than the limit of {}", length, MAX_COLUMN_NAME_SIZE),
+      TsfError::Corruption { reason } => write!(f, "Corruption: {}", reason),
+      TsfError::AlreadyLocked { path } => write!(f, "{} is locked by another writer", path),
+      TsfError::SchemaMismatch { expected, found } => write!(f, "Expected schema_id {} but the file has {}", expected, found),
+      TsfError::FileNotFound { path } => write!(f, "{} does not exist", path),
+      TsfError::PermissionDenied { path } => write!(f, "Permission denied for {}", path),
+      TsfError::FileExists { path } => write!(f, "{} already exists", path),
+      TsfError::InvalidPath { path, reason } => write!(f, "Invalid path {:?}: {}", path, reason),
+      TsfError::EmptySegment => write!(f, "Segment has no rows, so it has no date range to write"),
+      TsfError::InvalidDateRange { start, end } => write!(f, "Segment date_start {} is after date_end {}", start, end),
+    }
+  }
+}
+
+impl Error for TsfError {}
+
+impl From<TsfError> for io::Error {
+  fn from(err: TsfError) -> Self {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+  }
+}
+
+// Wraps the open errors users hit most in a TsfError naming the path, keeping the io::ErrorKind so callers
+// matching on it still work. Other errors are returned as they are
+pub fn open_error(err: io::Error, path: &Path) -> io::Error {
+  let path: String = path.display().to_string();
+  let tsf_err: TsfError = match err.kind() {
+    io::ErrorKind::NotFound => TsfError::FileNotFound { path },
+    io::ErrorKind::PermissionDenied => TsfError::PermissionDenied { path },
+    io::ErrorKind::AlreadyExists => TsfError::FileExists { path },
+    _ => return err,
+  };
+  io::Error::new(err.kind(), tsf_err)
+}
+
+// Checked before a reader, writer or editor opens the path, so an empty or directory path gets a clear
+// error instead of whatever the OS reports, and a writer never leaves a lock sidecar next to a directory
+pub fn check_path(path: &str) -> io::Result<PathBuf> {
+  let reason: &str = if path.is_empty() {
+    "the path is empty"
+  } else if Path::new(path).is_dir() {
+    "it is a directory"
+  } else {
+    return Ok(PathBuf::from(path));
+  };
+  Err(io::Error::new(io::ErrorKind::InvalidInput, TsfError::InvalidPath { path: path.to_string(), reason: reason.to_string() }))
+}
+
+impl From<TsfError> for String {
+  fn from(err: TsfError) -> Self {
+    err.to_string()
+  }
+}
@@ -1,14 +1,19 @@
 use std::{fs::File, io::{self, Read, Write}};
 
+use super::error::TsfError;
+
 // "TSFD" in hex Magic Number
 const TSFD_MAGIC_NUMBER: u32 = 0x54534644;
 // Version Number
 const TSFD_VERSION: u16 = 1;
+// Version 2 headers carry a u64 schema_id after the version, only written when a schema_id is set
+const TSFD_SCHEMA_VERSION: u16 = 2;
 
 #[repr(C)]
 pub struct FileHeader {
   magic_number: u32,
   version: u16,
+  schema_id: u64,
 }
 
 impl FileHeader {
@@ -16,13 +21,35 @@ impl FileHeader {
     FileHeader {
         magic_number: TSFD_MAGIC_NUMBER, 
         version: TSFD_VERSION,
+        schema_id: 0,
     }
   }
 
+  // User supplied id of the schema the file was written with, 0 means unset and keeps the version 1 header
+  pub fn set_schema_id(&mut self, schema_id: u64) {
+    self.schema_id = schema_id;
+    self.version = if schema_id == 0 { TSFD_VERSION } else { TSFD_SCHEMA_VERSION };
+  }
+
+  pub fn get_schema_id(&self) -> u64 {
+    self.schema_id
+  }
+
+  // An expected id of 0 accepts any file, otherwise the file must carry exactly that id
+  pub fn check_schema_id(&self, expected: u64) -> Result<(), TsfError> {
+    if expected != 0 && self.schema_id != expected {
+      return Err(TsfError::SchemaMismatch { expected, found: self.schema_id });
+    }
+    Ok(())
+  }
+
   pub fn write_header(&self, file: &mut File) -> io::Result<()> {
     let mut bytes: Vec<u8> = Vec::new();
     bytes.extend_from_slice(&self.magic_number.to_le_bytes());
     bytes.extend_from_slice(&self.version.to_le_bytes());
+    if self.version == TSFD_SCHEMA_VERSION {
+      bytes.extend_from_slice(&self.schema_id.to_le_bytes());
+    }
 
     file.write_all(&bytes)
   }
@@ -34,12 +61,19 @@ impl FileHeader {
 
     self.magic_number = u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
     self.version = u16::from_le_bytes([buffer[4], buffer[5]]);
+    self.schema_id = 0;
+
+    if self.version == TSFD_SCHEMA_VERSION {
+      let mut schema_id: [u8; 8] = [0u8; 8];
+      file.read_exact(&mut schema_id)?;
+      self.schema_id = u64::from_le_bytes(schema_id);
+    }
     
     Ok(())
   }
 
   pub fn verify_header(&self) -> bool {
-    self.magic_number == TSFD_MAGIC_NUMBER && self.version == TSFD_VERSION
+    self.magic_number == TSFD_MAGIC_NUMBER && (self.version == TSFD_VERSION || self.version == TSFD_SCHEMA_VERSION)
   }
 }
 
@@ -97,4 +131,26 @@ mod tests {
     
     Ok(())
   }
+
+  #[test]
+  fn test_schema_id_round_trip() -> io::Result<()> {
+    let mut file: File = tempfile()?;
+    let mut header: FileHeader = FileHeader::new();
+    header.set_schema_id(0x0102030405060708);
+    header.write_header(&mut file)?;
+    assert_eq!(file.stream_position()?, 14);
+
+    file.seek(SeekFrom::Start(0))?;
+    let mut read_back: FileHeader = FileHeader::new();
+    read_back.read_header(&mut file)?;
+
+    assert!(read_back.verify_header());
+    assert_eq!(read_back.version, 2);
+    assert_eq!(read_back.get_schema_id(), 0x0102030405060708);
+    assert!(read_back.check_schema_id(0).is_ok());
+    assert!(read_back.check_schema_id(0x0102030405060708).is_ok());
+    assert_eq!(read_back.check_schema_id(7), Err(TsfError::SchemaMismatch { expected: 7, found: 0x0102030405060708 }));
+
+    Ok(())
+  }
 }
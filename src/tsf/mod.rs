@@ -1,6 +1,11 @@
 pub mod segments;
+pub mod error;
 pub mod header;
+pub mod read_util;
 pub mod tsf_writer;
 pub mod tsf_reader;
+pub mod tsf_editor;
+pub mod write_lock;
 pub mod async_tsf_reader;
+pub mod async_tsf_writer;
 pub mod async_header;
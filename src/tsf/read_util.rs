@@ -0,0 +1,89 @@
+use std::{fs::File, io::{self, Read, Seek, SeekFrom}};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt};
+use tracing::trace;
+
+use super::error::TsfError;
+
+// The next segment has to start after this one and within the file, otherwise the chain could loop
+pub fn next_segment_pos(segment_pos: u64, segment_end: u64, file_len: u64, next_offset: Option<u32>) -> io::Result<u64> {
+  let next_offset: u32 = next_offset
+    .filter(|next_offset: &u32| *next_offset > 0)
+    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Segment next_offset is not set"))?;
+  let next_pos: u64 = segment_pos + next_offset as u64;
+  if next_pos < segment_end || next_pos > file_len {
+    return Err(TsfError::Corruption {
+      reason: format!("segment at {} has next_offset {} outside {}..={}", segment_pos, next_offset, segment_end, file_len),
+    }.into());
+  }
+  Ok(next_pos)
+}
+
+// Optional trailer written after the last segment, FOOTER_MAGIC followed by the last segment's position
+// (from the start of the file header) as a u64. It is rewritten on every append and only ever read as a
+// shortcut, readers check it against the segment it points at
+pub const FOOTER_MAGIC: [u8; 8] = *b"TSFLAST1";
+pub const FOOTER_SIZE: u64 = 16;
+
+pub fn encode_footer(last_segment_pos: u64) -> [u8; FOOTER_SIZE as usize] {
+  let mut footer: [u8; FOOTER_SIZE as usize] = [0u8; FOOTER_SIZE as usize];
+  footer[..8].copy_from_slice(&FOOTER_MAGIC);
+  footer[8..].copy_from_slice(&last_segment_pos.to_le_bytes());
+  footer
+}
+
+// The last segment position when bytes are a footer
+pub fn decode_footer(bytes: &[u8]) -> Option<u64> {
+  if bytes.len() != FOOTER_SIZE as usize || bytes[..8] != FOOTER_MAGIC {
+    return None;
+  }
+  Some(u64::from_le_bytes(bytes[8..].try_into().ok()?))
+}
+
+// Where the segment chain ends and the footer's last segment position. Without a footer the chain runs
+// to the end of the file. Leaves the file positioned at its end
+pub fn chain_end(file: &mut File) -> io::Result<(u64, Option<u64>)> {
+  let file_len: u64 = file.seek(SeekFrom::End(0))?;
+  if file_len < FOOTER_SIZE {
+    return Ok((file_len, None));
+  }
+
+  let mut footer: [u8; FOOTER_SIZE as usize] = [0u8; FOOTER_SIZE as usize];
+  file.seek(SeekFrom::Start(file_len - FOOTER_SIZE))?;
+  file.read_exact(&mut footer)?;
+  Ok(match decode_footer(&footer) {
+    Some(last_segment_pos) => (file_len - FOOTER_SIZE, Some(last_segment_pos)),
+    None => (file_len, None),
+  })
+}
+
+pub async fn async_chain_end(file: &mut tokio::fs::File) -> io::Result<(u64, Option<u64>)> {
+  let file_len: u64 = file.seek(SeekFrom::End(0)).await?;
+  if file_len < FOOTER_SIZE {
+    return Ok((file_len, None));
+  }
+
+  let mut footer: [u8; FOOTER_SIZE as usize] = [0u8; FOOTER_SIZE as usize];
+  file.seek(SeekFrom::Start(file_len - FOOTER_SIZE)).await?;
+  async_read_exact_retry(file, &mut footer).await?;
+  Ok(match decode_footer(&footer) {
+    Some(last_segment_pos) => (file_len - FOOTER_SIZE, Some(last_segment_pos)),
+    None => (file_len, None),
+  })
+}
+
+pub async fn async_read_exact_retry<R: AsyncRead + Unpin>(reader: &mut R, buffer: &mut [u8]) -> io::Result<()> {
+  trace!("async_read_exact_retry");
+
+  let mut filled: usize = 0;
+  while filled < buffer.len() {
+    match reader.read(&mut buffer[filled..]).await {
+      Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "failed to fill whole buffer")),
+      Ok(read) => filled += read,
+      Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+      Err(e) => return Err(e),
+    }
+  }
+
+  Ok(())
+}
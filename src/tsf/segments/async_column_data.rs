@@ -1,10 +1,16 @@
-use std::io::Cursor;
+use std::sync::Arc;
 
 use tokio::fs::File;
-use tokio::io::{self, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, SeekFrom};
+use tokio::io::{self, AsyncSeekExt, AsyncWriteExt, SeekFrom};
 use tracing::trace;
 
-use super::types::{EnumColumnData, EnumDataComp, EnumDataEnc, EnumDataType};
+use crate::tsf::error::TsfError;
+use crate::tsf::read_util::async_read_exact_retry;
+
+use super::checksum::calculate_checksum;
+use super::codec::ColumnCodec;
+use super::compression::{compress, decompress};
+use super::types::{EnumChecksumAlg, EnumColumnData, EnumDataComp, EnumDataEnc, EnumDataType, EnumDataValue};
 
 pub trait ColumnDataCreator {
   fn create_segment_column_data(column: Vec<Self>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData
@@ -20,13 +26,61 @@ impl ColumnDataCreator for i8 {
 
 impl ColumnDataCreator for i16 {
   fn create_segment_column_data(column: Vec<Self>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
-    SegmentColumnData::new_int16_vec(column, file_pos, encoding, compression)
+    SegmentColumnData::from_column_data(EnumColumnData::Int16Vec(column), file_pos, encoding, compression)
   }
 }
 
 impl ColumnDataCreator for i32 {
   fn create_segment_column_data(column: Vec<Self>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
-    SegmentColumnData::new_int32_vec(column, file_pos, encoding, compression)
+    SegmentColumnData::from_column_data(EnumColumnData::Int32Vec(column), file_pos, encoding, compression)
+  }
+}
+
+impl ColumnDataCreator for i64 {
+  fn create_segment_column_data(column: Vec<Self>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
+    SegmentColumnData::from_column_data(EnumColumnData::Int64Vec(column), file_pos, encoding, compression)
+  }
+}
+
+impl ColumnDataCreator for u8 {
+  fn create_segment_column_data(column: Vec<Self>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
+    SegmentColumnData::from_column_data(EnumColumnData::UInt8Vec(column), file_pos, encoding, compression)
+  }
+}
+
+impl ColumnDataCreator for u16 {
+  fn create_segment_column_data(column: Vec<Self>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
+    SegmentColumnData::from_column_data(EnumColumnData::UInt16Vec(column), file_pos, encoding, compression)
+  }
+}
+
+impl ColumnDataCreator for u32 {
+  fn create_segment_column_data(column: Vec<Self>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
+    SegmentColumnData::from_column_data(EnumColumnData::UInt32Vec(column), file_pos, encoding, compression)
+  }
+}
+
+impl ColumnDataCreator for u64 {
+  fn create_segment_column_data(column: Vec<Self>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
+    SegmentColumnData::from_column_data(EnumColumnData::UInt64Vec(column), file_pos, encoding, compression)
+  }
+}
+
+impl ColumnDataCreator for f32 {
+  fn create_segment_column_data(column: Vec<Self>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
+    SegmentColumnData::from_column_data(EnumColumnData::Float32Vec(column), file_pos, encoding, compression)
+  }
+}
+
+impl ColumnDataCreator for f64 {
+  fn create_segment_column_data(column: Vec<Self>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
+    SegmentColumnData::from_column_data(EnumColumnData::Float64Vec(column), file_pos, encoding, compression)
+  }
+}
+
+impl ColumnDataCreator for bool {
+  fn create_segment_column_data(column: Vec<Self>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
+    SegmentColumnData::from_column_data(EnumColumnData::BooleanVec(column), file_pos, encoding, compression)
   }
 }
 
@@ -36,6 +90,10 @@ pub struct SegmentColumnData {
   encoding: EnumDataEnc,
   compression: EnumDataComp,
   buffer: Option<Vec<u8>>,
+  checksum_alg: EnumChecksumAlg,
+  column_check: [u8; 8],
+  // Write only: the codec's bytes are stored in place of the built in encoding
+  codec: Option<Arc<dyn ColumnCodec>>,
 }
 
 impl SegmentColumnData {
@@ -44,6 +102,19 @@ impl SegmentColumnData {
     Some(&self.data)
   }
 
+  // On-disk bytes, still encoded and compressed, between read_file_into_buffer and convert_buffer_into_data
+  pub fn raw_buffer(&self) -> Option<&[u8]> {
+    self.buffer.as_deref()
+  }
+
+  pub fn get_file_pos(&self) -> usize {
+    self.file_pos
+  }
+
+  pub fn set_file_pos(&mut self, file_pos: usize) {
+    self.file_pos = file_pos;
+  }
+
   pub fn new(data_type: EnumDataType, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
     trace!("SegmentColumnData::new");
     SegmentColumnData {
@@ -52,227 +123,110 @@ impl SegmentColumnData {
       encoding: encoding,
       compression: compression,
       buffer: None,
+      checksum_alg: EnumChecksumAlg::default(),
+      column_check: [0u8; 8],
+      codec: None,
     }
   }
 
-  pub fn new_int8_vec(initial_data: Vec<i8>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    trace!("SegmentColumnData::new_int8_vec");
+  // Builds a column from any data variant, for callers that only know the type at runtime
+  pub fn from_column_data(data: EnumColumnData, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
+    trace!("SegmentColumnData::from_column_data");
     SegmentColumnData {
-        data: EnumColumnData::Int8Vec(initial_data),
-        file_pos: file_pos,
-        encoding: encoding,
-        compression: compression,
+        data,
+        file_pos,
+        encoding,
+        compression,
         buffer: None,
+        checksum_alg: EnumChecksumAlg::default(),
+        column_check: [0u8; 8],
+        codec: None,
     }
   }
 
-  fn new_int16_vec(initial_data: Vec<i16>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    trace!("SegmentColumnData::new_int16_vec");
-    SegmentColumnData {
-        data: EnumColumnData::Int16Vec(initial_data),
-        file_pos: file_pos,
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
-    }
+  // Column of a custom type, the codec encodes it when the segment is written so sorting still applies
+  pub fn with_codec(mut self, codec: Arc<dyn ColumnCodec>) -> Self {
+    self.codec = Some(codec);
+    self
   }
 
-  fn new_int32_vec(initial_data: Vec<i32>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    trace!("SegmentColumnData::new_int32_vec");
-    SegmentColumnData {
-        data: EnumColumnData::Int32Vec(initial_data),
-        file_pos: file_pos,
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
+  // Bytes before compression, from the codec when the column has one
+  fn encoded_bytes(&self) -> Vec<u8> {
+    match &self.codec {
+      Some(codec) => codec.encode(&self.data),
+      None => self.data.to_encoded_bytes(self.encoding),
     }
   }
 
-  fn new_int64_vec(initial_data: Vec<i64>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    trace!("SegmentColumnData::new_int64_vec");
-    SegmentColumnData {
-        data: EnumColumnData::Int64Vec(initial_data),
-        file_pos: file_pos,
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
-    }
+  pub fn new_int8_vec(initial_data: Vec<i8>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
+    trace!("SegmentColumnData::new_int8_vec");
+    Self::from_column_data(EnumColumnData::Int8Vec(initial_data), file_pos, encoding, compression)
   }
 
-  fn new_uint8_vec(initial_data: Vec<u8>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::UInt8Vec(initial_data),
-        file_pos: file_pos,
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
-    }
-  }
+  pub fn convert_data_into_buffer(&mut self) -> io::Result<usize> {
+    trace!("SegmentColumnData::convert_data_into_buffer");
+    let buffer: Vec<u8> = compress(self.compression, self.encoded_bytes())?;
 
-  fn new_uint16_vec(initial_data: Vec<u16>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::UInt16Vec(initial_data),
-        file_pos: file_pos,
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
-    }
-  }
+    let total_bytes: usize = buffer.len();
+    self.column_check = calculate_checksum(self.checksum_alg, &buffer);
+    self.buffer = Some(buffer);
 
-  fn new_uint32_vec(initial_data: Vec<u32>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::UInt32Vec(initial_data),
-        file_pos: file_pos,
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
-    }
+    Ok(total_bytes)
   }
 
-  fn new_uint64_vec(initial_data: Vec<u64>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::UInt64Vec(initial_data),
-        file_pos: file_pos,
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
-    }
+  // Algorithm of the segment the column belongs to, used for column_check
+  pub fn set_checksum_alg(&mut self, checksum_alg: EnumChecksumAlg) {
+    self.checksum_alg = checksum_alg;
   }
 
-  fn new_float32_vec(initial_data: Vec<f32>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::Float32Vec(initial_data),
-        file_pos: file_pos,
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
-    }
+  // Checksum of the bytes prepared by convert_data_into_buffer, for the column header's column_check
+  pub fn get_column_check(&self) -> [u8; 8] {
+    self.column_check
   }
 
-  fn new_float64_vec(initial_data: Vec<f64>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::Float64Vec(initial_data),
-        file_pos: file_pos,
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
+  // Checks the bytes read by read_file_into_buffer against the header's column_check. Files written before
+  // column checks have all zero checks and are not verified, repair_checksums fills them in
+  pub fn verify_column_check(&self, column_check: [u8; 8]) -> bool {
+    match self.buffer.as_deref() {
+      Some(buffer) => column_check_matches(self.checksum_alg, column_check, buffer),
+      None => false,
     }
   }
 
-  fn new_boolean_vec(initial_data: Vec<bool>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::BooleanVec(initial_data),
-        file_pos: file_pos,
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
-    }
-  }
+  // Compressed size over raw size for this column's settings, without touching the prepared buffer
+  pub fn estimate_compression_ratio(&self) -> io::Result<f64> {
+    trace!("SegmentColumnData::estimate_compression_ratio");
 
-  fn new_datetime32_vec(initial_data: Vec<i32>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::DateTime32Vec(initial_data),
-        file_pos: file_pos,
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
+    let raw: Vec<u8> = self.encoded_bytes();
+    if raw.is_empty() {
+      return Ok(1.0);
     }
+    let raw_len: usize = raw.len();
+    let compressed: Vec<u8> = compress(self.compression, raw)?;
+
+    Ok(compressed.len() as f64 / raw_len as f64)
   }
 
-  fn new_datetime64_vec(initial_data: Vec<i64>, file_pos: usize, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::DateTime64Vec(initial_data),
-        file_pos: file_pos,
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
-    }
+  // Distinct values in the decoded column, a low count relative to the rows suggests dictionary encoding
+  pub fn distinct_count(&self) -> usize {
+    trace!("SegmentColumnData::distinct_count");
+
+    self.data.distinct_count()
   }
 
-  pub fn convert_data_into_buffer(&mut self) -> io::Result<usize> {
-    trace!("SegmentColumnData::convert_data_into_buffer");
-    let mut buffer: Vec<u8> = Vec::new();
-
-    match &self.data {
-      EnumColumnData::Int8Vec(data) => {
-        for &value in data {
-          byteorder::WriteBytesExt::write_i8(&mut buffer, value)?;
-        }
-      },
-      EnumColumnData::Int16Vec(data) => {
-        for &value in data {
-          byteorder::WriteBytesExt::write_i16::<byteorder::LittleEndian>(&mut buffer, value)?;
-        }
-      },
-      EnumColumnData::Int32Vec(data) => {
-        for &value in data {
-          byteorder::WriteBytesExt::write_i32::<byteorder::LittleEndian>(&mut buffer, value)?;
-        }
-      },
-      EnumColumnData::Int64Vec(data) => {
-        for &value in data {
-          byteorder::WriteBytesExt::write_i64::<byteorder::LittleEndian>(&mut buffer, value)?;
-        }
-      },
-      EnumColumnData::UInt8Vec(data) => {
-        for &value in data {
-          byteorder::WriteBytesExt::write_u8(&mut buffer, value)?;
-        }
-      },
-      EnumColumnData::UInt16Vec(data) => {
-        for &value in data {
-          byteorder::WriteBytesExt::write_u16::<byteorder::LittleEndian>(&mut buffer, value)?;
-        }
-      },
-      EnumColumnData::UInt32Vec(data) => {
-        for &value in data {
-          byteorder::WriteBytesExt::write_u32::<byteorder::LittleEndian>(&mut buffer, value)?;
-        }
-      },
-      EnumColumnData::UInt64Vec(data) => {
-        for &value in data {
-          byteorder::WriteBytesExt::write_u64::<byteorder::LittleEndian>(&mut buffer, value)?;
-        }
-      },
-      EnumColumnData::Float32Vec(data) => {
-        for &value in data {
-          byteorder::WriteBytesExt::write_f32::<byteorder::LittleEndian>(&mut buffer, value)?;
-        }
-      },
-      EnumColumnData::Float64Vec(data) => {
-        for &value in data {
-          byteorder::WriteBytesExt::write_f64::<byteorder::LittleEndian>(&mut buffer, value)?;
-        }
-      },
-      EnumColumnData::BooleanVec(data) => {
-        for &value in data {
-          // Convert bool to u8 (true -> 255, false -> 0)
-          let byte_value: u8 = if value { 255u8 } else { 0u8 };
-          byteorder::WriteBytesExt::write_u8(&mut buffer, byte_value)?;
-        }
-      },
-      EnumColumnData::DateTime32Vec(data) => {
-        for &value in data {
-          byteorder::WriteBytesExt::write_i32::<byteorder::LittleEndian>(&mut buffer, value)?;
-        }
-      },
-      EnumColumnData::DateTime64Vec(data) => {
-        for &value in data {
-          byteorder::WriteBytesExt::write_i64::<byteorder::LittleEndian>(&mut buffer, value)?;
-        }
-      },
-      // EnumColumnData::StringVec(data) => {
-      //   for value in data {
-      //     file.write_all(value.as_bytes())?;
-      //   }
-      // },
-      // Handle other types...
-    }
+  // A single value of a column read with read_file_into_buffer. Unencoded, uncompressed columns are fixed
+  // width so the value is read straight from the buffer, anything else is decoded in place first
+  pub fn value_at(&mut self, index: usize) -> io::Result<Option<EnumDataValue>> {
+    trace!("SegmentColumnData::value_at");
 
-    let total_bytes: usize = buffer.len();
-    self.buffer = Some(buffer);
+    if let Some(buffer) = self.buffer.as_deref() {
+      if self.encoding == EnumDataEnc::None && self.compression == EnumDataComp::None {
+        return Ok(self.data.data_type().value_from_le_bytes(buffer, index));
+      }
+      self.convert_buffer_into_data()?;
+    }
 
-    Ok(total_bytes)
+    Ok(self.data.value_at(index))
   }
 
   pub fn convert_buffer_into_data(&mut self) -> io::Result<()> {
@@ -281,181 +235,7 @@ impl SegmentColumnData {
     let buffer: Vec<u8> = self.buffer.take()
       .ok_or(io::Error::new(io::ErrorKind::Other, "Buffer is empty"))?;
 
-    let mut cursor: Cursor<Vec<u8>> = Cursor::new(buffer);
-
-    match &mut self.data {
-      EnumColumnData::Int8Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = byteorder::ReadBytesExt::read_i8(&mut cursor) {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = byteorder::ReadBytesExt::read_i8(&mut cursor) {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::Int16Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = byteorder::ReadBytesExt::read_i16::<byteorder::LittleEndian>(&mut cursor) {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = byteorder::ReadBytesExt::read_i16::<byteorder::LittleEndian>(&mut cursor) {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::Int32Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = byteorder::ReadBytesExt::read_i32::<byteorder::LittleEndian>(&mut cursor) {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = byteorder::ReadBytesExt::read_i32::<byteorder::LittleEndian>(&mut cursor) {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::Int64Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = byteorder::ReadBytesExt::read_i64::<byteorder::LittleEndian>(&mut cursor) {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = byteorder::ReadBytesExt::read_i64::<byteorder::LittleEndian>(&mut cursor) {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::UInt8Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = byteorder::ReadBytesExt::read_u8(&mut cursor) {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = byteorder::ReadBytesExt::read_u8(&mut cursor) {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::UInt16Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = byteorder::ReadBytesExt::read_u16::<byteorder::LittleEndian>(&mut cursor) {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = byteorder::ReadBytesExt::read_u16::<byteorder::LittleEndian>(&mut cursor) {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::UInt32Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = byteorder::ReadBytesExt::read_u32::<byteorder::LittleEndian>(&mut cursor) {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = byteorder::ReadBytesExt::read_u32::<byteorder::LittleEndian>(&mut cursor) {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::UInt64Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = byteorder::ReadBytesExt::read_u64::<byteorder::LittleEndian>(&mut cursor) {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = byteorder::ReadBytesExt::read_u64::<byteorder::LittleEndian>(&mut cursor) {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::Float32Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = byteorder::ReadBytesExt::read_f32::<byteorder::LittleEndian>(&mut cursor) {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = byteorder::ReadBytesExt::read_f32::<byteorder::LittleEndian>(&mut cursor) {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::Float64Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = byteorder::ReadBytesExt::read_f64::<byteorder::LittleEndian>(&mut cursor) {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = byteorder::ReadBytesExt::read_f64::<byteorder::LittleEndian>(&mut cursor) {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::BooleanVec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = byteorder::ReadBytesExt::read_u8(&mut cursor) {
-          // Convert bool to u8 (true -> 255, false -> 0)
-          let bool_value: bool = if value == 0u8 { false } else { true };
-          data_vec.push(bool_value);
-        }
-
-        if let Err(e) = byteorder::ReadBytesExt::read_u8(&mut cursor) {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::DateTime32Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = byteorder::ReadBytesExt::read_i32::<byteorder::LittleEndian>(&mut cursor) {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = byteorder::ReadBytesExt::read_i32::<byteorder::LittleEndian>(&mut cursor) {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::DateTime64Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = byteorder::ReadBytesExt::read_i64::<byteorder::LittleEndian>(&mut cursor) {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = byteorder::ReadBytesExt::read_i64::<byteorder::LittleEndian>(&mut cursor) {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-    }
+    self.data.read_encoded_bytes(self.encoding, &decompress(self.compression, &buffer)?)?;
 
     Ok(())
   }
@@ -486,12 +266,41 @@ impl SegmentColumnData {
 
     // Prepare the buffer
     let mut buffer: Vec<u8> = vec![0u8; bytes];
-    file.read_exact(&mut buffer).await?;
+    async_read_exact_retry(file, &mut buffer).await?;
     
     self.buffer = Some(buffer);
 
     Ok(())
   }
+
+  // Reads and decodes bytes using the caller's buffer, so a loop over many columns can share one allocation.
+  // The bytes are checked against the header's column_check before they are decompressed
+  pub async fn read_into(&mut self, file: &mut File, bytes: usize, column_check: [u8; 8], reuse_buffer: &mut Vec<u8>) -> io::Result<()> {
+    trace!("SegmentColumnData::read_into");
+
+    let current_position: usize = file.seek(SeekFrom::Current(0)).await? as usize;
+    if current_position != self.file_pos {
+      file.seek(SeekFrom::Start(self.file_pos as u64)).await?;
+    }
+
+    reuse_buffer.clear();
+    reuse_buffer.resize(bytes, 0u8);
+    async_read_exact_retry(file, reuse_buffer).await?;
+    if !column_check_matches(self.checksum_alg, column_check, reuse_buffer) {
+      return Err(TsfError::Corruption { reason: "Column failed column check verification".to_string() }.into());
+    }
+    match self.compression {
+      EnumDataComp::None => self.data.read_encoded_bytes(self.encoding, reuse_buffer)?,
+      _ => self.data.read_encoded_bytes(self.encoding, &decompress(self.compression, reuse_buffer)?)?,
+    }
+
+    Ok(())
+  }
+}
+
+// An all zero column_check comes from a file written before column checks and matches any bytes
+fn column_check_matches(checksum_alg: EnumChecksumAlg, column_check: [u8; 8], bytes: &[u8]) -> bool {
+  column_check == [0u8; 8] || column_check == calculate_checksum(checksum_alg, bytes)
 }
 
 #[cfg(test)]
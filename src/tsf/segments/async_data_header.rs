@@ -1,14 +1,27 @@
-use std::io::{self, Cursor};
+use std::{collections::BTreeMap, io::{self, Cursor}};
 
 use byteorder::{ByteOrder, LittleEndian};
-use tokio::{fs::File, io::{AsyncReadExt, AsyncWriteExt}};
+use tokio::{fs::File, io::AsyncWriteExt};
 use tracing::trace;
 
-use super::types::{ColumnMeta, EnumDataComp, EnumDataEnc, EnumDataType};
+use crate::tsf::error::TsfError;
+use crate::tsf::read_util::async_read_exact_retry;
+
+use super::checksum::calculate_checksum;
+use super::metadata::{decode_metadata, encode_metadata, MAX_METADATA_SIZE};
+use super::types::{ColumnMeta, EnumChecksumAlg, EnumDataComp, EnumDataEnc, EnumDataType, UnsupportedColumn};
+
+// Stored in ts_column when the segment has no timestamp column
+pub const NO_TS_COLUMN: u16 = u16::MAX;
+
+// Most columns a segment header may declare before it is taken for corruption, a bad column_count
+// would otherwise have every one of up to 65535 column headers parsed
+pub const DEFAULT_MAX_COLUMN_COUNT: u16 = 4096;
 
 #[repr(C)]
 pub struct SegmentDataHeader {
   pub tombstone: bool,
+  pub checksum_alg: EnumChecksumAlg,
   pub next_offset: Option<u32>,
   pub uuid_txid: Option<[u8; 16]>,
   date_start: Option<i64>,
@@ -18,13 +31,25 @@ pub struct SegmentDataHeader {
   ts_column: Option<u16>,
   pub column_header_size: u32,
   pub column_headers: Vec<SegmentColumnHeader>,
+  pub metadata_size: u16,
+  metadata: BTreeMap<String, String>,
+  // Wall clock seconds since the epoch when the segment was written, 0 if it never was
+  created_at: i64,
   segment_check: Option<[u8; 8]>,
+  // Read only: columns with unknown type codes are skipped instead of failing the read
+  skip_unsupported_columns: bool,
+  unsupported_columns: Vec<UnsupportedColumn>,
+  // Read only: type codes with a registered codec, kept like unsupported columns even when not skipping
+  custom_type_codes: Vec<u16>,
+  // Read only: a larger column_count fails the read with Corruption
+  max_column_count: u16,
 }
 
 impl SegmentDataHeader {
   pub fn new() -> Self {
     SegmentDataHeader {
       tombstone: false,
+      checksum_alg: EnumChecksumAlg::default(),
       next_offset: None,
       uuid_txid: None,
       date_start: None,
@@ -34,7 +59,14 @@ impl SegmentDataHeader {
       ts_column: None,
       column_header_size: 0,
       column_headers: vec![],
+      metadata_size: 0,
+      metadata: BTreeMap::new(),
+      created_at: 0,
       segment_check: None,
+      skip_unsupported_columns: false,
+      unsupported_columns: vec![],
+      custom_type_codes: vec![],
+      max_column_count: DEFAULT_MAX_COLUMN_COUNT,
     }
   }
 
@@ -52,6 +84,19 @@ impl SegmentDataHeader {
     new_column_index
   }
 
+  pub fn describe_column(&mut self, column_name: &str, description: &str) -> Result<(), String> {
+    trace!("SegmentDataHeader::describe_column");
+
+    let column_header: &mut SegmentColumnHeader = self.column_headers.iter_mut()
+      .find(|header: &&mut SegmentColumnHeader| header.column_name == column_name)
+      .ok_or_else(|| format!("Column {} not found", column_name))?;
+    column_header.set_description(description)?;
+    self.column_header_size = self.column_headers.iter()
+      .map(|header| header.byte_size())
+      .sum();
+    Ok(())
+  }
+
   pub fn set_ts_column(&mut self, ts_column_index: u16) -> Result<(), String> {
     trace!("SegmentDataHeader::set_ts_column");
 
@@ -67,6 +112,58 @@ impl SegmentDataHeader {
     Ok(())
   }
 
+  pub fn get_ts_column(&self) -> Option<u16> {
+    self.ts_column
+  }
+
+  pub fn set_skip_unsupported_columns(&mut self, skip_unsupported_columns: bool) {
+    self.skip_unsupported_columns = skip_unsupported_columns;
+  }
+
+  pub fn set_max_column_count(&mut self, max_column_count: u16) {
+    self.max_column_count = max_column_count;
+  }
+
+  pub fn set_custom_type_codes(&mut self, custom_type_codes: Vec<u16>) {
+    self.custom_type_codes = custom_type_codes;
+  }
+
+  pub fn get_unsupported_columns(&self) -> &[UnsupportedColumn] {
+    &self.unsupported_columns
+  }
+
+  // Bytes of skipped columns stored right before column_headers[index], or after the last column
+  // when index is column_headers.len()
+  pub fn skipped_bytes_before(&self, index: usize) -> u64 {
+    self.skipped_before(index)
+      .map(|column: &UnsupportedColumn| column.column_size)
+      .sum()
+  }
+
+  // Skipped columns stored right before column_headers[index], in file order
+  pub fn skipped_before(&self, index: usize) -> impl Iterator<Item = &UnsupportedColumn> {
+    self.unsupported_columns.iter()
+      .enumerate()
+      .filter(move |(rank, column): &(usize, &UnsupportedColumn)| column.position - rank == index)
+      .map(|(_, column): (usize, &UnsupportedColumn)| column)
+  }
+
+  // Shifts ts_column and column_count from on-disk positions to indexes into column_headers
+  fn drop_unsupported_columns(&mut self) -> io::Result<()> {
+    if let Some(ts_column) = self.ts_column {
+      if self.unsupported_columns.iter().any(|column: &UnsupportedColumn| column.position == ts_column as usize) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Timestamp column has an unsupported type"));
+      }
+      let skipped: usize = self.unsupported_columns.iter()
+        .filter(|column: &&UnsupportedColumn| column.position < ts_column as usize)
+        .count();
+      self.ts_column = Some(ts_column - skipped as u16);
+    }
+    self.column_count = self.column_headers.len() as u16;
+
+    Ok(())
+  }
+
   pub fn set_date_start(&mut self, date_start: i64) {
     self.date_start = Some(date_start);
   }
@@ -75,30 +172,59 @@ impl SegmentDataHeader {
     self.date_end = Some(date_end);
   }
 
+  pub fn get_date_range(&self) -> Option<(i64, i64)> {
+    self.date_start.zip(self.date_end)
+  }
+
   pub fn calculate_header_size(&self) -> usize {
     trace!("SegmentDataHeader::calculate_header_size");
 
-    // Fixed size parts: 1 (tombstone) + 4 (next_offset) + 16 (uuid_txid) + 8 (date_start) + 8 (date_end) + 
-    // 4 (row_count) + 2 (column_count) + 2 (ts_column) + 4 (column_header_size) + 8 (segment_check)
-    let fixed_size: usize = 1 + 4 + 16 + 8 + 8 + 4 + 2 + 2 + 4 + 8;
+    // Fixed size parts: 1 (tombstone) + 1 (checksum_alg) + 4 (next_offset) + 16 (uuid_txid) + 8 (date_start) +
+    // 8 (date_end) + 4 (row_count) + 2 (column_count) + 2 (ts_column) + 4 (column_header_size) + 2 (metadata_size) +
+    // 8 (created_at) + 8 (segment_check)
+    let fixed_size: usize = 1 + 1 + 4 + 16 + 8 + 8 + 4 + 2 + 2 + 4 + 2 + 8 + 8;
+
+    fixed_size + self.column_header_size as usize + self.metadata_size as usize
+  }
+
+  pub fn set_checksum_alg(&mut self, checksum_alg: EnumChecksumAlg) {
+    self.checksum_alg = checksum_alg;
+  }
+
+  pub fn set_metadata(&mut self, metadata: BTreeMap<String, String>) -> Result<(), String> {
+    trace!("SegmentDataHeader::set_metadata");
 
-    fixed_size + self.column_header_size as usize
+    self.metadata_size = encode_metadata(&metadata)?.len() as u16;
+    self.metadata = metadata;
+    Ok(())
   }
 
-  fn calculate_checksum(&self) -> [u8; 8] {
-    // @TODO xxhash64
-    let dummy_checksum: [u8; 8] = [0xBB; 8]; // Placeholder checksum value
-    dummy_checksum
+  pub fn get_metadata(&self) -> &BTreeMap<String, String> {
+    &self.metadata
   }
 
-  fn update_segment_check(&mut self) {
-    // @TODO update segment_check
-    self.segment_check = Some(self.calculate_checksum());
+  pub fn set_created_at(&mut self, created_at: i64) {
+    self.created_at = created_at;
   }
 
-  fn verify_segment_check(&self) -> bool {
-    // @TODO add checker
-    return true;
+  pub fn get_created_at(&self) -> i64 {
+    self.created_at
+  }
+
+  // Checksum covers every serialized header byte before the segment_check itself
+  fn calculate_checksum(&self, header_bytes: &[u8]) -> [u8; 8] {
+    calculate_checksum(self.checksum_alg, header_bytes)
+  }
+
+  fn update_segment_check(&mut self, header_bytes: &[u8]) {
+    self.segment_check = Some(self.calculate_checksum(header_bytes));
+  }
+
+  fn verify_segment_check(&self, header_bytes: &[u8]) -> bool {
+    match self.segment_check {
+      Some(segment_check) => segment_check == self.calculate_checksum(header_bytes),
+      None => false,
+    }
   }
 
   pub async fn write_header(&mut self, file: &mut File) -> io::Result<()> {
@@ -107,6 +233,7 @@ impl SegmentDataHeader {
     let mut buffer: Vec<u8> = Vec::new();
 
     buffer.push(self.tombstone as u8);
+    buffer.push(self.checksum_alg as u8);
 
     match self.next_offset {
       Some(next_offset) => byteorder::WriteBytesExt::write_u32::<LittleEndian>(&mut buffer, next_offset)?,
@@ -131,10 +258,7 @@ impl SegmentDataHeader {
     byteorder::WriteBytesExt::write_u32::<LittleEndian>(&mut buffer, self.row_count)?;
     byteorder::WriteBytesExt::write_u16::<LittleEndian>(&mut buffer, self.column_count)?;
 
-    match self.ts_column {
-      Some(ts_column) => byteorder::WriteBytesExt::write_u16::<LittleEndian>(&mut buffer, ts_column)?,
-      None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "ts_column was not set")),
-    }
+    byteorder::WriteBytesExt::write_u16::<LittleEndian>(&mut buffer, self.ts_column.unwrap_or(NO_TS_COLUMN))?;
 
     // Serialize and write each column header, keeping track of the total size
     let mut column_headers_buffer: Vec<u8> = Vec::new();
@@ -150,7 +274,15 @@ impl SegmentDataHeader {
     // Append the serialized column headers
     buffer.extend_from_slice(&column_headers_buffer);
 
-    self.update_segment_check();
+    // Metadata follows the column headers so the segment check covers it
+    let metadata_buffer: Vec<u8> = encode_metadata(&self.metadata)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    self.metadata_size = metadata_buffer.len() as u16;
+    buffer.extend_from_slice(&self.metadata_size.to_le_bytes());
+    buffer.extend_from_slice(&metadata_buffer);
+    buffer.extend_from_slice(&self.created_at.to_le_bytes());
+
+    self.update_segment_check(&buffer);
 
     // Writes the segment check
     match self.segment_check {
@@ -165,50 +297,108 @@ impl SegmentDataHeader {
   }
 
   pub async fn read_segment_header(&mut self, file: &mut File) -> io::Result<()> {
-    let mut header_buffer: Vec<u8> = vec![0; 49]; // Fixed size for the header
-    file.read_exact(&mut header_buffer).await?;
+    let mut header_buffer: Vec<u8> = vec![0; 50]; // Fixed size for the header
+    async_read_exact_retry(file, &mut header_buffer).await?;
 
     let cursor = Cursor::new(header_buffer);
     
     // Correct usage of byteorder for synchronous in-memory operations
     self.tombstone = cursor.get_ref()[0] != 0;
+
+    self.checksum_alg = EnumChecksumAlg::from_u8(cursor.get_ref()[1])
+      .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid checksum algorithm"))?;
     
-    self.next_offset = Some(LittleEndian::read_u32(&cursor.get_ref()[1..5]));
+    self.next_offset = Some(LittleEndian::read_u32(&cursor.get_ref()[2..6]));
     
     let mut uuid_txid_arr = [0u8; 16];
-    uuid_txid_arr.copy_from_slice(&cursor.get_ref()[5..21]);
+    uuid_txid_arr.copy_from_slice(&cursor.get_ref()[6..22]);
     self.uuid_txid = Some(uuid_txid_arr);
 
-    self.date_start = Some(LittleEndian::read_i64(&cursor.get_ref()[21..29]));
-    self.date_end = Some(LittleEndian::read_i64(&cursor.get_ref()[29..37]));
+    self.date_start = Some(LittleEndian::read_i64(&cursor.get_ref()[22..30]));
+    self.date_end = Some(LittleEndian::read_i64(&cursor.get_ref()[30..38]));
     
-    self.row_count = LittleEndian::read_u32(&cursor.get_ref()[37..41]);
-    self.column_count = LittleEndian::read_u16(&cursor.get_ref()[41..43]);
+    self.row_count = LittleEndian::read_u32(&cursor.get_ref()[38..42]);
+    self.column_count = LittleEndian::read_u16(&cursor.get_ref()[42..44]);
     
-    self.ts_column = Some(LittleEndian::read_u16(&cursor.get_ref()[43..45]));
+    self.ts_column = Some(LittleEndian::read_u16(&cursor.get_ref()[44..46])).filter(|ts_column: &u16| *ts_column != NO_TS_COLUMN);
     
-    self.column_header_size = LittleEndian::read_u32(&cursor.get_ref()[45..49]);
+    self.column_header_size = LittleEndian::read_u32(&cursor.get_ref()[46..50]);
+
+    // Checked before any column header is parsed, so a corrupt count can't drive a huge loop
+    if self.column_count > self.max_column_count {
+      return Err(TsfError::Corruption {
+        reason: format!("Segment declares {} columns, more than the limit of {}", self.column_count, self.max_column_count),
+      }.into());
+    }
 
-    // Now read the dynamic part: column headers + segment check
-    let header_size: usize = self.column_header_size as usize + 8; // +8 for segment check
+    // Now read the dynamic part: column headers + metadata_size
+    let dynamic_size: usize = self.column_header_size as usize + 2; // +2 for metadata_size
 
-    let mut dynamic_buffer: Vec<u8> = vec![0; header_size];
-    file.read_exact(&mut dynamic_buffer).await?;
+    let mut dynamic_buffer: Vec<u8> = vec![0; dynamic_size];
+    async_read_exact_retry(file, &mut dynamic_buffer).await?;
 
     let mut dynamic_cursor: Cursor<Vec<u8>> = Cursor::new(dynamic_buffer);
 
     self.column_headers.clear();
-    for _ in 0..self.column_count {
+    self.unsupported_columns.clear();
+    for position in 0..self.column_count as usize {
+      if self.skip_unsupported_columns || !self.custom_type_codes.is_empty() {
+        let header_pos: u64 = dynamic_cursor.position();
+        let unsupported: Option<UnsupportedColumn> = SegmentColumnHeader::read_unsupported_from_buffer(&mut dynamic_cursor, position)
+          .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if let Some(column) = unsupported.filter(|column: &UnsupportedColumn| {
+          self.skip_unsupported_columns || self.custom_type_codes.contains(&column.type_code)
+        }) {
+          self.unsupported_columns.push(column);
+          continue;
+        }
+        dynamic_cursor.set_position(header_pos);
+      }
+
       let column_header: SegmentColumnHeader = SegmentColumnHeader::read_from_buffer(&mut dynamic_cursor)
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
       self.column_headers.push(column_header);
     }
 
-    // Assuming segment check is the last 8 bytes
+    // The parsed headers must account for exactly the declared column_header_size
+    if dynamic_cursor.position() != self.column_header_size as u64 {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Column header size mismatch: declared {} bytes, parsed {}", self.column_header_size, dynamic_cursor.position()),
+      ));
+    }
+
+    self.metadata_size = LittleEndian::read_u16(&dynamic_cursor.get_ref()[(dynamic_size - 2)..]);
+    if self.metadata_size as usize > MAX_METADATA_SIZE {
+      return Err(io::Error::new(io::ErrorKind::InvalidData, "Segment metadata too large"));
+    }
+
+    // Then the metadata, created_at and the segment check as the last 8 bytes
+    let trailer_size: usize = self.metadata_size as usize + 8 + 8;
+    let mut trailer_buffer: Vec<u8> = vec![0; trailer_size];
+    async_read_exact_retry(file, &mut trailer_buffer).await?;
+
     let mut segment_check_arr: [u8; 8] = [0; 8];
-    segment_check_arr.copy_from_slice(&dynamic_cursor.get_ref()[(header_size - 8)..]);
+    segment_check_arr.copy_from_slice(&trailer_buffer[(trailer_size - 8)..]);
     self.segment_check = Some(segment_check_arr);
 
+    // Verify against the same bytes the writer checksummed
+    let mut header_bytes: Vec<u8> = cursor.into_inner();
+    header_bytes.extend_from_slice(dynamic_cursor.get_ref());
+    header_bytes.extend_from_slice(&trailer_buffer[..(trailer_size - 8)]);
+    if !self.verify_segment_check(&header_bytes) {
+      return Err(io::Error::new(io::ErrorKind::InvalidData, "Segment check verification failed"));
+    }
+
+    let metadata_end: usize = self.metadata_size as usize;
+    self.created_at = i64::from_le_bytes(trailer_buffer[metadata_end..metadata_end + 8].try_into().unwrap_or_default());
+    self.metadata = decode_metadata(&trailer_buffer[..metadata_end])
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    if !self.unsupported_columns.is_empty() {
+      self.drop_unsupported_columns()?;
+    }
+
     Ok(())
   }
 }
@@ -219,10 +409,13 @@ pub struct SegmentColumnHeader {
   pub column_type: EnumDataType,
   column_meta_length: u16,
   column_meta: ColumnMeta,
+  column_description: Option<String>,
   pub column_enc: EnumDataEnc,
   pub column_comp: EnumDataComp,
   pub column_size: u64,
-  column_check: [u8; 8]
+  column_check: [u8; 8],
+  // Write only: stored in place of column_type for a custom column, see codec::ColumnCodec
+  type_code: Option<u16>,
 }
 
 impl SegmentColumnHeader {
@@ -241,13 +434,59 @@ impl SegmentColumnHeader {
         column_type,
         column_meta_length,
         column_meta,
+        column_description: None,
         column_enc,
         column_comp,
         column_size,
         column_check,
+        type_code: None,
     }
   }
 
+  pub fn with_meta(mut self, column_meta: ColumnMeta) -> Self {
+    trace!("SegmentColumnHeader::with_meta");
+
+    self.column_meta_length = column_meta.to_bytes_with_description(self.column_description.as_deref()).len() as u16;
+    self.column_meta = column_meta;
+    self
+  }
+
+  pub fn with_type_code(mut self, type_code: u16) -> Self {
+    self.type_code = Some(type_code);
+    self
+  }
+
+  // Type code written to the file, column_type unless the column has a custom type
+  pub fn type_code(&self) -> u16 {
+    self.type_code.unwrap_or(self.column_type as u16)
+  }
+
+  pub fn get_column_meta(&self) -> &ColumnMeta {
+    &self.column_meta
+  }
+
+  pub fn get_column_check(&self) -> [u8; 8] {
+    self.column_check
+  }
+
+  pub fn set_column_check(&mut self, column_check: [u8; 8]) {
+    self.column_check = column_check;
+  }
+
+  // Free text for readers of the file, e.g. units or the sensor a column came from
+  pub fn set_description(&mut self, description: &str) -> Result<(), String> {
+    trace!("SegmentColumnHeader::set_description");
+
+    ColumnMeta::validate_description(description)?;
+    self.column_description = Some(description.to_string());
+    self.column_meta_length = self.column_meta.to_bytes_with_description(self.column_description.as_deref()).len() as u16;
+    Ok(())
+  }
+
+  pub fn get_description(&self) -> Option<&str> {
+    self.column_description.as_deref()
+  }
+
   pub fn byte_size(&self) -> u32 {
     trace!("SegmentColumnHeader::byte_size");
     // Start with the size of fixed-length fields.
@@ -279,13 +518,13 @@ impl SegmentColumnHeader {
     buffer.extend_from_slice(self.column_name.as_bytes());
 
     // Write column type
-    let _ = byteorder::WriteBytesExt::write_u16::<LittleEndian>(&mut buffer, self.column_type as u16);
+    let _ = byteorder::WriteBytesExt::write_u16::<LittleEndian>(&mut buffer, self.type_code());
 
     // Write column meta length
     let _ = byteorder::WriteBytesExt::write_u16::<LittleEndian>(&mut buffer, self.column_meta_length as u16);
 
-    // Assuming column_meta is serialized here. For simplicity, skipping actual serialization
-    // You might need to serialize `column_meta` based on its type and content
+    // Write column meta
+    buffer.extend_from_slice(&self.column_meta.to_bytes_with_description(self.column_description.as_deref()));
 
     // Write column_enc and column_comp
     let column_enc_val: u8 = self.column_enc as u8;
@@ -303,6 +542,38 @@ impl SegmentColumnHeader {
     Ok(buffer)
   }
 
+  // Parses a column header only as far as its layout, Some when the type code is unknown to this build
+  fn read_unsupported_from_buffer(cursor: &mut Cursor<Vec<u8>>, position: usize) -> Result<Option<UnsupportedColumn>, String> {
+    let column_name_length: u16 = byteorder::ReadBytesExt::read_u16::<LittleEndian>(cursor)
+      .map_err(|_| "Failed to read column name length".to_string())?;
+    let mut column_name_bytes: Vec<u8> = vec![0; column_name_length as usize];
+    io::Read::read_exact(cursor, &mut column_name_bytes)
+      .map_err(|_| "Failed to read column name".to_string())?;
+
+    let type_code: u16 = byteorder::ReadBytesExt::read_u16::<LittleEndian>(cursor)
+      .map_err(|_| "Failed to read column type".to_string())?;
+    if EnumDataType::from_u16(type_code).is_some() {
+      return Ok(None);
+    }
+
+    // Meta, encoding and compression may be just as new, so they are skipped unparsed
+    let column_meta_length: u16 = byteorder::ReadBytesExt::read_u16::<LittleEndian>(cursor)
+      .map_err(|_| "Failed to read column meta length".to_string())?;
+    cursor.set_position(cursor.position() + column_meta_length as u64 + 2);
+    let column_size: u64 = byteorder::ReadBytesExt::read_u64::<LittleEndian>(cursor)
+      .map_err(|_| "Failed to read column size".to_string())?;
+    let mut column_check: [u8; 8] = [0u8; 8];
+    io::Read::read_exact(cursor, &mut column_check)
+      .map_err(|_| "Failed to read column check".to_string())?;
+
+    Ok(Some(UnsupportedColumn {
+      name: String::from_utf8_lossy(&column_name_bytes).into_owned(),
+      type_code,
+      position,
+      column_size,
+    }))
+  }
+
   fn read_from_buffer(cursor: &mut Cursor<Vec<u8>>) -> Result<Self, String> {
     // Directly use byteorder's ReadBytesExt methods on Cursor
     let column_name_length = byteorder::ReadBytesExt::read_u16::<LittleEndian>(cursor)
@@ -320,6 +591,13 @@ impl SegmentColumnHeader {
     let column_meta_length = byteorder::ReadBytesExt::read_u16::<LittleEndian>(cursor)
       .map_err(|_| "Failed to read column meta length".to_string())?;
 
+    let mut column_meta_bytes = vec![0u8; column_meta_length as usize];
+    io::Read::read_exact(cursor, &mut column_meta_bytes)
+      .map_err(|_| "Failed to read column meta".to_string())?;
+    // The meta is parsed from exactly the declared bytes and must use all of them, so a wrong length can't shift enc
+    let (column_meta, column_description): (ColumnMeta, Option<String>) = ColumnMeta::from_bytes_with_description(&column_meta_bytes)
+      .map_err(|e: String| format!("Column {}: {} ({} meta bytes declared)", column_name, e, column_meta_length))?;
+
     let column_enc = byteorder::ReadBytesExt::read_u8(cursor)
       .map_err(|_| "Failed to read column encoding".to_string())?;
     let column_comp = byteorder::ReadBytesExt::read_u8(cursor)
@@ -337,12 +615,13 @@ impl SegmentColumnHeader {
       column_name,
       column_type: EnumDataType::from_u16(column_type).ok_or_else(|| "Invalid column type".to_string())?,
       column_meta_length,
-      // Assuming default for demonstration; replace as necessary
-      column_meta: Default::default(), 
+      column_meta,
+      column_description,
       column_enc: EnumDataEnc::from_u8(column_enc).ok_or_else(|| "Invalid encoding type".to_string())?,
       column_comp: EnumDataComp::from_u8(column_comp).ok_or_else(|| "Invalid compression type".to_string())?,
       column_size,
       column_check,
+      type_code: None,
     })
   }
 }
@@ -359,6 +638,7 @@ mod tests {
     // Setup: Create a SegmentDataHeader with test data
     let mut header: SegmentDataHeader = SegmentDataHeader {
       tombstone: true,
+      checksum_alg: EnumChecksumAlg::XxHash64,
       next_offset: Some(123),
       uuid_txid: Some([0xAA; 16]),
       date_start: Some(1625097600),
@@ -368,7 +648,14 @@ mod tests {
       ts_column: Some(3),
       column_header_size: 0, // This gets overwritten
       column_headers: vec![],
+      metadata_size: 0,
+      metadata: BTreeMap::new(),
+      created_at: 1700000000,
       segment_check: Some([0xBB; 8]), // This gets overwritten
+      skip_unsupported_columns: false,
+      unsupported_columns: vec![],
+      custom_type_codes: vec![],
+      max_column_count: DEFAULT_MAX_COLUMN_COUNT,
     };
 
     // Write the header to a temporary file
@@ -387,18 +674,22 @@ mod tests {
 
     // Read back the written data
     let read_tombstone = cursor.get_ref()[0];
-    let read_next_offset = LittleEndian::read_u32(&cursor.get_ref()[1..5]);
-    let read_uuid_txid: [u8; 16] = cursor.get_ref()[5..21].try_into().unwrap();
-    let read_date_start = LittleEndian::read_i64(&cursor.get_ref()[21..29]);
-    let read_date_end = LittleEndian::read_i64(&cursor.get_ref()[29..37]);
-    let read_row_count = LittleEndian::read_u32(&cursor.get_ref()[37..41]);
-    let read_column_count = LittleEndian::read_u16(&cursor.get_ref()[41..43]);
-    let read_ts_column = LittleEndian::read_u16(&cursor.get_ref()[43..45]);
-    let read_column_header_size = LittleEndian::read_u32(&cursor.get_ref()[45..49]);
-    let read_segment_check: [u8; 8] = cursor.get_ref()[49..57].try_into().unwrap();
+    let read_checksum_alg = cursor.get_ref()[1];
+    let read_next_offset = LittleEndian::read_u32(&cursor.get_ref()[2..6]);
+    let read_uuid_txid: [u8; 16] = cursor.get_ref()[6..22].try_into().unwrap();
+    let read_date_start = LittleEndian::read_i64(&cursor.get_ref()[22..30]);
+    let read_date_end = LittleEndian::read_i64(&cursor.get_ref()[30..38]);
+    let read_row_count = LittleEndian::read_u32(&cursor.get_ref()[38..42]);
+    let read_column_count = LittleEndian::read_u16(&cursor.get_ref()[42..44]);
+    let read_ts_column = LittleEndian::read_u16(&cursor.get_ref()[44..46]);
+    let read_column_header_size = LittleEndian::read_u32(&cursor.get_ref()[46..50]);
+    let read_metadata_size = LittleEndian::read_u16(&cursor.get_ref()[50..52]);
+    let read_created_at = LittleEndian::read_i64(&cursor.get_ref()[52..60]);
+    let read_segment_check: [u8; 8] = cursor.get_ref()[60..68].try_into().unwrap();
 
     // Verify the data read matches what was written
     assert_eq!(read_tombstone, 1u8);
+    assert_eq!(read_checksum_alg, EnumChecksumAlg::XxHash64 as u8);
     assert_eq!(read_next_offset, 123);
     assert_eq!(read_uuid_txid, [0xAA; 16]);
     assert_eq!(read_date_start, 1625097600);
@@ -407,7 +698,9 @@ mod tests {
     assert_eq!(read_column_count, 5);
     assert_eq!(read_ts_column, 3);
     assert_eq!(read_column_header_size, 0);
-    assert_eq!(read_segment_check, [0xBB; 8]);
+    assert_eq!(read_metadata_size, 0);
+    assert_eq!(read_created_at, 1700000000);
+    assert_eq!(Some(read_segment_check), header.segment_check);
 
     Ok(())
   }
@@ -420,10 +713,12 @@ mod tests {
       column_type: EnumDataType::Int32, // Example, ensure this matches an actual variant
       column_meta_length: 0, // Simplified for the test
       column_meta: ColumnMeta::None, // Assuming ColumnMeta::None is the default
+      column_description: None,
       column_enc: EnumDataEnc::None, // Example, ensure this matches an actual variant
       column_comp: EnumDataComp::None, // Example, ensure this matches an actual variant
       column_size: 123, // Example size
       column_check: [1, 2, 3, 4, 5, 6, 7, 8], // Example checksum
+      type_code: None,
     };
 
     let buffer: Vec<u8> = header.prepare_buffer()?;
@@ -472,10 +767,10 @@ mod tests {
     let column_count: u16 = 0;
     let ts_column: u16 = 0;
     let column_header_size: u32 = 0;
-    let segment_check: [u8; 8] = [0xBB; 8];
 
     let mut buf = Vec::new();
     buf.push(tombstone);
+    buf.push(EnumChecksumAlg::Crc32c as u8);
     buf.extend_from_slice(&next_offset.to_le_bytes());
     buf.extend_from_slice(&uuid_txid);
     buf.extend_from_slice(&date_start.to_le_bytes());
@@ -484,6 +779,9 @@ mod tests {
     buf.extend_from_slice(&column_count.to_le_bytes());
     buf.extend_from_slice(&ts_column.to_le_bytes());
     buf.extend_from_slice(&column_header_size.to_le_bytes());
+    buf.extend_from_slice(&0u16.to_le_bytes()); // metadata_size
+    buf.extend_from_slice(&1700000000i64.to_le_bytes()); // created_at
+    let segment_check: [u8; 8] = calculate_checksum(EnumChecksumAlg::Crc32c, &buf);
     buf.extend_from_slice(&segment_check);
 
     // Write the buffer to the tempfile
@@ -495,6 +793,7 @@ mod tests {
     // Attempt to read the header back from the tempfile
     let mut header: SegmentDataHeader = SegmentDataHeader {
       tombstone: false,
+      checksum_alg: EnumChecksumAlg::XxHash64,
       next_offset: Some(0),
       uuid_txid: Some([0; 16]),
       date_start: Some(0),
@@ -504,13 +803,21 @@ mod tests {
       ts_column: Some(0),
       column_header_size: 0,
       column_headers: Vec::new(),
+      metadata_size: 0,
+      metadata: BTreeMap::new(),
+      created_at: 0,
       segment_check: Some([0; 8]),
+      skip_unsupported_columns: false,
+      unsupported_columns: vec![],
+      custom_type_codes: vec![],
+      max_column_count: DEFAULT_MAX_COLUMN_COUNT,
     };
     
     header.read_segment_header(&mut file).await?;
 
     // Perform assertions
     assert_eq!(header.tombstone, true);
+    assert_eq!(header.checksum_alg, EnumChecksumAlg::Crc32c);
     assert_eq!(header.next_offset, Some(123));
     assert_eq!(header.uuid_txid, Some([0xAA; 16]));
     assert_eq!(header.date_start, Some(1625097600));
@@ -519,7 +826,48 @@ mod tests {
     assert_eq!(header.column_count, 0);
     assert_eq!(header.ts_column, Some(0));
     assert_eq!(header.column_header_size, 0); // Simplified
-    assert_eq!(header.segment_check, Some([0xBB; 8]));
+    assert_eq!(header.created_at, 1700000000);
+    assert_eq!(header.segment_check, Some(segment_check));
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_flipped_header_byte_fails_segment_check() -> io::Result<()> {
+    let mut header: SegmentDataHeader = SegmentDataHeader::new();
+    header.add_column_header(SegmentColumnHeader::new(
+      "temperature".to_string(),
+      EnumDataType::Int8,
+      EnumDataEnc::None,
+      EnumDataComp::None,
+    ));
+    header.set_ts_column(0).map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    header.next_offset = Some(header.calculate_header_size() as u32);
+    header.uuid_txid = Some([0xAA; 16]);
+    header.set_date_start(1710555318);
+    header.set_date_end(1710555321);
+
+    let mut file: File = File::from_std(tempfile()?);
+    header.write_header(&mut file).await?;
+    let mut bytes: Vec<u8> = Vec::new();
+    file.seek(SeekFrom::Start(0)).await?;
+    file.read_to_end(&mut bytes).await?;
+
+    file.seek(SeekFrom::Start(0)).await?;
+    SegmentDataHeader::new().read_segment_header(&mut file).await?;
+
+    // date_start, a byte of the column name, and the stored segment_check itself
+    let name_offset: usize = bytes.windows(11).position(|window: &[u8]| window == b"temperature").unwrap();
+    for offset in [22, name_offset + 3, bytes.len() - 1] {
+      let mut tampered: Vec<u8> = bytes.clone();
+      tampered[offset] ^= 0x01;
+      let mut file: File = File::from_std(tempfile()?);
+      file.write_all(&tampered).await?;
+      file.seek(SeekFrom::Start(0)).await?;
+
+      let err: io::Error = SegmentDataHeader::new().read_segment_header(&mut file).await.expect_err("Tampered header was accepted");
+      assert_eq!(err.kind(), io::ErrorKind::InvalidData, "Flipping byte {} gave {:?}", offset, err);
+    }
 
     Ok(())
   }
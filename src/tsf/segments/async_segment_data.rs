@@ -1,18 +1,76 @@
-use std::io::{self};
+use std::{collections::BTreeMap, io::{self}, pin::Pin, sync::Arc, task::{Context, Poll}, thread::{self, ThreadId}};
 
 use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt, BufWriter, SeekFrom};
+use chrono::Utc;
 use tracing::trace;
 use uuid7;
 
 use super::async_column_data::SegmentColumnData;
+use super::checksum::ChecksumWriter;
+use super::codec::{CodecRegistry, ColumnCodec, CustomColumn};
+use super::segment_data::{alignment_padding, DEFAULT_FLUSH_THRESHOLD};
 use super::async_data_header::{SegmentColumnHeader, SegmentDataHeader};
-use super::types::EnumColumnData;
+use super::types::{validate_column_name, ColumnLayout, ColumnSlice, EnumChecksumAlg, EnumColumnData, EnumDataComp, EnumDataEnc, EnumDataType, UnsupportedColumn};
+use crate::tsf::error::TsfError;
+use crate::tsf::read_util::async_read_exact_retry;
 
 #[repr(C)]
 pub struct SegmentData {
   data_header: SegmentDataHeader,
   data: Vec<SegmentColumnData>,
-  data_pos: usize,
+  // Offset of the column being written or read, counted from the end of the segment header
+  data_pos: u64,
+  segment_pos: u64,
+  auto_sort: bool,
+  // Next segment start is padded up to a multiple of this, 1 writes segments back to back
+  alignment: u64,
+  // Extend the file to the segment's final size before writing any of it
+  presize: bool,
+  // Decode columns with spawn_blocking so decompression doesn't stall the runtime
+  decode_in_background: bool,
+  // Thread that decoded the columns of the last segment read
+  decode_thread: Option<ThreadId>,
+  // Set between begin_stream and finish_stream while the segment is written in batches
+  stream: Option<SegmentStream>,
+  flush_threshold: usize,
+  // Writes that reached the spools of the last finished stream
+  stream_flushes: usize,
+  // Read only: decodes columns whose type code has a codec, they are collected in custom_columns
+  codecs: CodecRegistry,
+  custom_columns: Vec<CustomColumn>,
+}
+
+// Open ended segment being written in batches, see the sync SegmentData::begin_stream
+struct SegmentStream {
+  segment_pos: u64,
+  header_size: u64,
+  spools: Vec<BufWriter<SpoolFile>>,
+  last_timestamp: Option<i64>,
+}
+
+// A column's spool, counting the buffer flushes that reach it
+struct SpoolFile {
+  file: File,
+  writes: usize,
+}
+
+impl AsyncWrite for SpoolFile {
+  fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+    let written: Poll<io::Result<usize>> = Pin::new(&mut self.file).poll_write(cx, buf);
+    if written.is_ready() {
+      self.writes += 1;
+    }
+    written
+  }
+
+  fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    Pin::new(&mut self.file).poll_flush(cx)
+  }
+
+  fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    Pin::new(&mut self.file).poll_shutdown(cx)
+  }
 }
 
 impl SegmentData {
@@ -22,6 +80,17 @@ impl SegmentData {
         data_header,
         data: vec![],
         data_pos: 0,
+        segment_pos: 0,
+        auto_sort: false,
+        alignment: 1,
+        presize: false,
+        decode_in_background: false,
+        decode_thread: None,
+        stream: None,
+        flush_threshold: DEFAULT_FLUSH_THRESHOLD,
+        stream_flushes: 0,
+        codecs: CodecRegistry::new(),
+        custom_columns: vec![],
     }
   }
 
@@ -46,9 +115,101 @@ impl SegmentData {
     self.data_header.row_count as usize
   }
 
-  pub fn get_column_data_pos(&self) -> usize {
-    // File Header + Size of Header + data_position
-    return 6 + self.data_header.calculate_header_size() + self.data_pos;
+  // Skip columns whose type code this build doesn't know instead of failing the read
+  pub fn set_skip_unsupported_columns(&mut self, skip_unsupported_columns: bool) {
+    self.data_header.set_skip_unsupported_columns(skip_unsupported_columns);
+  }
+
+  pub fn set_max_column_count(&mut self, max_column_count: u16) {
+    self.data_header.set_max_column_count(max_column_count);
+  }
+
+  pub fn get_unsupported_columns(&self) -> &[UnsupportedColumn] {
+    self.data_header.get_unsupported_columns()
+  }
+
+  // Columns of a type code in codecs are read and decoded instead of failing the read
+  pub fn set_codecs(&mut self, codecs: CodecRegistry) {
+    self.data_header.set_custom_type_codes(codecs.type_codes());
+    self.codecs = codecs;
+  }
+
+  pub fn get_custom_columns(&self) -> &[CustomColumn] {
+    &self.custom_columns
+  }
+
+  // Segment start + size of header + data position, segment_pos comes from the file so it can't be trusted to fit
+  pub fn get_column_data_pos(&self) -> io::Result<u64> {
+    self.segment_pos.checked_add(self.data_header.calculate_header_size() as u64)
+      .and_then(|pos: u64| pos.checked_add(self.data_pos))
+      .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Column data position overflows u64"))
+  }
+
+  // Moves data_pos past a column, or past bytes the header says to skip
+  fn advance_data_pos(&mut self, bytes: u64) -> io::Result<()> {
+    self.data_pos = self.data_pos.checked_add(bytes)
+      .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Column data position overflows u64"))?;
+    Ok(())
+  }
+
+  // Column positions are kept as usize, which is narrower than a file offset on 32 bit targets
+  fn column_file_pos(&self) -> io::Result<usize> {
+    usize::try_from(self.get_column_data_pos()?)
+      .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Column data position does not fit in usize"))
+  }
+
+  pub fn column_layout(&self) -> Vec<ColumnLayout> {
+    trace!("SegmentData::column_layout");
+
+    self.data_header.column_headers.iter()
+      .zip(&self.data)
+      .map(|(header, column_data): (&SegmentColumnHeader, &SegmentColumnData)| ColumnLayout {
+        name: header.column_name.clone(),
+        file_pos: column_data.get_file_pos() as u64,
+        column_size: header.column_size,
+        column_type: header.column_type,
+        encoding: header.column_enc,
+        compression: header.column_comp,
+      })
+      .collect()
+  }
+
+  // Seconds since the epoch when the segment was written
+  pub fn get_created_at(&self) -> i64 {
+    self.data_header.get_created_at()
+  }
+
+  // Where the segment last written or read starts in the file
+  pub fn get_segment_pos(&self) -> u64 {
+    self.segment_pos
+  }
+
+  // Bytes from the start of this segment to the start of the next one
+  pub fn get_next_offset(&self) -> Option<u32> {
+    self.data_header.next_offset
+  }
+
+  pub fn is_tombstoned(&self) -> bool {
+    self.data_header.tombstone
+  }
+
+  pub fn get_ts_column(&self) -> Option<usize> {
+    trace!("SegmentData::get_ts_column");
+
+    self.data_header.get_ts_column().map(|index: u16| index as usize)
+  }
+
+  pub fn get_column_header(&self, index: usize) -> Option<&SegmentColumnHeader> {
+    trace!("SegmentData::get_column_header");
+
+    self.data_header.column_headers.get(index)
+  }
+
+  // Borrows the decoded column without copying, None if the index or type doesn't match
+  pub fn column_slice<T: ColumnSlice>(&self, index: usize) -> Option<&[T]> {
+    trace!("SegmentData::column_slice");
+
+    self.data.get(index).and_then(|column_data: &SegmentColumnData| T::column_slice(&column_data.data))
   }
 
   pub fn get_segment_data<'a>(&'a self, index: usize) -> Option<&'a SegmentColumnData> {
@@ -60,6 +221,21 @@ impl SegmentData {
   pub fn add_column_header(&mut self, column_header: SegmentColumnHeader, ts_column: bool) -> Result<(), String> {
     trace!("SegmentData::add_column_header");
 
+    validate_column_name(&column_header.column_name)?;
+    if !column_header.column_enc.is_valid_for(column_header.column_type) {
+      return Err(TsfError::UnsupportedEncoding {
+        column: column_header.column_name,
+        encoding: column_header.column_enc,
+        data_type: column_header.column_type,
+      }.into());
+    }
+
+    // column_header_size is a u32, the sum is checked before it can wrap
+    let column_header_size: u64 = self.data_header.column_header_size as u64 + column_header.byte_size() as u64;
+    if column_header_size > u32::MAX as u64 {
+      return Err(format!("Column headers would take {} bytes, more than the limit of {}", column_header_size, u32::MAX));
+    }
+
     let index: u16 = self.data_header.add_column_header(column_header);
     if ts_column {
       self.data_header.set_ts_column(index)?;
@@ -76,13 +252,7 @@ impl SegmentData {
       return Err("No corresponding column header for the data.".to_string());
     }
 
-    let data_row_count: usize = match &data.data {
-      EnumColumnData::Int8Vec(vec) => vec.len(),
-      EnumColumnData::Int16Vec(vec) => vec.len(),
-      EnumColumnData::Int32Vec(vec) => vec.len(),
-      // @TODO Add cases for other data types...
-      _ => 0,
-    };
+    let data_row_count: usize = data.data.len();
 
     // Can't add empty rows
     if data_row_count == 0 {
@@ -104,21 +274,158 @@ impl SegmentData {
     Ok(())
   }
 
+  // Header and data of a column stored under a type code this crate doesn't define, encoded by codec.
+  // Every column before it must already have its data, so the two are added together
+  pub fn add_custom_column(&mut self, column_name: &str, type_code: u16, codec: Arc<dyn ColumnCodec>, data: EnumColumnData) -> Result<(), String> {
+    trace!("SegmentData::add_custom_column");
+
+    if let Some(data_type) = EnumDataType::from_u16(type_code) {
+      return Err(format!("Type code {} is the built in type {:?}", type_code, data_type));
+    }
+    if self.data_header.column_count as usize != self.data.len() {
+      return Err("Custom columns are added after the data of the columns before them".to_string());
+    }
+    if data.is_empty() {
+      return Err("Zero rows added".to_string());
+    }
+    if self.data_header.row_count != 0 && self.data_header.row_count as usize != data.len() {
+      return Err("Inconsistent number of rows.".to_string());
+    }
+
+    let header: SegmentColumnHeader = SegmentColumnHeader::new(column_name.to_string(), data.data_type(), EnumDataEnc::None, EnumDataComp::None)
+      .with_type_code(type_code);
+    self.add_column_header(header, false)?;
+    self.add_column_data(SegmentColumnData::from_column_data(data, 0, EnumDataEnc::None, EnumDataComp::None).with_codec(codec))
+  }
+
+  pub fn set_checksum_alg(&mut self, checksum_alg: EnumChecksumAlg) {
+    self.data_header.set_checksum_alg(checksum_alg);
+  }
+
+  pub fn describe_column(&mut self, column_name: &str, description: &str) -> Result<(), String> {
+    trace!("SegmentData::describe_column");
+
+    self.data_header.describe_column(column_name, description)
+  }
+
+  // Small key/value provenance blob stored in the segment header
+  pub fn set_metadata(&mut self, metadata: BTreeMap<String, String>) -> Result<(), String> {
+    trace!("SegmentData::set_metadata");
+
+    self.data_header.set_metadata(metadata)
+  }
+
+  pub fn get_metadata(&self) -> &BTreeMap<String, String> {
+    self.data_header.get_metadata()
+  }
+
+  pub fn set_alignment(&mut self, alignment: u64) -> Result<(), String> {
+    trace!("SegmentData::set_alignment");
+
+    if alignment == 0 {
+      return Err("Segment alignment must be at least 1".to_string());
+    }
+    self.alignment = alignment;
+    Ok(())
+  }
+
+  pub fn set_presize(&mut self, presize: bool) {
+    self.presize = presize;
+  }
+
+  pub fn set_decode_in_background(&mut self, decode_in_background: bool) {
+    self.decode_in_background = decode_in_background;
+  }
+
+  // Tells whether the last read decoded on the runtime's thread or on the blocking pool
+  pub fn get_decode_thread(&self) -> Option<ThreadId> {
+    self.decode_thread
+  }
+
+  // Bytes each column of a stream buffers before writing them out, 0 writes every batch straight through
+  pub fn set_flush_threshold(&mut self, flush_threshold: usize) {
+    self.flush_threshold = flush_threshold;
+  }
+
+  pub fn get_stream_flushes(&self) -> usize {
+    self.stream_flushes
+  }
+
+  // Sort the whole segment by the timestamp column on write instead of rejecting it
+  pub fn set_auto_sort(&mut self, auto_sort: bool) {
+    self.auto_sort = auto_sort;
+  }
+
+  // Pruning trusts the header's date range, so a segment is only written when that range means something
+  fn check_date_range(&self) -> Result<(), TsfError> {
+    trace!("SegmentData::check_date_range");
+
+    if self.data_header.row_count == 0 {
+      return Err(TsfError::EmptySegment);
+    }
+    match self.data_header.get_date_range() {
+      Some((start, end)) if start > end => Err(TsfError::InvalidDateRange { start, end }),
+      _ => Ok(()),
+    }
+  }
+
+  fn ensure_sorted_timestamps(&mut self) -> Result<(), TsfError> {
+    trace!("SegmentData::ensure_sorted_timestamps");
+
+    let ts_index: usize = match self.get_ts_column() {
+      Some(ts_index) => ts_index,
+      None => return Ok(()),
+    };
+
+    let ts_data: &EnumColumnData = match self.data.get(ts_index) {
+      Some(column_data) => &column_data.data,
+      None => return Ok(()),
+    };
+
+    if ts_data.is_sorted_ascending() {
+      return Ok(());
+    }
+
+    if !self.auto_sort {
+      return Err(TsfError::TimestampNotSorted {
+        column: self.data_header.column_headers[ts_index].column_name.clone(),
+      });
+    }
+
+    // Every column is reordered with the same permutation so rows stay aligned
+    let indices: Vec<usize> = ts_data.sorted_indices();
+    for column_data in self.data.iter_mut() {
+      column_data.data = column_data.data.take(&indices);
+    }
+
+    Ok(())
+  }
+
   pub fn update_header_dates(&mut self, date_start: i64, date_end: i64) {
     self.data_header.set_date_start(date_start);
     self.data_header.set_date_end(date_end);
   }
 
+  pub fn get_date_range(&self) -> Option<(i64, i64)> {
+    self.data_header.get_date_range()
+  }
+
   // Writes the SegmentData to a file, including the header and data.
   pub async fn write_to_file(&mut self, file: &mut File) -> io::Result<()> {
     trace!("SegmentData::write_to_file");
 
+    self.check_date_range()?;
+    self.data_header.set_created_at(Utc::now().timestamp());
+    self.ensure_sorted_timestamps()?;
+
     // First, ensure column sizes in headers match the data that will be written.
     let mut total_data_size: usize = 0;
     for (index, column_data) in self.data.iter_mut().enumerate() {
-      // Prepare the buffer for each column and get its size.
+      // Prepare the buffer for each column and get its size and checksum.
+      column_data.set_checksum_alg(self.data_header.checksum_alg);
       let data_size: usize = column_data.convert_data_into_buffer()?;
       self.data_header.column_headers[index].column_size = data_size as u64;
+      self.data_header.column_headers[index].set_column_check(column_data.get_column_check());
       total_data_size += data_size;
     }
 
@@ -127,16 +434,176 @@ impl SegmentData {
         .map(|header| header.byte_size() as u32)
         .sum::<u32>();
 
-    // Calculate next_offset based on header size and total data size.
-    self.data_header.next_offset = Some((self.data_header.calculate_header_size() + total_data_size) as u32);
+    // Calculate next_offset based on header size, total data size and the padding up to the next aligned start.
+    let segment_pos: u64 = file.seek(SeekFrom::Current(0)).await?;
+    let segment_size: u64 = self.data_header.calculate_header_size() as u64 + total_data_size as u64;
+    let padding: u64 = alignment_padding(segment_pos + segment_size, self.alignment);
+    let next_offset: u32 = u32::try_from(segment_size + padding)
+      .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Segment is too large for next_offset"))?;
+    self.data_header.next_offset = Some(next_offset);
+
+    // The whole segment is known now, so the file can grow once instead of with every column
+    if self.presize {
+      file.set_len(segment_pos + segment_size + padding).await?;
+    }
 
     // Now, write the header to the file.
     self.data_header.write_header(file).await?;
 
+    // Columns are laid out back to back right after the header
+    self.segment_pos = segment_pos;
+    self.data_pos = 0;
+    for index in 0..self.data.len() {
+      let column_file_pos: usize = self.column_file_pos()?;
+      self.data[index].set_file_pos(column_file_pos);
+      self.advance_data_pos(self.data_header.column_headers[index].column_size)?;
+    }
+
     // Write each column's data from its prepared buffer to the file.
     for column_data in &self.data {
       column_data.write_buffer_into_file(file).await?;
     }
+    file.write_all(&vec![0u8; padding as usize]).await?;
+
+    Ok(())
+  }
+
+  // Starts an open ended segment at the file's position, only the header space is written until finish_stream
+  pub async fn begin_stream(&mut self, file: &mut File) -> io::Result<()> {
+    trace!("SegmentData::begin_stream");
+
+    if self.stream.is_some() || !self.data.is_empty() {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, "Segment already has data"));
+    }
+    if self.data_header.column_headers.is_empty() {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, "Segment has no columns"));
+    }
+    if let Some(header) = self.data_header.column_headers.iter()
+      .find(|header: &&SegmentColumnHeader| header.column_enc != EnumDataEnc::None || header.column_comp != EnumDataComp::None) {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Column {} must be unencoded and uncompressed to stream", header.column_name)));
+    }
+
+    let segment_pos: u64 = file.seek(SeekFrom::Current(0)).await?;
+    let header_size: u64 = self.data_header.calculate_header_size() as u64;
+    file.write_all(&vec![0u8; header_size as usize]).await?;
+
+    let mut spools: Vec<BufWriter<SpoolFile>> = Vec::new();
+    for _ in &self.data_header.column_headers {
+      let file: File = File::from_std(tempfile::tempfile()?);
+      spools.push(BufWriter::with_capacity(self.flush_threshold, SpoolFile { file, writes: 0 }));
+    }
+
+    self.data_header.row_count = 0;
+    self.stream_flushes = 0;
+    self.segment_pos = segment_pos;
+    self.stream = Some(SegmentStream { segment_pos, header_size, spools, last_timestamp: None });
+
+    Ok(())
+  }
+
+  // Adds one batch of rows, a column per header in header order, the timestamp column stays ascending across batches
+  pub async fn append_batch(&mut self, columns: &[EnumColumnData]) -> Result<(), String> {
+    trace!("SegmentData::append_batch");
+
+    let ts_column: Option<usize> = self.get_ts_column();
+    let stream: &mut SegmentStream = self.stream.as_mut()
+      .ok_or("No segment stream has been started".to_string())?;
+
+    if columns.len() != self.data_header.column_headers.len() {
+      return Err(format!("Batch has {} columns, the segment has {}", columns.len(), self.data_header.column_headers.len()));
+    }
+    let batch_rows: usize = columns[0].len();
+    if batch_rows == 0 {
+      return Err("Zero rows added".to_string());
+    }
+    for (header, column) in self.data_header.column_headers.iter().zip(columns) {
+      if column.data_type() != header.column_type {
+        return Err(format!("Column {} is {:?} but the batch has {:?}", header.column_name, header.column_type, column.data_type()));
+      }
+      if column.len() != batch_rows {
+        return Err("Inconsistent number of rows.".to_string());
+      }
+    }
+    let row_count: u32 = u32::try_from(self.data_header.row_count as usize + batch_rows)
+      .map_err(|_| "Segment row count overflows u32".to_string())?;
+
+    let mut batch_range: Option<(i64, i64)> = None;
+    if let Some(ts_index) = ts_column {
+      let ts_data: &EnumColumnData = &columns[ts_index];
+      let first: Option<i64> = ts_data.value_at(0).and_then(|value| value.as_i64());
+      let last: Option<i64> = ts_data.value_at(batch_rows - 1).and_then(|value| value.as_i64());
+      let continues: bool = match (stream.last_timestamp, first) {
+        (Some(previous), Some(first)) => previous <= first,
+        _ => true,
+      };
+      if !ts_data.is_sorted_ascending() || !continues {
+        return Err(TsfError::TimestampNotSorted {
+          column: self.data_header.column_headers[ts_index].column_name.clone(),
+        }.into());
+      }
+      batch_range = first.zip(last);
+    }
+
+    for (spool, column) in stream.spools.iter_mut().zip(columns) {
+      spool.write_all(&column.to_le_bytes()).await.map_err(|e: io::Error| e.to_string())?;
+    }
+
+    if let Some((first, last)) = batch_range {
+      if stream.last_timestamp.is_none() {
+        self.data_header.set_date_start(first);
+      }
+      self.data_header.set_date_end(last);
+      stream.last_timestamp = Some(last);
+    }
+    self.data_header.row_count = row_count;
+
+    Ok(())
+  }
+
+  // Lays the spooled columns out after the reserved header space, then seeks back and writes the header
+  pub async fn finish_stream(&mut self, file: &mut File) -> io::Result<()> {
+    trace!("SegmentData::finish_stream");
+
+    let stream: SegmentStream = self.stream.take()
+      .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "No segment stream has been started"))?;
+    self.check_date_range()?;
+    self.data_header.set_created_at(Utc::now().timestamp());
+
+    let mut column_pos: u64 = stream.segment_pos + stream.header_size;
+    file.seek(SeekFrom::Start(column_pos)).await?;
+    for (index, mut spool) in stream.spools.into_iter().enumerate() {
+      // Whatever is still buffered is flushed before the spool is copied over, into_inner would drop it
+      spool.flush().await?;
+      let mut spool: SpoolFile = spool.into_inner();
+      self.stream_flushes += spool.writes;
+      spool.file.seek(SeekFrom::Start(0)).await?;
+      // Copied in chunks so each one can be checksummed on the way
+      let mut checksum: ChecksumWriter<std::io::Sink> = ChecksumWriter::new(self.data_header.checksum_alg, std::io::sink());
+      let mut chunk: Vec<u8> = vec![0u8; 64 * 1024];
+      let mut column_size: u64 = 0;
+      loop {
+        let read: usize = spool.file.read(&mut chunk).await?;
+        if read == 0 {
+          break;
+        }
+        file.write_all(&chunk[..read]).await?;
+        std::io::Write::write_all(&mut checksum, &chunk[..read])?;
+        column_size += read as u64;
+      }
+      self.data_header.column_headers[index].column_size = column_size;
+      self.data_header.column_headers[index].set_column_check(checksum.finish());
+      column_pos += column_size;
+    }
+
+    let padding: u64 = alignment_padding(column_pos, self.alignment);
+    file.write_all(&vec![0u8; padding as usize]).await?;
+    let next_offset: u32 = u32::try_from(column_pos + padding - stream.segment_pos)
+      .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Segment is too large for next_offset"))?;
+    self.data_header.next_offset = Some(next_offset);
+
+    file.seek(SeekFrom::Start(stream.segment_pos)).await?;
+    self.data_header.write_header(file).await?;
+    file.seek(SeekFrom::End(0)).await?;
 
     Ok(())
   }
@@ -145,34 +612,98 @@ impl SegmentData {
   pub async fn read_segment_from_file(&mut self, file: &mut File) -> io::Result<()> {
     trace!("SegmentData::read_segment_from_file");
 
+    self.segment_pos = file.seek(SeekFrom::Current(0)).await?;
     self.data_header.read_segment_header(file).await?;
     self.read_segment_data(file).await?;
 
     Ok(())
   }
 
-  async fn read_segment_data(&mut self, file: &mut File) -> io::Result<()> {
+  // Reads only the header of the segment at the file's position, leaving the column data unread
+  pub async fn read_header_from_file(&mut self, file: &mut File) -> io::Result<()> {
+    trace!("SegmentData::read_header_from_file");
+
+    self.segment_pos = file.seek(SeekFrom::Current(0)).await?;
+    self.data_header.read_segment_header(file).await
+  }
+
+  // Reads the columns of the segment whose header read_header_from_file just read
+  pub async fn read_segment_data(&mut self, file: &mut File) -> io::Result<()> {
     trace!("SegmentData::read_segment_data");
 
     // Ensure the data vector is clear
     self.data.clear();
+    self.custom_columns.clear();
 
-    let mut column_data_pos: usize = self.get_column_data_pos();
+    self.data_pos = 0;
 
     // This reads all the columns
-    for header in &self.data_header.column_headers {
+    for index in 0..self.data_header.column_headers.len() {
+      self.read_skipped_columns(file, index).await?;
+      let header: &SegmentColumnHeader = &self.data_header.column_headers[index];
+      let column_size: u64 = header.column_size;
       let mut column_data: SegmentColumnData = SegmentColumnData::new(
         header.column_type,
-        column_data_pos,
+        self.column_file_pos()?,
         header.column_enc,
         header.column_comp,
       );
-      column_data.read_file_into_buffer(file, header.column_size as usize).await?;
-      column_data.convert_buffer_into_data()?;
+      column_data.set_checksum_alg(self.data_header.checksum_alg);
+      column_data.read_file_into_buffer(file, column_size as usize).await?;
+      if !column_data.verify_column_check(header.get_column_check()) {
+        return Err(TsfError::Corruption { reason: format!("Column {} failed column check verification", header.column_name) }.into());
+      }
       self.data.push(column_data);
-      column_data_pos = column_data_pos + header.column_size as usize;
+      self.advance_data_pos(column_size)?;
+    }
+
+    // Columns are all read before any is decoded, so the decode can be handed off in one go
+    let data: Vec<SegmentColumnData> = std::mem::take(&mut self.data);
+    let (data, decode_thread): (Vec<SegmentColumnData>, ThreadId) = if self.decode_in_background {
+      tokio::task::spawn_blocking(move || decode_columns(data))
+        .await
+        .map_err(io::Error::other)??
+    } else {
+      decode_columns(data)?
+    };
+    self.data = data;
+    self.decode_thread = Some(decode_thread);
+
+    let column_count: usize = self.data_header.column_headers.len();
+    if self.data_header.skipped_bytes_before(column_count) > 0 {
+      self.read_skipped_columns(file, column_count).await?;
+      file.seek(SeekFrom::Start(self.get_column_data_pos()?)).await?;
     }
 
     Ok(())
   }
+
+  // Decodes the skipped columns before column_headers[index] that have a codec and moves data_pos past all of them
+  async fn read_skipped_columns(&mut self, file: &mut File, index: usize) -> io::Result<()> {
+    let columns: Vec<UnsupportedColumn> = self.data_header.skipped_before(index).cloned().collect();
+    for column in columns {
+      if let Some(codec) = self.codecs.get(column.type_code) {
+        file.seek(SeekFrom::Start(self.get_column_data_pos()?)).await?;
+        let mut bytes: Vec<u8> = vec![0u8; column.column_size as usize];
+        async_read_exact_retry(file, &mut bytes).await?;
+        self.custom_columns.push(CustomColumn {
+          name: column.name.clone(),
+          type_code: column.type_code,
+          data: codec.decode(&bytes).map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        });
+      }
+      self.advance_data_pos(column.column_size)?;
+    }
+
+    Ok(())
+  }
+}
+
+// Also returns the thread the columns were decoded on
+fn decode_columns(mut data: Vec<SegmentColumnData>) -> io::Result<(Vec<SegmentColumnData>, ThreadId)> {
+  for column_data in data.iter_mut() {
+    column_data.convert_buffer_into_data()?;
+  }
+
+  Ok((data, thread::current().id()))
 }
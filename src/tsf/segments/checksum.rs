@@ -0,0 +1,102 @@
+use std::io::{self, Write};
+
+use tracing::trace;
+use xxhash_rust::xxh64::{xxh64, Xxh64};
+
+use super::types::EnumChecksumAlg;
+
+// Checksums are always stored in 8 bytes, narrower digests are zero padded
+pub fn calculate_checksum(alg: EnumChecksumAlg, bytes: &[u8]) -> [u8; 8] {
+  trace!("checksum::calculate_checksum");
+
+  match alg {
+    EnumChecksumAlg::XxHash64 => xxh64(bytes, 0).to_le_bytes(),
+    EnumChecksumAlg::Crc32c => {
+      let mut checksum: [u8; 8] = [0u8; 8];
+      checksum[..4].copy_from_slice(&crc32c::crc32c(bytes).to_le_bytes());
+      checksum
+    },
+  }
+}
+
+enum ChecksumState {
+  XxHash64(Box<Xxh64>),
+  Crc32c(u32),
+}
+
+// Passes writes through to inner and checksums them on the way, so copied bytes don't have to be held in
+// memory. finish gives the same digest calculate_checksum would over everything written
+pub struct ChecksumWriter<W: Write> {
+  inner: W,
+  state: ChecksumState,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+  pub fn new(alg: EnumChecksumAlg, inner: W) -> Self {
+    let state: ChecksumState = match alg {
+      EnumChecksumAlg::XxHash64 => ChecksumState::XxHash64(Box::new(Xxh64::new(0))),
+      EnumChecksumAlg::Crc32c => ChecksumState::Crc32c(0),
+    };
+    ChecksumWriter { inner, state }
+  }
+
+  pub fn finish(self) -> [u8; 8] {
+    match self.state {
+      ChecksumState::XxHash64(hasher) => hasher.digest().to_le_bytes(),
+      ChecksumState::Crc32c(crc) => {
+        let mut checksum: [u8; 8] = [0u8; 8];
+        checksum[..4].copy_from_slice(&crc.to_le_bytes());
+        checksum
+      },
+    }
+  }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let written: usize = self.inner.write(buf)?;
+    match &mut self.state {
+      ChecksumState::XxHash64(hasher) => hasher.update(&buf[..written]),
+      ChecksumState::Crc32c(crc) => *crc = crc32c::crc32c_append(*crc, &buf[..written]),
+    }
+    Ok(written)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_checksum_algorithms_differ() {
+    let bytes: &[u8] = b"rtimedb";
+
+    let xxhash: [u8; 8] = calculate_checksum(EnumChecksumAlg::XxHash64, bytes);
+    let crc: [u8; 8] = calculate_checksum(EnumChecksumAlg::Crc32c, bytes);
+
+    assert_eq!(xxhash, xxh64(bytes, 0).to_le_bytes());
+    assert_eq!(crc[..4], crc32c::crc32c(bytes).to_le_bytes());
+    assert_eq!(crc[4..], [0u8; 4]);
+    assert_ne!(xxhash, crc);
+  }
+
+  #[test]
+  fn test_checksum_writer_matches_calculate_checksum() -> io::Result<()> {
+    let bytes: Vec<u8> = (0..10_000u32).map(|value: u32| (value % 251) as u8).collect();
+
+    for alg in [EnumChecksumAlg::XxHash64, EnumChecksumAlg::Crc32c] {
+      let mut writer: ChecksumWriter<Vec<u8>> = ChecksumWriter::new(alg, Vec::new());
+      for chunk in bytes.chunks(333) {
+        writer.write_all(chunk)?;
+      }
+      assert_eq!(writer.inner, bytes);
+      assert_eq!(writer.finish(), calculate_checksum(alg, &bytes));
+    }
+
+    Ok(())
+  }
+}
@@ -0,0 +1,82 @@
+use std::{collections::HashMap, sync::Arc};
+
+use super::types::{EnumColumnData, EnumDataType};
+
+// Encoding for a column type this crate doesn't define, registered under the type code it is stored with.
+// The codec gets the column's stored bytes as they are, so any encoding or compression is up to it.
+// decode fails on bytes it can't have written, readers report that as InvalidData
+pub trait ColumnCodec: Send + Sync {
+  fn encode(&self, data: &EnumColumnData) -> Vec<u8>;
+  fn decode(&self, bytes: &[u8]) -> Result<EnumColumnData, String>;
+}
+
+// Codecs by type code, cheap to clone so one registry can be shared by several readers
+#[derive(Clone, Default)]
+pub struct CodecRegistry {
+  codecs: HashMap<u16, Arc<dyn ColumnCodec>>,
+}
+
+impl CodecRegistry {
+  pub fn new() -> Self {
+    CodecRegistry::default()
+  }
+
+  // Built in type codes can't be overridden, and a type code takes a single codec
+  pub fn register(&mut self, type_code: u16, codec: Arc<dyn ColumnCodec>) -> Result<(), String> {
+    if let Some(data_type) = EnumDataType::from_u16(type_code) {
+      return Err(format!("Type code {} is the built in type {:?}", type_code, data_type));
+    }
+    if self.codecs.contains_key(&type_code) {
+      return Err(format!("Type code {} already has a codec", type_code));
+    }
+
+    self.codecs.insert(type_code, codec);
+    Ok(())
+  }
+
+  pub fn get(&self, type_code: u16) -> Option<&dyn ColumnCodec> {
+    self.codecs.get(&type_code).map(|codec: &Arc<dyn ColumnCodec>| codec.as_ref())
+  }
+
+  pub fn type_codes(&self) -> Vec<u16> {
+    self.codecs.keys().copied().collect()
+  }
+}
+
+// A column of a registered custom type, decoded by its codec
+#[derive(Debug, Clone)]
+pub struct CustomColumn {
+  pub name: String,
+  pub type_code: u16,
+  pub data: EnumColumnData,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  struct RawBytesCodec;
+
+  impl ColumnCodec for RawBytesCodec {
+    fn encode(&self, data: &EnumColumnData) -> Vec<u8> {
+      data.to_le_bytes()
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<EnumColumnData, String> {
+      Ok(EnumColumnData::UInt8Vec(bytes.to_vec()))
+    }
+  }
+
+  #[test]
+  fn test_register_rejects_builtin_and_duplicate_codes() {
+    let mut registry: CodecRegistry = CodecRegistry::new();
+
+    assert!(registry.register(EnumDataType::Int32 as u16, Arc::new(RawBytesCodec)).is_err());
+    registry.register(900, Arc::new(RawBytesCodec)).unwrap();
+    assert_eq!(registry.register(900, Arc::new(RawBytesCodec)), Err("Type code 900 already has a codec".to_string()));
+
+    assert!(registry.get(900).is_some());
+    assert!(registry.get(901).is_none());
+    assert_eq!(registry.type_codes(), vec![900]);
+  }
+}
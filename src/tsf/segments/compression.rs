@@ -0,0 +1,42 @@
+use std::io;
+
+use tracing::trace;
+
+use super::types::EnumDataComp;
+
+const ZSTD_LEVEL: i32 = 3;
+
+pub fn compress(compression: EnumDataComp, bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+  trace!("compression::compress");
+
+  match compression {
+    EnumDataComp::None => Ok(bytes),
+    EnumDataComp::ZStd => zstd::encode_all(&bytes[..], ZSTD_LEVEL),
+  }
+}
+
+pub fn decompress(compression: EnumDataComp, bytes: &[u8]) -> io::Result<Vec<u8>> {
+  trace!("compression::decompress");
+
+  match compression {
+    EnumDataComp::None => Ok(bytes.to_vec()),
+    EnumDataComp::ZStd => zstd::decode_all(bytes),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_zstd_round_trip_shrinks_repetitive_data() -> io::Result<()> {
+    let bytes: Vec<u8> = [1u8, 2, 3, 4].repeat(256);
+
+    let compressed: Vec<u8> = compress(EnumDataComp::ZStd, bytes.clone())?;
+
+    assert!(compressed.len() < bytes.len());
+    assert_eq!(decompress(EnumDataComp::ZStd, &compressed)?, bytes);
+
+    Ok(())
+  }
+}
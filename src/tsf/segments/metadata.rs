@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+
+use tracing::trace;
+
+// Upper bound on the serialized metadata so headers stay small
+pub const MAX_METADATA_SIZE: usize = 4096;
+
+// Serialized as a u16 entry count then u16 length prefixed key and value strings, an empty map is zero bytes
+pub fn encode_metadata(metadata: &BTreeMap<String, String>) -> Result<Vec<u8>, String> {
+  trace!("metadata::encode_metadata");
+
+  let mut buffer: Vec<u8> = Vec::new();
+  if metadata.is_empty() {
+    return Ok(buffer);
+  }
+
+  buffer.extend_from_slice(&(metadata.len() as u16).to_le_bytes());
+  for (key, value) in metadata {
+    for entry in [key, value] {
+      let length: u16 = u16::try_from(entry.len())
+        .map_err(|_| "Segment metadata entry too large".to_string())?;
+      buffer.extend_from_slice(&length.to_le_bytes());
+      buffer.extend_from_slice(entry.as_bytes());
+    }
+  }
+
+  if buffer.len() > MAX_METADATA_SIZE {
+    return Err(format!("Segment metadata is {} bytes, the limit is {}", buffer.len(), MAX_METADATA_SIZE));
+  }
+
+  Ok(buffer)
+}
+
+pub fn decode_metadata(bytes: &[u8]) -> Result<BTreeMap<String, String>, String> {
+  trace!("metadata::decode_metadata");
+
+  let mut metadata: BTreeMap<String, String> = BTreeMap::new();
+  if bytes.is_empty() {
+    return Ok(metadata);
+  }
+
+  let mut pos: usize = 0;
+  let count: u16 = u16::from_le_bytes(read_bytes(bytes, &mut pos, 2)?.try_into().unwrap());
+  for _ in 0..count {
+    let key: String = read_string(bytes, &mut pos)?;
+    let value: String = read_string(bytes, &mut pos)?;
+    metadata.insert(key, value);
+  }
+
+  if pos != bytes.len() {
+    return Err("Segment metadata has trailing bytes".to_string());
+  }
+
+  Ok(metadata)
+}
+
+fn read_bytes<'a>(bytes: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], String> {
+  let end: usize = pos.checked_add(len).ok_or_else(|| "Segment metadata is truncated".to_string())?;
+  let slice: &[u8] = bytes.get(*pos..end).ok_or_else(|| "Segment metadata is truncated".to_string())?;
+  *pos = end;
+  Ok(slice)
+}
+
+fn read_string(bytes: &[u8], pos: &mut usize) -> Result<String, String> {
+  let len: u16 = u16::from_le_bytes(read_bytes(bytes, pos, 2)?.try_into().unwrap());
+  let slice: &[u8] = read_bytes(bytes, pos, len as usize)?;
+  String::from_utf8(slice.to_vec()).map_err(|e: std::string::FromUtf8Error| e.to_string())
+}
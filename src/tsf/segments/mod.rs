@@ -5,3 +5,7 @@ pub mod segment_column_data;
 pub mod segment_data_header;
 pub mod segment_data;
 pub mod types;
+pub mod checksum;
+pub mod codec;
+pub mod compression;
+pub mod metadata;
@@ -1,9 +1,13 @@
-use std::{fs::File, io::{self, Cursor, Read, Write}};
+use std::{fs::File, io::{self, Read, Write}, sync::Arc};
 
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use tracing::trace;
 
-use super::types::{EnumColumnData, EnumDataComp, EnumDataEnc, EnumDataType};
+use crate::tsf::error::TsfError;
+
+use super::checksum::calculate_checksum;
+use super::codec::ColumnCodec;
+use super::compression::{compress, decompress};
+use super::types::{EnumChecksumAlg, EnumColumnData, EnumDataComp, EnumDataEnc, EnumDataType, EnumDataValue};
 
 pub trait ColumnDataCreator {
   fn create_segment_column_data(column: Vec<Self>, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData
@@ -19,13 +23,61 @@ impl ColumnDataCreator for i8 {
 
 impl ColumnDataCreator for i16 {
   fn create_segment_column_data(column: Vec<Self>, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
-    SegmentColumnData::new_int16_vec(column, encoding, compression)
+    SegmentColumnData::from_column_data(EnumColumnData::Int16Vec(column), encoding, compression)
   }
 }
 
 impl ColumnDataCreator for i32 {
   fn create_segment_column_data(column: Vec<Self>, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
-    SegmentColumnData::new_int32_vec(column, encoding, compression)
+    SegmentColumnData::from_column_data(EnumColumnData::Int32Vec(column), encoding, compression)
+  }
+}
+
+impl ColumnDataCreator for i64 {
+  fn create_segment_column_data(column: Vec<Self>, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
+    SegmentColumnData::from_column_data(EnumColumnData::Int64Vec(column), encoding, compression)
+  }
+}
+
+impl ColumnDataCreator for u8 {
+  fn create_segment_column_data(column: Vec<Self>, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
+    SegmentColumnData::from_column_data(EnumColumnData::UInt8Vec(column), encoding, compression)
+  }
+}
+
+impl ColumnDataCreator for u16 {
+  fn create_segment_column_data(column: Vec<Self>, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
+    SegmentColumnData::from_column_data(EnumColumnData::UInt16Vec(column), encoding, compression)
+  }
+}
+
+impl ColumnDataCreator for u32 {
+  fn create_segment_column_data(column: Vec<Self>, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
+    SegmentColumnData::from_column_data(EnumColumnData::UInt32Vec(column), encoding, compression)
+  }
+}
+
+impl ColumnDataCreator for u64 {
+  fn create_segment_column_data(column: Vec<Self>, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
+    SegmentColumnData::from_column_data(EnumColumnData::UInt64Vec(column), encoding, compression)
+  }
+}
+
+impl ColumnDataCreator for f32 {
+  fn create_segment_column_data(column: Vec<Self>, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
+    SegmentColumnData::from_column_data(EnumColumnData::Float32Vec(column), encoding, compression)
+  }
+}
+
+impl ColumnDataCreator for f64 {
+  fn create_segment_column_data(column: Vec<Self>, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
+    SegmentColumnData::from_column_data(EnumColumnData::Float64Vec(column), encoding, compression)
+  }
+}
+
+impl ColumnDataCreator for bool {
+  fn create_segment_column_data(column: Vec<Self>, encoding: EnumDataEnc, compression: EnumDataComp) -> SegmentColumnData {
+    SegmentColumnData::from_column_data(EnumColumnData::BooleanVec(column), encoding, compression)
   }
 }
 
@@ -34,6 +86,10 @@ pub struct SegmentColumnData {
   encoding: EnumDataEnc,
   compression: EnumDataComp,
   buffer: Option<Vec<u8>>,
+  checksum_alg: EnumChecksumAlg,
+  column_check: [u8; 8],
+  // Write only: the codec's bytes are stored in place of the built in encoding
+  codec: Option<Arc<dyn ColumnCodec>>,
 }
 
 impl SegmentColumnData {
@@ -41,6 +97,11 @@ impl SegmentColumnData {
     Some(&self.data)
   }
 
+  // On-disk bytes, still encoded and compressed, between read_file_into_buffer and convert_buffer_into_data
+  pub fn raw_buffer(&self) -> Option<&[u8]> {
+    self.buffer.as_deref()
+  }
+
   pub fn new(data_type: EnumDataType, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
     trace!("SegmentColumnData::new");
     SegmentColumnData {
@@ -48,211 +109,109 @@ impl SegmentColumnData {
       encoding: encoding,
       compression: compression,
       buffer: None,
+      checksum_alg: EnumChecksumAlg::default(),
+      column_check: [0u8; 8],
+      codec: None,
     }
   }
 
-  pub fn new_int8_vec(initial_data: Vec<i8>, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    trace!("SegmentColumnData::new_int8_vec");
+  // Builds a column from any data variant, for callers that only know the type at runtime
+  pub fn from_column_data(data: EnumColumnData, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
+    trace!("SegmentColumnData::from_column_data");
     SegmentColumnData {
-        data: EnumColumnData::Int8Vec(initial_data),
-        encoding: encoding,
-        compression: compression,
+        data,
+        encoding,
+        compression,
         buffer: None,
+        checksum_alg: EnumChecksumAlg::default(),
+        column_check: [0u8; 8],
+        codec: None,
     }
   }
 
-  fn new_int16_vec(initial_data: Vec<i16>, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::Int16Vec(initial_data),
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
-    }
+  // Column of a custom type, the codec encodes it when the segment is written so sorting still applies
+  pub fn with_codec(mut self, codec: Arc<dyn ColumnCodec>) -> Self {
+    self.codec = Some(codec);
+    self
   }
 
-  fn new_int32_vec(initial_data: Vec<i32>, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::Int32Vec(initial_data),
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
+  // Bytes before compression, from the codec when the column has one
+  fn encoded_bytes(&self) -> Vec<u8> {
+    match &self.codec {
+      Some(codec) => codec.encode(&self.data),
+      None => self.data.to_encoded_bytes(self.encoding),
     }
   }
 
-  fn new_int64_vec(initial_data: Vec<i64>, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::Int64Vec(initial_data),
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
-    }
+  pub fn new_int8_vec(initial_data: Vec<i8>, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
+    trace!("SegmentColumnData::new_int8_vec");
+    Self::from_column_data(EnumColumnData::Int8Vec(initial_data), encoding, compression)
   }
 
-  fn new_uint8_vec(initial_data: Vec<u8>, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::UInt8Vec(initial_data),
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
-    }
-  }
+  pub fn convert_data_into_buffer(&mut self) -> io::Result<usize> {
+    trace!("SegmentColumnData::convert_data_into_buffer");
+    let buffer: Vec<u8> = compress(self.compression, self.encoded_bytes())?;
 
-  fn new_uint16_vec(initial_data: Vec<u16>, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::UInt16Vec(initial_data),
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
-    }
-  }
+    let total_bytes: usize = buffer.len();
+    self.column_check = calculate_checksum(self.checksum_alg, &buffer);
+    self.buffer = Some(buffer);
 
-  fn new_uint32_vec(initial_data: Vec<u32>, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::UInt32Vec(initial_data),
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
-    }
+    Ok(total_bytes)
   }
 
-  fn new_uint64_vec(initial_data: Vec<u64>, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::UInt64Vec(initial_data),
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
-    }
+  // Algorithm of the segment the column belongs to, used for column_check
+  pub fn set_checksum_alg(&mut self, checksum_alg: EnumChecksumAlg) {
+    self.checksum_alg = checksum_alg;
   }
 
-  fn new_float32_vec(initial_data: Vec<f32>, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::Float32Vec(initial_data),
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
-    }
+  // Checksum of the bytes prepared by convert_data_into_buffer, for the column header's column_check
+  pub fn get_column_check(&self) -> [u8; 8] {
+    self.column_check
   }
 
-  fn new_float64_vec(initial_data: Vec<f64>, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::Float64Vec(initial_data),
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
+  // Checks the bytes read by read_file_into_buffer against the header's column_check. Files written before
+  // column checks have all zero checks and are not verified, repair_checksums fills them in
+  pub fn verify_column_check(&self, column_check: [u8; 8]) -> bool {
+    match self.buffer.as_deref() {
+      Some(buffer) => column_check_matches(self.checksum_alg, column_check, buffer),
+      None => false,
     }
   }
 
-  fn new_boolean_vec(initial_data: Vec<bool>, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::BooleanVec(initial_data),
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
-    }
-  }
+  // Compressed size over raw size for this column's settings, without touching the prepared buffer
+  pub fn estimate_compression_ratio(&self) -> io::Result<f64> {
+    trace!("SegmentColumnData::estimate_compression_ratio");
 
-  fn new_datetime32_vec(initial_data: Vec<i32>, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::DateTime32Vec(initial_data),
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
+    let raw: Vec<u8> = self.encoded_bytes();
+    if raw.is_empty() {
+      return Ok(1.0);
     }
+    let raw_len: usize = raw.len();
+    let compressed: Vec<u8> = compress(self.compression, raw)?;
+
+    Ok(compressed.len() as f64 / raw_len as f64)
   }
 
-  fn new_datetime64_vec(initial_data: Vec<i64>, encoding: EnumDataEnc, compression: EnumDataComp) -> Self {
-    SegmentColumnData {
-        data: EnumColumnData::DateTime64Vec(initial_data),
-        encoding: encoding,
-        compression: compression,
-        buffer: None,
-    }
+  // Distinct values in the decoded column, a low count relative to the rows suggests dictionary encoding
+  pub fn distinct_count(&self) -> usize {
+    trace!("SegmentColumnData::distinct_count");
+
+    self.data.distinct_count()
   }
 
-  pub fn convert_data_into_buffer(&mut self) -> io::Result<usize> {
-    trace!("SegmentColumnData::convert_data_into_buffer");
-    let mut buffer: Vec<u8> = Vec::new();
-
-    match &self.data {
-      EnumColumnData::Int8Vec(data) => {
-        for &value in data {
-          buffer.write_i8(value)?;
-        }
-      },
-      EnumColumnData::Int16Vec(data) => {
-        for &value in data {
-          buffer.write_i16::<LittleEndian>(value)?;
-        }
-      },
-      EnumColumnData::Int32Vec(data) => {
-        for &value in data {
-          buffer.write_i32::<LittleEndian>(value)?;
-        }
-      },
-      EnumColumnData::Int64Vec(data) => {
-        for &value in data {
-          buffer.write_i64::<LittleEndian>(value)?;
-        }
-      },
-      EnumColumnData::UInt8Vec(data) => {
-        for &value in data {
-          buffer.write_u8(value)?;
-        }
-      },
-      EnumColumnData::UInt16Vec(data) => {
-        for &value in data {
-          buffer.write_u16::<LittleEndian>(value)?;
-        }
-      },
-      EnumColumnData::UInt32Vec(data) => {
-        for &value in data {
-          buffer.write_u32::<LittleEndian>(value)?;
-        }
-      },
-      EnumColumnData::UInt64Vec(data) => {
-        for &value in data {
-          buffer.write_u64::<LittleEndian>(value)?;
-        }
-      },
-      EnumColumnData::Float32Vec(data) => {
-        for &value in data {
-          buffer.write_f32::<LittleEndian>(value)?;
-        }
-      },
-      EnumColumnData::Float64Vec(data) => {
-        for &value in data {
-          buffer.write_f64::<LittleEndian>(value)?;
-        }
-      },
-      EnumColumnData::BooleanVec(data) => {
-        for &value in data {
-          // Convert bool to u8 (true -> 255, false -> 0)
-          let byte_value: u8 = if value { 255u8 } else { 0u8 };
-          buffer.write_u8(byte_value)?;
-        }
-      },
-      EnumColumnData::DateTime32Vec(data) => {
-        for &value in data {
-          buffer.write_i32::<LittleEndian>(value)?;
-        }
-      },
-      EnumColumnData::DateTime64Vec(data) => {
-        for &value in data {
-          buffer.write_i64::<LittleEndian>(value)?;
-        }
-      },
-      // EnumColumnData::StringVec(data) => {
-      //   for value in data {
-      //     file.write_all(value.as_bytes())?;
-      //   }
-      // },
-      // Handle other types...
-    }
+  // A single value of a column read with read_file_into_buffer. Unencoded, uncompressed columns are fixed
+  // width so the value is read straight from the buffer, anything else is decoded in place first
+  pub fn value_at(&mut self, index: usize) -> io::Result<Option<EnumDataValue>> {
+    trace!("SegmentColumnData::value_at");
 
-    let total_bytes: usize = buffer.len();
-    self.buffer = Some(buffer);
+    if let Some(buffer) = self.buffer.as_deref() {
+      if self.encoding == EnumDataEnc::None && self.compression == EnumDataComp::None {
+        return Ok(self.data.data_type().value_from_le_bytes(buffer, index));
+      }
+      self.convert_buffer_into_data()?;
+    }
 
-    Ok(total_bytes)
+    Ok(self.data.value_at(index))
   }
 
   pub fn convert_buffer_into_data(&mut self) -> io::Result<()> {
@@ -261,181 +220,7 @@ impl SegmentColumnData {
     let buffer: Vec<u8> = self.buffer.take()
       .ok_or(io::Error::new(io::ErrorKind::Other, "Buffer is empty"))?;
 
-    let mut cursor: Cursor<Vec<u8>> = Cursor::new(buffer);
-
-    match &mut self.data {
-      EnumColumnData::Int8Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = cursor.read_i8() {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = cursor.read_i8() {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::Int16Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = cursor.read_i16::<LittleEndian>() {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = cursor.read_i16::<LittleEndian>() {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::Int32Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = cursor.read_i32::<LittleEndian>() {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = cursor.read_i32::<LittleEndian>() {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::Int64Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = cursor.read_i64::<LittleEndian>() {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = cursor.read_i64::<LittleEndian>() {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::UInt8Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = cursor.read_u8() {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = cursor.read_u8() {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::UInt16Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = cursor.read_u16::<LittleEndian>() {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = cursor.read_u16::<LittleEndian>() {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::UInt32Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = cursor.read_u32::<LittleEndian>() {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = cursor.read_u32::<LittleEndian>() {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::UInt64Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = cursor.read_u64::<LittleEndian>() {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = cursor.read_u64::<LittleEndian>() {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::Float32Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = cursor.read_f32::<LittleEndian>() {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = cursor.read_f32::<LittleEndian>() {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::Float64Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = cursor.read_f64::<LittleEndian>() {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = cursor.read_f64::<LittleEndian>() {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::BooleanVec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = cursor.read_u8() {
-          // Convert bool to u8 (true -> 255, false -> 0)
-          let bool_value: bool = if value == 0u8 { false } else { true };
-          data_vec.push(bool_value);
-        }
-
-        if let Err(e) = cursor.read_u8() {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::DateTime32Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = cursor.read_i32::<LittleEndian>() {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = cursor.read_i32::<LittleEndian>() {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-      EnumColumnData::DateTime64Vec(data_vec) => {
-        data_vec.clear();
-
-        while let Ok(value) = cursor.read_i64::<LittleEndian>() {
-          data_vec.push(value);
-        }
-
-        if let Err(e) = cursor.read_i64::<LittleEndian>() {
-          if e.kind() != io::ErrorKind::UnexpectedEof {
-            return Err(e);
-          }
-        }
-      },
-    }
+    self.data.read_encoded_bytes(self.encoding, &decompress(self.compression, &buffer)?)?;
 
     Ok(())
   }
@@ -467,13 +252,54 @@ impl SegmentColumnData {
     Ok(())
   }
 
+  // Reads and decodes bytes using the caller's buffer, so a loop over many columns can share one allocation.
+  // The bytes are checked against the header's column_check before they are decompressed
+  pub fn read_into(&mut self, file: &mut File, bytes: usize, column_check: [u8; 8], reuse_buffer: &mut Vec<u8>) -> io::Result<()> {
+    trace!("SegmentColumnData::read_into");
+
+    reuse_buffer.clear();
+    reuse_buffer.resize(bytes, 0u8);
+    file.read_exact(reuse_buffer)?;
+    if !column_check_matches(self.checksum_alg, column_check, reuse_buffer) {
+      return Err(TsfError::Corruption { reason: "Column failed column check verification".to_string() }.into());
+    }
+    match self.compression {
+      EnumDataComp::None => self.data.read_encoded_bytes(self.encoding, reuse_buffer)?,
+      _ => self.data.read_encoded_bytes(self.encoding, &decompress(self.compression, reuse_buffer)?)?,
+    }
+
+    Ok(())
+  }
+
+}
+
+// An all zero column_check comes from a file written before column checks and matches any bytes
+fn column_check_matches(checksum_alg: EnumChecksumAlg, column_check: [u8; 8], bytes: &[u8]) -> bool {
+  column_check == [0u8; 8] || column_check == calculate_checksum(checksum_alg, bytes)
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use byteorder::WriteBytesExt;
   use std::io::{Read, Seek, SeekFrom};
-  use tempfile::tempfile;
+  use tempfile::{tempfile, NamedTempFile};
+  use crate::tsf::header::FileHeader;
+  use crate::tsf::segments::segment_data_header::SegmentDataHeader;
+  use crate::tsf::tsf_writer::TSFWriter;
+
+  #[test]
+  fn test_distinct_count() {
+    let column: SegmentColumnData = SegmentColumnData::from_column_data(
+      EnumColumnData::Int32Vec(vec![5, 1, 5, 3, 1, 5, 9]),
+      EnumDataEnc::None,
+      EnumDataComp::None,
+    );
+    assert_eq!(column.distinct_count(), 4);
+
+    let column: SegmentColumnData = SegmentColumnData::from_column_data(EnumColumnData::BooleanVec(vec![true, true]), EnumDataEnc::None, EnumDataComp::None);
+    assert_eq!(column.distinct_count(), 1);
+  }
 
   #[test]
   fn test_prepare_and_write_int8_data() -> io::Result<()> {
@@ -529,4 +355,146 @@ mod tests {
 
       Ok(())
   }
+
+  #[test]
+  fn test_read_into_reused_buffer() -> io::Result<()> {
+      let mut temp_file: File = tempfile()?;
+      let mut written: SegmentColumnData = SegmentColumnData::from_column_data(
+        EnumColumnData::Int32Vec(vec![1710555318, -7, 0, 42]),
+        EnumDataEnc::None,
+        EnumDataComp::None
+      );
+      let bytes: usize = written.convert_data_into_buffer()?;
+      written.write_buffer_into_file(&mut temp_file)?;
+      written.write_buffer_into_file(&mut temp_file)?;
+      temp_file.seek(SeekFrom::Start(0))?;
+
+      // Starts out larger than needed and holding stale bytes
+      let mut reuse_buffer: Vec<u8> = vec![0xFF; 64];
+      for _ in 0..2 {
+        let mut read: SegmentColumnData = SegmentColumnData::new(EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None);
+        read.read_into(&mut temp_file, bytes, written.get_column_check(), &mut reuse_buffer)?;
+
+        assert!(matches!(read.get_data(), Some(EnumColumnData::Int32Vec(v)) if v == &vec![1710555318, -7, 0, 42]));
+        assert!(read.buffer.is_none());
+      }
+      assert!(reuse_buffer.capacity() >= 64);
+
+      Ok(())
+  }
+
+  #[test]
+  fn test_read_into_rejects_corrupted_column() -> io::Result<()> {
+      let mut temp_file: File = tempfile()?;
+      let mut written: SegmentColumnData = SegmentColumnData::from_column_data(
+        EnumColumnData::Int32Vec(vec![1710555318, -7, 0, 42]),
+        EnumDataEnc::Delta,
+        EnumDataComp::ZStd
+      );
+      let bytes: usize = written.convert_data_into_buffer()?;
+      written.write_buffer_into_file(&mut temp_file)?;
+
+      // Flip a bit of the last stored byte
+      temp_file.seek(SeekFrom::Start(bytes as u64 - 1))?;
+      let mut last: [u8; 1] = [0u8; 1];
+      temp_file.read_exact(&mut last)?;
+      temp_file.seek(SeekFrom::Start(bytes as u64 - 1))?;
+      temp_file.write_all(&[last[0] ^ 0x01])?;
+      temp_file.seek(SeekFrom::Start(0))?;
+
+      let mut reuse_buffer: Vec<u8> = Vec::new();
+      let mut read: SegmentColumnData = SegmentColumnData::new(EnumDataType::Int32, EnumDataEnc::Delta, EnumDataComp::ZStd);
+      let error: io::Error = read.read_into(&mut temp_file, bytes, written.get_column_check(), &mut reuse_buffer).unwrap_err();
+      assert!(matches!(error.get_ref().and_then(|inner| inner.downcast_ref::<TsfError>()), Some(TsfError::Corruption { .. })));
+      assert!(read.get_data().is_some_and(|data: &EnumColumnData| data.is_empty()));
+
+      Ok(())
+  }
+
+  #[test]
+  fn test_raw_buffer_matches_column_size() -> io::Result<()> {
+      let temp_file: NamedTempFile = NamedTempFile::new()?;
+      let file_path: &str = temp_file.path().to_str().unwrap();
+
+      let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+      writer.add_column_header("temperature", EnumDataType::Int16, EnumDataEnc::None, EnumDataComp::ZStd, false)
+        .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+      writer.add_column_data(vec![21i16; 500], EnumDataEnc::None, EnumDataComp::ZStd)
+        .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+      writer.update_segment_dates(0, 0);
+      writer.try_save()?;
+
+      let mut file: File = File::open(file_path)?;
+      FileHeader::new().read_header(&mut file)?;
+      let mut data_header: SegmentDataHeader = SegmentDataHeader::new();
+      data_header.read_segment_header(&mut file)?;
+      let column_size: usize = data_header.column_headers[0].column_size as usize;
+
+      let mut column_data: SegmentColumnData = SegmentColumnData::new(EnumDataType::Int16, EnumDataEnc::None, EnumDataComp::ZStd);
+      assert!(column_data.raw_buffer().is_none());
+      column_data.read_file_into_buffer(&mut file, column_size)?;
+
+      // Still compressed, so well under the 1000 decoded bytes
+      assert_eq!(column_data.raw_buffer().map(|buffer: &[u8]| buffer.len()), Some(column_size));
+      assert!(column_size < 1000);
+
+      column_data.convert_buffer_into_data()?;
+      assert!(column_data.raw_buffer().is_none());
+      assert!(matches!(column_data.get_data(), Some(EnumColumnData::Int16Vec(v)) if v == &vec![21i16; 500]));
+
+      Ok(())
+  }
+
+  #[test]
+  fn test_value_at_reads_one_element_from_the_buffer() -> io::Result<()> {
+      for compression in [EnumDataComp::None, EnumDataComp::ZStd] {
+        let mut written: SegmentColumnData = SegmentColumnData::from_column_data(
+          EnumColumnData::Int32Vec((0..1000).collect()),
+          EnumDataEnc::None,
+          compression
+        );
+        let column_size: usize = written.convert_data_into_buffer()?;
+        let mut file: File = tempfile()?;
+        written.write_buffer_into_file(&mut file)?;
+        file.seek(SeekFrom::Start(0))?;
+
+        let mut column_data: SegmentColumnData = SegmentColumnData::new(EnumDataType::Int32, EnumDataEnc::None, compression);
+        column_data.read_file_into_buffer(&mut file, column_size)?;
+
+        assert!(matches!(column_data.value_at(500)?, Some(EnumDataValue::Int32Value(500))));
+        assert!(column_data.value_at(1000)?.is_none());
+        // Only the compressed column had to be decoded to answer
+        assert_eq!(column_data.raw_buffer().is_some(), compression == EnumDataComp::None);
+      }
+
+      Ok(())
+  }
+
+  #[test]
+  fn test_estimate_compression_ratio() -> io::Result<()> {
+      let repetitive: SegmentColumnData = SegmentColumnData::from_column_data(
+        EnumColumnData::Int32Vec(vec![1710555318; 1000]),
+        EnumDataEnc::None,
+        EnumDataComp::ZStd
+      );
+      assert!(repetitive.estimate_compression_ratio()? < 1.0);
+
+      // xorshift noise has nothing for zstd to find
+      let mut state: u64 = 0x9E3779B97F4A7C15;
+      let noise: Vec<i64> = (0..1000).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as i64
+      }).collect();
+      let random: SegmentColumnData = SegmentColumnData::from_column_data(EnumColumnData::Int64Vec(noise), EnumDataEnc::None, EnumDataComp::ZStd);
+      let ratio: f64 = random.estimate_compression_ratio()?;
+      assert!((0.95..1.05).contains(&ratio), "ratio {}", ratio);
+
+      let uncompressed: SegmentColumnData = SegmentColumnData::new_int8_vec(vec![1, 1, 1, 1], EnumDataEnc::None, EnumDataComp::None);
+      assert_eq!(uncompressed.estimate_compression_ratio()?, 1.0);
+      assert!(uncompressed.raw_buffer().is_none());
+
+      Ok(())
+  }
 }
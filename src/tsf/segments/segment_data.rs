@@ -1,16 +1,72 @@
-use std::{fs::File, io::{self, Cursor, Read, Write}};
+use std::{collections::BTreeMap, fs::File, io::{self, BufWriter, Cursor, Read, Seek, SeekFrom, Write}, sync::Arc, time::{Duration, Instant}};
 
+use sha2::{Digest, Sha256};
+use chrono::Utc;
 use tracing::trace;
 use uuid7;
 
+use super::checksum::ChecksumWriter;
+use super::codec::{CodecRegistry, ColumnCodec, CustomColumn};
 use super::segment_column_data::SegmentColumnData;
 use super::segment_data_header::{SegmentColumnHeader, SegmentDataHeader};
-use super::types::{ColumnMeta, EnumColumnData, EnumDataEnc, EnumDataType};
+use super::types::{validate_column_name, ColumnLayout, ColumnMeta, ColumnSlice, EnumChecksumAlg, EnumColumnData, EnumDataComp, EnumDataEnc, EnumDataType, UnsupportedColumn};
+use crate::tsf::error::TsfError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnReadStats {
+  pub column_name: String,
+  pub bytes_read: u64,
+  pub decode_time: Duration,
+}
+
+// Bytes a stream buffers per column before they are written to the column's spool
+pub const DEFAULT_FLUSH_THRESHOLD: usize = 64 * 1024;
 
 #[repr(C)]
 pub struct SegmentData {
   data_header: SegmentDataHeader,
   data: Vec<SegmentColumnData>,
+  read_stats: Vec<ColumnReadStats>,
+  // Read only: decodes columns whose type code has a codec, they are collected in custom_columns
+  codecs: CodecRegistry,
+  custom_columns: Vec<CustomColumn>,
+  segment_pos: u64,
+  auto_sort: bool,
+  // Next segment start is padded up to a multiple of this, 1 writes segments back to back
+  alignment: u64,
+  // Extend the file to the segment's final size before writing any of it
+  presize: bool,
+  // Set between begin_stream and finish_stream while the segment is written in batches
+  stream: Option<SegmentStream>,
+  flush_threshold: usize,
+  // Writes that reached the spools of the last finished stream
+  stream_flushes: usize,
+}
+
+// Open ended segment being written in batches. The header space is reserved in the file and every column
+// is spooled to its own temp file, since columns are stored back to back and their sizes aren't known yet
+struct SegmentStream {
+  segment_pos: u64,
+  header_size: u64,
+  spools: Vec<BufWriter<SpoolFile>>,
+  last_timestamp: Option<i64>,
+}
+
+// A column's spool, counting the buffer flushes that reach it
+struct SpoolFile {
+  file: File,
+  writes: usize,
+}
+
+impl Write for SpoolFile {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.writes += 1;
+    self.file.write(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.file.flush()
+  }
 }
 
 impl SegmentData {
@@ -19,6 +75,16 @@ impl SegmentData {
     SegmentData {
         data_header,
         data: vec![],
+        read_stats: vec![],
+        codecs: CodecRegistry::new(),
+        custom_columns: vec![],
+        segment_pos: 0,
+        auto_sort: false,
+        alignment: 1,
+        presize: false,
+        stream: None,
+        flush_threshold: DEFAULT_FLUSH_THRESHOLD,
+        stream_flushes: 0,
     }
   }
 
@@ -43,6 +109,94 @@ impl SegmentData {
     self.data_header.row_count as usize
   }
 
+  // Seconds since the epoch when the segment was written
+  pub fn get_created_at(&self) -> i64 {
+    self.data_header.get_created_at()
+  }
+
+  // Where the segment last written or read starts in the file
+  pub fn get_segment_pos(&self) -> u64 {
+    self.segment_pos
+  }
+
+  // Bytes from the start of this segment to the start of the next one
+  pub fn get_next_offset(&self) -> Option<u32> {
+    self.data_header.next_offset
+  }
+
+  pub fn is_tombstoned(&self) -> bool {
+    self.data_header.tombstone
+  }
+
+  pub fn get_ts_column(&self) -> Option<usize> {
+    trace!("SegmentData::get_ts_column");
+
+    self.data_header.get_ts_column().map(|index: u16| index as usize)
+  }
+
+  // Columns are stored back to back right after the segment header
+  pub fn column_layout(&self) -> Vec<ColumnLayout> {
+    trace!("SegmentData::column_layout");
+
+    let mut file_pos: u64 = self.segment_pos + self.data_header.calculate_header_size() as u64;
+    self.data_header.column_headers.iter()
+      .enumerate()
+      .map(|(index, header): (usize, &SegmentColumnHeader)| {
+        file_pos += self.data_header.skipped_bytes_before(index);
+        let layout: ColumnLayout = ColumnLayout {
+          name: header.column_name.clone(),
+          file_pos,
+          column_size: header.column_size,
+          column_type: header.column_type,
+          encoding: header.column_enc,
+          compression: header.column_comp,
+        };
+        file_pos += header.column_size;
+        layout
+      })
+      .collect()
+  }
+
+  // Skip columns whose type code this build doesn't know instead of failing the read
+  pub fn set_skip_unsupported_columns(&mut self, skip_unsupported_columns: bool) {
+    self.data_header.set_skip_unsupported_columns(skip_unsupported_columns);
+  }
+
+  pub fn set_max_column_count(&mut self, max_column_count: u16) {
+    self.data_header.set_max_column_count(max_column_count);
+  }
+
+  pub fn get_unsupported_columns(&self) -> &[UnsupportedColumn] {
+    self.data_header.get_unsupported_columns()
+  }
+
+  // Columns of a type code in codecs are read and decoded instead of failing the read
+  pub fn set_codecs(&mut self, codecs: CodecRegistry) {
+    self.data_header.set_custom_type_codes(codecs.type_codes());
+    self.codecs = codecs;
+  }
+
+  pub fn get_custom_columns(&self) -> &[CustomColumn] {
+    &self.custom_columns
+  }
+
+  pub fn get_read_stats(&self) -> &[ColumnReadStats] {
+    &self.read_stats
+  }
+
+  pub fn get_column_header(&self, index: usize) -> Option<&SegmentColumnHeader> {
+    trace!("SegmentData::get_column_header");
+
+    self.data_header.column_headers.get(index)
+  }
+
+  // Borrows the decoded column without copying, None if the index or type doesn't match
+  pub fn column_slice<T: ColumnSlice>(&self, index: usize) -> Option<&[T]> {
+    trace!("SegmentData::column_slice");
+
+    self.data.get(index).and_then(|column_data: &SegmentColumnData| T::column_slice(&column_data.data))
+  }
+
   pub fn get_segment_data<'a>(&'a self, index: usize) -> Option<&'a SegmentColumnData> {
     trace!("SegmentData::get_segment_data");
 
@@ -52,6 +206,21 @@ impl SegmentData {
   pub fn add_column_header(&mut self, column_header: SegmentColumnHeader, ts_column: bool) -> Result<(), String> {
     trace!("SegmentData::add_column_header");
 
+    validate_column_name(&column_header.column_name)?;
+    if !column_header.column_enc.is_valid_for(column_header.column_type) {
+      return Err(TsfError::UnsupportedEncoding {
+        column: column_header.column_name,
+        encoding: column_header.column_enc,
+        data_type: column_header.column_type,
+      }.into());
+    }
+
+    // column_header_size is a u32, the sum is checked before it can wrap
+    let column_header_size: u64 = self.data_header.column_header_size as u64 + column_header.byte_size() as u64;
+    if column_header_size > u32::MAX as u64 {
+      return Err(format!("Column headers would take {} bytes, more than the limit of {}", column_header_size, u32::MAX));
+    }
+
     let index: u16 = self.data_header.add_column_header(column_header);
     if ts_column {
       self.data_header.set_ts_column(index)?;
@@ -68,13 +237,7 @@ impl SegmentData {
       return Err("No corresponding column header for the data.".to_string());
     }
 
-    let data_row_count: usize = match &data.data {
-      EnumColumnData::Int8Vec(vec) => vec.len(),
-      EnumColumnData::Int16Vec(vec) => vec.len(),
-      EnumColumnData::Int32Vec(vec) => vec.len(),
-      // @TODO Add cases for other data types...
-      _ => 0,
-    };
+    let data_row_count: usize = data.data.len();
 
     // Can't add empty rows
     if data_row_count == 0 {
@@ -96,21 +259,161 @@ impl SegmentData {
     Ok(())
   }
 
+  // Header and data of a column stored under a type code this crate doesn't define, encoded by codec.
+  // Every column before it must already have its data, so the two are added together
+  pub fn add_custom_column(&mut self, column_name: &str, type_code: u16, codec: Arc<dyn ColumnCodec>, data: EnumColumnData) -> Result<(), String> {
+    trace!("SegmentData::add_custom_column");
+
+    if let Some(data_type) = EnumDataType::from_u16(type_code) {
+      return Err(format!("Type code {} is the built in type {:?}", type_code, data_type));
+    }
+    if self.data_header.column_count as usize != self.data.len() {
+      return Err("Custom columns are added after the data of the columns before them".to_string());
+    }
+    if data.is_empty() {
+      return Err("Zero rows added".to_string());
+    }
+    if self.data_header.row_count != 0 && self.data_header.row_count as usize != data.len() {
+      return Err("Inconsistent number of rows.".to_string());
+    }
+
+    let header: SegmentColumnHeader = SegmentColumnHeader::new(column_name.to_string(), data.data_type(), EnumDataEnc::None, EnumDataComp::None)
+      .with_type_code(type_code);
+    self.add_column_header(header, false)?;
+    self.add_column_data(SegmentColumnData::from_column_data(data, EnumDataEnc::None, EnumDataComp::None).with_codec(codec))
+  }
+
+  pub fn set_checksum_alg(&mut self, checksum_alg: EnumChecksumAlg) {
+    self.data_header.set_checksum_alg(checksum_alg);
+  }
+
+  pub fn describe_column(&mut self, column_name: &str, description: &str) -> Result<(), String> {
+    trace!("SegmentData::describe_column");
+
+    self.data_header.describe_column(column_name, description)
+  }
+
+  // Small key/value provenance blob stored in the segment header
+  pub fn set_metadata(&mut self, metadata: BTreeMap<String, String>) -> Result<(), String> {
+    trace!("SegmentData::set_metadata");
+
+    self.data_header.set_metadata(metadata)
+  }
+
+  pub fn get_metadata(&self) -> &BTreeMap<String, String> {
+    self.data_header.get_metadata()
+  }
+
+  // Column values are hashed decoded, so the digest doesn't depend on how they were stored
+  pub fn update_content_digest(&self, hasher: &mut Sha256) {
+    trace!("SegmentData::update_content_digest");
+
+    self.data_header.update_content_digest(hasher);
+    for column_data in &self.data {
+      let bytes: Vec<u8> = column_data.data.to_le_bytes();
+      hasher.update((bytes.len() as u64).to_le_bytes());
+      hasher.update(bytes);
+    }
+  }
+
+  pub fn set_alignment(&mut self, alignment: u64) -> Result<(), String> {
+    trace!("SegmentData::set_alignment");
+
+    if alignment == 0 {
+      return Err("Segment alignment must be at least 1".to_string());
+    }
+    self.alignment = alignment;
+    Ok(())
+  }
+
+  pub fn set_presize(&mut self, presize: bool) {
+    self.presize = presize;
+  }
+
+  // Bytes each column of a stream buffers before writing them out, 0 writes every batch straight through
+  pub fn set_flush_threshold(&mut self, flush_threshold: usize) {
+    self.flush_threshold = flush_threshold;
+  }
+
+  pub fn get_stream_flushes(&self) -> usize {
+    self.stream_flushes
+  }
+
+  // Sort the whole segment by the timestamp column on write instead of rejecting it
+  pub fn set_auto_sort(&mut self, auto_sort: bool) {
+    self.auto_sort = auto_sort;
+  }
+
+  // Pruning trusts the header's date range, so a segment is only written when that range means something
+  fn check_date_range(&self) -> Result<(), TsfError> {
+    trace!("SegmentData::check_date_range");
+
+    if self.data_header.row_count == 0 {
+      return Err(TsfError::EmptySegment);
+    }
+    match self.data_header.get_date_range() {
+      Some((start, end)) if start > end => Err(TsfError::InvalidDateRange { start, end }),
+      _ => Ok(()),
+    }
+  }
+
+  fn ensure_sorted_timestamps(&mut self) -> Result<(), TsfError> {
+    trace!("SegmentData::ensure_sorted_timestamps");
+
+    let ts_index: usize = match self.get_ts_column() {
+      Some(ts_index) => ts_index,
+      None => return Ok(()),
+    };
+
+    let ts_data: &EnumColumnData = match self.data.get(ts_index) {
+      Some(column_data) => &column_data.data,
+      None => return Ok(()),
+    };
+
+    if ts_data.is_sorted_ascending() {
+      return Ok(());
+    }
+
+    if !self.auto_sort {
+      return Err(TsfError::TimestampNotSorted {
+        column: self.data_header.column_headers[ts_index].column_name.clone(),
+      });
+    }
+
+    // Every column is reordered with the same permutation so rows stay aligned
+    let indices: Vec<usize> = ts_data.sorted_indices();
+    for column_data in self.data.iter_mut() {
+      column_data.data = column_data.data.take(&indices);
+    }
+
+    Ok(())
+  }
+
   pub fn update_header_dates(&mut self, date_start: i64, date_end: i64) {
     self.data_header.set_date_start(date_start);
     self.data_header.set_date_end(date_end);
   }
 
+  pub fn get_date_range(&self) -> Option<(i64, i64)> {
+    self.data_header.get_date_range()
+  }
+
   // Writes the SegmentData to a file, including the header and data.
   pub fn write_to_file(&mut self, file: &mut File) -> io::Result<()> {
     trace!("SegmentData::write_to_file");
 
+    self.check_date_range()?;
+    self.data_header.set_created_at(Utc::now().timestamp());
+    self.ensure_sorted_timestamps()?;
+
     // First, ensure column sizes in headers match the data that will be written.
     let mut total_data_size: usize = 0;
     for (index, column_data) in self.data.iter_mut().enumerate() {
-      // Prepare the buffer for each column and get its size.
+      // Prepare the buffer for each column and get its size and checksum.
+      column_data.set_checksum_alg(self.data_header.checksum_alg);
       let data_size: usize = column_data.convert_data_into_buffer()?;
       self.data_header.column_headers[index].column_size = data_size as u64;
+      self.data_header.column_headers[index].set_column_check(column_data.get_column_check());
       total_data_size += data_size;
     }
 
@@ -119,8 +422,19 @@ impl SegmentData {
         .map(|header| header.byte_size() as u32)
         .sum::<u32>();
 
-    // Calculate next_offset based on header size and total data size.
-    self.data_header.next_offset = Some(self.data_header.calculate_header_size() + total_data_size as u32);
+    // Calculate next_offset based on header size, total data size and the padding up to the next aligned start.
+    let segment_pos: u64 = file.stream_position()?;
+    self.segment_pos = segment_pos;
+    let segment_size: u64 = self.data_header.calculate_header_size() as u64 + total_data_size as u64;
+    let padding: u64 = alignment_padding(segment_pos + segment_size, self.alignment);
+    let next_offset: u32 = u32::try_from(segment_size + padding)
+      .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Segment is too large for next_offset"))?;
+    self.data_header.next_offset = Some(next_offset);
+
+    // The whole segment is known now, so the file can grow once instead of with every column
+    if self.presize {
+      file.set_len(segment_pos + segment_size + padding)?;
+    }
 
     // Now, write the header to the file.
     self.data_header.write_header(file)?;
@@ -129,6 +443,136 @@ impl SegmentData {
     for column_data in &self.data {
       column_data.write_buffer_into_file(file)?;
     }
+    file.write_all(&vec![0u8; padding as usize])?;
+
+    Ok(())
+  }
+
+  // Starts an open ended segment at the file's position. Only the header space is written, batches
+  // are added with append_batch and finish_stream writes the header once all of them are in. The
+  // columns have to be unencoded and uncompressed so each batch's bytes can be appended as they are
+  pub fn begin_stream(&mut self, file: &mut File) -> io::Result<()> {
+    trace!("SegmentData::begin_stream");
+
+    if self.stream.is_some() || !self.data.is_empty() {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, "Segment already has data"));
+    }
+    if self.data_header.column_headers.is_empty() {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, "Segment has no columns"));
+    }
+    if let Some(header) = self.data_header.column_headers.iter()
+      .find(|header: &&SegmentColumnHeader| header.column_enc != EnumDataEnc::None || header.column_comp != EnumDataComp::None) {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Column {} must be unencoded and uncompressed to stream", header.column_name)));
+    }
+
+    let segment_pos: u64 = file.stream_position()?;
+    let header_size: u64 = self.data_header.calculate_header_size() as u64;
+    file.write_all(&vec![0u8; header_size as usize])?;
+
+    let spools: Vec<BufWriter<SpoolFile>> = self.data_header.column_headers.iter()
+      .map(|_| tempfile::tempfile().map(|file: File| BufWriter::with_capacity(self.flush_threshold, SpoolFile { file, writes: 0 })))
+      .collect::<io::Result<Vec<BufWriter<SpoolFile>>>>()?;
+
+    self.data_header.row_count = 0;
+    self.stream_flushes = 0;
+    self.segment_pos = segment_pos;
+    self.stream = Some(SegmentStream { segment_pos, header_size, spools, last_timestamp: None });
+
+    Ok(())
+  }
+
+  // Adds one batch of rows, a column per header in header order. The timestamp column has to stay
+  // ascending across batches, the segment dates follow it
+  pub fn append_batch(&mut self, columns: &[EnumColumnData]) -> Result<(), String> {
+    trace!("SegmentData::append_batch");
+
+    let ts_column: Option<usize> = self.get_ts_column();
+    let stream: &mut SegmentStream = self.stream.as_mut()
+      .ok_or("No segment stream has been started".to_string())?;
+
+    if columns.len() != self.data_header.column_headers.len() {
+      return Err(format!("Batch has {} columns, the segment has {}", columns.len(), self.data_header.column_headers.len()));
+    }
+    let batch_rows: usize = columns[0].len();
+    if batch_rows == 0 {
+      return Err("Zero rows added".to_string());
+    }
+    for (header, column) in self.data_header.column_headers.iter().zip(columns) {
+      if column.data_type() != header.column_type {
+        return Err(format!("Column {} is {:?} but the batch has {:?}", header.column_name, header.column_type, column.data_type()));
+      }
+      if column.len() != batch_rows {
+        return Err("Inconsistent number of rows.".to_string());
+      }
+    }
+    let row_count: u32 = u32::try_from(self.data_header.row_count as usize + batch_rows)
+      .map_err(|_| "Segment row count overflows u32".to_string())?;
+
+    let mut batch_range: Option<(i64, i64)> = None;
+    if let Some(ts_index) = ts_column {
+      let ts_data: &EnumColumnData = &columns[ts_index];
+      let first: Option<i64> = ts_data.value_at(0).and_then(|value| value.as_i64());
+      let last: Option<i64> = ts_data.value_at(batch_rows - 1).and_then(|value| value.as_i64());
+      let continues: bool = match (stream.last_timestamp, first) {
+        (Some(previous), Some(first)) => previous <= first,
+        _ => true,
+      };
+      if !ts_data.is_sorted_ascending() || !continues {
+        return Err(TsfError::TimestampNotSorted {
+          column: self.data_header.column_headers[ts_index].column_name.clone(),
+        }.into());
+      }
+      batch_range = first.zip(last);
+    }
+
+    for (spool, column) in stream.spools.iter_mut().zip(columns) {
+      spool.write_all(&column.to_le_bytes()).map_err(|e: io::Error| e.to_string())?;
+    }
+
+    if let Some((first, last)) = batch_range {
+      if stream.last_timestamp.is_none() {
+        self.data_header.set_date_start(first);
+      }
+      self.data_header.set_date_end(last);
+      stream.last_timestamp = Some(last);
+    }
+    self.data_header.row_count = row_count;
+
+    Ok(())
+  }
+
+  // Lays the spooled columns out after the reserved header space, then seeks back and writes the header
+  pub fn finish_stream(&mut self, file: &mut File) -> io::Result<()> {
+    trace!("SegmentData::finish_stream");
+
+    let stream: SegmentStream = self.stream.take()
+      .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "No segment stream has been started"))?;
+    self.check_date_range()?;
+    self.data_header.set_created_at(Utc::now().timestamp());
+
+    let mut column_pos: u64 = stream.segment_pos + stream.header_size;
+    file.seek(SeekFrom::Start(column_pos))?;
+    for (index, spool) in stream.spools.into_iter().enumerate() {
+      // Whatever is still buffered is flushed before the spool is copied over
+      let mut spool: SpoolFile = spool.into_inner().map_err(|e: io::IntoInnerError<BufWriter<SpoolFile>>| e.into_error())?;
+      self.stream_flushes += spool.writes;
+      spool.file.seek(SeekFrom::Start(0))?;
+      let mut checked_file: ChecksumWriter<&mut File> = ChecksumWriter::new(self.data_header.checksum_alg, &mut *file);
+      let column_size: u64 = io::copy(&mut spool.file, &mut checked_file)?;
+      self.data_header.column_headers[index].column_size = column_size;
+      self.data_header.column_headers[index].set_column_check(checked_file.finish());
+      column_pos += column_size;
+    }
+
+    let padding: u64 = alignment_padding(column_pos, self.alignment);
+    file.write_all(&vec![0u8; padding as usize])?;
+    let next_offset: u32 = u32::try_from(column_pos + padding - stream.segment_pos)
+      .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Segment is too large for next_offset"))?;
+    self.data_header.next_offset = Some(next_offset);
+
+    file.seek(SeekFrom::Start(stream.segment_pos))?;
+    self.data_header.write_header(file)?;
+    file.seek(SeekFrom::End(0))?;
 
     Ok(())
   }
@@ -137,30 +581,117 @@ impl SegmentData {
   pub fn read_segment_from_file(&mut self, file: &mut File) -> io::Result<()> {
     trace!("SegmentData::read_segment_from_file");
 
+    self.segment_pos = file.stream_position()?;
     self.data_header.read_segment_header(file)?;
     self.read_segment_data(file)?;
 
     Ok(())
   }
 
+  // Reads only the header of the segment at the file's position, leaving the column data unread
+  pub fn read_header_from_file(&mut self, file: &mut File) -> io::Result<()> {
+    trace!("SegmentData::read_header_from_file");
+
+    self.segment_pos = file.stream_position()?;
+    self.data_header.read_segment_header(file)
+  }
+
+  // Reads and decodes a single column of a segment whose header has been read, without keeping it
+  pub fn read_column_from_file(&self, file: &mut File, index: usize) -> io::Result<EnumColumnData> {
+    trace!("SegmentData::read_column_from_file");
+
+    let layout: ColumnLayout = self.column_layout().into_iter().nth(index)
+      .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Column {} is out of range", index)))?;
+    file.seek(SeekFrom::Start(layout.file_pos))?;
+
+    let mut column_data: SegmentColumnData = read_checked_column(file, &self.data_header.column_headers[index], self.data_header.checksum_alg)?;
+    column_data.convert_buffer_into_data()?;
+
+    Ok(column_data.get_data().cloned().unwrap_or_else(|| EnumColumnData::from_enum_data_type(layout.column_type)))
+  }
+
   fn read_segment_data(&mut self, file: &mut File) -> io::Result<()> {
     trace!("SegmentData::read_segment_data");
 
     // Ensure the data vector is clear
     self.data.clear();
+    self.custom_columns.clear();
 
     // This reads all the columns
-    for header in &self.data_header.column_headers {
-      let mut column_data: SegmentColumnData = SegmentColumnData::new(
-        header.column_type,
-        header.column_enc,
-        header.column_comp,
-      );
-      column_data.read_file_into_buffer(file, header.column_size as usize)?;
+    for (index, header) in self.data_header.column_headers.iter().enumerate() {
+      self.custom_columns.extend(read_skipped_columns(file, self.data_header.skipped_before(index), &self.codecs)?);
+      let mut column_data: SegmentColumnData = read_checked_column(file, header, self.data_header.checksum_alg)?;
+
+      let decode_start: Instant = Instant::now();
       column_data.convert_buffer_into_data()?;
+      let decode_time: Duration = decode_start.elapsed();
+
+      // Stats accumulate across reads, keyed by column position
+      match self.read_stats.get_mut(index) {
+        Some(stats) => {
+          stats.bytes_read += header.column_size;
+          stats.decode_time += decode_time;
+        },
+        None => self.read_stats.push(ColumnReadStats {
+          column_name: header.column_name.clone(),
+          bytes_read: header.column_size,
+          decode_time,
+        }),
+      }
+
       self.data.push(column_data);
     }
+    let column_count: usize = self.data_header.column_headers.len();
+    self.custom_columns.extend(read_skipped_columns(file, self.data_header.skipped_before(column_count), &self.codecs)?);
 
     Ok(())
   }
 }
+
+// Reads a column's bytes at the file's position and checks them against the header's column_check, so
+// corruption is reported for the column it is in
+fn read_checked_column(file: &mut File, header: &SegmentColumnHeader, checksum_alg: EnumChecksumAlg) -> io::Result<SegmentColumnData> {
+  let mut column_data: SegmentColumnData = SegmentColumnData::new(header.column_type, header.column_enc, header.column_comp);
+  column_data.set_checksum_alg(checksum_alg);
+  column_data.read_file_into_buffer(file, header.column_size as usize)?;
+
+  if !column_data.verify_column_check(header.get_column_check()) {
+    return Err(TsfError::Corruption { reason: format!("Column {} failed column check verification", header.column_name) }.into());
+  }
+
+  Ok(column_data)
+}
+
+// Decodes the skipped columns that have a codec and moves past the data of the rest
+fn read_skipped_columns<'a>(file: &mut File, columns: impl Iterator<Item = &'a UnsupportedColumn>, codecs: &CodecRegistry) -> io::Result<Vec<CustomColumn>> {
+  let mut custom_columns: Vec<CustomColumn> = Vec::new();
+  for column in columns {
+    match codecs.get(column.type_code) {
+      Some(codec) => {
+        let mut bytes: Vec<u8> = vec![0u8; column.column_size as usize];
+        file.read_exact(&mut bytes)?;
+        custom_columns.push(CustomColumn {
+          name: column.name.clone(),
+          type_code: column.type_code,
+          data: codec.decode(&bytes).map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        });
+      },
+      None => skip_bytes(file, column.column_size)?,
+    }
+  }
+
+  Ok(custom_columns)
+}
+
+// Moves past the data of columns skipped as unsupported
+fn skip_bytes(file: &mut File, bytes: u64) -> io::Result<()> {
+  if bytes > 0 {
+    file.seek(SeekFrom::Current(bytes as i64))?;
+  }
+  Ok(())
+}
+
+// Zero bytes needed after end so the next segment starts on a multiple of alignment
+pub fn alignment_padding(end: u64, alignment: u64) -> u64 {
+  (alignment - end % alignment) % alignment
+}
@@ -1,13 +1,26 @@
-use std::{fs::File, io::{self, Cursor, Read, Write}};
+use std::{collections::BTreeMap, fs::File, io::{self, Cursor, Read, Write}};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use sha2::{Digest, Sha256};
 use tracing::trace;
 
-use super::types::{ColumnMeta, EnumDataComp, EnumDataEnc, EnumDataType};
+use crate::tsf::error::TsfError;
+
+use super::checksum::calculate_checksum;
+use super::metadata::{decode_metadata, encode_metadata, MAX_METADATA_SIZE};
+use super::types::{ColumnMeta, EnumChecksumAlg, EnumDataComp, EnumDataEnc, EnumDataType, UnsupportedColumn};
+
+// Stored in ts_column when the segment has no timestamp column
+pub const NO_TS_COLUMN: u16 = u16::MAX;
+
+// Most columns a segment header may declare before it is taken for corruption, a bad column_count
+// would otherwise have every one of up to 65535 column headers parsed
+pub const DEFAULT_MAX_COLUMN_COUNT: u16 = 4096;
 
 #[repr(C)]
 pub struct SegmentDataHeader {
   pub tombstone: bool,
+  pub checksum_alg: EnumChecksumAlg,
   pub next_offset: Option<u32>,
   pub uuid_txid: Option<[u8; 16]>,
   date_start: Option<i64>,
@@ -17,13 +30,25 @@ pub struct SegmentDataHeader {
   ts_column: Option<u16>,
   pub column_header_size: u32,
   pub column_headers: Vec<SegmentColumnHeader>,
+  pub metadata_size: u16,
+  metadata: BTreeMap<String, String>,
+  // Wall clock seconds since the epoch when the segment was written, 0 if it never was
+  created_at: i64,
   segment_check: Option<[u8; 8]>,
+  // Read only: columns with unknown type codes are skipped instead of failing the read
+  skip_unsupported_columns: bool,
+  unsupported_columns: Vec<UnsupportedColumn>,
+  // Read only: type codes with a registered codec, kept like unsupported columns even when not skipping
+  custom_type_codes: Vec<u16>,
+  // Read only: a larger column_count fails the read with Corruption
+  max_column_count: u16,
 }
 
 impl SegmentDataHeader {
   pub fn new() -> Self {
     SegmentDataHeader {
       tombstone: false,
+      checksum_alg: EnumChecksumAlg::default(),
       next_offset: None,
       uuid_txid: None,
       date_start: None,
@@ -33,7 +58,14 @@ impl SegmentDataHeader {
       ts_column: None,
       column_header_size: 0,
       column_headers: vec![],
+      metadata_size: 0,
+      metadata: BTreeMap::new(),
+      created_at: 0,
       segment_check: None,
+      skip_unsupported_columns: false,
+      unsupported_columns: vec![],
+      custom_type_codes: vec![],
+      max_column_count: DEFAULT_MAX_COLUMN_COUNT,
     }
   }
 
@@ -51,6 +83,19 @@ impl SegmentDataHeader {
     new_column_index
   }
 
+  pub fn describe_column(&mut self, column_name: &str, description: &str) -> Result<(), String> {
+    trace!("SegmentDataHeader::describe_column");
+
+    let column_header: &mut SegmentColumnHeader = self.column_headers.iter_mut()
+      .find(|header: &&mut SegmentColumnHeader| header.column_name == column_name)
+      .ok_or_else(|| format!("Column {} not found", column_name))?;
+    column_header.set_description(description)?;
+    self.column_header_size = self.column_headers.iter()
+      .map(|header| header.byte_size())
+      .sum();
+    Ok(())
+  }
+
   pub fn set_ts_column(&mut self, ts_column_index: u16) -> Result<(), String> {
     trace!("SegmentDataHeader::set_ts_column");
 
@@ -66,6 +111,58 @@ impl SegmentDataHeader {
     Ok(())
   }
 
+  pub fn get_ts_column(&self) -> Option<u16> {
+    self.ts_column
+  }
+
+  pub fn set_skip_unsupported_columns(&mut self, skip_unsupported_columns: bool) {
+    self.skip_unsupported_columns = skip_unsupported_columns;
+  }
+
+  pub fn set_max_column_count(&mut self, max_column_count: u16) {
+    self.max_column_count = max_column_count;
+  }
+
+  pub fn set_custom_type_codes(&mut self, custom_type_codes: Vec<u16>) {
+    self.custom_type_codes = custom_type_codes;
+  }
+
+  pub fn get_unsupported_columns(&self) -> &[UnsupportedColumn] {
+    &self.unsupported_columns
+  }
+
+  // Bytes of skipped columns stored right before column_headers[index], or after the last column
+  // when index is column_headers.len()
+  pub fn skipped_bytes_before(&self, index: usize) -> u64 {
+    self.skipped_before(index)
+      .map(|column: &UnsupportedColumn| column.column_size)
+      .sum()
+  }
+
+  // Skipped columns stored right before column_headers[index], in file order
+  pub fn skipped_before(&self, index: usize) -> impl Iterator<Item = &UnsupportedColumn> {
+    self.unsupported_columns.iter()
+      .enumerate()
+      .filter(move |(rank, column): &(usize, &UnsupportedColumn)| column.position - rank == index)
+      .map(|(_, column): (usize, &UnsupportedColumn)| column)
+  }
+
+  // Shifts ts_column and column_count from on-disk positions to indexes into column_headers
+  fn drop_unsupported_columns(&mut self) -> io::Result<()> {
+    if let Some(ts_column) = self.ts_column {
+      if self.unsupported_columns.iter().any(|column: &UnsupportedColumn| column.position == ts_column as usize) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Timestamp column has an unsupported type"));
+      }
+      let skipped: usize = self.unsupported_columns.iter()
+        .filter(|column: &&UnsupportedColumn| column.position < ts_column as usize)
+        .count();
+      self.ts_column = Some(ts_column - skipped as u16);
+    }
+    self.column_count = self.column_headers.len() as u16;
+
+    Ok(())
+  }
+
   pub fn set_date_start(&mut self, date_start: i64) {
     self.date_start = Some(date_start);
   }
@@ -74,30 +171,88 @@ impl SegmentDataHeader {
     self.date_end = Some(date_end);
   }
 
+  pub fn get_date_range(&self) -> Option<(i64, i64)> {
+    self.date_start.zip(self.date_end)
+  }
+
   pub fn calculate_header_size(&self) -> u32 {
     trace!("SegmentDataHeader::calculate_header_size");
 
-    // Fixed size parts: 1 (tombstone) + 4 (next_offset) + 16 (uuid_txid) + 8 (date_start) + 8 (date_end) + 
-    // 4 (row_count) + 2 (column_count) + 2 (ts_column) + 4 (column_header_size) + 8 (segment_check)
-    let fixed_size: u32 = 1 + 4 + 16 + 8 + 8 + 4 + 2 + 2 + 4 + 8;
+    // Fixed size parts: 1 (tombstone) + 1 (checksum_alg) + 4 (next_offset) + 16 (uuid_txid) + 8 (date_start) +
+    // 8 (date_end) + 4 (row_count) + 2 (column_count) + 2 (ts_column) + 4 (column_header_size) + 2 (metadata_size) +
+    // 8 (created_at) + 8 (segment_check)
+    let fixed_size: u32 = 1 + 1 + 4 + 16 + 8 + 8 + 4 + 2 + 2 + 4 + 2 + 8 + 8;
+
+    fixed_size + self.column_header_size + self.metadata_size as u32
+  }
+
+  pub fn set_checksum_alg(&mut self, checksum_alg: EnumChecksumAlg) {
+    self.checksum_alg = checksum_alg;
+  }
+
+  pub fn set_metadata(&mut self, metadata: BTreeMap<String, String>) -> Result<(), String> {
+    trace!("SegmentDataHeader::set_metadata");
+
+    self.metadata_size = encode_metadata(&metadata)?.len() as u16;
+    self.metadata = metadata;
+    Ok(())
+  }
+
+  pub fn get_metadata(&self) -> &BTreeMap<String, String> {
+    &self.metadata
+  }
+
+  pub fn set_created_at(&mut self, created_at: i64) {
+    self.created_at = created_at;
+  }
 
-    fixed_size + self.column_header_size
+  pub fn get_created_at(&self) -> i64 {
+    self.created_at
   }
 
-  fn calculate_checksum(&self) -> [u8; 8] {
-    // @TODO xxhash64
-    let dummy_checksum: [u8; 8] = [0xBB; 8]; // Placeholder checksum value
-    dummy_checksum
+  // Feeds the logical header fields into hasher. Left out on purpose: tombstone, checksum_alg,
+  // next_offset, uuid_txid, created_at, segment_check, and per column encoding, compression, size and
+  // column_check, they change with the transaction or the storage layout but not with the data
+  pub fn update_content_digest(&self, hasher: &mut Sha256) {
+    trace!("SegmentDataHeader::update_content_digest");
+
+    hasher.update(self.date_start.unwrap_or_default().to_le_bytes());
+    hasher.update(self.date_end.unwrap_or_default().to_le_bytes());
+    hasher.update(self.row_count.to_le_bytes());
+    hasher.update(self.column_count.to_le_bytes());
+    hasher.update(self.ts_column.unwrap_or(NO_TS_COLUMN).to_le_bytes());
+    for column_header in &self.column_headers {
+      Self::update_digest_bytes(hasher, column_header.column_name.as_bytes());
+      hasher.update(column_header.type_code().to_le_bytes());
+      Self::update_digest_bytes(hasher, &column_header.column_meta.to_bytes_with_description(column_header.get_description()));
+    }
+    hasher.update((self.metadata.len() as u32).to_le_bytes());
+    for (key, value) in &self.metadata {
+      Self::update_digest_bytes(hasher, key.as_bytes());
+      Self::update_digest_bytes(hasher, value.as_bytes());
+    }
   }
 
-  fn update_segment_check(&mut self) {
-    // @TODO update segment_check
-    self.segment_check = Some(self.calculate_checksum());
+  // Length prefixed so neighbouring fields can't run into each other
+  fn update_digest_bytes(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update((bytes.len() as u64).to_le_bytes());
+    hasher.update(bytes);
   }
 
-  fn verify_segment_check(&self) -> bool {
-    // @TODO add checker
-    return true;
+  // Checksum covers every serialized header byte before the segment_check itself
+  fn calculate_checksum(&self, header_bytes: &[u8]) -> [u8; 8] {
+    calculate_checksum(self.checksum_alg, header_bytes)
+  }
+
+  fn update_segment_check(&mut self, header_bytes: &[u8]) {
+    self.segment_check = Some(self.calculate_checksum(header_bytes));
+  }
+
+  fn verify_segment_check(&self, header_bytes: &[u8]) -> bool {
+    match self.segment_check {
+      Some(segment_check) => segment_check == self.calculate_checksum(header_bytes),
+      None => false,
+    }
   }
 
   pub fn write_header(&mut self, file: &mut File) -> io::Result<()> {
@@ -106,6 +261,7 @@ impl SegmentDataHeader {
     let mut buffer: Vec<u8> = Vec::new();
 
     buffer.push(self.tombstone as u8);
+    buffer.push(self.checksum_alg as u8);
 
     match self.next_offset {
       Some(next_offset) => buffer.write_u32::<LittleEndian>(next_offset)?,
@@ -130,10 +286,7 @@ impl SegmentDataHeader {
     buffer.write_u32::<LittleEndian>(self.row_count)?;
     buffer.write_u16::<LittleEndian>(self.column_count)?;
 
-    match self.ts_column {
-      Some(ts_column) => buffer.write_u16::<LittleEndian>(ts_column)?,
-      None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "ts_column was not set")),
-    }
+    buffer.write_u16::<LittleEndian>(self.ts_column.unwrap_or(NO_TS_COLUMN))?;
 
     // Serialize and write each column header, keeping track of the total size
     let mut column_headers_buffer: Vec<u8> = Vec::new();
@@ -149,7 +302,15 @@ impl SegmentDataHeader {
     // Append the serialized column headers
     buffer.extend_from_slice(&column_headers_buffer);
 
-    self.update_segment_check();
+    // Metadata follows the column headers so the segment check covers it
+    let metadata_buffer: Vec<u8> = encode_metadata(&self.metadata)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    self.metadata_size = metadata_buffer.len() as u16;
+    buffer.extend_from_slice(&self.metadata_size.to_le_bytes());
+    buffer.extend_from_slice(&metadata_buffer);
+    buffer.extend_from_slice(&self.created_at.to_le_bytes());
+
+    self.update_segment_check(&buffer);
 
     // Writes the segment check
     match self.segment_check {
@@ -164,12 +325,23 @@ impl SegmentDataHeader {
   }
 
   pub fn read_segment_header(&mut self, file: &mut File) -> io::Result<()> {
-    let mut header_buffer: Vec<u8> = vec![0; 49]; // Assuming 49 is the fixed size of the header part
+    self.read_segment_header_checked(file, true)
+  }
+
+  // Parses the header without checking segment_check, only for tools that are about to rewrite it
+  pub fn read_unverified_segment_header(&mut self, file: &mut File) -> io::Result<()> {
+    self.read_segment_header_checked(file, false)
+  }
+
+  fn read_segment_header_checked(&mut self, file: &mut File, verify: bool) -> io::Result<()> {
+    let mut header_buffer: Vec<u8> = vec![0; 50]; // Assuming 50 is the fixed size of the header part
     file.read_exact(&mut header_buffer)?;
 
     let mut cursor: Cursor<Vec<u8>> = Cursor::new(header_buffer);
 
     self.tombstone = cursor.read_u8()? != 0;
+    self.checksum_alg = EnumChecksumAlg::from_u8(cursor.read_u8()?)
+      .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid checksum algorithm"))?;
     self.next_offset = Some(cursor.read_u32::<LittleEndian>()?);
     let mut uuid_txid_arr: [u8; 16] = [0; 16];
     cursor.read_exact(&mut uuid_txid_arr)?;
@@ -178,29 +350,83 @@ impl SegmentDataHeader {
     self.date_end = Some(cursor.read_i64::<LittleEndian>()?);
     self.row_count = cursor.read_u32::<LittleEndian>()?;
     self.column_count = cursor.read_u16::<LittleEndian>()?;
-    self.ts_column = Some(cursor.read_u16::<LittleEndian>()?);
+    self.ts_column = Some(cursor.read_u16::<LittleEndian>()?).filter(|ts_column: &u16| *ts_column != NO_TS_COLUMN);
     self.column_header_size = cursor.read_u32::<LittleEndian>()?;
 
-    // Now read the dynamic part: column headers + segment check
-    let total_size: usize = self.column_header_size as usize + 8; // +8 for segment check
-    let mut dynamic_buffer: Vec<u8> = vec![0; total_size];
+    // Checked before any column header is parsed, so a corrupt count can't drive a huge loop
+    if self.column_count > self.max_column_count {
+      return Err(TsfError::Corruption {
+        reason: format!("Segment declares {} columns, more than the limit of {}", self.column_count, self.max_column_count),
+      }.into());
+    }
+
+    // Now read the dynamic part: column headers + metadata_size
+    let dynamic_size: usize = self.column_header_size as usize + 2; // +2 for metadata_size
+    let mut dynamic_buffer: Vec<u8> = vec![0; dynamic_size];
     file.read_exact(&mut dynamic_buffer)?;
 
     let mut dynamic_cursor: Cursor<Vec<u8>> = Cursor::new(dynamic_buffer);
 
     self.column_headers.clear();
-    for _ in 0..self.column_count {
+    self.unsupported_columns.clear();
+    for position in 0..self.column_count as usize {
+      if self.skip_unsupported_columns || !self.custom_type_codes.is_empty() {
+        let header_pos: u64 = dynamic_cursor.position();
+        let unsupported: Option<UnsupportedColumn> = SegmentColumnHeader::read_unsupported_from_buffer(&mut dynamic_cursor, position)
+          .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if let Some(column) = unsupported.filter(|column: &UnsupportedColumn| {
+          self.skip_unsupported_columns || self.custom_type_codes.contains(&column.type_code)
+        }) {
+          self.unsupported_columns.push(column);
+          continue;
+        }
+        dynamic_cursor.set_position(header_pos);
+      }
+
       let column_header: SegmentColumnHeader = SegmentColumnHeader::read_from_buffer(&mut dynamic_cursor)
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
       self.column_headers.push(column_header);
     }
 
-    // Assuming segment check is the last 8 bytes
+    // The parsed headers must account for exactly the declared column_header_size
+    if dynamic_cursor.position() != self.column_header_size as u64 {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Column header size mismatch: declared {} bytes, parsed {}", self.column_header_size, dynamic_cursor.position()),
+      ));
+    }
+
+    self.metadata_size = dynamic_cursor.read_u16::<LittleEndian>()?;
+    if self.metadata_size as usize > MAX_METADATA_SIZE {
+      return Err(io::Error::new(io::ErrorKind::InvalidData, "Segment metadata too large"));
+    }
+
+    // Then the metadata, created_at and the segment check as the last 8 bytes
+    let trailer_size: usize = self.metadata_size as usize + 8 + 8;
+    let mut trailer_buffer: Vec<u8> = vec![0; trailer_size];
+    file.read_exact(&mut trailer_buffer)?;
+
     let mut segment_check_arr: [u8; 8] = [0; 8];
-    dynamic_cursor.set_position(total_size as u64 - 8);
-    dynamic_cursor.read_exact(&mut segment_check_arr)?;
+    segment_check_arr.copy_from_slice(&trailer_buffer[trailer_size - 8..]);
     self.segment_check = Some(segment_check_arr);
 
+    // Verify against the same bytes the writer checksummed
+    let mut header_bytes: Vec<u8> = cursor.into_inner();
+    header_bytes.extend_from_slice(dynamic_cursor.get_ref());
+    header_bytes.extend_from_slice(&trailer_buffer[..trailer_size - 8]);
+    if verify && !self.verify_segment_check(&header_bytes) {
+      return Err(io::Error::new(io::ErrorKind::InvalidData, "Segment check verification failed"));
+    }
+
+    let metadata_end: usize = self.metadata_size as usize;
+    self.created_at = i64::from_le_bytes(trailer_buffer[metadata_end..metadata_end + 8].try_into().unwrap_or_default());
+    self.metadata = decode_metadata(&trailer_buffer[..metadata_end])
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    if !self.unsupported_columns.is_empty() {
+      self.drop_unsupported_columns()?;
+    }
+
     Ok(())
   }
 }
@@ -211,10 +437,13 @@ pub struct SegmentColumnHeader {
   pub column_type: EnumDataType,
   column_meta_length: u16,
   column_meta: ColumnMeta,
+  column_description: Option<String>,
   pub column_enc: EnumDataEnc,
   pub column_comp: EnumDataComp,
   pub column_size: u64,
-  column_check: [u8; 8]
+  column_check: [u8; 8],
+  // Write only: stored in place of column_type for a custom column, see codec::ColumnCodec
+  type_code: Option<u16>,
 }
 
 impl SegmentColumnHeader {
@@ -233,13 +462,59 @@ impl SegmentColumnHeader {
         column_type,
         column_meta_length,
         column_meta,
+        column_description: None,
         column_enc,
         column_comp,
         column_size,
         column_check,
+        type_code: None,
     }
   }
 
+  pub fn with_meta(mut self, column_meta: ColumnMeta) -> Self {
+    trace!("SegmentColumnHeader::with_meta");
+
+    self.column_meta_length = column_meta.to_bytes_with_description(self.column_description.as_deref()).len() as u16;
+    self.column_meta = column_meta;
+    self
+  }
+
+  pub fn with_type_code(mut self, type_code: u16) -> Self {
+    self.type_code = Some(type_code);
+    self
+  }
+
+  // Type code written to the file, column_type unless the column has a custom type
+  pub fn type_code(&self) -> u16 {
+    self.type_code.unwrap_or(self.column_type as u16)
+  }
+
+  pub fn get_column_meta(&self) -> &ColumnMeta {
+    &self.column_meta
+  }
+
+  pub fn get_column_check(&self) -> [u8; 8] {
+    self.column_check
+  }
+
+  pub fn set_column_check(&mut self, column_check: [u8; 8]) {
+    self.column_check = column_check;
+  }
+
+  // Free text for readers of the file, e.g. units or the sensor a column came from
+  pub fn set_description(&mut self, description: &str) -> Result<(), String> {
+    trace!("SegmentColumnHeader::set_description");
+
+    ColumnMeta::validate_description(description)?;
+    self.column_description = Some(description.to_string());
+    self.column_meta_length = self.column_meta.to_bytes_with_description(self.column_description.as_deref()).len() as u16;
+    Ok(())
+  }
+
+  pub fn get_description(&self) -> Option<&str> {
+    self.column_description.as_deref()
+  }
+
   pub fn byte_size(&self) -> u32 {
     trace!("SegmentColumnHeader::byte_size");
     // Start with the size of fixed-length fields.
@@ -249,9 +524,7 @@ impl SegmentColumnHeader {
     size += self.column_name.len() as u32; // Length of the column_name string
     size += 2; // column_type (u16)
     size += 2; // column_meta_length (u16)
-    // Add the size of column_meta, assuming it can be determined.
-    // For simplicity, this example assumes no metadata or fixed-size metadata.
-    size += self.column_meta_length as u32;
+    size += self.column_meta_length as u32; // Serialized column_meta
     size += 1; // column_enc (u8)
     size += 1; // column_comp (u8)
     size += 8; // column_size (u64)
@@ -271,14 +544,13 @@ impl SegmentColumnHeader {
     buffer.extend_from_slice(self.column_name.as_bytes());
 
     // Write column type
-    let column_type_val: u16 = self.column_type as u16;
-    buffer.write_u16::<LittleEndian>(column_type_val)?;
+    buffer.write_u16::<LittleEndian>(self.type_code())?;
 
     // Write column meta length
     buffer.write_u16::<LittleEndian>(self.column_meta_length)?;
 
-    // Assuming column_meta is serialized here. For simplicity, skipping actual serialization
-    // You might need to serialize `column_meta` based on its type and content
+    // Write column meta
+    buffer.extend_from_slice(&self.column_meta.to_bytes_with_description(self.column_description.as_deref()));
 
     // Write column_enc and column_comp
     let column_enc_val: u8 = self.column_enc as u8;
@@ -296,6 +568,38 @@ impl SegmentColumnHeader {
     Ok(buffer)
   }
 
+  // Parses a column header only as far as its layout, Some when the type code is unknown to this build
+  fn read_unsupported_from_buffer(cursor: &mut Cursor<Vec<u8>>, position: usize) -> Result<Option<UnsupportedColumn>, String> {
+    let column_name_length: u16 = cursor.read_u16::<LittleEndian>()
+      .map_err(|_| "Failed to read column name length".to_string())?;
+    let mut column_name_bytes: Vec<u8> = vec![0; column_name_length as usize];
+    cursor.read_exact(&mut column_name_bytes)
+      .map_err(|_| "Failed to read column name".to_string())?;
+
+    let type_code: u16 = cursor.read_u16::<LittleEndian>()
+      .map_err(|_| "Failed to read column type".to_string())?;
+    if EnumDataType::from_u16(type_code).is_some() {
+      return Ok(None);
+    }
+
+    // Meta, encoding and compression may be just as new, so they are skipped unparsed
+    let column_meta_length: u16 = cursor.read_u16::<LittleEndian>()
+      .map_err(|_| "Failed to read column meta length".to_string())?;
+    cursor.set_position(cursor.position() + column_meta_length as u64 + 2);
+    let column_size: u64 = cursor.read_u64::<LittleEndian>()
+      .map_err(|_| "Failed to read column size".to_string())?;
+    let mut column_check: [u8; 8] = [0u8; 8];
+    cursor.read_exact(&mut column_check)
+      .map_err(|_| "Failed to read column check".to_string())?;
+
+    Ok(Some(UnsupportedColumn {
+      name: String::from_utf8_lossy(&column_name_bytes).into_owned(),
+      type_code,
+      position,
+      column_size,
+    }))
+  }
+
   fn read_from_buffer(cursor: &mut Cursor<Vec<u8>>) -> Result<Self, String> {
     let column_name_length: u16 = cursor.read_u16::<LittleEndian>()
       .map_err(|_| "Failed to read column name length".to_string())?;
@@ -311,7 +615,12 @@ impl SegmentColumnHeader {
     let column_meta_length: u16 = cursor.read_u16::<LittleEndian>()
       .map_err(|_| "Failed to read column meta length".to_string())?;
 
-    // Assuming meta bytes are not important for the example. If they are, read them here.
+    let mut column_meta_bytes: Vec<u8> = vec![0; column_meta_length as usize];
+    cursor.read_exact(&mut column_meta_bytes)
+      .map_err(|_| "Failed to read column meta".to_string())?;
+    // The meta is parsed from exactly the declared bytes and must use all of them, so a wrong length can't shift enc
+    let (column_meta, column_description): (ColumnMeta, Option<String>) = ColumnMeta::from_bytes_with_description(&column_meta_bytes)
+      .map_err(|e: String| format!("Column {}: {} ({} meta bytes declared)", column_name, e, column_meta_length))?;
 
     let column_enc: u8 = cursor.read_u8().map_err(|_| "Failed to read column encoding".to_string())?;
     let column_comp: u8 = cursor.read_u8().map_err(|_| "Failed to read column compression".to_string())?;
@@ -325,11 +634,13 @@ impl SegmentColumnHeader {
       column_name,
       column_type: EnumDataType::from_u16(column_type).ok_or_else(|| "Invalid column type".to_string())?,
       column_meta_length,
-      column_meta: ColumnMeta::default(), // Assuming default meta for simplicity
+      column_meta,
+      column_description,
       column_enc: EnumDataEnc::from_u8(column_enc).ok_or_else(|| "Invalid encoding type".to_string())?,
       column_comp: EnumDataComp::from_u8(column_comp).ok_or_else(|| "Invalid compression type".to_string())?,
       column_size,
       column_check,
+      type_code: None,
     })
   }
 }
@@ -337,7 +648,7 @@ impl SegmentColumnHeader {
 #[cfg(test)]
 mod tests {
   use super::*;
-  use std::io::{Read, Seek, SeekFrom};
+  use std::io::{Read, Seek, SeekFrom, Write};
   use tempfile::tempfile;
 
   #[test]
@@ -345,6 +656,7 @@ mod tests {
     // Setup: Create a SegmentDataHeader with test data
     let mut header: SegmentDataHeader = SegmentDataHeader {
       tombstone: true,
+      checksum_alg: EnumChecksumAlg::XxHash64,
       next_offset: Some(123),
       uuid_txid: Some([0xAA; 16]),
       date_start: Some(1625097600),
@@ -354,7 +666,14 @@ mod tests {
       ts_column: Some(3),
       column_header_size: 0, // This gets overwritten
       column_headers: vec![],
+      metadata_size: 0,
+      metadata: BTreeMap::new(),
+      created_at: 1700000000,
       segment_check: Some([0xBB; 8]), // This gets overwritten
+      skip_unsupported_columns: false,
+      unsupported_columns: vec![],
+      custom_type_codes: vec![],
+      max_column_count: DEFAULT_MAX_COLUMN_COUNT,
     };
 
     // Write the header to a temporary file
@@ -367,6 +686,7 @@ mod tests {
     // Read back the written data
     let mut read_tombstone: [u8; 1] = [0u8; 1];
     file.read_exact(&mut read_tombstone)?;
+    let read_checksum_alg: u8 = file.read_u8()?;
     let read_next_offset: u32 = file.read_u32::<LittleEndian>()?;
     let mut read_uuid_txid: [u8; 16] = [0u8; 16];
     file.read_exact(&mut read_uuid_txid)?;
@@ -377,11 +697,14 @@ mod tests {
     let read_ts_column: u16 = file.read_u16::<LittleEndian>()?;
     let read_column_header_size: u32 = file.read_u32::<LittleEndian>()?;
     // For column_headers, you would read and deserialize them here based on read_column_count and read_column_header_size
+    let read_metadata_size: u16 = file.read_u16::<LittleEndian>()?;
+    let read_created_at: i64 = file.read_i64::<LittleEndian>()?;
     let mut read_segment_check: [u8; 8] = [0; 8];
     file.read_exact(&mut read_segment_check)?;
 
     // Verify the data read matches what was written
     assert_eq!(read_tombstone[0], 1u8);
+    assert_eq!(read_checksum_alg, EnumChecksumAlg::XxHash64 as u8);
     assert_eq!(read_next_offset, 123);
     assert_eq!(read_uuid_txid, [0xAA; 16]);
     assert_eq!(read_date_start, 1625097600);
@@ -390,7 +713,9 @@ mod tests {
     assert_eq!(read_column_count, 5);
     assert_eq!(read_ts_column, 3);
     assert_eq!(read_column_header_size, 0);
-    assert_eq!(read_segment_check, [0xBB; 8]);
+    assert_eq!(read_metadata_size, 0);
+    assert_eq!(read_created_at, 1700000000);
+    assert_eq!(Some(read_segment_check), header.segment_check);
 
     Ok(())
   }
@@ -403,10 +728,12 @@ mod tests {
       column_type: EnumDataType::Int32, // Example, ensure this matches an actual variant
       column_meta_length: 0, // Simplified for the test
       column_meta: ColumnMeta::None, // Assuming ColumnMeta::None is the default
+      column_description: None,
       column_enc: EnumDataEnc::None, // Example, ensure this matches an actual variant
       column_comp: EnumDataComp::None, // Example, ensure this matches an actual variant
       column_size: 123, // Example size
       column_check: [1, 2, 3, 4, 5, 6, 7, 8], // Example checksum
+      type_code: None,
     };
 
     let buffer: Vec<u8> = header.prepare_buffer()?;
@@ -441,20 +768,25 @@ mod tests {
       let column_count: u16 = 0;
       let ts_column: u16 = 0;
       let column_header_size: u32 = 0;
-      let segment_check: [u8; 8] = [0xBB; 8];
-
-      // Write these values to the tempfile
-      file.write_all(&[tombstone])?;
-      file.write_u32::<LittleEndian>(next_offset)?;
-      file.write_all(&uuid_txid)?;
-      file.write_u64::<LittleEndian>(date_start)?;
-      file.write_u64::<LittleEndian>(date_end)?;
-      file.write_u32::<LittleEndian>(row_count)?;
-      file.write_u16::<LittleEndian>(column_count)?;
-      file.write_u16::<LittleEndian>(ts_column)?;
-      file.write_u32::<LittleEndian>(column_header_size)?;
+
+      // Write these values to a buffer so the segment check can be computed over them
+      let mut buffer: Vec<u8> = Vec::new();
+      buffer.write_all(&[tombstone])?;
+      buffer.write_u8(EnumChecksumAlg::XxHash64 as u8)?;
+      buffer.write_u32::<LittleEndian>(next_offset)?;
+      buffer.write_all(&uuid_txid)?;
+      buffer.write_u64::<LittleEndian>(date_start)?;
+      buffer.write_u64::<LittleEndian>(date_end)?;
+      buffer.write_u32::<LittleEndian>(row_count)?;
+      buffer.write_u16::<LittleEndian>(column_count)?;
+      buffer.write_u16::<LittleEndian>(ts_column)?;
+      buffer.write_u32::<LittleEndian>(column_header_size)?;
       // Assuming column_headers would be written here
-      file.write_all(&segment_check)?;
+      buffer.write_u16::<LittleEndian>(0)?; // metadata_size
+      buffer.write_i64::<LittleEndian>(1700000000)?; // created_at
+      let segment_check: [u8; 8] = calculate_checksum(EnumChecksumAlg::XxHash64, &buffer);
+      buffer.write_all(&segment_check)?;
+      file.write_all(&buffer)?;
 
       // Reset file position to the beginning before reading
       file.seek(io::SeekFrom::Start(0))?;
@@ -462,6 +794,7 @@ mod tests {
       // Attempt to read the header back from the tempfile
       let mut header: SegmentDataHeader = SegmentDataHeader {
           tombstone: false,
+          checksum_alg: EnumChecksumAlg::Crc32c,
           next_offset: Some(0),
           uuid_txid: Some([0; 16]),
           date_start: Some(0),
@@ -471,13 +804,21 @@ mod tests {
           ts_column: Some(0),
           column_header_size: 0,
           column_headers: Vec::new(),
+          metadata_size: 0,
+          metadata: BTreeMap::new(),
+          created_at: 0,
           segment_check: Some([0; 8]),
+          skip_unsupported_columns: false,
+          unsupported_columns: vec![],
+          custom_type_codes: vec![],
+          max_column_count: DEFAULT_MAX_COLUMN_COUNT,
       };
       
       header.read_segment_header(&mut file)?;
 
       // Perform assertions
       assert_eq!(header.tombstone, true);
+      assert_eq!(header.checksum_alg, EnumChecksumAlg::XxHash64);
       assert_eq!(header.next_offset, Some(123));
       assert_eq!(header.uuid_txid, Some([0xAA; 16]));
       assert_eq!(header.date_start, Some(1625097600));
@@ -486,11 +827,148 @@ mod tests {
       assert_eq!(header.column_count, 0);
       assert_eq!(header.ts_column, Some(0));
       assert_eq!(header.column_header_size, 0); // Simplified
-      assert_eq!(header.segment_check, Some([0xBB; 8]));
+      assert_eq!(header.created_at, 1700000000);
+      assert_eq!(header.segment_check, Some(segment_check));
 
       Ok(())
   }
 
+  #[test]
+  fn test_read_header_with_inconsistent_column_header_size() -> io::Result<()> {
+    let mut file: File = tempfile()?;
+
+    // No column headers, but 4 bytes of column header region are declared
+    let mut buffer: Vec<u8> = Vec::new();
+    buffer.write_u8(0)?;
+    buffer.write_u8(EnumChecksumAlg::XxHash64 as u8)?;
+    buffer.write_u32::<LittleEndian>(0)?;
+    buffer.write_all(&[0xAA; 16])?;
+    buffer.write_i64::<LittleEndian>(1625097600)?;
+    buffer.write_i64::<LittleEndian>(1627689600)?;
+    buffer.write_u32::<LittleEndian>(0)?;
+    buffer.write_u16::<LittleEndian>(0)?;
+    buffer.write_u16::<LittleEndian>(0)?;
+    buffer.write_u32::<LittleEndian>(4)?;
+    buffer.write_all(&[0u8; 4])?;
+    // A valid checksum so only the size mismatch can fail the read
+    let segment_check: [u8; 8] = calculate_checksum(EnumChecksumAlg::XxHash64, &buffer);
+    buffer.write_all(&segment_check)?;
+    file.write_all(&buffer)?;
+    file.seek(io::SeekFrom::Start(0))?;
+
+    let mut header: SegmentDataHeader = SegmentDataHeader::new();
+    let result: io::Result<()> = header.read_segment_header(&mut file);
+
+    let err: io::Error = result.expect_err("Inconsistent column_header_size should fail");
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("Column header size mismatch"));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_read_header_with_too_many_columns() -> io::Result<()> {
+    let mut file: File = tempfile()?;
+
+    // Only the fixed part, the claimed column headers are never read
+    let mut buffer: Vec<u8> = Vec::new();
+    buffer.write_u8(0)?;
+    buffer.write_u8(EnumChecksumAlg::XxHash64 as u8)?;
+    buffer.write_u32::<LittleEndian>(0)?;
+    buffer.write_all(&[0xAA; 16])?;
+    buffer.write_i64::<LittleEndian>(1625097600)?;
+    buffer.write_i64::<LittleEndian>(1627689600)?;
+    buffer.write_u32::<LittleEndian>(1)?;
+    buffer.write_u16::<LittleEndian>(60000)?;
+    buffer.write_u16::<LittleEndian>(NO_TS_COLUMN)?;
+    buffer.write_u32::<LittleEndian>(0)?;
+    file.write_all(&buffer)?;
+    file.seek(io::SeekFrom::Start(0))?;
+
+    let mut header: SegmentDataHeader = SegmentDataHeader::new();
+    let err: io::Error = header.read_segment_header(&mut file).expect_err("60000 columns should be rejected");
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let tsf_err: Option<&TsfError> = err.get_ref().and_then(|inner| inner.downcast_ref::<TsfError>());
+    assert_eq!(tsf_err, Some(&TsfError::Corruption { reason: "Segment declares 60000 columns, more than the limit of 4096".to_string() }));
+
+    // The limit can be raised for files known to be that wide
+    file.seek(io::SeekFrom::Start(0))?;
+    header.set_max_column_count(u16::MAX);
+    let err: io::Error = header.read_segment_header(&mut file).expect_err("The file is still truncated");
+    assert!(err.get_ref().and_then(|inner| inner.downcast_ref::<TsfError>()).is_none());
+
+    Ok(())
+  }
+
+  fn write_and_read_with_checksum(checksum_alg: EnumChecksumAlg) -> io::Result<SegmentDataHeader> {
+    let mut header: SegmentDataHeader = SegmentDataHeader::new();
+    header.set_checksum_alg(checksum_alg);
+    header.add_column_header(SegmentColumnHeader::new(
+      "temperature".to_string(),
+      EnumDataType::Int8,
+      EnumDataEnc::None,
+      EnumDataComp::None,
+    ));
+    header.set_ts_column(0).map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    header.next_offset = Some(header.calculate_header_size());
+    header.uuid_txid = Some([0xAA; 16]);
+    header.set_date_start(1710555318);
+    header.set_date_end(1710555321);
+
+    let mut file: File = tempfile()?;
+    header.write_header(&mut file)?;
+    file.seek(SeekFrom::Start(0))?;
+
+    let mut read_header: SegmentDataHeader = SegmentDataHeader::new();
+    read_header.read_segment_header(&mut file)?;
+
+    assert_eq!(read_header.segment_check, header.segment_check);
+    Ok(read_header)
+  }
+
+  #[test]
+  fn test_write_and_read_xxhash64_checksum() -> io::Result<()> {
+    let header: SegmentDataHeader = write_and_read_with_checksum(EnumChecksumAlg::XxHash64)?;
+    assert_eq!(header.checksum_alg, EnumChecksumAlg::XxHash64);
+    assert_eq!(header.column_headers[0].column_name, "temperature");
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_write_and_read_crc32c_checksum() -> io::Result<()> {
+    let header: SegmentDataHeader = write_and_read_with_checksum(EnumChecksumAlg::Crc32c)?;
+    assert_eq!(header.checksum_alg, EnumChecksumAlg::Crc32c);
+    assert_eq!(header.column_headers[0].column_name, "temperature");
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_flipped_header_byte_fails_segment_check() -> io::Result<()> {
+    let mut header: SegmentDataHeader = write_and_read_with_checksum(EnumChecksumAlg::XxHash64)?;
+    let mut file: File = tempfile()?;
+    header.write_header(&mut file)?;
+    let mut bytes: Vec<u8> = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_end(&mut bytes)?;
+
+    // date_start, a byte of the column name, and the stored segment_check itself
+    let name_offset: usize = bytes.windows(11).position(|window: &[u8]| window == b"temperature").unwrap();
+    for offset in [22, name_offset + 3, bytes.len() - 1] {
+      let mut tampered: Vec<u8> = bytes.clone();
+      tampered[offset] ^= 0x01;
+      let mut file: File = tempfile()?;
+      file.write_all(&tampered)?;
+      file.seek(SeekFrom::Start(0))?;
+
+      let err: io::Error = SegmentDataHeader::new().read_segment_header(&mut file).expect_err("Tampered header was accepted");
+      assert_eq!(err.kind(), io::ErrorKind::InvalidData, "Flipping byte {} gave {:?}", offset, err);
+    }
+
+    Ok(())
+  }
+
   #[test]
   fn test_segment_column_header_read_from_buffer() -> io::Result<()> {
       // Prepare a buffer to simulate serialized SegmentColumnHeader data
@@ -535,4 +1013,43 @@ mod tests {
 
       Ok(())
   }
+
+  #[test]
+  fn test_column_meta_length_is_consumed_exactly() -> io::Result<()> {
+    let meta: ColumnMeta = ColumnMeta::Decimal { precision: 10, scale: 2 };
+    let meta_bytes: Vec<u8> = meta.to_bytes();
+    let column_header = |meta_bytes: &[u8]| -> io::Result<Vec<u8>> {
+      let mut buffer: Vec<u8> = vec![];
+      buffer.write_u16::<LittleEndian>(5)?;
+      buffer.extend_from_slice(b"price");
+      buffer.write_u16::<LittleEndian>(EnumDataType::Int64 as u16)?;
+      buffer.write_u16::<LittleEndian>(meta_bytes.len() as u16)?;
+      buffer.extend_from_slice(meta_bytes);
+      buffer.push(EnumDataEnc::Delta as u8);
+      buffer.push(EnumDataComp::ZStd as u8);
+      buffer.write_u64::<LittleEndian>(64)?;
+      buffer.extend_from_slice(&[0u8; 8]);
+      Ok(buffer)
+    };
+
+    let mut cursor: Cursor<Vec<u8>> = Cursor::new(column_header(&meta_bytes)?);
+    let header: SegmentColumnHeader = SegmentColumnHeader::read_from_buffer(&mut cursor)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    assert_eq!(header.column_meta, meta);
+    assert_eq!(header.column_enc, EnumDataEnc::Delta);
+    assert_eq!(header.column_comp, EnumDataComp::ZStd);
+    assert_eq!(header.column_size, 64);
+    assert_eq!(cursor.position(), cursor.get_ref().len() as u64);
+
+    // A declared length that covers more than the meta is rejected rather than read as enc
+    let mut padded_meta: Vec<u8> = meta_bytes.clone();
+    padded_meta.push(EnumDataEnc::Delta as u8);
+    let mut cursor: Cursor<Vec<u8>> = Cursor::new(column_header(&padded_meta)?);
+    assert_eq!(
+      SegmentColumnHeader::read_from_buffer(&mut cursor).err(),
+      Some("Column price: Column meta has trailing bytes (10 meta bytes declared)".to_string())
+    );
+
+    Ok(())
+  }
 }
@@ -1,4 +1,8 @@
-use std::fmt;
+use std::{cmp::Ordering, collections::HashSet, fmt, io};
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+use crate::tsf::error::TsfError;
 
 #[repr(u16)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -32,61 +36,532 @@ pub enum EnumDataType {
   // etc...
 }
 
-impl EnumDataType {
-  pub fn from_u16(value: u16) -> Option<Self> {
+// Fixed width little endian encoding of a single column value
+trait LeBytes: Sized {
+  const SIZE: usize;
+  fn write_le(&self, buffer: &mut Vec<u8>);
+  fn read_le(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_le_bytes {
+  ($($elem:ty),*) => {
+    $(
+      impl LeBytes for $elem {
+        const SIZE: usize = std::mem::size_of::<$elem>();
+
+        fn write_le(&self, buffer: &mut Vec<u8>) {
+          buffer.extend_from_slice(&self.to_le_bytes());
+        }
+
+        fn read_le(bytes: &[u8]) -> Self {
+          let mut raw: [u8; std::mem::size_of::<$elem>()] = [0u8; std::mem::size_of::<$elem>()];
+          raw.copy_from_slice(bytes);
+          <$elem>::from_le_bytes(raw)
+        }
+      }
+    )*
+  };
+}
+
+impl_le_bytes!(i8, i16, i32, i64, u8, u16, u32, u64, f32, f64);
+
+fn distinct_le_values<T: LeBytes>(values: &[T]) -> usize {
+  let mut seen: HashSet<Vec<u8>> = HashSet::new();
+  let mut bytes: Vec<u8> = Vec::with_capacity(T::SIZE);
+  for value in values {
+    bytes.clear();
+    value.write_le(&mut bytes);
+    if !seen.contains(&bytes) {
+      seen.insert(bytes.clone());
+    }
+  }
+
+  seen.len()
+}
+
+// First value followed by the wrapping difference of each value from the one before
+fn delta_encode(values: &[i64]) -> Vec<i64> {
+  let mut previous: i64 = 0;
+  values.iter().map(|value: &i64| {
+    let delta: i64 = value.wrapping_sub(previous);
+    previous = *value;
+    delta
+  }).collect()
+}
+
+fn delta_decode(deltas: &[i64]) -> Vec<i64> {
+  let mut previous: i64 = 0;
+  deltas.iter().map(|delta: &i64| {
+    previous = previous.wrapping_add(*delta);
+    previous
+  }).collect()
+}
+
+// Deltas again after the first value, so evenly spaced timestamps become runs of zeros
+fn delta_encode_tail(mut deltas: Vec<i64>) -> Vec<i64> {
+  if deltas.len() > 1 {
+    let tail: Vec<i64> = delta_encode(&deltas[1..]);
+    deltas.splice(1.., tail);
+  }
+  deltas
+}
+
+fn delta_decode_tail(mut deltas: Vec<i64>) -> Vec<i64> {
+  if deltas.len() > 1 {
+    let tail: Vec<i64> = delta_decode(&deltas[1..]);
+    deltas.splice(1.., tail);
+  }
+  deltas
+}
+
+fn write_zigzag_varint(buffer: &mut Vec<u8>, value: i64) {
+  let mut zigzag: u64 = ((value << 1) ^ (value >> 63)) as u64;
+  while zigzag >= 0x80 {
+    buffer.push((zigzag as u8) | 0x80);
+    zigzag >>= 7;
+  }
+  buffer.push(zigzag as u8);
+}
+
+fn read_zigzag_varint(bytes: &[u8], pos: &mut usize) -> io::Result<i64> {
+  let mut zigzag: u64 = 0;
+  for shift in (0..64).step_by(7) {
+    let byte: u8 = *bytes.get(*pos)
+      .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Delta column ends inside a value"))?;
+    *pos += 1;
+    zigzag |= ((byte & 0x7F) as u64) << shift;
+    if byte & 0x80 == 0 {
+      return Ok((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64));
+    }
+  }
+
+  Err(io::Error::new(io::ErrorKind::InvalidData, "Delta column value is longer than 10 bytes"))
+}
+
+// Booleans take a byte each unless the column is BitPacked, true is written as 255 and false as 0. Any non
+// zero byte reads back as true so files from writers that use 1 for true read the same
+impl LeBytes for bool {
+  const SIZE: usize = 1;
+
+  fn write_le(&self, buffer: &mut Vec<u8>) {
+    buffer.push(if *self { 255u8 } else { 0u8 });
+  }
+
+  fn read_le(bytes: &[u8]) -> Self {
+    bytes[0] != 0u8
+  }
+}
+
+// How an element type takes part in sorting, the delta encodings and casts. The defaults fit floats
+trait ColumnValues: Sized {
+  // A column of an unordered type never counts as sorted
+  const ORDERED: bool = true;
+
+  // Integer and timestamp values widened to i64 for the delta encodings, u64 wraps and comes back unchanged.
+  // None for floats and booleans
+  fn to_i64_vec(_: &[Self]) -> Option<Vec<i64>> {
+    None
+  }
+
+  // Reverse of to_i64_vec, every value came from the column's own type so the casts are exact
+  fn from_i64_vec(_: &[i64]) -> Option<Vec<Self>> {
+    None
+  }
+
+  fn to_i128_vec(_: &[Self]) -> Option<Vec<i128>> {
+    None
+  }
+}
+
+macro_rules! impl_integer_values {
+  ($($int:ty),*) => {
+    $(
+      impl ColumnValues for $int {
+        fn to_i64_vec(values: &[Self]) -> Option<Vec<i64>> {
+          Some(values.iter().map(|value: &$int| *value as i64).collect())
+        }
+
+        fn from_i64_vec(values: &[i64]) -> Option<Vec<Self>> {
+          Some(values.iter().map(|value: &i64| *value as $int).collect())
+        }
+
+        fn to_i128_vec(values: &[Self]) -> Option<Vec<i128>> {
+          Some(values.iter().map(|value: &$int| *value as i128).collect())
+        }
+      }
+    )*
+  };
+}
+
+impl_integer_values!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+impl ColumnValues for f32 {}
+
+impl ColumnValues for f64 {}
+
+impl ColumnValues for bool {
+  const ORDERED: bool = false;
+}
+
+// Order columns are sorted by. Floats use total_cmp so a NaN sorts after every number instead of
+// leaving the order of the whole column unspecified
+trait TotalOrder {
+  fn total_order(&self, other: &Self) -> Ordering;
+}
+
+macro_rules! impl_total_order {
+  ($($elem:ty => $cmp:ident),*) => {
+    $(
+      impl TotalOrder for $elem {
+        fn total_order(&self, other: &Self) -> Ordering {
+          self.$cmp(other)
+        }
+      }
+    )*
+  };
+}
+
+impl_total_order!(
+  i8 => cmp, i16 => cmp, i32 => cmp, i64 => cmp, u8 => cmp, u16 => cmp, u32 => cmp, u64 => cmp,
+  f32 => total_cmp, f64 => total_cmp, bool => cmp
+);
+
+// Generates EnumColumnData, EnumDataValue, every conversion between them and EnumDataType and the
+// ColumnSlice impls from a single table, so adding a type is one new row (plus its EnumDataType
+// discriminant). The matches are exhaustive, a data type missing from the table fails to compile.
+// Timestamp rows share their element type with an integer row, which lists their column after | so
+// its ColumnSlice borrows them too.
+macro_rules! impl_column_types {
+  (
+    $($data_type:ident => $column:ident($elem:ty), $value:ident $(| $shared:ident)*;)*
+    @timestamps
+    $($ts_data_type:ident => $ts_column:ident($ts_elem:ty), $ts_value:ident;)*
+  ) => {
+    impl_column_types!(@types $($data_type => $column($elem), $value;)* $($ts_data_type => $ts_column($ts_elem), $ts_value;)*);
+
+    $(
+      impl ColumnSlice for $elem {
+        fn column_slice(data: &EnumColumnData) -> Option<&[Self]> {
+          match data {
+            EnumColumnData::$column(values) $(| EnumColumnData::$shared(values))* => Some(values),
+            _ => None,
+          }
+        }
+      }
+    )*
+  };
+  (@types $($data_type:ident => $column:ident($elem:ty), $value:ident;)*) => {
+    #[derive(Debug, Clone)]
+    pub enum EnumColumnData {
+      $($column(Vec<$elem>),)*
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum EnumDataValue {
+      $($value($elem),)*
+    }
+
+    impl EnumDataType {
+      pub const ALL: &'static [EnumDataType] = &[$(EnumDataType::$data_type),*];
+
+      pub fn from_u16(value: u16) -> Option<Self> {
+        Self::ALL.iter().copied().find(|data_type: &EnumDataType| *data_type as u16 == value)
+      }
+
+      // Bytes one value takes in an unencoded, uncompressed column
+      pub fn value_size(&self) -> usize {
+        match self {
+          $(EnumDataType::$data_type => <$elem as LeBytes>::SIZE,)*
+        }
+      }
+
+      // The value at index of an unencoded, uncompressed column's bytes, None past the end
+      pub fn value_from_le_bytes(&self, bytes: &[u8], index: usize) -> Option<EnumDataValue> {
+        let start: usize = index.checked_mul(self.value_size())?;
+        let value_bytes: &[u8] = bytes.get(start..start.checked_add(self.value_size())?)?;
+        match self {
+          $(EnumDataType::$data_type => Some(EnumDataValue::$value(<$elem as LeBytes>::read_le(value_bytes))),)*
+        }
+      }
+    }
+
+    impl EnumDataValue {
+      pub fn from_data_type(data_type: EnumDataType) -> Self {
+        match data_type {
+          $(EnumDataType::$data_type => EnumDataValue::$value(<$elem>::default()),)*
+        }
+      }
+    }
+
+    impl EnumColumnData {
+      pub fn from_enum_data_type(data_type: EnumDataType) -> EnumColumnData {
+        match data_type {
+          $(EnumDataType::$data_type => EnumColumnData::$column(Vec::new()),)*
+        }
+      }
+
+      pub fn len(&self) -> usize {
+        match self {
+          $(EnumColumnData::$column(values) => values.len(),)*
+        }
+      }
+
+      pub fn data_type(&self) -> EnumDataType {
+        match self {
+          $(EnumColumnData::$column(_) => EnumDataType::$data_type,)*
+        }
+      }
+
+      // Exact number of distinct values, compared by their stored bytes so 0.0 and -0.0 count as two
+      pub fn distinct_count(&self) -> usize {
+        match self {
+          $(EnumColumnData::$column(values) => distinct_le_values(values),)*
+        }
+      }
+
+      // The value at index wrapped in the matching EnumDataValue variant
+      pub fn value_at(&self, index: usize) -> Option<EnumDataValue> {
+        match self {
+          $(EnumColumnData::$column(values) => values.get(index).map(|value: &$elem| EnumDataValue::$value(*value)),)*
+        }
+      }
+
+      // Appends value when it has the column's type, otherwise leaves the column untouched and returns false
+      pub fn push_value(&mut self, value: &EnumDataValue) -> bool {
+        match (self, value) {
+          $((EnumColumnData::$column(values), EnumDataValue::$value(value)) => {
+            values.push(*value);
+            true
+          },)*
+          _ => false,
+        }
+      }
+
+      // Values written back to back in little endian, the on disk layout of an unencoded column
+      pub fn to_le_bytes(&self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+        match self {
+          $(EnumColumnData::$column(values) => {
+            buffer.reserve(values.len() * <$elem as LeBytes>::SIZE);
+            for value in values {
+              value.write_le(&mut buffer);
+            }
+          },)*
+        }
+        buffer
+      }
+
+      // Replaces the values with the ones decoded from bytes, a trailing partial value is ignored
+      pub fn read_le_bytes(&mut self, bytes: &[u8]) {
+        match self {
+          $(EnumColumnData::$column(values) => {
+            *values = bytes.chunks_exact(<$elem as LeBytes>::SIZE).map(<$elem as LeBytes>::read_le).collect();
+          },)*
+        }
+      }
+
+      fn integer_values(&self) -> Option<Vec<i64>> {
+        match self {
+          $(EnumColumnData::$column(values) => <$elem as ColumnValues>::to_i64_vec(values),)*
+        }
+      }
+
+      // A float or boolean column is left as it is
+      fn set_integer_values(&mut self, values: &[i64]) {
+        match self {
+          $(EnumColumnData::$column(column) => {
+            if let Some(decoded) = <$elem as ColumnValues>::from_i64_vec(values) {
+              *column = decoded;
+            }
+          },)*
+        }
+      }
+
+      fn to_i128_vec(&self) -> Option<Vec<i128>> {
+        match self {
+          $(EnumColumnData::$column(values) => <$elem as ColumnValues>::to_i128_vec(values),)*
+        }
+      }
+
+      // Non decreasing order, booleans are not orderable and always return false
+      pub fn is_sorted_ascending(&self) -> bool {
+        match self {
+          $(EnumColumnData::$column(values) => <$elem as ColumnValues>::ORDERED && Self::is_sorted(values),)*
+        }
+      }
+
+      // Row order that sorts the column ascending, equal values keep their original order
+      pub fn sorted_indices(&self) -> Vec<usize> {
+        match self {
+          $(EnumColumnData::$column(values) => Self::argsort(values),)*
+        }
+      }
+
+      // New column holding the rows at the given indices, in that order
+      pub fn take(&self, indices: &[usize]) -> EnumColumnData {
+        match self {
+          $(EnumColumnData::$column(values) => EnumColumnData::$column(Self::take_values(values, indices)),)*
+        }
+      }
+    }
+  };
+}
+
+impl_column_types! {
+  Int8 => Int8Vec(i8), Int8Value;
+  Int16 => Int16Vec(i16), Int16Value;
+  Int32 => Int32Vec(i32), Int32Value | DateTime32Vec;
+  Int64 => Int64Vec(i64), Int64Value | DateTime64Vec;
+  UInt8 => UInt8Vec(u8), UInt8Value;
+  UInt16 => UInt16Vec(u16), UInt16Value;
+  UInt32 => UInt32Vec(u32), UInt32Value;
+  UInt64 => UInt64Vec(u64), UInt64Value;
+  Float32 => Float32Vec(f32), Float32Value;
+  Float64 => Float64Vec(f64), Float64Value;
+  Boolean => BooleanVec(bool), BooleanValue;
+  @timestamps
+  DateTime32 => DateTime32Vec(i32), DateTime32Value;
+  DateTime64 => DateTime64Vec(i64), DateTime64Value;
+}
+
+// Unit of a timestamp column's epoch values
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnumTimeResolution {
+  Seconds = 0,
+  Millis = 1,
+  Micros = 2,
+  Nanos = 3,
+}
+
+impl EnumTimeResolution {
+  pub fn from_u8(value: u8) -> Option<Self> {
     match value {
-      1 => Some(EnumDataType::Int8),
-      2 => Some(EnumDataType::Int16),
-      3 => Some(EnumDataType::Int32),
-      4 => Some(EnumDataType::Int64),
-      6 => Some(EnumDataType::UInt8),
-      7 => Some(EnumDataType::UInt16),
-      8 => Some(EnumDataType::UInt32),
-      9 => Some(EnumDataType::UInt64),
-      11 => Some(EnumDataType::Float32),
-      12 => Some(EnumDataType::Float64),
-      13 => Some(EnumDataType::Boolean),
-      16 => Some(EnumDataType::DateTime32),
-      17 => Some(EnumDataType::DateTime64),
+      0 => Some(EnumTimeResolution::Seconds),
+      1 => Some(EnumTimeResolution::Millis),
+      2 => Some(EnumTimeResolution::Micros),
+      3 => Some(EnumTimeResolution::Nanos),
+      _ => None,
+    }
+  }
+
+  // Resolution a column has without DateTime meta: DateTime32 (and Int32) timestamps are seconds,
+  // DateTime64 (and Int64) are milliseconds
+  pub fn implied_by(data_type: EnumDataType) -> Option<Self> {
+    match data_type {
+      EnumDataType::DateTime32 | EnumDataType::Int32 => Some(EnumTimeResolution::Seconds),
+      EnumDataType::DateTime64 | EnumDataType::Int64 => Some(EnumTimeResolution::Millis),
       _ => None,
     }
   }
+
+  pub fn to_utc(self, value: i64) -> Option<DateTime<Utc>> {
+    match self {
+      EnumTimeResolution::Seconds => Utc.timestamp_opt(value, 0).single(),
+      EnumTimeResolution::Millis => Utc.timestamp_millis_opt(value).single(),
+      EnumTimeResolution::Micros => Utc.timestamp_micros(value).single(),
+      EnumTimeResolution::Nanos => Some(Utc.timestamp_nanos(value)),
+    }
+  }
+
+  // Length of duration counted in this unit, None when it doesn't fit an i64
+  pub fn units_in(self, duration: Duration) -> Option<i64> {
+    match self {
+      EnumTimeResolution::Seconds => Some(duration.num_seconds()),
+      EnumTimeResolution::Millis => Some(duration.num_milliseconds()),
+      EnumTimeResolution::Micros => duration.num_microseconds(),
+      EnumTimeResolution::Nanos => duration.num_nanoseconds(),
+    }
+  }
 }
 
-#[derive(Debug, Clone)]
-pub enum EnumDataValue {
-    Int8Value(i8),
-    Int16Value(i16),
-    Int32Value(i32),
-    Int64Value(i64),
-    UInt8Value(u8),
-    UInt16Value(u16),
-    UInt32Value(u32),
-    UInt64Value(u64),
-    Float32Value(f32),
-    Float64Value(f64),
-    BooleanValue(bool),
-    DateTime32Value(i32),
-    DateTime64Value(i64),
+pub fn epoch_to_utc(value: i64, data_type: EnumDataType) -> Option<DateTime<Utc>> {
+  EnumTimeResolution::implied_by(data_type)?.to_utc(value)
+}
+
+impl EnumDataType {
+  // Whether values of both types can be compared, integers and timestamps share one ordering
+  pub fn is_comparable_with(&self, other: EnumDataType) -> bool {
+    self.comparison_kind() == other.comparison_kind()
+  }
+
+  fn comparison_kind(&self) -> u8 {
+    match self {
+      EnumDataType::Float32 | EnumDataType::Float64 => 1,
+      EnumDataType::Boolean => 2,
+      _ => 0,
+    }
+  }
 }
 
 impl EnumDataValue {
-  pub fn from_data_type(data_type: EnumDataType) -> Self {
-    match data_type {
-      EnumDataType::Int8 => EnumDataValue::Int8Value(0),
-      EnumDataType::Int16 => EnumDataValue::Int16Value(0),
-      EnumDataType::Int32 => EnumDataValue::Int32Value(0),
-      EnumDataType::Int64 => EnumDataValue::Int64Value(0),
-      EnumDataType::UInt8 => EnumDataValue::UInt8Value(0),
-      EnumDataType::UInt16 => EnumDataValue::UInt16Value(0),
-      EnumDataType::UInt32 => EnumDataValue::UInt32Value(0),
-      EnumDataType::UInt64 => EnumDataValue::UInt64Value(0),
-      EnumDataType::Float32 => EnumDataValue::Float32Value(0.0),
-      EnumDataType::Float64 => EnumDataValue::Float64Value(0.0),
-      EnumDataType::Boolean => EnumDataValue::BooleanValue(false),
-      EnumDataType::DateTime32 => EnumDataValue::Int32Value(0),
-      EnumDataType::DateTime64 => EnumDataValue::Int64Value(0),
-      _ => unimplemented!(),
+  // Integer and timestamp values widened for ordering, None for anything else
+  pub fn as_i64(&self) -> Option<i64> {
+    match self {
+      EnumDataValue::Int8Value(val) => Some(*val as i64),
+      EnumDataValue::Int16Value(val) => Some(*val as i64),
+      EnumDataValue::Int32Value(val) => Some(*val as i64),
+      EnumDataValue::Int64Value(val) => Some(*val),
+      EnumDataValue::UInt8Value(val) => Some(*val as i64),
+      EnumDataValue::UInt16Value(val) => Some(*val as i64),
+      EnumDataValue::UInt32Value(val) => Some(*val as i64),
+      EnumDataValue::UInt64Value(val) => i64::try_from(*val).ok(),
+      EnumDataValue::DateTime32Value(val) => Some(*val as i64),
+      EnumDataValue::DateTime64Value(val) => Some(*val),
+      _ => None,
+    }
+  }
+
+  // Numeric values as f64, None for booleans
+  pub fn as_f64(&self) -> Option<f64> {
+    match self {
+      EnumDataValue::Float32Value(val) => Some(*val as f64),
+      EnumDataValue::Float64Value(val) => Some(*val),
+      EnumDataValue::BooleanValue(_) => None,
+      _ => self.as_i64().map(|val: i64| val as f64),
+    }
+  }
+
+  // Integer values of a fixed point column as Float64, value / 10^scale. Other values are returned as they are
+  pub fn with_scale(self, scale: u32) -> EnumDataValue {
+    match self {
+      EnumDataValue::Int8Value(_) | EnumDataValue::Int16Value(_) | EnumDataValue::Int32Value(_) | EnumDataValue::Int64Value(_) |
+      EnumDataValue::UInt8Value(_) | EnumDataValue::UInt16Value(_) | EnumDataValue::UInt32Value(_) | EnumDataValue::UInt64Value(_) => {
+        let value: f64 = self.as_f64().unwrap_or_default();
+        EnumDataValue::Float64Value(value / 10f64.powi(scale.min(i32::MAX as u32) as i32))
+      },
+      _ => self,
+    }
+  }
+
+  // Integers and timestamps compare exactly, mixed with floats they compare as f64. Booleans only compare
+  // with booleans, None for anything else
+  pub fn compare(&self, other: &EnumDataValue) -> Option<Ordering> {
+    match (self, other) {
+      (EnumDataValue::BooleanValue(left), EnumDataValue::BooleanValue(right)) => Some(left.cmp(right)),
+      _ => match (self.as_i64(), other.as_i64()) {
+        (Some(left), Some(right)) => Some(left.cmp(&right)),
+        _ => self.as_f64()?.partial_cmp(&other.as_f64()?),
+      },
+    }
+  }
+
+  // Same variant as self holding value, integers are rounded and saturate at the type bounds
+  pub fn with_f64(&self, value: f64) -> Option<Self> {
+    match self {
+      EnumDataValue::Int8Value(_) => Some(EnumDataValue::Int8Value(value.round() as i8)),
+      EnumDataValue::Int16Value(_) => Some(EnumDataValue::Int16Value(value.round() as i16)),
+      EnumDataValue::Int32Value(_) => Some(EnumDataValue::Int32Value(value.round() as i32)),
+      EnumDataValue::Int64Value(_) => Some(EnumDataValue::Int64Value(value.round() as i64)),
+      EnumDataValue::UInt8Value(_) => Some(EnumDataValue::UInt8Value(value.round() as u8)),
+      EnumDataValue::UInt16Value(_) => Some(EnumDataValue::UInt16Value(value.round() as u16)),
+      EnumDataValue::UInt32Value(_) => Some(EnumDataValue::UInt32Value(value.round() as u32)),
+      EnumDataValue::UInt64Value(_) => Some(EnumDataValue::UInt64Value(value.round() as u64)),
+      EnumDataValue::Float32Value(_) => Some(EnumDataValue::Float32Value(value as f32)),
+      EnumDataValue::Float64Value(_) => Some(EnumDataValue::Float64Value(value)),
+      EnumDataValue::BooleanValue(_) => None,
+      EnumDataValue::DateTime32Value(_) => Some(EnumDataValue::DateTime32Value(value.round() as i32)),
+      EnumDataValue::DateTime64Value(_) => Some(EnumDataValue::DateTime64Value(value.round() as i64)),
     }
   }
 }
@@ -111,13 +586,16 @@ impl fmt::Display for EnumDataValue {
   }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ColumnMeta {
   None,
   Decimal { precision: u32, scale: u32 },
   Enum { mappings: Vec<String> },
-  DateTime { format: String },
+  // resolution overrides the one implied by the column type, None keeps it
+  DateTime { format: String, resolution: Option<EnumTimeResolution> },
   Text { encoding: String },
+  // Fixed point integers, stored as value * 10^scale. Readers set to apply it present them as floats
+  Scale { scale: u32 },
 }
 
 impl Default for ColumnMeta {
@@ -126,13 +604,199 @@ impl Default for ColumnMeta {
   }
 }
 
+// Upper bound on a column description so headers stay small
+pub const MAX_COLUMN_DESCRIPTION_SIZE: usize = 1024;
+
+// Tag of the optional description stored ahead of the meta in a column's meta bytes
+const DESCRIPTION_TAG: u8 = 5;
+
+impl ColumnMeta {
+  // Decimal places of a fixed point column, None for any other meta
+  pub fn scale(&self) -> Option<u32> {
+    match self {
+      ColumnMeta::Scale { scale } => Some(*scale),
+      _ => None,
+    }
+  }
+
+  // Serialized as a tag byte followed by the payload, None is zero bytes
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut buffer: Vec<u8> = Vec::new();
+
+    match self {
+      ColumnMeta::None => {},
+      ColumnMeta::Decimal { precision, scale } => {
+        buffer.push(1);
+        buffer.extend_from_slice(&precision.to_le_bytes());
+        buffer.extend_from_slice(&scale.to_le_bytes());
+      },
+      ColumnMeta::Enum { mappings } => {
+        buffer.push(2);
+        buffer.extend_from_slice(&(mappings.len() as u16).to_le_bytes());
+        for mapping in mappings {
+          Self::write_string(&mut buffer, mapping);
+        }
+      },
+      ColumnMeta::DateTime { format, resolution } => {
+        buffer.push(3);
+        Self::write_string(&mut buffer, format);
+        // Optional trailing byte, so meta written before resolutions existed still reads
+        if let Some(resolution) = resolution {
+          buffer.push(*resolution as u8);
+        }
+      },
+      ColumnMeta::Text { encoding } => {
+        buffer.push(4);
+        Self::write_string(&mut buffer, encoding);
+      },
+      ColumnMeta::Scale { scale } => {
+        buffer.push(6);
+        buffer.extend_from_slice(&scale.to_le_bytes());
+      },
+    }
+
+    buffer
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+    if bytes.is_empty() {
+      return Ok(ColumnMeta::None);
+    }
+
+    let mut pos: usize = 1;
+    let meta: ColumnMeta = match bytes[0] {
+      1 => {
+        let precision: u32 = Self::read_u32(bytes, &mut pos)?;
+        let scale: u32 = Self::read_u32(bytes, &mut pos)?;
+        ColumnMeta::Decimal { precision, scale }
+      },
+      2 => {
+        let count: u16 = Self::read_u16(bytes, &mut pos)?;
+        let mut mappings: Vec<String> = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+          mappings.push(Self::read_string(bytes, &mut pos)?);
+        }
+        ColumnMeta::Enum { mappings }
+      },
+      3 => {
+        let format: String = Self::read_string(bytes, &mut pos)?;
+        let resolution: Option<EnumTimeResolution> = match pos < bytes.len() {
+          true => {
+            let value: u8 = Self::take(bytes, &mut pos, 1)?[0];
+            Some(EnumTimeResolution::from_u8(value).ok_or_else(|| format!("Invalid time resolution {}", value))?)
+          },
+          false => None,
+        };
+        ColumnMeta::DateTime { format, resolution }
+      },
+      4 => ColumnMeta::Text { encoding: Self::read_string(bytes, &mut pos)? },
+      6 => ColumnMeta::Scale { scale: Self::read_u32(bytes, &mut pos)? },
+      tag => return Err(format!("Invalid column meta tag {}", tag)),
+    };
+
+    if pos != bytes.len() {
+      return Err("Column meta has trailing bytes".to_string());
+    }
+
+    Ok(meta)
+  }
+
+  // A description goes in front of the meta under its own tag, so readers that predate descriptions reject it
+  pub fn to_bytes_with_description(&self, description: Option<&str>) -> Vec<u8> {
+    let mut buffer: Vec<u8> = Vec::new();
+    if let Some(description) = description {
+      buffer.push(DESCRIPTION_TAG);
+      Self::write_string(&mut buffer, description);
+    }
+    buffer.extend_from_slice(&self.to_bytes());
+
+    buffer
+  }
+
+  pub fn from_bytes_with_description(bytes: &[u8]) -> Result<(Self, Option<String>), String> {
+    if bytes.first() != Some(&DESCRIPTION_TAG) {
+      return Ok((Self::from_bytes(bytes)?, None));
+    }
+
+    let mut pos: usize = 1;
+    let description: String = Self::read_string(bytes, &mut pos)?;
+    Self::validate_description(&description)?;
+
+    Ok((Self::from_bytes(&bytes[pos..])?, Some(description)))
+  }
+
+  pub fn validate_description(description: &str) -> Result<(), String> {
+    if description.len() > MAX_COLUMN_DESCRIPTION_SIZE {
+      return Err(format!("Column description is {} bytes, the limit is {}", description.len(), MAX_COLUMN_DESCRIPTION_SIZE));
+    }
+    Ok(())
+  }
+
+  // Resolution of a timestamp column with this meta, from the meta when set or else implied by the type
+  pub fn time_resolution(&self, data_type: EnumDataType) -> Option<EnumTimeResolution> {
+    match self {
+      ColumnMeta::DateTime { resolution: Some(resolution), .. } => Some(*resolution),
+      _ => EnumTimeResolution::implied_by(data_type),
+    }
+  }
+
+  // Displays an epoch value of a column with this meta using its format, RFC 3339 when no format is set
+  pub fn format_timestamp(&self, value: i64, data_type: EnumDataType) -> Option<String> {
+    use std::fmt::Write;
+
+    let timestamp: DateTime<Utc> = self.time_resolution(data_type)?.to_utc(value)?;
+    match self {
+      ColumnMeta::DateTime { format, .. } if !format.is_empty() => {
+        // chrono reports a bad format string as a fmt error rather than a parse error
+        let mut formatted: String = String::new();
+        write!(formatted, "{}", timestamp.format(format)).ok()?;
+        Some(formatted)
+      },
+      _ => Some(timestamp.to_rfc3339()),
+    }
+  }
+
+  fn write_string(buffer: &mut Vec<u8>, value: &str) {
+    buffer.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    buffer.extend_from_slice(value.as_bytes());
+  }
+
+  // Lengths come from the file, so they are checked against what is left before anything is sliced
+  fn take<'a>(bytes: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], String> {
+    let end: usize = pos.checked_add(len).ok_or_else(|| "Column meta is truncated".to_string())?;
+    let slice: &[u8] = bytes.get(*pos..end).ok_or_else(|| "Column meta is truncated".to_string())?;
+    *pos = end;
+    Ok(slice)
+  }
+
+  fn read_u16(bytes: &[u8], pos: &mut usize) -> Result<u16, String> {
+    let slice: &[u8] = Self::take(bytes, pos, 2)?;
+    Ok(u16::from_le_bytes([slice[0], slice[1]]))
+  }
+
+  fn read_u32(bytes: &[u8], pos: &mut usize) -> Result<u32, String> {
+    let slice: &[u8] = Self::take(bytes, pos, 4)?;
+    Ok(u32::from_le_bytes([slice[0], slice[1], slice[2], slice[3]]))
+  }
+
+  fn read_string(bytes: &[u8], pos: &mut usize) -> Result<String, String> {
+    let len: u16 = Self::read_u16(bytes, pos)?;
+    let slice: &[u8] = Self::take(bytes, pos, len as usize)?;
+    String::from_utf8(slice.to_vec()).map_err(|e: std::string::FromUtf8Error| e.to_string())
+  }
+}
+
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EnumDataEnc {
   // Types of Encoding
   None = 0,
+  // Integers and timestamps only, varint differences between consecutive values
   Delta = 1,
+  // Integers and timestamps only, varint differences between consecutive differences
   DoubleDelta = 2,
+  // Booleans only, a u32 value count then 8 values per byte, least significant bit first
+  BitPacked = 3,
 }
 
 impl EnumDataEnc {
@@ -141,9 +805,33 @@ impl EnumDataEnc {
       0 => Some(EnumDataEnc::None),
       1 => Some(EnumDataEnc::Delta),
       2 => Some(EnumDataEnc::DoubleDelta),
+      3 => Some(EnumDataEnc::BitPacked),
       _ => None,
     }
   }
+
+  // Name used on the command line
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "none" => Some(EnumDataEnc::None),
+      "delta" => Some(EnumDataEnc::Delta),
+      "double-delta" => Some(EnumDataEnc::DoubleDelta),
+      "bit-packed" => Some(EnumDataEnc::BitPacked),
+      _ => None,
+    }
+  }
+
+  // Delta encodings only make sense over integer and timestamp values
+  pub fn is_valid_for(&self, data_type: EnumDataType) -> bool {
+    match self {
+      EnumDataEnc::None => true,
+      EnumDataEnc::Delta | EnumDataEnc::DoubleDelta => matches!(data_type,
+        EnumDataType::Int8 | EnumDataType::Int16 | EnumDataType::Int32 | EnumDataType::Int64 |
+        EnumDataType::UInt8 | EnumDataType::UInt16 | EnumDataType::UInt32 | EnumDataType::UInt64 |
+        EnumDataType::DateTime32 | EnumDataType::DateTime64),
+      EnumDataEnc::BitPacked => data_type == EnumDataType::Boolean,
+    }
+  }
 }
 
 #[repr(u8)]
@@ -162,43 +850,485 @@ impl EnumDataComp {
       _ => None,
     }
   }
+
+  // Name used on the command line
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "none" => Some(EnumDataComp::None),
+      "zstd" => Some(EnumDataComp::ZStd),
+      _ => None,
+    }
+  }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EnumChecksumAlg {
+  // Types of Checksum
+  #[default]
+  XxHash64 = 0,
+  Crc32c = 1,
 }
 
-#[derive(Debug, Clone)]
-pub enum EnumColumnData {
-  Int8Vec(Vec<i8>),
-  Int16Vec(Vec<i16>),
-  Int32Vec(Vec<i32>),
-  Int64Vec(Vec<i64>),
-  UInt8Vec(Vec<u8>),
-  UInt16Vec(Vec<u16>),
-  UInt32Vec(Vec<u32>),
-  UInt64Vec(Vec<u64>),
-  Float32Vec(Vec<f32>),
-  Float64Vec(Vec<f64>),
-  BooleanVec(Vec<bool>),
-  DateTime32Vec(Vec<i32>),
-  DateTime64Vec(Vec<i64>),
-  // StringVec(Vec<String>),
+impl EnumChecksumAlg {
+  pub fn from_u8(value: u8) -> Option<Self> {
+    match value {
+      0 => Some(EnumChecksumAlg::XxHash64),
+      1 => Some(EnumChecksumAlg::Crc32c),
+      _ => None,
+    }
+  }
 }
 
 impl EnumColumnData {
-  pub fn from_enum_data_type(data_type: EnumDataType) -> EnumColumnData {
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  // Column bytes before compression. Delta and DoubleDelta store a u32 value count then zigzag varints: the
+  // first value followed by the differences (Delta) or the first difference followed by the differences
+  // between differences (DoubleDelta). Types the encoding doesn't apply to are stored as is
+  pub fn to_encoded_bytes(&self, encoding: EnumDataEnc) -> Vec<u8> {
+    if let Some(values) = self.integer_values().filter(|_| matches!(encoding, EnumDataEnc::Delta | EnumDataEnc::DoubleDelta)) {
+      let mut buffer: Vec<u8> = Vec::with_capacity(4 + values.len() * 2);
+      buffer.extend_from_slice(&(values.len() as u32).to_le_bytes());
+      let deltas: Vec<i64> = delta_encode(&values);
+      let deltas: Vec<i64> = match encoding {
+        EnumDataEnc::DoubleDelta => delta_encode_tail(deltas),
+        _ => deltas,
+      };
+      for delta in deltas {
+        write_zigzag_varint(&mut buffer, delta);
+      }
+      return buffer;
+    }
+
+    match (encoding, self) {
+      (EnumDataEnc::BitPacked, EnumColumnData::BooleanVec(values)) => {
+        let mut buffer: Vec<u8> = Vec::with_capacity(4 + values.len().div_ceil(8));
+        buffer.extend_from_slice(&(values.len() as u32).to_le_bytes());
+        for chunk in values.chunks(8) {
+          buffer.push(chunk.iter().enumerate().fold(0u8, |byte: u8, (bit, value): (usize, &bool)| byte | ((*value as u8) << bit)));
+        }
+        buffer
+      },
+      _ => self.to_le_bytes(),
+    }
+  }
+
+  // Reverse of to_encoded_bytes, so files of either boolean layout decode to the same values
+  pub fn read_encoded_bytes(&mut self, encoding: EnumDataEnc, bytes: &[u8]) -> io::Result<()> {
+    if matches!(encoding, EnumDataEnc::Delta | EnumDataEnc::DoubleDelta) && self.integer_values().is_some() {
+      let count: usize = bytes.get(..4)
+        .map(|count: &[u8]| u32::from_le_bytes(count.try_into().unwrap()) as usize)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Delta column is missing its value count"))?;
+      let mut deltas: Vec<i64> = Vec::with_capacity(count.min(bytes.len()));
+      let mut pos: usize = 4;
+      while pos < bytes.len() {
+        deltas.push(read_zigzag_varint(bytes, &mut pos)?);
+      }
+      if deltas.len() != count {
+        return Err(io::Error::new(
+          io::ErrorKind::InvalidData,
+          format!("Delta column holds {} values, its count is {}", deltas.len(), count),
+        ));
+      }
+      let deltas: Vec<i64> = match encoding {
+        EnumDataEnc::DoubleDelta => delta_decode_tail(deltas),
+        _ => deltas,
+      };
+      self.set_integer_values(&delta_decode(&deltas));
+      return Ok(());
+    }
+
+    match (encoding, self) {
+      (EnumDataEnc::BitPacked, EnumColumnData::BooleanVec(values)) => {
+        let count: usize = bytes.get(..4)
+          .map(|count: &[u8]| u32::from_le_bytes(count.try_into().unwrap()) as usize)
+          .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Packed boolean column is missing its value count"))?;
+        let packed: &[u8] = &bytes[4..];
+        if packed.len() != count.div_ceil(8) {
+          return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Packed boolean column holds {} bytes, {} values need {}", packed.len(), count, count.div_ceil(8)),
+          ));
+        }
+        *values = (0..count).map(|index: usize| packed[index / 8] & (1 << (index % 8)) != 0).collect();
+      },
+      (_, column) => column.read_le_bytes(bytes),
+    }
+
+    Ok(())
+  }
+
+  // Same order as argsort, so a column it accepts is one sorting would leave as it is
+  fn is_sorted<T: TotalOrder>(values: &[T]) -> bool {
+    values.windows(2).all(|pair: &[T]| pair[0].total_order(&pair[1]) != Ordering::Greater)
+  }
+
+  fn argsort<T: TotalOrder>(values: &[T]) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..values.len()).collect();
+    indices.sort_by(|&a, &b| values[a].total_order(&values[b]));
+    indices
+  }
+
+  fn take_values<T: Copy>(values: &[T], indices: &[usize]) -> Vec<T> {
+    indices.iter().map(|&index| values[index]).collect()
+  }
+
+  // Integer and datetime casts are checked per value, float targets only accept sources they hold exactly
+  pub fn cast_to(&self, data_type: EnumDataType) -> Result<EnumColumnData, TsfError> {
+    let from: EnumDataType = self.data_type();
+    if from == data_type {
+      return Ok(self.clone());
+    }
+
+    let invalid_cast: TsfError = TsfError::InvalidCast { from, to: data_type };
+
+    match (self, data_type) {
+      (EnumColumnData::Float32Vec(v), EnumDataType::Float64) => {
+        return Ok(EnumColumnData::Float64Vec(v.iter().map(|&value| value as f64).collect()));
+      },
+      (EnumColumnData::Float32Vec(_), _) | (EnumColumnData::Float64Vec(_), _) | (EnumColumnData::BooleanVec(_), _) => {
+        return Err(invalid_cast);
+      },
+      _ => {},
+    }
+
+    let values: Vec<i128> = self.to_i128_vec().ok_or(invalid_cast.clone())?;
+
     match data_type {
-      EnumDataType::Int8 => EnumColumnData::Int8Vec(Vec::new()),
-      EnumDataType::Int16 => EnumColumnData::Int16Vec(Vec::new()),
-      EnumDataType::Int32 => EnumColumnData::Int32Vec(Vec::new()),
-      EnumDataType::Int64 => EnumColumnData::Int64Vec(Vec::new()),
-      EnumDataType::UInt8 => EnumColumnData::UInt8Vec(Vec::new()),
-      EnumDataType::UInt16 => EnumColumnData::UInt16Vec(Vec::new()),
-      EnumDataType::UInt32 => EnumColumnData::UInt32Vec(Vec::new()),
-      EnumDataType::UInt64 => EnumColumnData::UInt64Vec(Vec::new()),
-      EnumDataType::Float32 => EnumColumnData::Float32Vec(Vec::new()),
-      EnumDataType::Float64 => EnumColumnData::Float64Vec(Vec::new()),
-      EnumDataType::Boolean => EnumColumnData::BooleanVec(Vec::new()),
-      EnumDataType::DateTime32 => EnumColumnData::DateTime32Vec(Vec::new()),
-      EnumDataType::DateTime64 => EnumColumnData::DateTime64Vec(Vec::new()),
-      // Add cases for other data types as needed...
+      EnumDataType::Int8 => Ok(EnumColumnData::Int8Vec(Self::cast_values(&values, data_type)?)),
+      EnumDataType::Int16 => Ok(EnumColumnData::Int16Vec(Self::cast_values(&values, data_type)?)),
+      EnumDataType::Int32 => Ok(EnumColumnData::Int32Vec(Self::cast_values(&values, data_type)?)),
+      EnumDataType::Int64 => Ok(EnumColumnData::Int64Vec(Self::cast_values(&values, data_type)?)),
+      EnumDataType::UInt8 => Ok(EnumColumnData::UInt8Vec(Self::cast_values(&values, data_type)?)),
+      EnumDataType::UInt16 => Ok(EnumColumnData::UInt16Vec(Self::cast_values(&values, data_type)?)),
+      EnumDataType::UInt32 => Ok(EnumColumnData::UInt32Vec(Self::cast_values(&values, data_type)?)),
+      EnumDataType::UInt64 => Ok(EnumColumnData::UInt64Vec(Self::cast_values(&values, data_type)?)),
+      EnumDataType::DateTime32 => Ok(EnumColumnData::DateTime32Vec(Self::cast_values(&values, data_type)?)),
+      EnumDataType::DateTime64 => Ok(EnumColumnData::DateTime64Vec(Self::cast_values(&values, data_type)?)),
+      // Integers up to 16 bits fit in an f32 mantissa, up to 32 bits in an f64
+      EnumDataType::Float32 => match from {
+        EnumDataType::Int8 | EnumDataType::Int16 | EnumDataType::UInt8 | EnumDataType::UInt16 => {
+          Ok(EnumColumnData::Float32Vec(values.iter().map(|&value| value as f32).collect()))
+        },
+        _ => Err(invalid_cast),
+      },
+      EnumDataType::Float64 => match from {
+        EnumDataType::Int8 | EnumDataType::Int16 | EnumDataType::Int32 | EnumDataType::UInt8 |
+        EnumDataType::UInt16 | EnumDataType::UInt32 | EnumDataType::DateTime32 => {
+          Ok(EnumColumnData::Float64Vec(values.iter().map(|&value| value as f64).collect()))
+        },
+        _ => Err(invalid_cast),
+      },
+      EnumDataType::Boolean => Err(invalid_cast),
+    }
+  }
+
+  fn cast_values<T: TryFrom<i128>>(values: &[i128], data_type: EnumDataType) -> Result<Vec<T>, TsfError> {
+    values.iter()
+      .map(|&value| T::try_from(value).map_err(|_| TsfError::CastOverflow { to: data_type, value: value.to_string() }))
+      .collect()
+  }
+}
+
+// Separator used for CSV output, column names may not contain it
+pub const CSV_DELIMITER: &str = ",";
+
+// Longest column name in bytes, the length is stored as a u16
+pub const MAX_COLUMN_NAME_SIZE: usize = u16::MAX as usize;
+
+pub fn validate_column_name(column_name: &str) -> Result<(), TsfError> {
+  if column_name.len() > MAX_COLUMN_NAME_SIZE {
+    return Err(TsfError::ColumnNameTooLong { length: column_name.len() });
+  }
+  if column_name.chars().any(char::is_control) || column_name.contains(CSV_DELIMITER) {
+    return Err(TsfError::InvalidColumnName { column: column_name.to_string() });
+  }
+  Ok(())
+}
+
+// Where a column's bytes live in the file, for tools reading TSF data directly
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnLayout {
+  pub name: String,
+  pub file_pos: u64,
+  pub column_size: u64,
+  pub column_type: EnumDataType,
+  pub encoding: EnumDataEnc,
+  pub compression: EnumDataComp,
+}
+
+impl ColumnLayout {
+  // Stored size over the raw size the type and row count imply, below 1.0 when encoding and compression pay off
+  pub fn compression_ratio(&self, row_count: usize) -> f64 {
+    let raw_size: usize = row_count * self.column_type.value_size();
+    if raw_size == 0 {
+      return 1.0;
+    }
+    self.column_size as f64 / raw_size as f64
+  }
+}
+
+// A column's name and type, enough to plan against a table without its data
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnSchema {
+  pub name: String,
+  pub data_type: EnumDataType,
+  pub description: Option<String>,
+}
+
+// Ordered columns of a table, compared column by column before files are appended to, concatenated or merged
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+  pub columns: Vec<ColumnSchema>,
+}
+
+impl From<Vec<ColumnSchema>> for Schema {
+  fn from(columns: Vec<ColumnSchema>) -> Self {
+    Schema { columns }
+  }
+}
+
+impl Schema {
+  // Same column count, and the same name and type at every position. Descriptions don't count.
+  // Reports a count mismatch or else the first column that diverges, self being the expected side
+  pub fn is_compatible_with(&self, other: &Schema) -> Result<(), SchemaMismatch> {
+    if self.columns.len() != other.columns.len() {
+      return Err(SchemaMismatch::ColumnCount { expected: self.columns.len(), found: other.columns.len() });
+    }
+
+    for (index, (expected, found)) in self.columns.iter().zip(&other.columns).enumerate() {
+      if expected.name != found.name {
+        return Err(match other.columns.iter().position(|column: &ColumnSchema| column.name == expected.name) {
+          Some(found_index) => SchemaMismatch::Reordered { index, name: expected.name.clone(), found_index },
+          None => SchemaMismatch::Name { index, expected: expected.name.clone(), found: found.name.clone() },
+        });
+      }
+      if expected.data_type != found.data_type {
+        return Err(SchemaMismatch::Type { index, name: expected.name.clone(), expected: expected.data_type, found: found.data_type });
+      }
+    }
+
+    Ok(())
+  }
+}
+
+// First difference Schema::is_compatible_with found between two schemas
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaMismatch {
+  ColumnCount { expected: usize, found: usize },
+  // The expected column is in the other schema, at found_index
+  Reordered { index: usize, name: String, found_index: usize },
+  Name { index: usize, expected: String, found: String },
+  Type { index: usize, name: String, expected: EnumDataType, found: EnumDataType },
+}
+
+impl SchemaMismatch {
+  // Column where the schemas diverge
+  pub fn index(&self) -> usize {
+    match self {
+      // Past the end of the shorter schema
+      SchemaMismatch::ColumnCount { expected, found } => *expected.min(found),
+      SchemaMismatch::Reordered { index, .. } | SchemaMismatch::Name { index, .. } | SchemaMismatch::Type { index, .. } => *index,
+    }
+  }
+}
+
+impl fmt::Display for SchemaMismatch {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SchemaMismatch::ColumnCount { expected, found } => write!(f, "Expected {} columns, found {}", expected, found),
+      SchemaMismatch::Reordered { index, name, found_index } => write!(f, "Column {} is {} but was expected at {}", found_index, name, index),
+      SchemaMismatch::Name { index, expected, found } => write!(f, "Column {} is {}, expected {}", index, found, expected),
+      SchemaMismatch::Type { index, name, expected, found } => write!(f, "Column {} ({}) is {:?}, expected {:?}", index, name, found, expected),
+    }
+  }
+}
+
+// A column skipped on read because its type code is newer than this build
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsupportedColumn {
+  pub name: String,
+  pub type_code: u16,
+  // Index among all of the segment's columns as stored on disk
+  pub position: usize,
+  pub column_size: u64,
+}
+
+// Primitive types a decoded column can be borrowed as
+pub trait ColumnSlice: Sized {
+  fn column_slice(data: &EnumColumnData) -> Option<&[Self]>;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_boolean_bytes_decode_any_non_zero_as_true() {
+    let mut column: EnumColumnData = EnumColumnData::BooleanVec(vec![]);
+    column.read_le_bytes(&[1, 255, 0, 2]);
+    assert!(matches!(&column, EnumColumnData::BooleanVec(values) if values == &[true, true, false, true]));
+
+    // Written canonically regardless of what was read
+    assert_eq!(column.to_le_bytes(), vec![255, 255, 0, 255]);
+  }
+
+  #[test]
+  fn test_corrupted_string_length_prefix_is_rejected() {
+    let meta: ColumnMeta = ColumnMeta::Enum { mappings: vec!["idle".to_string(), "running".to_string()] };
+    let mut bytes: Vec<u8> = meta.to_bytes_with_description(Some("Pump state"));
+
+    // Description length prefix claims far more than the buffer holds
+    bytes[1..3].copy_from_slice(&u16::MAX.to_le_bytes());
+    assert_eq!(ColumnMeta::from_bytes_with_description(&bytes).unwrap_err(), "Column meta is truncated");
+
+    // Second mapping's prefix runs past the end of the meta
+    let mut bytes: Vec<u8> = meta.to_bytes();
+    let second: usize = 1 + 2 + 2 + "idle".len();
+    bytes[second..second + 2].copy_from_slice(&1000u16.to_le_bytes());
+    assert_eq!(ColumnMeta::from_bytes(&bytes).unwrap_err(), "Column meta is truncated");
+  }
+
+  #[test]
+  fn test_epoch_to_utc_resolutions_match() {
+    let from_seconds: DateTime<Utc> = epoch_to_utc(1710555318, EnumDataType::DateTime32).unwrap();
+    let from_millis: DateTime<Utc> = epoch_to_utc(1710555318000, EnumDataType::DateTime64).unwrap();
+
+    assert_eq!(from_seconds, from_millis);
+    assert_eq!(from_seconds.to_rfc3339(), "2024-03-16T02:15:18+00:00");
+    assert!(epoch_to_utc(1710555318, EnumDataType::Float64).is_none());
+  }
+
+  #[test]
+  fn test_datetime_meta_resolution_is_optional() -> Result<(), String> {
+    let implied: ColumnMeta = ColumnMeta::DateTime { format: "%Y".to_string(), resolution: None };
+    let nanos: ColumnMeta = ColumnMeta::DateTime { format: "%Y".to_string(), resolution: Some(EnumTimeResolution::Nanos) };
+
+    assert_eq!(implied.to_bytes().len() + 1, nanos.to_bytes().len());
+    assert_eq!(ColumnMeta::from_bytes(&implied.to_bytes())?, implied);
+    assert_eq!(ColumnMeta::from_bytes(&nanos.to_bytes())?, nanos);
+    assert_eq!(implied.time_resolution(EnumDataType::DateTime64), Some(EnumTimeResolution::Millis));
+    assert_eq!(nanos.time_resolution(EnumDataType::DateTime64), Some(EnumTimeResolution::Nanos));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_is_sorted_ascending() {
+    assert!(EnumColumnData::Int32Vec(vec![1710555318, 1710555318, 1710555320]).is_sorted_ascending());
+    assert!(!EnumColumnData::Int32Vec(vec![1710555318, 1710555320, 1710555319]).is_sorted_ascending());
+    assert!(!EnumColumnData::BooleanVec(vec![false, true]).is_sorted_ascending());
+  }
+
+  #[test]
+  fn test_sorted_indices_put_nan_after_numbers() {
+    let column: EnumColumnData = EnumColumnData::Float64Vec(vec![2.0, f64::NAN, -1.0, f64::NAN, 0.5]);
+    assert_eq!(column.sorted_indices(), vec![2, 4, 0, 1, 3]);
+    assert!(!column.is_sorted_ascending());
+    assert!(column.take(&column.sorted_indices()).is_sorted_ascending());
+
+    assert!(EnumColumnData::Float32Vec(vec![-1.5, 3.0, f32::NAN]).is_sorted_ascending());
+    assert!(!EnumColumnData::Float32Vec(vec![f32::NAN, -1.5]).is_sorted_ascending());
+  }
+
+  #[test]
+  fn test_cast_int32_widens_to_int64() {
+    let column: EnumColumnData = EnumColumnData::Int32Vec(vec![i32::MIN, -1, 0, i32::MAX]);
+
+    let casted: EnumColumnData = column.cast_to(EnumDataType::Int64).unwrap();
+
+    assert!(matches!(casted, EnumColumnData::Int64Vec(ref v) if v == &vec![i32::MIN as i64, -1, 0, i32::MAX as i64]));
+  }
+
+  #[test]
+  fn test_cast_int64_to_int8_overflow_is_rejected() {
+    let column: EnumColumnData = EnumColumnData::Int64Vec(vec![1, 2, 300]);
+
+    let result: Result<EnumColumnData, TsfError> = column.cast_to(EnumDataType::Int8);
+
+    assert_eq!(result.unwrap_err(), TsfError::CastOverflow { to: EnumDataType::Int8, value: "300".to_string() });
+    assert!(matches!(
+      EnumColumnData::Float64Vec(vec![1.5]).cast_to(EnumDataType::Int32),
+      Err(TsfError::InvalidCast { from: EnumDataType::Float64, to: EnumDataType::Int32 })
+    ));
+  }
+
+  #[test]
+  fn test_generated_type_conversions_round_trip() {
+    for &data_type in EnumDataType::ALL {
+      assert_eq!(EnumDataType::from_u16(data_type as u16), Some(data_type));
+      assert_eq!(EnumColumnData::from_enum_data_type(data_type).data_type(), data_type);
+      let value: EnumDataValue = EnumDataValue::from_data_type(data_type);
+      assert_eq!(value.to_string(), if data_type == EnumDataType::Boolean { "false" } else { "0" });
     }
+    assert_eq!(EnumDataType::from_u16(5), None);
+  }
+
+  #[test]
+  fn test_generated_serializers_round_trip() {
+    let columns: Vec<EnumColumnData> = vec![
+      EnumColumnData::Int8Vec(vec![i8::MIN, -1, 0, i8::MAX]),
+      EnumColumnData::Int16Vec(vec![i16::MIN, 0, i16::MAX]),
+      EnumColumnData::Int32Vec(vec![i32::MIN, 0, i32::MAX]),
+      EnumColumnData::Int64Vec(vec![i64::MIN, 0, i64::MAX]),
+      EnumColumnData::UInt8Vec(vec![0, u8::MAX]),
+      EnumColumnData::UInt16Vec(vec![0, u16::MAX]),
+      EnumColumnData::UInt32Vec(vec![0, u32::MAX]),
+      EnumColumnData::UInt64Vec(vec![0, u64::MAX]),
+      EnumColumnData::Float32Vec(vec![-1.5, 0.0, f32::MAX]),
+      EnumColumnData::Float64Vec(vec![-1.5, 0.0, f64::MAX]),
+      EnumColumnData::BooleanVec(vec![true, false, true]),
+      EnumColumnData::DateTime32Vec(vec![1710555318, 1710555319]),
+      EnumColumnData::DateTime64Vec(vec![1710555318000, 1710555319000]),
+    ];
+    assert_eq!(columns.len(), EnumDataType::ALL.len());
+
+    for column in columns {
+      let bytes: Vec<u8> = column.to_le_bytes();
+      let mut decoded: EnumColumnData = EnumColumnData::from_enum_data_type(column.data_type());
+      decoded.read_le_bytes(&bytes);
+
+      assert_eq!(format!("{:?}", decoded), format!("{:?}", column));
+    }
+    assert_eq!(EnumColumnData::BooleanVec(vec![true, false]).to_le_bytes(), vec![255u8, 0]);
+  }
+
+  fn schema_of(columns: &[(&str, EnumDataType)]) -> Schema {
+    Schema::from(columns.iter()
+      .map(|(name, data_type): &(&str, EnumDataType)| ColumnSchema { name: name.to_string(), data_type: *data_type, description: None })
+      .collect::<Vec<ColumnSchema>>())
+  }
+
+  #[test]
+  fn test_schema_compatibility_reports_first_divergence() {
+    let expected: Schema = schema_of(&[("metric_time", EnumDataType::Int32), ("temperature", EnumDataType::Int8)]);
+
+    let mut described: Schema = expected.clone();
+    described.columns[1].description = Some("Celsius".to_string());
+    assert_eq!(expected.is_compatible_with(&described), Ok(()));
+
+    let reordered: Schema = schema_of(&[("temperature", EnumDataType::Int8), ("metric_time", EnumDataType::Int32)]);
+    assert_eq!(expected.is_compatible_with(&reordered), Err(SchemaMismatch::Reordered { index: 0, name: "metric_time".to_string(), found_index: 1 }));
+
+    let renamed: Schema = schema_of(&[("metric_time", EnumDataType::Int32), ("humidity", EnumDataType::Int8)]);
+    let mismatch: SchemaMismatch = expected.is_compatible_with(&renamed).unwrap_err();
+    assert_eq!(mismatch, SchemaMismatch::Name { index: 1, expected: "temperature".to_string(), found: "humidity".to_string() });
+    assert_eq!(mismatch.to_string(), "Column 1 is humidity, expected temperature");
+
+    let retyped: Schema = schema_of(&[("metric_time", EnumDataType::Int64), ("temperature", EnumDataType::Int8)]);
+    assert_eq!(expected.is_compatible_with(&retyped), Err(SchemaMismatch::Type {
+      index: 0,
+      name: "metric_time".to_string(),
+      expected: EnumDataType::Int32,
+      found: EnumDataType::Int64,
+    }));
+
+    let shorter: Schema = schema_of(&[("metric_time", EnumDataType::Int32)]);
+    let mismatch: SchemaMismatch = expected.is_compatible_with(&shorter).unwrap_err();
+    assert_eq!(mismatch, SchemaMismatch::ColumnCount { expected: 2, found: 1 });
+    assert_eq!(mismatch.index(), 1);
   }
 }
@@ -0,0 +1,314 @@
+use std::{fs::{File, OpenOptions}, io::{self, Read, Seek, SeekFrom}, path::PathBuf};
+
+use tracing::trace;
+
+use super::error::{check_path, open_error};
+use super::header::FileHeader;
+use super::read_util::{chain_end, next_segment_pos};
+use super::segments::checksum::calculate_checksum;
+use super::segments::segment_data_header::{SegmentColumnHeader, SegmentDataHeader};
+
+// Which segments a retain pass keeps, a segment has to pass every limit that is set
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RetentionPolicy {
+  // UTS cutoff, segments created before it are dropped. Segments without a created_at are kept
+  pub created_after: Option<i64>,
+  // Inclusive UTS window, segments whose date range lies wholly outside it are dropped.
+  // Segments without a date range are kept
+  pub keep_window: Option<(i64, i64)>,
+}
+
+impl RetentionPolicy {
+  pub fn keeps(&self, data_header: &SegmentDataHeader) -> bool {
+    let created_at: i64 = data_header.get_created_at();
+    if let Some(cutoff) = self.created_after {
+      if created_at != 0 && created_at < cutoff {
+        return false;
+      }
+    }
+    if let (Some((keep_start, keep_end)), Some((date_start, date_end))) = (self.keep_window, data_header.get_date_range()) {
+      if date_end < keep_start || date_start > keep_end {
+        return false;
+      }
+    }
+
+    true
+  }
+}
+
+// Read-write handle for changing segment headers in place, e.g. tombstoning or backfilling stats.
+// Column data is never moved, so a rewritten header has to keep its serialized size
+pub struct TSFEditor {
+  file: File,
+  first_segment_pos: u64,
+}
+
+impl TSFEditor {
+  pub fn new(file_path: &str) -> io::Result<Self> {
+    let path: PathBuf = check_path(file_path)?;
+    let mut file: File = OpenOptions::new()
+      .read(true)
+      .write(true)
+      .open(&path)
+      .map_err(|e: io::Error| open_error(e, &path))?;
+
+    let mut file_header: FileHeader = FileHeader::new();
+    file_header.read_header(&mut file)?;
+    if !file_header.verify_header() {
+      return Err(io::Error::new(io::ErrorKind::InvalidData, "File header verification failed"));
+    }
+    let first_segment_pos: u64 = file.stream_position()?;
+
+    Ok(TSFEditor {
+      file,
+      first_segment_pos,
+    })
+  }
+
+  // Start of every segment in file order, found by following next_offset through the headers
+  pub fn segment_offsets(&mut self) -> io::Result<Vec<u64>> {
+    trace!("TSFEditor::segment_offsets");
+
+    self.walk_segments(true)
+  }
+
+  fn walk_segments(&mut self, verify: bool) -> io::Result<Vec<u64>> {
+    let (segments_end, _): (u64, Option<u64>) = chain_end(&mut self.file)?;
+    let mut segment_offsets: Vec<u64> = Vec::new();
+    let mut segment_pos: u64 = self.first_segment_pos;
+
+    while segment_pos < segments_end {
+      let data_header: SegmentDataHeader = match verify {
+        true => self.read_segment_header(segment_pos)?,
+        false => self.read_unverified_segment_header(segment_pos)?,
+      };
+      let column_bytes: u64 = data_header.column_headers.iter()
+        .map(|header: &SegmentColumnHeader| header.column_size)
+        .sum();
+      let segment_end: u64 = self.file.stream_position()? + column_bytes;
+
+      segment_offsets.push(segment_pos);
+      segment_pos = next_segment_pos(segment_pos, segment_end, segments_end, data_header.next_offset)?;
+    }
+
+    Ok(segment_offsets)
+  }
+
+  pub fn read_segment_header(&mut self, segment_pos: u64) -> io::Result<SegmentDataHeader> {
+    trace!("TSFEditor::read_segment_header");
+
+    self.file.seek(SeekFrom::Start(segment_pos))?;
+    let mut data_header: SegmentDataHeader = SegmentDataHeader::new();
+    data_header.read_segment_header(&mut self.file)?;
+
+    Ok(data_header)
+  }
+
+  fn read_unverified_segment_header(&mut self, segment_pos: u64) -> io::Result<SegmentDataHeader> {
+    self.file.seek(SeekFrom::Start(segment_pos))?;
+    let mut data_header: SegmentDataHeader = SegmentDataHeader::new();
+    data_header.read_unverified_segment_header(&mut self.file)?;
+
+    Ok(data_header)
+  }
+
+  // Overwrites the header at segment_pos, re-signing it. Fails without writing when the new header
+  // would not fit exactly over the old one
+  pub fn write_segment_header(&mut self, segment_pos: u64, data_header: &mut SegmentDataHeader) -> io::Result<()> {
+    trace!("TSFEditor::write_segment_header");
+
+    let current_size: u32 = self.read_segment_header(segment_pos)?.calculate_header_size();
+    if data_header.calculate_header_size() != current_size {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("Segment header at {} is {} bytes, the replacement is {}", segment_pos, current_size, data_header.calculate_header_size()),
+      ));
+    }
+
+    self.file.seek(SeekFrom::Start(segment_pos))?;
+    data_header.write_header(&mut self.file)?;
+    self.file.sync_data()
+  }
+
+  pub fn set_tombstone(&mut self, segment_pos: u64, tombstone: bool) -> io::Result<()> {
+    trace!("TSFEditor::set_tombstone");
+
+    let mut data_header: SegmentDataHeader = self.read_segment_header(segment_pos)?;
+    data_header.tombstone = tombstone;
+    self.write_segment_header(segment_pos, &mut data_header)
+  }
+
+  // Tombstones every live segment the policy doesn't keep, returns how many were tombstoned
+  pub fn retain(&mut self, policy: &RetentionPolicy) -> io::Result<usize> {
+    trace!("TSFEditor::retain");
+
+    let mut tombstoned: usize = 0;
+    for segment_pos in self.segment_offsets()? {
+      let mut data_header: SegmentDataHeader = self.read_segment_header(segment_pos)?;
+      if data_header.tombstone || policy.keeps(&data_header) {
+        continue;
+      }
+
+      data_header.tombstone = true;
+      self.write_segment_header(segment_pos, &mut data_header)?;
+      tombstoned += 1;
+    }
+
+    Ok(tombstoned)
+  }
+
+  // Recomputes every segment_check and column_check from the bytes on disk and rewrites the headers
+  // that were stale, e.g. files from before real checksums. Returns how many segments were rewritten
+  pub fn repair_checksums(&mut self) -> io::Result<usize> {
+    trace!("TSFEditor::repair_checksums");
+
+    let mut repaired: usize = 0;
+    for segment_pos in self.walk_segments(false)? {
+      let segment_verified: bool = self.read_segment_header(segment_pos).is_ok();
+      let mut data_header: SegmentDataHeader = self.read_unverified_segment_header(segment_pos)?;
+
+      let mut columns_verified: bool = true;
+      let mut column_pos: u64 = segment_pos + data_header.calculate_header_size() as u64;
+      for column_header in data_header.column_headers.iter_mut() {
+        let mut column_bytes: Vec<u8> = vec![0; column_header.column_size as usize];
+        self.file.seek(SeekFrom::Start(column_pos))?;
+        self.file.read_exact(&mut column_bytes)?;
+        column_pos += column_header.column_size;
+
+        let column_check: [u8; 8] = calculate_checksum(data_header.checksum_alg, &column_bytes);
+        if column_header.get_column_check() != column_check {
+          column_header.set_column_check(column_check);
+          columns_verified = false;
+        }
+      }
+
+      if !segment_verified || !columns_verified {
+        // Only fixed width checks changed, so the header still fits over the old one
+        self.file.seek(SeekFrom::Start(segment_pos))?;
+        data_header.write_header(&mut self.file)?;
+        repaired += 1;
+      }
+    }
+
+    self.file.sync_data()?;
+    Ok(repaired)
+  }
+}
+
+// Upgrades the checksums of the file at file_path in place, see TSFEditor::repair_checksums
+pub fn repair_checksums(file_path: &str) -> io::Result<usize> {
+  TSFEditor::new(file_path)?.repair_checksums()
+}
+
+// Applies a retention policy to the file at file_path in place, see TSFEditor::retain
+pub fn retain(file_path: &str, policy: &RetentionPolicy) -> io::Result<usize> {
+  TSFEditor::new(file_path)?.retain(policy)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::NamedTempFile;
+  use crate::test_support::append_segment;
+  use std::io::Write;
+  use crate::tsf::tsf_reader::TSFReader;
+
+  #[test]
+  fn test_set_tombstone_is_seen_by_reader() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    append_segment(file_path, vec![1, 2], vec![10, 20])?;
+    append_segment(file_path, vec![3, 4], vec![30, 40])?;
+    let file_len: u64 = std::fs::metadata(file_path)?.len();
+
+    let mut editor: TSFEditor = TSFEditor::new(file_path)?;
+    let segment_offsets: Vec<u64> = editor.segment_offsets()?;
+    assert_eq!(segment_offsets.len(), 2);
+    assert_eq!(segment_offsets[0], 6);
+
+    editor.set_tombstone(segment_offsets[0], true)?;
+    assert!(editor.read_segment_header(segment_offsets[0])?.tombstone);
+    assert!(!editor.read_segment_header(segment_offsets[1])?.tombstone);
+    assert_eq!(std::fs::metadata(file_path)?.len(), file_len);
+
+    // The header still verifies and the column data behind it is untouched
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+    assert!(reader.is_tombstoned());
+    assert_eq!(reader.column_slice::<i32>(0).unwrap(), &[1, 2]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_repair_checksums_upgrades_dummy_checks() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    append_segment(file_path, vec![1, 2], vec![10, 20])?;
+    append_segment(file_path, vec![3, 4], vec![30, 40])?;
+
+    // Stamp the placeholder check older writers left behind over each segment_check
+    let mut editor: TSFEditor = TSFEditor::new(file_path)?;
+    let segment_offsets: Vec<u64> = editor.segment_offsets()?;
+    for segment_pos in &segment_offsets {
+      let header_size: u64 = editor.read_segment_header(*segment_pos)?.calculate_header_size() as u64;
+      editor.file.seek(SeekFrom::Start(segment_pos + header_size - 8))?;
+      editor.file.write_all(&[0xBB; 8])?;
+    }
+    assert!(TSFReader::new(file_path)?.read_all().is_err());
+
+    let file_len: u64 = std::fs::metadata(file_path)?.len();
+    assert_eq!(repair_checksums(file_path)?, 2);
+    assert_eq!(repair_checksums(file_path)?, 0);
+    assert_eq!(std::fs::metadata(file_path)?.len(), file_len);
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+    assert_eq!(reader.column_slice::<i32>(0).unwrap(), &[1, 2]);
+
+    // Column checks now match the stored column bytes
+    let mut editor: TSFEditor = TSFEditor::new(file_path)?;
+    let data_header: SegmentDataHeader = editor.read_segment_header(segment_offsets[1])?;
+    let mut column_pos: u64 = segment_offsets[1] + data_header.calculate_header_size() as u64;
+    for column_header in &data_header.column_headers {
+      let mut column_bytes: Vec<u8> = vec![0; column_header.column_size as usize];
+      editor.file.seek(SeekFrom::Start(column_pos))?;
+      editor.file.read_exact(&mut column_bytes)?;
+      column_pos += column_header.column_size;
+      assert_eq!(column_header.get_column_check(), calculate_checksum(data_header.checksum_alg, &column_bytes));
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_retain_tombstones_segments_outside_policy() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    append_segment(file_path, vec![1, 2], vec![10, 20])?;
+    append_segment(file_path, vec![300, 400], vec![30, 40])?;
+    append_segment(file_path, vec![500, 600], vec![50, 60])?;
+
+    // Backdate the first segment so it falls before the cutoff
+    let mut editor: TSFEditor = TSFEditor::new(file_path)?;
+    let segment_offsets: Vec<u64> = editor.segment_offsets()?;
+    let mut data_header: SegmentDataHeader = editor.read_segment_header(segment_offsets[0])?;
+    data_header.set_created_at(1_000);
+    editor.write_segment_header(segment_offsets[0], &mut data_header)?;
+
+    let policy: RetentionPolicy = RetentionPolicy {
+      created_after: Some(1_700_000_000),
+      keep_window: Some((450, 1_000)),
+    };
+    assert_eq!(retain(file_path, &policy)?, 2);
+    assert_eq!(retain(file_path, &policy)?, 0);
+
+    let mut editor: TSFEditor = TSFEditor::new(file_path)?;
+    let tombstones: Vec<bool> = segment_offsets.iter()
+      .map(|segment_pos: &u64| editor.read_segment_header(*segment_pos).map(|header: SegmentDataHeader| header.tombstone))
+      .collect::<io::Result<Vec<bool>>>()?;
+    assert_eq!(tombstones, vec![true, true, false]);
+
+    Ok(())
+  }
+}
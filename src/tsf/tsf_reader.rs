@@ -1,91 +1,442 @@
-use std::{fs::{File, OpenOptions}, io, path::Path};
+use std::{collections::BTreeMap, fs::{File, OpenOptions}, io::{self, Read, Seek, SeekFrom}, path::PathBuf};
 
 use futures::stream::BoxStream;
+use sha2::{Digest, Sha256};
 use tokio_stream::StreamExt;
 use tracing::trace;
 
+use super::error::{check_path, open_error, TsfError};
 use super::header::FileHeader;
-use super::segments::{segment_data::SegmentData, types::{EnumColumnData, EnumDataValue}};
+use super::read_util::{chain_end, next_segment_pos};
+use super::segments::{codec::{CodecRegistry, CustomColumn}, segment_data_header::{SegmentColumnHeader, SegmentDataHeader}, segment_data::{ColumnReadStats, SegmentData}, types::{ColumnLayout, ColumnSchema, ColumnSlice, EnumColumnData, EnumDataValue, UnsupportedColumn}};
 
 #[derive(Debug)]
 pub struct DataRow {
   pub values: Vec<EnumDataValue>
 }
 
+impl DataRow {
+  // Pairs each value with its column name from schema, in column order
+  pub fn named_iter<'a>(&'a self, schema: &'a [ColumnSchema]) -> impl Iterator<Item = (&'a str, &'a EnumDataValue)> + 'a {
+    schema.iter().map(|column: &ColumnSchema| column.name.as_str()).zip(self.values.iter())
+  }
+}
+
 pub struct TSFReader {
   file: File,
+  // Position of the FileHeader, non zero when the TSF data is embedded in a larger file
+  start_offset: u64,
   file_header: FileHeader,
   segment_data: SegmentData,
+  apply_scale: bool,
+  ignore_trailing_bytes: bool,
 }
 
 impl TSFReader {
   pub fn new(file_path: &str) -> io::Result<Self> {
-    let file: File = OpenOptions::new()
+    Self::new_at(file_path, 0)
+  }
+
+  // Reads TSF data packed into a container file, starting with the FileHeader at start_offset.
+  // Segments link to each other with relative offsets, so nothing past the header needs adjusting
+  pub fn new_at(file_path: &str, start_offset: u64) -> io::Result<Self> {
+    let path: PathBuf = check_path(file_path)?;
+    let mut file: File = OpenOptions::new()
       .read(true)
-      .open(Path::new(file_path))?;
+      .open(&path)
+      .map_err(|e: io::Error| open_error(e, &path))?;
+    file.seek(SeekFrom::Start(start_offset))?;
 
     let file_header: FileHeader = FileHeader::new();
     let segment_data: SegmentData = SegmentData::new();
 
     Ok(TSFReader {
       file,
+      start_offset,
       file_header,
       segment_data,
+      apply_scale: false,
+      ignore_trailing_bytes: false,
     })
   }
 
+  // When set, stream_rows presents integer columns with ColumnMeta::Scale as floats. Raw access such as
+  // column_slice keeps the stored integers
+  pub fn set_apply_scale(&mut self, apply_scale: bool) {
+    self.apply_scale = apply_scale;
+  }
+
+  // When set, walking the segment chain stops at bytes after the last segment that do not parse as a
+  // segment header instead of failing, see AsyncTSFReader::set_ignore_trailing_bytes
+  pub fn set_ignore_trailing_bytes(&mut self, ignore_trailing_bytes: bool) {
+    self.ignore_trailing_bytes = ignore_trailing_bytes;
+  }
+
+  // Read files with columns of unknown type, listing them in unsupported_columns instead of failing
+  pub fn set_skip_unsupported_columns(&mut self, skip_unsupported_columns: bool) {
+    self.segment_data.set_skip_unsupported_columns(skip_unsupported_columns);
+  }
+
+  // Segments declaring more columns than this are rejected as corrupt, DEFAULT_MAX_COLUMN_COUNT unless set
+  pub fn set_max_column_count(&mut self, max_column_count: u16) {
+    self.segment_data.set_max_column_count(max_column_count);
+  }
+
+  pub fn unsupported_columns(&self) -> &[UnsupportedColumn] {
+    self.segment_data.get_unsupported_columns()
+  }
+
+  // Columns stored with a type code registered in codecs are decoded by its codec. They stay out of
+  // the regular columns and rows, and are listed in unsupported_columns like skipped ones
+  pub fn set_codecs(&mut self, codecs: CodecRegistry) {
+    self.segment_data.set_codecs(codecs);
+  }
+
+  pub fn custom_columns(&self) -> &[CustomColumn] {
+    self.segment_data.get_custom_columns()
+  }
+
+  // Set when the segment has been marked deleted in place
+  pub fn is_tombstoned(&self) -> bool {
+    self.segment_data.is_tombstoned()
+  }
+
+  // Schema id from the file header, 0 when the writer did not set one. Valid after read_header
+  pub fn get_schema_id(&self) -> u64 {
+    self.file_header.get_schema_id()
+  }
+
+  // Rejects a file written with another schema, an expected id of 0 accepts any file
+  pub fn check_schema_id(&self, expected: u64) -> io::Result<()> {
+    self.file_header.check_schema_id(expected).map_err(io::Error::from)
+  }
+
+  pub fn get_row_count(&self) -> usize {
+    self.segment_data.get_row_count()
+  }
+
+  // Rows in every segment of the file, only the segment headers are read. Segments store a u32 count
+  // each, so the total is accumulated as u64 and does not wrap for files past u32::MAX rows
+  pub fn row_count(&mut self) -> io::Result<u64> {
+    trace!("TSFReader::row_count");
+
+    let segment_headers: Vec<(u64, SegmentDataHeader)> = self.read_segment_headers()?;
+    Ok(sum_row_counts(segment_headers.iter().map(|(_, data_header): &(u64, SegmentDataHeader)| data_header.row_count)))
+  }
+
+  // Bytes the decoded columns of every segment take, row_count times the type width per column. Only the
+  // segment headers are read, so a tool can pick between streaming and read_all before loading anything
+  pub fn estimated_memory(file_path: &str) -> io::Result<u64> {
+    trace!("TSFReader::estimated_memory");
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    let segment_headers: Vec<(u64, SegmentDataHeader)> = reader.read_segment_headers()?;
+    Ok(segment_headers.iter()
+      .flat_map(|(_, data_header): &(u64, SegmentDataHeader)| data_header.column_headers.iter()
+        .map(|column_header: &SegmentColumnHeader| data_header.row_count as u64 * column_header.column_type.value_size() as u64))
+      .sum())
+  }
+
+  // Start of every segment in file order, found by following next_offset from the first segment. Only the
+  // headers are read, load a segment with read_segment_at
+  pub fn segment_offsets(&mut self) -> io::Result<Vec<u64>> {
+    trace!("TSFReader::segment_offsets");
+
+    Ok(self.read_segment_headers()?.into_iter()
+      .map(|(segment_pos, _): (u64, SegmentDataHeader)| segment_pos)
+      .collect())
+  }
+
+  // Loads the segment starting at segment_pos, one of segment_offsets, in place of the current one
+  pub fn read_segment_at(&mut self, segment_pos: u64) -> io::Result<()> {
+    trace!("TSFReader::read_segment_at");
+
+    self.file.seek(SeekFrom::Start(segment_pos))?;
+    self.read_data()
+  }
+
+  // Reads only the header of the segment at segment_pos, so its row count and column layout are known
+  // without loading any column data
+  pub fn read_segment_header_at(&mut self, segment_pos: u64) -> io::Result<()> {
+    trace!("TSFReader::read_segment_header_at");
+
+    self.file.seek(SeekFrom::Start(segment_pos))?;
+    self.segment_data.read_header_from_file(&mut self.file)
+  }
+
+  // Loads the newest segment in place of the first one. A footer points straight at it, when there is
+  // none or the segment it names doesn't end where the footer starts the chain is walked instead
+  pub fn last_segment(&mut self) -> io::Result<()> {
+    trace!("TSFReader::last_segment");
+
+    self.file.seek(SeekFrom::Start(self.start_offset))?;
+    self.read_header()?;
+    let (segments_end, footer): (u64, Option<u64>) = chain_end(&mut self.file)?;
+
+    if let Some(segment_pos) = footer.and_then(|footer: u64| footer.checked_add(self.start_offset)) {
+      if self.read_segment_ending_at(segment_pos, segments_end).is_ok() {
+        return Ok(());
+      }
+    }
+
+    let segment_pos: u64 = self.read_segment_headers()?.last()
+      .map(|(segment_pos, _): &(u64, SegmentDataHeader)| *segment_pos)
+      .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "File has no segments"))?;
+    self.read_segment_ending_at(segment_pos, segments_end)
+  }
+
+  // Reads the segment at segment_pos, failing unless it is the last one before segments_end
+  fn read_segment_ending_at(&mut self, segment_pos: u64, segments_end: u64) -> io::Result<()> {
+    self.file.seek(SeekFrom::Start(segment_pos))?;
+    self.segment_data.read_segment_from_file(&mut self.file)?;
+    let segment_end: u64 = self.file.stream_position()?;
+
+    let next_pos: u64 = next_segment_pos(segment_pos, segment_end, segments_end, self.segment_data.get_next_offset())?;
+    if next_pos != segments_end {
+      return Err(TsfError::Corruption {
+        reason: format!("segment at {} is followed by another at {}, not the end of the chain at {}", segment_pos, next_pos, segments_end),
+      }.into());
+    }
+
+    Ok(())
+  }
+
+  // Position and header of every segment, following next_offset from the first segment
+  fn read_segment_headers(&mut self) -> io::Result<Vec<(u64, SegmentDataHeader)>> {
+    self.file.seek(SeekFrom::Start(self.start_offset))?;
+    self.read_header()?;
+    let mut segment_pos: u64 = self.file.stream_position()?;
+    let (segments_end, _): (u64, Option<u64>) = chain_end(&mut self.file)?;
+    let mut segment_headers: Vec<(u64, SegmentDataHeader)> = Vec::new();
+
+    while segment_pos < segments_end {
+      self.file.seek(SeekFrom::Start(segment_pos))?;
+      let mut data_header: SegmentDataHeader = SegmentDataHeader::new();
+      match data_header.read_segment_header(&mut self.file) {
+        Ok(()) => {},
+        Err(e) if self.ignore_trailing_bytes && !segment_headers.is_empty()
+          && matches!(e.kind(), io::ErrorKind::UnexpectedEof | io::ErrorKind::InvalidData) => break,
+        Err(e) => return Err(e),
+      }
+      let column_bytes: u64 = data_header.column_headers.iter()
+        .map(|header: &SegmentColumnHeader| header.column_size)
+        .sum();
+      let segment_end: u64 = self.file.stream_position()? + column_bytes;
+
+      let next_pos: u64 = next_segment_pos(segment_pos, segment_end, segments_end, data_header.next_offset)?;
+      segment_headers.push((segment_pos, data_header));
+      segment_pos = next_pos;
+    }
+
+    Ok(segment_headers)
+  }
+
+  // Serialized bytes of the segment starting at offset in the file (see TSFEditor::segment_offsets), header
+  // and columns up to where the next segment starts, without decoding anything. The header is verified so
+  // an offset that isn't a segment start fails instead of copying garbage
+  pub fn read_raw_segment(&mut self, offset: u64) -> io::Result<Vec<u8>> {
+    trace!("TSFReader::read_raw_segment");
+
+    let (segments_end, _): (u64, Option<u64>) = chain_end(&mut self.file)?;
+    self.file.seek(SeekFrom::Start(offset))?;
+    let mut data_header: SegmentDataHeader = SegmentDataHeader::new();
+    data_header.read_segment_header(&mut self.file)?;
+    let column_bytes: u64 = data_header.column_headers.iter()
+      .map(|header: &SegmentColumnHeader| header.column_size)
+      .sum();
+    let segment_end: u64 = self.file.stream_position()? + column_bytes;
+    let next_pos: u64 = next_segment_pos(offset, segment_end, segments_end, data_header.next_offset)?;
+
+    let mut bytes: Vec<u8> = vec![0u8; (next_pos - offset) as usize];
+    self.file.seek(SeekFrom::Start(offset))?;
+    self.file.read_exact(&mut bytes)?;
+
+    Ok(bytes)
+  }
+
+  pub fn get_ts_column(&self) -> Option<usize> {
+    self.segment_data.get_ts_column()
+  }
+
+  pub fn get_column_header(&self, index: usize) -> Option<&SegmentColumnHeader> {
+    self.segment_data.get_column_header(index)
+  }
+
+  pub fn get_column_data(&self, index: usize) -> Option<&EnumColumnData> {
+    self.segment_data.get_segment_data(index).and_then(|column| column.get_data())
+  }
+
+  // Decoded data of the column with this name, None if there is no such column or it wasn't read
+  pub fn get_column(&self, column_name: &str) -> Option<&EnumColumnData> {
+    (0..self.segment_data.get_column_count())
+      .find(|index: &usize| self.get_column_header(*index).is_some_and(|header: &SegmentColumnHeader| header.column_name == column_name))
+      .and_then(|index: usize| self.get_column_data(index))
+  }
+
+  // Every column's name and decoded data in column order, straight from the segment without building rows
+  pub fn to_columns(&self) -> Vec<(String, EnumColumnData)> {
+    (0..self.segment_data.get_column_count())
+      .filter_map(|index: usize| Some((self.get_column_header(index)?.column_name.clone(), self.get_column_data(index)?.clone())))
+      .collect()
+  }
+
+  pub fn schema(&self) -> Vec<ColumnSchema> {
+    (0..self.segment_data.get_column_count())
+      .filter_map(|index: usize| self.get_column_header(index))
+      .map(|header: &SegmentColumnHeader| ColumnSchema {
+        name: header.column_name.clone(),
+        data_type: header.column_type,
+        description: header.get_description().map(str::to_string),
+      })
+      .collect()
+  }
+
+  // Seconds since the epoch when the loaded segment was written, for retention by age
+  pub fn created_at(&self) -> i64 {
+    self.segment_data.get_created_at()
+  }
+
+  pub fn date_range(&self) -> Option<(i64, i64)> {
+    self.segment_data.get_date_range()
+  }
+
+  pub fn column_layout(&self) -> Vec<ColumnLayout> {
+    self.segment_data.column_layout()
+  }
+
+  // SHA-256 over the logical contents of every segment in file order, identical data ingested in different
+  // transactions gives the same digest, see SegmentDataHeader::update_content_digest for the excluded fields.
+  // Each segment is loaded in turn, the last one stays loaded
+  pub fn content_digest(&mut self) -> io::Result<[u8; 32]> {
+    trace!("TSFReader::content_digest");
+
+    let mut hasher: Sha256 = Sha256::new();
+    for segment_pos in self.segment_offsets()? {
+      self.read_segment_at(segment_pos)?;
+      self.segment_data.update_content_digest(&mut hasher);
+    }
+    Ok(hasher.finalize().into())
+  }
+
+  // content_digest of the loaded segment alone, e.g. to match a segment copied on its own
+  pub fn segment_content_digest(&self) -> [u8; 32] {
+    let mut hasher: Sha256 = Sha256::new();
+    self.segment_data.update_content_digest(&mut hasher);
+    hasher.finalize().into()
+  }
+
+  pub fn metadata(&self) -> &BTreeMap<String, String> {
+    self.segment_data.get_metadata()
+  }
+
+  pub fn column_slice<T: ColumnSlice>(&self, index: usize) -> Option<&[T]> {
+    self.segment_data.column_slice(index)
+  }
+
+  pub fn read_stats(&self) -> &[ColumnReadStats] {
+    self.segment_data.get_read_stats()
+  }
+
+  // Rows are built as the stream is polled, so a consumer that stops early never builds the rest. The
+  // columns are copied out so the stream doesn't borrow the reader
   pub fn stream_rows(&self) -> BoxStream<'static, io::Result<DataRow>> {
     let num_rows: usize = self.segment_data.get_row_count();
-    
-    let mut rows: Vec<Result<DataRow, io::Error>> = Vec::new();
-
-    for row_index in 0..num_rows {
-      let mut row_values: Vec<EnumDataValue> = Vec::new();
-
-      // Assuming you have a way to iterate over each column index
-      for column_index in 0..self.segment_data.get_column_count() {
-        if let Some(column) = self.segment_data.get_segment_data(column_index) {
-          if let Some(data) = column.get_data() {
-            match data {
-              EnumColumnData::Int8Vec(v) => {
-                if row_index < v.len() {
-                    row_values.push(EnumDataValue::Int8Value(v[row_index]));
-                }
-              },
-              EnumColumnData::Int16Vec(v) => {
-                if row_index < v.len() {
-                    row_values.push(EnumDataValue::Int16Value(v[row_index]));
-                }
-              },
-              EnumColumnData::Int32Vec(v) => {
-                if row_index < v.len() {
-                    row_values.push(EnumDataValue::Int32Value(v[row_index]));
-                }
-              },
-              EnumColumnData::Int64Vec(v) => {
-                if row_index < v.len() {
-                    row_values.push(EnumDataValue::Int64Value(v[row_index]));
-                }
-              },
-              _ => return Box::pin(tokio_stream::iter(vec![Err(io::Error::new(io::ErrorKind::Other, "EnumColumnData not implemented"))])),
-            }
-          }
-        } else {
-          // Handle the case where column data is missing
-          return Box::pin(tokio_stream::iter(vec![Err(io::Error::new(io::ErrorKind::Other, "Column data missing"))]));
-        }
+    if num_rows == 0 {
+      return Box::pin(tokio_stream::empty());
+    }
+
+    let scales: Vec<Option<u32>> = (0..self.segment_data.get_column_count())
+      .map(|column_index: usize| self.segment_data.get_column_header(column_index)
+        .and_then(|header: &SegmentColumnHeader| header.get_column_meta().scale())
+        .filter(|_| self.apply_scale))
+      .collect();
+    let mut columns: Vec<Option<EnumColumnData>> = Vec::with_capacity(scales.len());
+    for column_index in 0..self.segment_data.get_column_count() {
+      match self.segment_data.get_segment_data(column_index) {
+        Some(column) => columns.push(column.get_data().cloned()),
+        // Handle the case where column data is missing
+        None => return Box::pin(tokio_stream::iter(vec![Err(io::Error::new(io::ErrorKind::Other, "Column data missing"))])),
       }
+    }
+
+    Box::pin(tokio_stream::iter(0..num_rows).map(move |row_index: usize| {
+      let values: Vec<EnumDataValue> = columns.iter().zip(&scales)
+        .filter_map(|(column, scale): (&Option<EnumColumnData>, &Option<u32>)| {
+          let value: EnumDataValue = column.as_ref()?.value_at(row_index)?;
+          Some(match scale {
+            Some(scale) => value.with_scale(*scale),
+            None => value,
+          })
+        })
+        .collect();
+      Ok(DataRow { values })
+    }))
+  }
+
+  // Rows the predicate accepts, errors are passed through so the caller still sees them
+  pub fn stream_rows_where<P>(&self, predicate: P) -> BoxStream<'static, io::Result<DataRow>>
+  where
+    P: Fn(&DataRow) -> bool + Send + 'static,
+  {
+    Box::pin(self.stream_rows().filter(move |row: &io::Result<DataRow>| row.as_ref().map_or(true, |row: &DataRow| predicate(row))))
+  }
+
+  // Column schema from the file and segment headers alone, no column data is read. A file with no segments has none
+  pub fn read_schema(&mut self) -> io::Result<Vec<ColumnSchema>> {
+    trace!("TSFReader::read_schema");
+
+    self.file.seek(SeekFrom::Start(self.start_offset))?;
+    self.read_header()?;
+
+    let segment_pos: u64 = self.file.stream_position()?;
+    let (segments_end, _): (u64, Option<u64>) = chain_end(&mut self.file)?;
+    if segment_pos >= segments_end {
+      return Ok(self.schema());
+    }
+    self.file.seek(SeekFrom::Start(segment_pos))?;
+    self.segment_data.read_header_from_file(&mut self.file)?;
 
-      // Create a DataRow for each row of values
-      rows.push(Ok(DataRow { values: row_values }));
+    Ok(self.schema())
+  }
+
+  // Timestamps between start and end inclusive from every segment in file order, in the ts column's own
+  // units. Only the segment headers and ts columns are read, value columns stay on disk and read_stats is
+  // left alone
+  pub fn timestamps_in_range(&mut self, start: i64, end: i64) -> io::Result<Vec<i64>> {
+    trace!("TSFReader::timestamps_in_range");
+
+    let mut in_range: Vec<i64> = Vec::new();
+    for segment_pos in self.segment_offsets()? {
+      self.read_segment_header_at(segment_pos)?;
+
+      let ts_column: usize = self.get_ts_column()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Segment has no timestamp column"))?;
+      let column: EnumColumnData = self.segment_data.read_column_from_file(&mut self.file, ts_column)?;
+      let timestamps: Vec<i64> = (0..column.len())
+        .filter_map(|index: usize| column.value_at(index)?.as_i64())
+        .collect();
+
+      // The ts column is sorted on write, so the range is found by binary search
+      let from: usize = timestamps.partition_point(|timestamp: &i64| *timestamp < start);
+      let to: usize = timestamps.partition_point(|timestamp: &i64| *timestamp <= end);
+      in_range.extend_from_slice(&timestamps[from..to.max(from)]);
     }
 
-    Box::pin(tokio_stream::iter(rows))
+    Ok(in_range)
   }
 
+  // A file with a header and no segments, e.g. created but never saved to, reads as empty with 0 rows
   pub fn read_all(&mut self) -> io::Result<()> {
     trace!("TSFReader::read_all");
     self.read_header()?;
+
+    let segment_pos: u64 = self.file.stream_position()?;
+    let (segments_end, _): (u64, Option<u64>) = chain_end(&mut self.file)?;
+    if segment_pos >= segments_end {
+      return Ok(());
+    }
+    self.file.seek(SeekFrom::Start(segment_pos))?;
+
     self.read_data()?;
     Ok(())
   }
@@ -107,3 +458,1064 @@ impl TSFReader {
     Ok(())
   }
 }
+
+// Total of per segment row counts, widened to u64 before adding
+pub fn sum_row_counts<I: IntoIterator<Item = u32>>(row_counts: I) -> u64 {
+  row_counts.into_iter().map(u64::from).sum()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::NamedTempFile;
+  use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+  use crate::tsf::segments::checksum::calculate_checksum;
+  use crate::tsf::segments::codec::ColumnCodec;
+  use crate::tsf::segments::types::{ColumnMeta, EnumChecksumAlg, EnumDataComp, EnumDataEnc, EnumDataType, EnumTimeResolution, MAX_COLUMN_DESCRIPTION_SIZE};
+  use chrono::{TimeZone, Utc};
+  use crate::tsf::segments::segment_column_data::SegmentColumnData;
+  use crate::tsf::error::TsfError;
+  use crate::tsf::read_util;
+  use crate::tsf::tsf_editor::TSFEditor;
+  use crate::tsf::async_tsf_reader::{AsyncTSFReader, DataRow as AsyncDataRow};
+  use crate::tsf::tsf_writer::TSFWriter;
+  use crate::test_support::{append_segment, sample_segment, sample_value, write_temp_file, SAMPLE_START};
+
+  #[test]
+  fn test_read_stats_record_bytes_read() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    append_segment(file_path, vec![1710555318, 1710555319, 1710555320], vec![20, 22, 21])?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+
+    let stats: &[ColumnReadStats] = reader.read_stats();
+    assert_eq!(stats.len(), 2);
+    assert_eq!(stats[0].column_name, "metric_time");
+    assert_eq!(stats[0].bytes_read, 12);
+    assert_eq!(stats[1].column_name, "temperature");
+    assert_eq!(stats[1].bytes_read, 3);
+
+    Ok(())
+  }
+
+  // Rewrites a column's type code and re-signs the segment header, as if written by a newer version
+  fn set_column_type_code(file_path: &str, index: usize, type_code: u16) -> io::Result<()> {
+    let mut bytes: Vec<u8> = std::fs::read(file_path)?;
+    let segment_start: usize = 6;
+    let column_header_size: usize = u32::from_le_bytes(bytes[segment_start + 46..segment_start + 50].try_into().unwrap()) as usize;
+
+    let mut column_pos: usize = segment_start + 50;
+    for _ in 0..index {
+      let name_length: usize = u16::from_le_bytes(bytes[column_pos..column_pos + 2].try_into().unwrap()) as usize;
+      let meta_length: usize = u16::from_le_bytes(bytes[column_pos + 4 + name_length..column_pos + 6 + name_length].try_into().unwrap()) as usize;
+      column_pos += 2 + name_length + 2 + 2 + meta_length + 1 + 1 + 8 + 8;
+    }
+    let name_length: usize = u16::from_le_bytes(bytes[column_pos..column_pos + 2].try_into().unwrap()) as usize;
+    bytes[column_pos + 2 + name_length..column_pos + 4 + name_length].copy_from_slice(&type_code.to_le_bytes());
+
+    let metadata_pos: usize = segment_start + 50 + column_header_size;
+    let metadata_size: usize = u16::from_le_bytes(bytes[metadata_pos..metadata_pos + 2].try_into().unwrap()) as usize;
+    let check_pos: usize = metadata_pos + 2 + metadata_size + 8;
+    let checksum: [u8; 8] = calculate_checksum(EnumChecksumAlg::default(), &bytes[segment_start..check_pos]);
+    bytes[check_pos..check_pos + 8].copy_from_slice(&checksum);
+
+    std::fs::write(file_path, bytes)
+  }
+
+  #[test]
+  fn test_skip_unsupported_column_type() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.add_column_header("future", EnumDataType::Int16, EnumDataEnc::None, EnumDataComp::None, false)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![7i16, 8, 9], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![1710555318i32, 1710555319, 1710555320], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![20i8, 22, 21], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(1710555318, 1710555320);
+    writer.try_save()?;
+    set_column_type_code(file_path, 0, 999)?;
+
+    let mut strict_reader: TSFReader = TSFReader::new(file_path)?;
+    assert!(strict_reader.read_all().is_err());
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.set_skip_unsupported_columns(true);
+    reader.read_all()?;
+
+    assert_eq!(reader.unsupported_columns(), &[UnsupportedColumn {
+      name: "future".to_string(),
+      type_code: 999,
+      position: 0,
+      column_size: 6,
+    }]);
+    assert_eq!(reader.get_ts_column(), Some(0));
+    assert_eq!(reader.get_column_header(0).unwrap().column_name, "metric_time");
+    assert_eq!(reader.column_slice::<i32>(0).unwrap(), &[1710555318, 1710555319, 1710555320]);
+    assert_eq!(reader.column_slice::<i8>(1).unwrap(), &[20, 22, 21]);
+
+    // Layout still points at the right bytes past the skipped column
+    let bytes: Vec<u8> = std::fs::read(file_path)?;
+    let layout: Vec<ColumnLayout> = reader.column_layout();
+    let temperature_pos: usize = layout[1].file_pos as usize;
+    assert_eq!(&bytes[temperature_pos..temperature_pos + 3], &[20u8, 22, 21]);
+
+    Ok(())
+  }
+
+  // Stores i16 values big endian, just to differ from the built in little endian layout
+  struct BigEndianCodec;
+
+  impl ColumnCodec for BigEndianCodec {
+    fn encode(&self, data: &EnumColumnData) -> Vec<u8> {
+      match data {
+        EnumColumnData::Int16Vec(values) => values.iter().flat_map(|value: &i16| value.to_be_bytes()).collect(),
+        _ => Vec::new(),
+      }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<EnumColumnData, String> {
+      if !bytes.len().is_multiple_of(2) {
+        return Err(format!("{} bytes is not a whole number of i16 values", bytes.len()));
+      }
+      Ok(EnumColumnData::Int16Vec(bytes.chunks_exact(2).map(|pair: &[u8]| i16::from_be_bytes([pair[0], pair[1]])).collect()))
+    }
+  }
+
+  #[test]
+  fn test_custom_column_round_trips_through_codec() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    let codec: Arc<dyn ColumnCodec> = Arc::new(BigEndianCodec);
+    let custom: EnumColumnData = EnumColumnData::Int16Vec(vec![-2, 300, 7]);
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![1710555318i32, 1710555319, 1710555320], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    assert!(writer.add_custom_column("builtin", EnumDataType::Int16 as u16, codec.clone(), custom.clone()).is_err());
+    writer.add_custom_column("custom", 900, codec.clone(), custom)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(1710555318, 1710555320);
+    writer.try_save()?;
+
+    let mut strict_reader: TSFReader = TSFReader::new(file_path)?;
+    assert!(strict_reader.read_all().is_err());
+
+    let mut codecs: CodecRegistry = CodecRegistry::new();
+    codecs.register(900, codec.clone()).map_err(|e: String| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.set_codecs(codecs);
+    reader.read_all()?;
+
+    let custom_columns: &[CustomColumn] = reader.custom_columns();
+    assert_eq!(custom_columns.len(), 1);
+    assert_eq!(custom_columns[0].name, "custom");
+    assert_eq!(custom_columns[0].type_code, 900);
+    assert!(matches!(&custom_columns[0].data, EnumColumnData::Int16Vec(values) if values == &[-2, 300, 7]));
+    assert_eq!(reader.column_slice::<i32>(0).unwrap(), &[1710555318, 1710555319, 1710555320]);
+
+    // The async reader decodes the same column with the same registry
+    let mut async_codecs: CodecRegistry = CodecRegistry::new();
+    async_codecs.register(900, codec).map_err(|e: String| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    tokio::runtime::Runtime::new()?.block_on(async {
+      let mut async_reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+      async_reader.set_codecs(async_codecs);
+      async_reader.read_all().await?;
+
+      let custom_columns: &[CustomColumn] = async_reader.custom_columns();
+      assert_eq!(custom_columns.len(), 1);
+      assert_eq!(custom_columns[0].name, "custom");
+      assert_eq!(custom_columns[0].type_code, 900);
+      assert!(matches!(&custom_columns[0].data, EnumColumnData::Int16Vec(values) if values == &[-2, 300, 7]));
+      assert_eq!(async_reader.column_slice::<i32>(0).unwrap(), &[1710555318, 1710555319, 1710555320]);
+
+      Ok(())
+    })
+  }
+
+  // Writes BigEndianCodec's bytes without the last one
+  struct TruncatingCodec;
+
+  impl ColumnCodec for TruncatingCodec {
+    fn encode(&self, data: &EnumColumnData) -> Vec<u8> {
+      let mut bytes: Vec<u8> = BigEndianCodec.encode(data);
+      bytes.pop();
+      bytes
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<EnumColumnData, String> {
+      BigEndianCodec.decode(bytes)
+    }
+  }
+
+  #[test]
+  fn test_custom_column_decode_error_is_invalid_data() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    let codec: Arc<dyn ColumnCodec> = Arc::new(TruncatingCodec);
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![1710555318i32, 1710555319], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_custom_column("custom", 900, codec.clone(), EnumColumnData::Int16Vec(vec![-2, 300]))
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(1710555318, 1710555319);
+    writer.try_save()?;
+
+    let mut codecs: CodecRegistry = CodecRegistry::new();
+    codecs.register(900, codec).map_err(|e: String| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.set_codecs(codecs.clone());
+    let error: io::Error = reader.read_all().unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(error.to_string(), "3 bytes is not a whole number of i16 values");
+
+    tokio::runtime::Runtime::new()?.block_on(async {
+      let mut async_reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+      async_reader.set_codecs(codecs);
+      assert_eq!(async_reader.read_all().await.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+      Ok(())
+    })
+  }
+
+  #[test]
+  fn test_read_embedded_at_offset() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    append_segment(temp_file.path().to_str().unwrap(), vec![1, 2, 3], vec![20, 21, 22])?;
+    let tsf_bytes: Vec<u8> = std::fs::read(temp_file.path())?;
+
+    // Unrelated bytes on both sides of the TSF blob
+    let mut container: Vec<u8> = vec![0xAB; 1024];
+    container.extend_from_slice(&tsf_bytes);
+    container.extend_from_slice(&[0xCD; 64]);
+    let container_file: NamedTempFile = NamedTempFile::new()?;
+    std::fs::write(container_file.path(), &container)?;
+    let container_path: &str = container_file.path().to_str().unwrap();
+
+    let mut reader: TSFReader = TSFReader::new_at(container_path, 1024)?;
+    reader.read_all()?;
+    assert!(matches!(reader.get_column("temperature"), Some(EnumColumnData::Int8Vec(values)) if values == &vec![20, 21, 22]));
+    assert_eq!(reader.read_schema()?.len(), 2);
+    // Walking the segment chain runs into the container's bytes after the blob
+    reader.set_ignore_trailing_bytes(true);
+    assert_eq!(reader.timestamps_in_range(2, 3)?, vec![2, 3]);
+
+    // Without the offset the container doesn't start with a TSF header
+    let mut reader: TSFReader = TSFReader::new(container_path)?;
+    assert!(reader.read_all().is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_stream_rows_where() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    append_segment(file_path, vec![1, 2, 3, 4, 5], vec![20, 22, 21, 25, 23])?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+
+    let warm = |row: &DataRow| matches!(row.values[1], EnumDataValue::Int8Value(temperature) if temperature > 21);
+    let matches: Vec<io::Result<DataRow>> = futures::executor::block_on(reader.stream_rows_where(warm).collect::<Vec<_>>());
+    assert_eq!(matches.len(), 3);
+
+    // Rows are built and checked only as they are polled, taking one match stops at the first warm row
+    let checked: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+    let counter: Arc<AtomicUsize> = checked.clone();
+    let first_warm = move |row: &DataRow| {
+      counter.fetch_add(1, Ordering::SeqCst);
+      warm(row)
+    };
+    let first: Vec<DataRow> = futures::executor::block_on(reader.stream_rows_where(first_warm).take(1).collect::<io::Result<Vec<DataRow>>>())?;
+    assert!(matches!(first[0].values[0], EnumDataValue::Int32Value(2)));
+    assert_eq!(checked.load(Ordering::SeqCst), 2);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_datetime32_column_streams_as_datetime() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.add_column_header("metric_time", EnumDataType::DateTime32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![1710555318i32, 1710555319], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(1710555318, 1710555319);
+    writer.try_save()?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+
+    let rows: Vec<DataRow> = futures::executor::block_on(reader.stream_rows().collect::<Vec<_>>())
+      .into_iter()
+      .collect::<io::Result<Vec<DataRow>>>()?;
+
+    assert_eq!(rows.len(), 2);
+    assert!(matches!(rows[0].values[0], EnumDataValue::DateTime32Value(1710555318)));
+    assert!(matches!(rows[1].values[0], EnumDataValue::DateTime32Value(1710555319)));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_nanosecond_datetime64_converts_to_utc() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    let meta: ColumnMeta = ColumnMeta::DateTime { format: "%Y-%m-%dT%H:%M:%S%.9f".to_string(), resolution: Some(EnumTimeResolution::Nanos) };
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.add_column_header_with_meta("metric_time", EnumDataType::DateTime64, EnumDataEnc::None, EnumDataComp::None, true, meta.clone())
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_segment_column_data(SegmentColumnData::from_column_data(EnumColumnData::DateTime64Vec(vec![1710555318123456789]), EnumDataEnc::None, EnumDataComp::None))
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(1710555318123456789, 1710555318123456789);
+    writer.try_save()?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+
+    let header: &SegmentColumnHeader = reader.get_column_header(0).unwrap();
+    assert_eq!(header.get_column_meta(), &meta);
+    let value: i64 = reader.column_slice::<i64>(0).unwrap()[0];
+    let resolution: EnumTimeResolution = header.get_column_meta().time_resolution(header.column_type).unwrap();
+    assert_eq!(resolution.to_utc(value), Utc.timestamp_opt(1710555318, 123456789).single());
+    assert_eq!(header.get_column_meta().format_timestamp(value, header.column_type).as_deref(), Some("2024-03-16T02:15:18.123456789"));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_to_columns_matches_written_data() -> io::Result<()> {
+    let temp_file: NamedTempFile = write_temp_file(sample_segment(3, 3))?;
+    let mut reader: TSFReader = TSFReader::new(temp_file.path().to_str().unwrap())?;
+    reader.read_all()?;
+
+    let columns: Vec<(String, EnumColumnData)> = reader.to_columns();
+    let names: Vec<&str> = columns.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, vec!["metric_time", "column_1", "column_2"]);
+    assert!(matches!(&columns[0].1, EnumColumnData::Int32Vec(v) if v == &vec![SAMPLE_START, SAMPLE_START + 1, SAMPLE_START + 2]));
+    for (column, (_, data)) in columns.iter().enumerate().skip(1) {
+      let expected: Vec<i16> = (0..3).map(|row: usize| sample_value(row, column)).collect();
+      assert!(matches!(data, EnumColumnData::Int16Vec(v) if v == &expected));
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_column_slice_float64_sum() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_header("humidity", EnumDataType::Float64, EnumDataEnc::None, EnumDataComp::None, false)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![1710555318i32, 1710555319, 1710555320], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_segment_column_data(SegmentColumnData::from_column_data(
+      EnumColumnData::Float64Vec(vec![40.5, 41.25, 42.25]),
+      EnumDataEnc::None,
+      EnumDataComp::None,
+    )).map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(1710555318, 1710555320);
+    writer.try_save()?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+
+    let humidity: &[f64] = reader.column_slice::<f64>(1).unwrap();
+    assert_eq!(humidity.iter().sum::<f64>(), 124.0);
+    assert_eq!(reader.column_slice::<i32>(0).unwrap().len(), 3);
+    assert!(reader.column_slice::<f64>(0).is_none());
+    assert!(reader.column_slice::<f64>(2).is_none());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_segment_metadata_round_trip() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    let mut metadata: BTreeMap<String, String> = BTreeMap::new();
+    metadata.insert("source".to_string(), "sensors_2024_03_16.csv".to_string());
+    metadata.insert("ingest_version".to_string(), "0.1.0".to_string());
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.set_metadata(metadata.clone())
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![1710555318i32, 1710555319], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(1710555318, 1710555319);
+    writer.try_save()?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+
+    assert_eq!(reader.metadata(), &metadata);
+    assert_eq!(reader.column_slice::<i32>(0).unwrap(), &[1710555318, 1710555319]);
+
+    Ok(())
+  }
+
+  fn tsf_error(err: &io::Error) -> Option<&TsfError> {
+    err.get_ref().and_then(|inner| inner.downcast_ref::<TsfError>())
+  }
+
+  #[test]
+  fn test_open_errors_name_the_path() -> io::Result<()> {
+    let dir: tempfile::TempDir = tempfile::tempdir()?;
+    let missing: String = dir.path().join("missing.tsf").to_str().unwrap().to_string();
+
+    let err: io::Error = TSFReader::new(&missing).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    assert_eq!(tsf_error(&err), Some(&TsfError::FileNotFound { path: missing.clone() }));
+    assert_eq!(err.to_string(), format!("{} does not exist", missing));
+
+    let in_missing_dir: String = dir.path().join("nodir").join("new.tsf").to_str().unwrap().to_string();
+    let err: io::Error = TSFWriter::new(&in_missing_dir).err().unwrap();
+    assert_eq!(tsf_error(&err), Some(&TsfError::FileNotFound { path: in_missing_dir }));
+
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::PermissionsExt;
+
+      let locked: String = dir.path().join("locked.tsf").to_str().unwrap().to_string();
+      std::fs::write(&locked, b"")?;
+      std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000))?;
+      // Root ignores file modes, so the denial can only be checked for other users
+      if let Err(err) = TSFReader::new(&locked) {
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(tsf_error(&err), Some(&TsfError::PermissionDenied { path: locked }));
+      }
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_row_count_sums_segments_as_u64() -> io::Result<()> {
+    let total: u64 = sum_row_counts([u32::MAX, u32::MAX, 3]);
+    assert_eq!(total, 2 * u32::MAX as u64 + 3);
+    assert!(total > u32::MAX as u64);
+
+    let file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = file.path().to_str().unwrap();
+    append_segment(file_path, vec![1, 2, 3], vec![10, 20, 30])?;
+    append_segment(file_path, vec![4, 5], vec![40, 50])?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    assert_eq!(reader.row_count()?, 5);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_estimated_memory_matches_decoded_columns() -> io::Result<()> {
+    let file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = file.path().to_str().unwrap();
+    append_segment(file_path, vec![1, 2, 3], vec![10, 20, 30])?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+    let decoded: u64 = (0..)
+      .map_while(|index: usize| reader.get_column_data(index))
+      .map(|column: &EnumColumnData| (column.len() * column.data_type().value_size()) as u64)
+      .sum();
+    assert_eq!(decoded, 3 * 4 + 3);
+    assert_eq!(TSFReader::estimated_memory(file_path)?, decoded);
+
+    append_segment(file_path, vec![4, 5], vec![40, 50])?;
+    assert_eq!(TSFReader::estimated_memory(file_path)?, decoded + 2 * 4 + 2);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_header_only_file_reads_as_empty() -> io::Result<()> {
+    let mut file: NamedTempFile = NamedTempFile::new()?;
+    FileHeader::new().write_header(file.as_file_mut())?;
+    let file_path: &str = file.path().to_str().unwrap();
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+    assert_eq!(reader.get_row_count(), 0);
+    assert_eq!(reader.row_count()?, 0);
+    assert!(futures::executor::block_on(reader.stream_rows().collect::<Vec<_>>()).is_empty());
+
+    let async_row_count: usize = tokio::runtime::Runtime::new()?.block_on(async {
+      let mut reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+      reader.read_all().await?;
+      Ok::<usize, io::Error>(reader.stream_rows().collect::<Vec<_>>().await.len())
+    })?;
+    assert_eq!(async_row_count, 0);
+
+    Ok(())
+  }
+
+  fn append_footer_segment(file_path: &str, metric_time: Vec<i32>, temperatures: Vec<i8>) -> io::Result<()> {
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.set_footer(true);
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(metric_time[0] as i64, metric_time[metric_time.len() - 1] as i64);
+    writer.add_column_data(metric_time, EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(temperatures, EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.try_save()
+  }
+
+  #[test]
+  fn test_last_segment_from_footer() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    append_footer_segment(file_path, vec![1, 2], vec![10, 20])?;
+    append_footer_segment(file_path, vec![3, 4], vec![30, 40])?;
+
+    // Only the newest footer is kept and the chain still ends before it
+    let segment_offsets: Vec<u64> = TSFEditor::new(file_path)?.segment_offsets()?;
+    let bytes: Vec<u8> = std::fs::read(file_path)?;
+    assert_eq!(read_util::decode_footer(&bytes[bytes.len() - 16..]), Some(segment_offsets[1]));
+    assert_eq!(TSFReader::new(file_path)?.row_count()?, 4);
+
+    // Breaking the first segment's header stops a chain walk, the footer goes around it
+    let mut bytes: Vec<u8> = bytes;
+    bytes[segment_offsets[0] as usize + 22] ^= 0xFF;
+    std::fs::write(file_path, &bytes)?;
+    assert!(TSFReader::new(file_path)?.row_count().is_err());
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.last_segment()?;
+    assert_eq!(reader.column_slice::<i32>(0).unwrap(), &[3, 4]);
+    assert_eq!(reader.column_slice::<i8>(1).unwrap(), &[30, 40]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_last_segment_without_footer_walks_chain() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    append_footer_segment(file_path, vec![1, 2], vec![10, 20])?;
+    append_segment(file_path, vec![3, 4], vec![30, 40])?;
+    append_segment(file_path, vec![5, 6], vec![50, 60])?;
+
+    // Appending without a footer drops the stale one instead of leaving it inside the chain
+    let bytes: Vec<u8> = std::fs::read(file_path)?;
+    assert_eq!(read_util::decode_footer(&bytes[bytes.len() - 16..]), None);
+    assert_eq!(TSFEditor::new(file_path)?.segment_offsets()?.len(), 3);
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.last_segment()?;
+    assert_eq!(reader.column_slice::<i32>(0).unwrap(), &[5, 6]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_trailing_bytes_after_last_segment_are_ignored() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    append_segment(file_path, vec![1, 2], vec![10, 20])?;
+    append_segment(file_path, vec![3, 4], vec![30, 40])?;
+    let mut bytes: Vec<u8> = std::fs::read(file_path)?;
+    bytes.extend_from_slice(&[0u8, 0, 0xAB, 0xCD, 0xEF]);
+    std::fs::write(file_path, &bytes)?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    assert!(reader.segment_offsets().is_err());
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.set_ignore_trailing_bytes(true);
+    let segment_offsets: Vec<u64> = reader.segment_offsets()?;
+    assert_eq!(segment_offsets.len(), 2);
+    assert_eq!(reader.row_count()?, 4);
+    reader.read_segment_at(segment_offsets[1])?;
+    assert_eq!(reader.column_slice::<i32>(0).unwrap(), &[3, 4]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_empty_and_directory_paths_are_rejected() -> io::Result<()> {
+    let err: io::Error = TSFReader::new("").err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(tsf_error(&err), Some(&TsfError::InvalidPath { path: String::new(), reason: "the path is empty".to_string() }));
+
+    let dir: tempfile::TempDir = tempfile::tempdir()?;
+    let dir_path: String = dir.path().to_str().unwrap().to_string();
+    let err: io::Error = TSFReader::new(&dir_path).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(tsf_error(&err), Some(&TsfError::InvalidPath { path: dir_path.clone(), reason: "it is a directory".to_string() }));
+
+    // The writer checks before taking its lock, so no sidecar is left next to the directory
+    let err: io::Error = TSFWriter::new(&dir_path).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert!(!std::path::Path::new(&format!("{}.lock", dir_path)).exists());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_schema_id_round_trip_and_mismatch() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.set_schema_id(7);
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![1710555318i32, 1710555319], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(1710555318, 1710555319);
+    writer.try_save()?;
+    drop(writer);
+
+    // The longer header moves the first segment, later segments still chain from it
+    append_segment(file_path, vec![1710555320], vec![21])?;
+    assert_eq!(TSFEditor::new(file_path)?.segment_offsets()?[0], 14);
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+    assert_eq!(reader.get_schema_id(), 7);
+    assert_eq!(reader.column_slice::<i32>(0).unwrap(), &[1710555318, 1710555319]);
+    assert!(reader.check_schema_id(7).is_ok());
+    assert!(reader.check_schema_id(0).is_ok());
+
+    let err: io::Error = reader.check_schema_id(8).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(err.to_string(), "Expected schema_id 8 but the file has 7");
+
+    // Files without a schema_id keep the original header and read as unset
+    let plain_file: NamedTempFile = NamedTempFile::new()?;
+    append_segment(plain_file.path().to_str().unwrap(), vec![1710555318], vec![21])?;
+    let mut plain_reader: TSFReader = TSFReader::new(plain_file.path().to_str().unwrap())?;
+    plain_reader.read_all()?;
+    assert_eq!(plain_reader.get_schema_id(), 0);
+    assert!(plain_reader.check_schema_id(7).is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_named_iter_pairs_values_with_schema() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    append_segment(file_path, vec![1710555318], vec![21])?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+    let schema: Vec<ColumnSchema> = reader.schema();
+    let rows: Vec<DataRow> = futures::executor::block_on(reader.stream_rows().collect::<io::Result<Vec<DataRow>>>())?;
+
+    let pairs: Vec<(&str, String)> = rows[0].named_iter(&schema)
+      .map(|(name, value): (&str, &EnumDataValue)| (name, value.to_string()))
+      .collect();
+    assert_eq!(pairs, vec![("metric_time", "1710555318".to_string()), ("temperature", "21".to_string())]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_timestamps_in_range_reads_only_ts_column() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    append_segment(file_path, vec![10, 20, 30, 40, 50, 60], vec![1, 2, 3, 4, 5, 6])?;
+    append_segment(file_path, vec![70, 80], vec![7, 8])?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    assert_eq!(reader.timestamps_in_range(20, 45)?, vec![20, 30, 40]);
+    assert_eq!(reader.timestamps_in_range(55, 75)?, vec![60, 70]);
+    assert_eq!(reader.timestamps_in_range(i64::MIN, i64::MAX)?.len(), 8);
+    assert_eq!(reader.timestamps_in_range(0, 10)?, vec![10]);
+    assert_eq!(reader.timestamps_in_range(81, 100)?, Vec::<i64>::new());
+    assert_eq!(reader.timestamps_in_range(50, 20)?, Vec::<i64>::new());
+
+    // Nothing was decoded into the reader, value columns included
+    assert!(reader.read_stats().is_empty());
+    assert!(reader.get_column_data(1).is_none());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_column_description_round_trip() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    let meta: ColumnMeta = ColumnMeta::Decimal { precision: 5, scale: 1 };
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_header_with_meta("temperature", EnumDataType::Int16, EnumDataEnc::None, EnumDataComp::None, false, meta.clone())
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.describe_column("temperature", "Degrees celsius from the rooftop sensor")
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    assert!(writer.describe_column("humidity", "Missing").is_err());
+    assert!(writer.describe_column("temperature", &"x".repeat(MAX_COLUMN_DESCRIPTION_SIZE + 1)).is_err());
+    writer.add_column_data(vec![1710555318i32, 1710555319], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![215i16, 220], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(1710555318, 1710555319);
+    writer.try_save()?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+
+    let schema: Vec<ColumnSchema> = reader.schema();
+    assert_eq!(schema[0].description, None);
+    assert_eq!(schema[1].description.as_deref(), Some("Degrees celsius from the rooftop sensor"));
+    assert_eq!(reader.get_column_header(1).unwrap().get_column_meta(), &meta);
+    assert_eq!(reader.column_slice::<i16>(1).unwrap(), &[215, 220]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_scaled_integer_column_reads_as_float() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_header_with_meta("price", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, false, ColumnMeta::Scale { scale: 2 })
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![1710555318i32, 1710555319], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![1234i32, -5], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(1710555318, 1710555319);
+    writer.try_save()?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+    let raw: Vec<DataRow> = futures::executor::block_on(reader.stream_rows().collect::<io::Result<Vec<DataRow>>>())?;
+    assert!(matches!(raw[0].values[1], EnumDataValue::Int32Value(1234)));
+
+    reader.set_apply_scale(true);
+    let prices = |rows: &[DataRow]| -> Vec<f64> {
+      rows.iter().map(|row: &DataRow| match row.values[1] {
+        EnumDataValue::Float64Value(value) => value,
+        ref other => panic!("Expected a scaled float, got {:?}", other),
+      }).collect()
+    };
+    let scaled: Vec<DataRow> = futures::executor::block_on(reader.stream_rows().collect::<io::Result<Vec<DataRow>>>())?;
+    assert_eq!(prices(&scaled), vec![12.34, -0.05]);
+    assert!(matches!(scaled[0].values[0], EnumDataValue::Int32Value(1710555318)));
+    assert_eq!(reader.column_slice::<i32>(1).unwrap(), &[1234, -5]);
+
+    let async_scaled: Vec<DataRow> = tokio::runtime::Runtime::new()?.block_on(async {
+      let mut reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+      reader.set_apply_scale(true);
+      reader.read_all().await?;
+      let rows: Vec<io::Result<AsyncDataRow>> = reader.stream_rows().collect().await;
+      rows.into_iter().map(|row: io::Result<AsyncDataRow>| row.map(|row: AsyncDataRow| DataRow { values: row.values })).collect::<io::Result<Vec<DataRow>>>()
+    })?;
+    assert_eq!(prices(&async_scaled), vec![12.34, -0.05]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_column_layout_matches_written_bytes() -> io::Result<()> {
+    let temp_file: NamedTempFile = write_temp_file(sample_segment(2, 2))?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+    let layout: Vec<ColumnLayout> = reader.column_layout();
+
+    let bytes: Vec<u8> = std::fs::read(file_path)?;
+    let column_bytes = |index: usize| -> &[u8] {
+      let start: usize = layout[index].file_pos as usize;
+      &bytes[start..start + layout[index].column_size as usize]
+    };
+
+    assert_eq!(layout.len(), 2);
+    assert_eq!(layout[0].name, "metric_time");
+    assert_eq!(layout[0].column_type, EnumDataType::Int32);
+    assert_eq!(column_bytes(0), [SAMPLE_START.to_le_bytes(), (SAMPLE_START + 1).to_le_bytes()].concat());
+    assert_eq!(layout[1].file_pos, layout[0].file_pos + 8);
+    assert_eq!(column_bytes(1), [sample_value(0, 1).to_le_bytes(), sample_value(1, 1).to_le_bytes()].concat());
+    assert_eq!(layout[1].file_pos + layout[1].column_size, bytes.len() as u64);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_corrupted_column_fails_only_its_column_check() -> io::Result<()> {
+    let temp_file: NamedTempFile = write_temp_file(sample_segment(4, 3))?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+    let layout: Vec<ColumnLayout> = reader.column_layout();
+    let mut bytes: Vec<u8> = std::fs::read(file_path)?;
+    for (index, column) in layout.iter().enumerate() {
+      let start: usize = column.file_pos as usize;
+      let column_check: [u8; 8] = calculate_checksum(EnumChecksumAlg::XxHash64, &bytes[start..start + column.column_size as usize]);
+      assert_eq!(reader.get_column_header(index).unwrap().get_column_check(), column_check);
+    }
+
+    // Flip a bit in the last column, the segment header is untouched
+    bytes[layout[2].file_pos as usize + 1] ^= 0x01;
+    std::fs::write(file_path, &bytes)?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    let err: io::Error = reader.read_all().expect_err("Corrupted column was accepted");
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("Column column_2 failed column check verification"), "{}", err);
+
+    // Reading only the timestamp column still works
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    assert_eq!(reader.timestamps_in_range(i64::MIN, i64::MAX)?.len(), 4);
+
+    let async_err: io::Error = tokio::runtime::Runtime::new()?.block_on(async {
+      let mut reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+      reader.read_all().await
+    }).expect_err("Corrupted column was accepted");
+    assert!(async_err.to_string().contains("Column column_2 failed column check verification"), "{}", async_err);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_raw_segment_copies_to_a_new_file() -> io::Result<()> {
+    let source_file: NamedTempFile = NamedTempFile::new()?;
+    let source_path: &str = source_file.path().to_str().unwrap();
+    append_segment(source_path, vec![1710555318, 1710555319], vec![21, 22])?;
+    append_segment(source_path, vec![1710555320, 1710555321, 1710555322], vec![23, -24, 25])?;
+    let offsets: Vec<u64> = TSFEditor::new(source_path)?.segment_offsets()?;
+
+    let mut reader: TSFReader = TSFReader::new(source_path)?;
+    let raw: Vec<u8> = reader.read_raw_segment(offsets[1])?;
+    assert!(reader.read_raw_segment(offsets[1] + 1).is_err());
+
+    let mut copy_file: NamedTempFile = NamedTempFile::new()?;
+    FileHeader::new().write_header(copy_file.as_file_mut())?;
+    io::Write::write_all(copy_file.as_file_mut(), &raw)?;
+
+    let mut source: TSFReader = TSFReader::new(source_path)?;
+    source.last_segment()?;
+    let mut copy: TSFReader = TSFReader::new(copy_file.path().to_str().unwrap())?;
+    copy.read_all()?;
+
+    let rows = |reader: &TSFReader| -> io::Result<Vec<String>> {
+      let rows: Vec<DataRow> = futures::executor::block_on(reader.stream_rows().collect::<io::Result<Vec<DataRow>>>())?;
+      Ok(rows.iter().map(|row: &DataRow| format!("{:?}", row.values)).collect())
+    };
+    assert_eq!(rows(&copy)?.len(), 3);
+    assert_eq!(rows(&copy)?, rows(&source)?);
+    assert_eq!(copy.segment_content_digest(), source.segment_content_digest());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_content_digest_ignores_txid() -> io::Result<()> {
+    let write_file = |temperatures: Vec<i8>| -> io::Result<NamedTempFile> {
+      let temp_file: NamedTempFile = NamedTempFile::new()?;
+      let mut writer: TSFWriter = TSFWriter::new(temp_file.path().to_str().unwrap())?;
+      writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+        .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+      writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false)
+        .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+      writer.add_column_data(vec![1710555318i32, 1710555319], EnumDataEnc::None, EnumDataComp::None)
+        .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+      writer.add_column_data(temperatures, EnumDataEnc::None, EnumDataComp::None)
+        .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+      writer.update_segment_dates(1710555318, 1710555319);
+      writer.try_save()?;
+      Ok(temp_file)
+    };
+    let digest = |temp_file: &NamedTempFile| -> io::Result<[u8; 32]> {
+      let mut reader: TSFReader = TSFReader::new(temp_file.path().to_str().unwrap())?;
+      reader.content_digest()
+    };
+
+    let first: NamedTempFile = write_file(vec![20, 21])?;
+    let second: NamedTempFile = write_file(vec![20, 21])?;
+    let different: NamedTempFile = write_file(vec![20, 22])?;
+
+    // Each save gets its own uuid_txid, so the raw bytes differ
+    assert_ne!(std::fs::read(first.path())?, std::fs::read(second.path())?);
+    assert_eq!(digest(&first)?, digest(&second)?);
+    assert_ne!(digest(&first)?, digest(&different)?);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_content_digest_covers_every_segment() -> io::Result<()> {
+    let write_file = |second_temperature: i8| -> io::Result<NamedTempFile> {
+      let temp_file: NamedTempFile = NamedTempFile::new()?;
+      let file_path: &str = temp_file.path().to_str().unwrap();
+      append_segment(file_path, vec![1710555318, 1710555319], vec![20, 21])?;
+      append_segment(file_path, vec![1710555320], vec![second_temperature])?;
+      Ok(temp_file)
+    };
+    let digest = |temp_file: &NamedTempFile| -> io::Result<[u8; 32]> {
+      TSFReader::new(temp_file.path().to_str().unwrap())?.content_digest()
+    };
+
+    let first: NamedTempFile = write_file(22)?;
+    let second: NamedTempFile = write_file(22)?;
+    let different: NamedTempFile = write_file(23)?;
+
+    assert_eq!(digest(&first)?, digest(&second)?);
+    // Only the second segment differs
+    assert_ne!(digest(&first)?, digest(&different)?);
+
+    Ok(())
+  }
+
+  mod roundtrip {
+    use super::*;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use crate::tsf::async_tsf_reader::AsyncTSFReader;
+    use crate::tsf::segments::types::EnumDataType;
+
+    fn column_strategy() -> impl Strategy<Value = EnumColumnData> {
+      prop_oneof![
+        vec(any::<i8>(), 1..64).prop_map(EnumColumnData::Int8Vec),
+        vec(any::<i16>(), 1..64).prop_map(EnumColumnData::Int16Vec),
+        vec(any::<i32>(), 1..64).prop_map(EnumColumnData::Int32Vec),
+        vec(any::<i64>(), 1..64).prop_map(EnumColumnData::Int64Vec),
+        vec(any::<u8>(), 1..64).prop_map(EnumColumnData::UInt8Vec),
+        vec(any::<u16>(), 1..64).prop_map(EnumColumnData::UInt16Vec),
+        vec(any::<u32>(), 1..64).prop_map(EnumColumnData::UInt32Vec),
+        vec(any::<u64>(), 1..64).prop_map(EnumColumnData::UInt64Vec),
+        vec(any::<f32>(), 1..64).prop_map(EnumColumnData::Float32Vec),
+        vec(any::<f64>(), 1..64).prop_map(EnumColumnData::Float64Vec),
+        vec(any::<bool>(), 1..64).prop_map(EnumColumnData::BooleanVec),
+        vec(any::<i32>(), 1..64).prop_map(EnumColumnData::DateTime32Vec),
+        vec(any::<i64>(), 1..64).prop_map(EnumColumnData::DateTime64Vec),
+      ]
+    }
+
+    fn write_column(file_path: &str, column: &EnumColumnData, encoding: EnumDataEnc, compression: EnumDataComp) -> io::Result<()> {
+      let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+      writer.add_column_header("value", column.data_type(), encoding, compression, false)
+        .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+      writer.add_segment_column_data(SegmentColumnData::from_column_data(column.clone(), encoding, compression))
+        .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+      writer.update_segment_dates(0, 0);
+      writer.try_save()
+    }
+
+    #[test]
+    fn test_byte_and_packed_boolean_files_read_the_same() -> io::Result<()> {
+      let values: Vec<bool> = (0..19).map(|index: usize| index.is_multiple_of(3)).collect();
+      let column: EnumColumnData = EnumColumnData::BooleanVec(values.clone());
+      let byte_file: NamedTempFile = NamedTempFile::new()?;
+      let packed_file: NamedTempFile = NamedTempFile::new()?;
+      write_column(byte_file.path().to_str().unwrap(), &column, EnumDataEnc::None, EnumDataComp::None)?;
+      write_column(packed_file.path().to_str().unwrap(), &column, EnumDataEnc::BitPacked, EnumDataComp::None)?;
+
+      let mut byte_reader: TSFReader = TSFReader::new(byte_file.path().to_str().unwrap())?;
+      byte_reader.read_all()?;
+      let mut packed_reader: TSFReader = TSFReader::new(packed_file.path().to_str().unwrap())?;
+      packed_reader.read_all()?;
+
+      // A byte per value against a u32 count and 3 bytes of bits
+      assert_eq!(byte_reader.get_column_header(0).unwrap().column_size, 19);
+      assert_eq!(packed_reader.get_column_header(0).unwrap().column_size, 7);
+      assert_eq!(byte_reader.column_slice::<bool>(0).unwrap(), &values[..]);
+      assert_eq!(packed_reader.column_slice::<bool>(0).unwrap(), &values[..]);
+
+      Ok(())
+    }
+
+    // Rows as Debug strings, which keep NaN comparable where PartialEq would not
+    fn expected_rows(column: &EnumColumnData) -> Vec<String> {
+      (0..column.len()).map(|index: usize| format!("{:?}", vec![column.value_at(index).unwrap()])).collect()
+    }
+
+    proptest! {
+      #![proptest_config(ProptestConfig::with_cases(32))]
+
+      #[test]
+      fn test_every_type_encoding_and_compression_round_trips(column in column_strategy()) {
+        let data_type: EnumDataType = column.data_type();
+        for encoding in [EnumDataEnc::None, EnumDataEnc::Delta, EnumDataEnc::DoubleDelta, EnumDataEnc::BitPacked] {
+          if !encoding.is_valid_for(data_type) {
+            continue;
+          }
+          for compression in [EnumDataComp::None, EnumDataComp::ZStd] {
+            let temp_file: NamedTempFile = NamedTempFile::new()?;
+            let file_path: &str = temp_file.path().to_str().unwrap();
+            write_column(file_path, &column, encoding, compression)?;
+
+            let mut reader: TSFReader = TSFReader::new(file_path)?;
+            reader.read_all()?;
+            let header: &SegmentColumnHeader = reader.get_column_header(0).unwrap();
+            prop_assert_eq!(header.column_type, data_type);
+            prop_assert_eq!(header.column_enc, encoding);
+            prop_assert_eq!(header.column_comp, compression);
+
+            // Delta encodings have to change the stored bytes, not just the header
+            if compression == EnumDataComp::None {
+              let layout: ColumnLayout = reader.column_layout().remove(0);
+              let bytes: Vec<u8> = std::fs::read(file_path)?;
+              let stored: &[u8] = &bytes[layout.file_pos as usize..(layout.file_pos + layout.column_size) as usize];
+              prop_assert_eq!(stored, &column.to_encoded_bytes(encoding)[..]);
+              if encoding != EnumDataEnc::None {
+                prop_assert_ne!(stored, &column.to_le_bytes()[..]);
+              }
+            }
+
+            let decoded: &EnumColumnData = reader.segment_data.get_segment_data(0).unwrap().get_data().unwrap();
+            prop_assert_eq!(decoded.to_le_bytes(), column.to_le_bytes());
+
+            let expected: Vec<String> = expected_rows(&column);
+            let rows: Vec<String> = futures::executor::block_on(reader.stream_rows().collect::<Vec<_>>())
+              .into_iter()
+              .map(|row: io::Result<DataRow>| row.map(|row: DataRow| format!("{:?}", row.values)))
+              .collect::<io::Result<Vec<String>>>()?;
+            prop_assert_eq!(&rows, &expected);
+
+            let async_rows: Vec<String> = tokio::runtime::Runtime::new()?.block_on(async {
+              let mut reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+              reader.read_all().await?;
+              reader.stream_rows().collect::<Vec<_>>().await
+                .into_iter()
+                .map(|row| row.map(|row| format!("{:?}", row.values)))
+                .collect::<io::Result<Vec<String>>>()
+            })?;
+            prop_assert_eq!(&async_rows, &expected);
+          }
+        }
+      }
+    }
+  }
+}
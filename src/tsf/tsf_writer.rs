@@ -1,11 +1,15 @@
-use std::{fs::{self, File, OpenOptions}, io, path::PathBuf};
+use std::{collections::BTreeMap, fs::{self, File, OpenOptions}, io::{self, Seek, SeekFrom, Write}, path::PathBuf, sync::Arc};
 
+use super::error::{check_path, open_error};
 use super::header::FileHeader;
+use super::read_util::{chain_end, encode_footer};
+use super::write_lock::WriteLock;
 use super::segments::{
+  codec::ColumnCodec,
   segment_column_data::{ColumnDataCreator, SegmentColumnData},
   segment_data::SegmentData,
   segment_data_header::SegmentColumnHeader,
-  types::{EnumDataType, EnumDataEnc, EnumDataComp}
+  types::{ColumnMeta, EnumChecksumAlg, EnumColumnData, EnumDataType, EnumDataEnc, EnumDataComp}
 };
 
 pub struct TSFWriter {
@@ -14,23 +18,33 @@ pub struct TSFWriter {
   file_exists: bool,
   file_header: FileHeader,
   segment_data: SegmentData,
+  // Write a footer pointing at the segment after appending it, see read_util::FOOTER_MAGIC
+  footer: bool,
   cleanup: bool,
+  // Held for the writer's lifetime and released on drop, after any cleanup of a failed new file
+  _write_lock: WriteLock,
 }
 
 impl TSFWriter {
   pub fn new(path: &str) -> io::Result<Self> {
-    let path_buf: PathBuf = PathBuf::from(path);
+    let path_buf: PathBuf = check_path(path)?;
+    let write_lock: WriteLock = WriteLock::acquire(&path_buf)
+      .map_err(|e: io::Error| open_error(e, &path_buf))?;
     let file_exists: bool = path_buf.exists();
 
     let file: File = if file_exists {
       OpenOptions::new()
-        .append(true)
-        .open(&path_buf)?
+        .read(true)
+        .write(true)
+        .open(&path_buf)
+        .map_err(|e: io::Error| open_error(e, &path_buf))?
     } else {
       OpenOptions::new()
         .create(true)
+        .read(true)
         .write(true)
-        .open(&path_buf)?
+        .open(&path_buf)
+        .map_err(|e: io::Error| open_error(e, &path_buf))?
     };
 
     let file_header: FileHeader = FileHeader::new();
@@ -43,23 +57,34 @@ impl TSFWriter {
       file_exists,
       file_header,
       segment_data,
+      footer: false,
       cleanup: false,
+      _write_lock: write_lock,
     })
   }
 
   pub fn add_column_header(&mut self, column_name: &str, column_type: EnumDataType, encoding: EnumDataEnc, compression: EnumDataComp, ts_column: bool) -> Result<(), String> {
+    self.add_column_header_with_meta(column_name, column_type, encoding, compression, ts_column, ColumnMeta::None)
+  }
+
+  pub fn add_column_header_with_meta(&mut self, column_name: &str, column_type: EnumDataType, encoding: EnumDataEnc, compression: EnumDataComp, ts_column: bool, column_meta: ColumnMeta) -> Result<(), String> {
     let header: SegmentColumnHeader = SegmentColumnHeader::new(
       column_name.to_string(),
       column_type,
       encoding,
       compression,
-    );
-    
+    ).with_meta(column_meta);
+
     self.segment_data.add_column_header(header, ts_column)?;
 
     Ok(())
   }
 
+  // Stored with the column header, the column must already have been added
+  pub fn describe_column(&mut self, column_name: &str, description: &str) -> Result<(), String> {
+    self.segment_data.describe_column(column_name, description)
+  }
+
   pub fn add_column_data<T>(&mut self, column: Vec<T>, encoding: EnumDataEnc, compression: EnumDataComp) -> Result<(), String>
   where
       T: ColumnDataCreator + Sized,
@@ -75,10 +100,83 @@ impl TSFWriter {
       Ok(())
   }
 
+  // Adds a column that was already built, e.g. with SegmentColumnData::from_column_data
+  pub fn add_segment_column_data(&mut self, data_segment: SegmentColumnData) -> Result<(), String> {
+    self.segment_data.add_column_data(data_segment)
+  }
+
+  // Adds both header and data of a column read back by a reader with codec registered under type_code.
+  // Add it once the columns before it have their data
+  pub fn add_custom_column(&mut self, column_name: &str, type_code: u16, codec: Arc<dyn ColumnCodec>, data: EnumColumnData) -> Result<(), String> {
+    self.segment_data.add_custom_column(column_name, type_code, codec, data)
+  }
+
+  // Written to the header of a new file, appending to an existing file leaves its schema_id as it is
+  pub fn set_schema_id(&mut self, schema_id: u64) {
+    self.file_header.set_schema_id(schema_id);
+  }
+
+  pub fn set_checksum_alg(&mut self, checksum_alg: EnumChecksumAlg) {
+    self.segment_data.set_checksum_alg(checksum_alg);
+  }
+
+  pub fn set_metadata(&mut self, metadata: BTreeMap<String, String>) -> Result<(), String> {
+    self.segment_data.set_metadata(metadata)
+  }
+
+  // Pads the segment so the one appended after it starts on a multiple of alignment bytes, e.g. 4096
+  // for direct I/O. The first segment still starts right after the file header
+  pub fn set_segment_alignment(&mut self, alignment: u64) -> Result<(), String> {
+    self.segment_data.set_alignment(alignment)
+  }
+
+  // Grows the file to the segment's full size before writing it, for fewer and more contiguous extents
+  pub fn set_presize(&mut self, presize: bool) {
+    self.segment_data.set_presize(presize);
+  }
+
+  // Keep a footer with the last segment's position so readers can reach it without walking the chain
+  pub fn set_footer(&mut self, footer: bool) {
+    self.footer = footer;
+  }
+
+  // Bytes each column of a streamed segment buffers before it is written out, DEFAULT_FLUSH_THRESHOLD
+  // unless set. Smaller saves memory on wide streams, larger makes fewer writes
+  pub fn with_flush_threshold(mut self, flush_threshold: usize) -> Self {
+    self.segment_data.set_flush_threshold(flush_threshold);
+    self
+  }
+
+  // Writes the last finished stream made to its column spools
+  pub fn stream_flushes(&self) -> usize {
+    self.segment_data.get_stream_flushes()
+  }
+
+  pub fn set_auto_sort(&mut self, auto_sort: bool) {
+    self.segment_data.set_auto_sort(auto_sort);
+  }
+
   pub fn update_segment_dates(&mut self, date_start: i64, date_end: i64) {
     self.segment_data.update_header_dates(date_start, date_end);
   }
 
+  // Starts a segment written in batches instead of all at once, see SegmentData::begin_stream. The columns
+  // must have been added without data, each batch is appended with append_batch and finish_stream
+  // writes the segment header last
+  pub fn begin_stream(&mut self) -> io::Result<()> {
+    self.seek_segment_start()?;
+    self.segment_data.begin_stream(&mut self.file)
+  }
+
+  pub fn append_batch(&mut self, columns: &[EnumColumnData]) -> Result<(), String> {
+    self.segment_data.append_batch(columns)
+  }
+
+  pub fn finish_stream(&mut self) -> io::Result<()> {
+    self.segment_data.finish_stream(&mut self.file)?;
+    self.write_footer()
+  }
+
   pub fn try_save(&mut self) -> io::Result<()> {
     self.cleanup = false;
     if let Err(e) = self.save() {
@@ -90,8 +188,30 @@ impl TSFWriter {
 
   // Save the SegmentData to the file
   fn save(&mut self) -> io::Result<()> {
-    self.file_header.write_header(&mut self.file)?;
+    self.seek_segment_start()?;
     self.segment_data.write_to_file(&mut self.file)?;
+    self.write_footer()
+  }
+
+  // Appending to an existing file adds another segment after the ones already there, a footer from the
+  // last append is cut off first. The file is not opened in append mode so a presized segment can be
+  // written into the space it reserved
+  fn seek_segment_start(&mut self) -> io::Result<()> {
+    let (segments_end, footer): (u64, Option<u64>) = chain_end(&mut self.file)?;
+    if footer.is_some() {
+      self.file.set_len(segments_end)?;
+    }
+    if self.file.seek(SeekFrom::Start(segments_end))? == 0 {
+      self.file_header.write_header(&mut self.file)?;
+    }
+    Ok(())
+  }
+
+  fn write_footer(&mut self) -> io::Result<()> {
+    if self.footer {
+      self.file.seek(SeekFrom::End(0))?;
+      self.file.write_all(&encode_footer(self.segment_data.get_segment_pos()))?;
+    }
     Ok(())
   }
 }
@@ -109,6 +229,13 @@ mod tests {
   use super::*;
   use std::io::Read;
   use tempfile::NamedTempFile;
+  use tokio_stream::StreamExt;
+  use crate::tsf::error::TsfError;
+  use crate::tsf::segments::types::{EnumDataValue, MAX_COLUMN_NAME_SIZE};
+  use crate::tsf::tsf_reader::{DataRow, TSFReader};
+  use crate::tsf::async_tsf_reader::{AsyncTSFReader, DataRow as AsyncDataRow};
+  use crate::tsf::tsf_editor::TSFEditor;
+  use crate::test_support::append_segment;
 
   #[test]
   fn test_tsf_writer_new() -> io::Result<()> {
@@ -121,6 +248,86 @@ mod tests {
     Ok(())
   }
 
+  #[test]
+  fn test_second_writer_fails_while_first_is_open() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    let writer: TSFWriter = TSFWriter::new(file_path)?;
+    assert!(WriteLock::is_locked(temp_file.path()));
+    let err: io::Error = TSFWriter::new(file_path).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+    assert_eq!(err.to_string(), TsfError::AlreadyLocked { path: file_path.to_string() }.to_string());
+
+    // Readers ignore the lock, and dropping the writer releases it
+    assert!(TSFReader::new(file_path).is_ok());
+    drop(writer);
+    assert!(!WriteLock::is_locked(temp_file.path()));
+    assert!(TSFWriter::new(file_path).is_ok());
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_aligned_segments_start_on_alignment_and_read_back() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    for segment in 0..3i32 {
+      let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+      writer.set_segment_alignment(4096)
+        .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+      writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+        .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+      writer.add_column_data(vec![segment * 2, segment * 2 + 1], EnumDataEnc::None, EnumDataComp::None)
+        .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+      writer.update_segment_dates(segment as i64 * 2, segment as i64 * 2 + 1);
+      writer.try_save()?;
+    }
+
+    // The first segment follows the file header, every later one starts on a 4K boundary
+    let segment_offsets: Vec<u64> = TSFEditor::new(file_path)?.segment_offsets()?;
+    assert_eq!(segment_offsets, vec![6, 4096, 8192]);
+    assert_eq!(std::fs::metadata(file_path)?.len(), 12288);
+
+    let reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+    let rows: Vec<AsyncDataRow> = reader.stream_segments().collect::<io::Result<Vec<AsyncDataRow>>>().await?;
+    let timestamps: Vec<i64> = rows.iter().map(|row: &AsyncDataRow| row.values[0].as_i64().unwrap()).collect();
+    assert_eq!(timestamps, vec![0, 1, 2, 3, 4, 5]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_presized_segment_matches_next_offset() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.set_presize(true);
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_header("temperature", EnumDataType::Int16, EnumDataEnc::None, EnumDataComp::ZStd, false)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![1710555318i32, 1710555319, 1710555320], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![215i16, 220, 218], EnumDataEnc::None, EnumDataComp::ZStd)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(1710555318, 1710555320);
+    writer.try_save()?;
+    drop(writer);
+
+    let next_offset: u32 = TSFEditor::new(file_path)?.read_segment_header(6)?.next_offset.unwrap();
+    assert_eq!(std::fs::metadata(file_path)?.len(), 6 + next_offset as u64);
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+    assert_eq!(reader.column_slice::<i32>(0).unwrap(), &[1710555318, 1710555319, 1710555320]);
+    assert_eq!(reader.column_slice::<i16>(1).unwrap(), &[215, 220, 218]);
+
+    Ok(())
+  }
+
   #[test]
   fn test_add_column_header() -> io::Result<()> {
     let temp_file: NamedTempFile = NamedTempFile::new()?;
@@ -134,6 +341,97 @@ mod tests {
     Ok(())
   }
 
+  #[test]
+  fn test_column_names_with_newline_or_comma_rejected() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    for column_name in ["temp\nerature", "temp,erature"] {
+      let result: Result<(), String> = writer.add_column_header(column_name, EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false);
+      assert_eq!(result.unwrap_err(), String::from(TsfError::InvalidColumnName { column: column_name.to_string() }));
+    }
+    assert_eq!(writer.segment_data.get_column_count(), 0);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_column_name_at_u16_limit_round_trips() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    let longest_name: String = "n".repeat(MAX_COLUMN_NAME_SIZE);
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    let too_long: Result<(), String> = writer.add_column_header(&format!("{}n", longest_name), EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false);
+    assert_eq!(too_long.unwrap_err(), String::from(TsfError::ColumnNameTooLong { length: MAX_COLUMN_NAME_SIZE + 1 }));
+    assert_eq!(writer.segment_data.get_column_count(), 0);
+
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_header(&longest_name, EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![1710555318i32, 1710555319], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![20i8, 21], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(1710555318, 1710555319);
+    writer.try_save()?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+    assert_eq!(reader.get_column_header(1).unwrap().column_name, longest_name);
+    assert_eq!(reader.column_slice::<i8>(1).unwrap(), &[20, 21]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_delta_encoding_validated_per_type() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    let rejected: Result<(), String> = writer.add_column_header("value", EnumDataType::Float64, EnumDataEnc::Delta, EnumDataComp::None, false);
+    assert_eq!(rejected.unwrap_err(), String::from(TsfError::UnsupportedEncoding {
+      column: "value".to_string(),
+      encoding: EnumDataEnc::Delta,
+      data_type: EnumDataType::Float64,
+    }));
+    assert_eq!(writer.segment_data.get_column_count(), 0);
+
+    writer.add_column_header("metric_time", EnumDataType::Int64, EnumDataEnc::Delta, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    assert_eq!(writer.segment_data.get_column_count(), 1);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_created_at_round_trips_and_is_checksummed() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    let before: i64 = chrono::Utc::now().timestamp();
+    append_segment(file_path, vec![1, 2], vec![10, 20])?;
+    let after: i64 = chrono::Utc::now().timestamp();
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+    let created_at: i64 = reader.created_at();
+    assert!(before <= created_at && created_at <= after);
+
+    // created_at sits right before the 8 byte segment check at the end of the header
+    let layout_pos: usize = reader.column_layout()[0].file_pos as usize;
+    let mut bytes: Vec<u8> = std::fs::read(file_path)?;
+    assert_eq!(bytes[layout_pos - 16..layout_pos - 8], created_at.to_le_bytes());
+    bytes[layout_pos - 16] ^= 0xFF;
+    std::fs::write(file_path, &bytes)?;
+    assert!(TSFReader::new(file_path)?.read_all().is_err());
+
+    Ok(())
+  }
+
   #[test]
   fn test_add_column_data_and_save() -> io::Result<()> {
     let temp_file: NamedTempFile = NamedTempFile::new()?;
@@ -168,4 +466,338 @@ mod tests {
 
     Ok(())
   }
+
+  fn write_and_read_with_checksum(checksum_alg: EnumChecksumAlg) -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.set_checksum_alg(checksum_alg);
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![1710555318i32, 1710555319, 1710555320], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(1710555318, 1710555320);
+    writer.try_save()?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+
+    let rows: Vec<DataRow> = futures::executor::block_on(reader.stream_rows().collect::<Vec<_>>())
+      .into_iter()
+      .collect::<io::Result<Vec<DataRow>>>()?;
+    assert_eq!(rows.len(), 3);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_save_and_read_with_xxhash64() -> io::Result<()> {
+    write_and_read_with_checksum(EnumChecksumAlg::XxHash64)
+  }
+
+  #[test]
+  fn test_save_and_read_with_crc32c() -> io::Result<()> {
+    write_and_read_with_checksum(EnumChecksumAlg::Crc32c)
+  }
+
+  #[test]
+  fn test_decimal_meta_survives_write_and_read() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_header_with_meta("price", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, false,
+      ColumnMeta::Decimal { precision: 10, scale: 2 })
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![1710555318i32, 1710555319], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![1999i32, 2450], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(1710555318, 1710555319);
+    writer.try_save()?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+
+    let time_header: &SegmentColumnHeader = reader.get_column_header(0).unwrap();
+    assert_eq!(time_header.get_column_meta(), &ColumnMeta::None);
+    let price_header: &SegmentColumnHeader = reader.get_column_header(1).unwrap();
+    assert_eq!(price_header.column_name, "price");
+    assert_eq!(price_header.get_column_meta(), &ColumnMeta::Decimal { precision: 10, scale: 2 });
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_add_float64_column_from_column_data() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_header("humidity", EnumDataType::Float64, EnumDataEnc::None, EnumDataComp::None, false)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![1710555318i32, 1710555319], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let humidity: SegmentColumnData = SegmentColumnData::from_column_data(
+      EnumColumnData::Float64Vec(vec![41.5, 42.25]),
+      EnumDataEnc::None,
+      EnumDataComp::None,
+    );
+    writer.add_segment_column_data(humidity)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(1710555318, 1710555319);
+    writer.try_save()?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+
+    let humidity_header: &SegmentColumnHeader = reader.get_column_header(1).unwrap();
+    assert_eq!(humidity_header.column_type, EnumDataType::Float64);
+    assert_eq!(humidity_header.column_size, 16);
+
+    Ok(())
+  }
+
+  fn write_unsorted_timestamps(file_path: &str, auto_sort: bool) -> io::Result<()> {
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.set_auto_sort(auto_sort);
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![1710555320i32, 1710555318, 1710555319], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![22i8, 20, 21], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(1710555318, 1710555320);
+    writer.try_save()
+  }
+
+  #[test]
+  fn test_unsorted_timestamps_are_rejected() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    let err: io::Error = write_unsorted_timestamps(file_path, false).expect_err("Unsorted timestamps should fail");
+
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let tsf_err: &TsfError = err.get_ref().and_then(|inner| inner.downcast_ref::<TsfError>()).unwrap();
+    assert_eq!(tsf_err, &TsfError::TimestampNotSorted { column: "metric_time".to_string() });
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_empty_segment_or_inverted_dates_are_not_written() -> io::Result<()> {
+    let dir: tempfile::TempDir = tempfile::tempdir()?;
+    let file_path: std::path::PathBuf = dir.path().join("empty.tsf");
+    let tsf_error = |err: io::Error| err.get_ref().and_then(|inner| inner.downcast_ref::<TsfError>()).cloned();
+
+    // Headers but no rows, e.g. everything was filtered out before the write
+    let mut writer: TSFWriter = TSFWriter::new(file_path.to_str().unwrap())?;
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(1710555318, 1710555320);
+    assert_eq!(writer.try_save().map_err(tsf_error).unwrap_err(), Some(TsfError::EmptySegment));
+    drop(writer);
+    assert!(!file_path.exists());
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path.to_str().unwrap())?;
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![1710555318i32, 1710555320], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(1710555320, 1710555318);
+    assert_eq!(
+      writer.try_save().map_err(tsf_error).unwrap_err(),
+      Some(TsfError::InvalidDateRange { start: 1710555320, end: 1710555318 })
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_unsorted_timestamps_auto_sort() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+
+    write_unsorted_timestamps(file_path, true)?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+
+    let rows: Vec<DataRow> = futures::executor::block_on(reader.stream_rows().collect::<Vec<_>>())
+      .into_iter()
+      .collect::<io::Result<Vec<DataRow>>>()?;
+
+    let expected: Vec<(i32, i8)> = vec![(1710555318, 20), (1710555319, 21), (1710555320, 22)];
+    for (row, (time, temperature)) in rows.iter().zip(expected) {
+      assert!(matches!(row.values[0], EnumDataValue::Int32Value(value) if value == time));
+      assert!(matches!(row.values[1], EnumDataValue::Int8Value(value) if value == temperature));
+    }
+    assert_eq!(rows.len(), 3);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_streamed_segment_writes_header_once() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    let to_io = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true).map_err(to_io)?;
+    writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false).map_err(to_io)?;
+    writer.begin_stream()?;
+
+    let batches: Vec<(Vec<i32>, Vec<i8>)> = vec![(vec![10, 11], vec![1, 2]), (vec![12, 13, 14], vec![3, 4, 5]), (vec![15], vec![6])];
+    for (metric_time, temperature) in batches {
+      writer.append_batch(&[EnumColumnData::Int32Vec(metric_time), EnumColumnData::Int8Vec(temperature)]).map_err(to_io)?;
+    }
+    // A batch that goes back in time is rejected and leaves the segment as it was
+    assert!(writer.append_batch(&[EnumColumnData::Int32Vec(vec![9]), EnumColumnData::Int8Vec(vec![0])]).is_err());
+
+    // Until finish only the zeroed header space is in the file, the batches sit in the column spools
+    let pending: Vec<u8> = fs::read(file_path)?;
+    assert!(pending.len() > 6);
+    assert!(pending[6..].iter().all(|byte: &u8| *byte == 0));
+
+    writer.finish_stream()?;
+    drop(writer);
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+    assert_eq!(reader.column_slice::<i32>(0).unwrap(), &[10, 11, 12, 13, 14, 15]);
+    assert_eq!(reader.column_slice::<i8>(1).unwrap(), &[1, 2, 3, 4, 5, 6]);
+    assert_eq!(reader.date_range(), Some((10, 15)));
+    assert_eq!(reader.column_layout()[0].file_pos, pending.len() as u64);
+    assert_eq!(fs::metadata(file_path)?.len(), pending.len() as u64 + 6 * 4 + 6);
+
+    // The next segment chains on from the streamed one
+    append_segment(file_path, vec![16], vec![7])?;
+    assert_eq!(TSFEditor::new(file_path)?.segment_offsets()?.len(), 2);
+
+    Ok(())
+  }
+
+  fn stream_with_threshold(file_path: &str, flush_threshold: Option<usize>) -> io::Result<usize> {
+    let to_io = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    if let Some(flush_threshold) = flush_threshold {
+      writer = writer.with_flush_threshold(flush_threshold);
+    }
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true).map_err(to_io)?;
+    writer.add_column_header("temperature", EnumDataType::Int8, EnumDataEnc::None, EnumDataComp::None, false).map_err(to_io)?;
+    writer.begin_stream()?;
+    for batch in 0..50 {
+      let metric_time: Vec<i32> = (batch * 4..batch * 4 + 4).collect();
+      let temperature: Vec<i8> = metric_time.iter().map(|value: &i32| (*value % 100) as i8).collect();
+      writer.append_batch(&[EnumColumnData::Int32Vec(metric_time), EnumColumnData::Int8Vec(temperature)]).map_err(to_io)?;
+    }
+    writer.finish_stream()?;
+
+    Ok(writer.stream_flushes())
+  }
+
+  #[test]
+  fn test_flush_threshold_changes_write_count_not_output() -> io::Result<()> {
+    let buffered_file: NamedTempFile = NamedTempFile::new()?;
+    let small_file: NamedTempFile = NamedTempFile::new()?;
+
+    // 50 batches of 16 and 4 bytes fit the default buffers, so each spool is written once at finish
+    let buffered_flushes: usize = stream_with_threshold(buffered_file.path().to_str().unwrap(), None)?;
+    let small_flushes: usize = stream_with_threshold(small_file.path().to_str().unwrap(), Some(32))?;
+    assert_eq!(buffered_flushes, 2);
+    assert!(small_flushes > buffered_flushes * 10);
+
+    let mut buffered: TSFReader = TSFReader::new(buffered_file.path().to_str().unwrap())?;
+    buffered.read_all()?;
+    let mut small: TSFReader = TSFReader::new(small_file.path().to_str().unwrap())?;
+    small.read_all()?;
+    assert_eq!(buffered.column_slice::<i32>(0).unwrap(), small.column_slice::<i32>(0).unwrap());
+    assert_eq!(buffered.column_slice::<i8>(1).unwrap(), small.column_slice::<i8>(1).unwrap());
+    assert_eq!(buffered.column_slice::<i32>(0).unwrap().len(), 200);
+    assert_eq!(fs::metadata(buffered_file.path())?.len(), fs::metadata(small_file.path())?.len());
+
+    Ok(())
+  }
+
+  fn write_int32_column(file_path: &str, values: Vec<i32>, compression: EnumDataComp) -> io::Result<()> {
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, compression, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(values[0] as i64, values[values.len() - 1] as i64);
+    writer.add_column_data(values, EnumDataEnc::None, compression)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.try_save()
+  }
+
+  #[test]
+  fn test_zstd_column_is_smaller_and_reads_back_in_both_readers() -> io::Result<()> {
+    let values: Vec<i32> = (0..100_000).map(|index: i32| 1_700_000_000 + index / 10).collect();
+    let plain_file: NamedTempFile = NamedTempFile::new()?;
+    let zstd_file: NamedTempFile = NamedTempFile::new()?;
+    write_int32_column(plain_file.path().to_str().unwrap(), values.clone(), EnumDataComp::None)?;
+    write_int32_column(zstd_file.path().to_str().unwrap(), values.clone(), EnumDataComp::ZStd)?;
+    assert!(fs::metadata(zstd_file.path())?.len() * 10 < fs::metadata(plain_file.path())?.len());
+
+    // column_size is the compressed length, the column runs to the end of the file
+    let file_path: &str = zstd_file.path().to_str().unwrap();
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+    let column_size: u64 = reader.get_column_header(0).unwrap().column_size;
+    assert_eq!(reader.column_layout()[0].file_pos + column_size, fs::metadata(file_path)?.len());
+    assert!(column_size < values.len() as u64 * 4);
+    assert_eq!(reader.column_slice::<i32>(0).unwrap(), &values[..]);
+
+    let async_values: Vec<i32> = tokio::runtime::Runtime::new()?.block_on(async {
+      let mut reader: AsyncTSFReader = AsyncTSFReader::new(file_path).await?;
+      reader.read_all().await?;
+      Ok::<Vec<i32>, io::Error>(reader.column_slice::<i32>(0).unwrap().to_vec())
+    })?;
+    assert_eq!(async_values, values);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_add_float64_and_uint64_columns() -> io::Result<()> {
+    let temp_file: NamedTempFile = NamedTempFile::new()?;
+    let file_path: &str = temp_file.path().to_str().unwrap();
+    let prices: Vec<f64> = vec![1.0, 2.5, -0.125];
+    let counts: Vec<u64> = vec![0, 42, u64::MAX];
+
+    let mut writer: TSFWriter = TSFWriter::new(file_path)?;
+    writer.add_column_header("metric_time", EnumDataType::Int32, EnumDataEnc::None, EnumDataComp::None, true)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_header("price", EnumDataType::Float64, EnumDataEnc::None, EnumDataComp::None, false)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_header("count", EnumDataType::UInt64, EnumDataEnc::None, EnumDataComp::None, false)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(vec![1710555318i32, 1710555319, 1710555320], EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(prices.clone(), EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.add_column_data(counts.clone(), EnumDataEnc::None, EnumDataComp::None)
+      .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.update_segment_dates(1710555318, 1710555320);
+    writer.try_save()?;
+
+    let mut reader: TSFReader = TSFReader::new(file_path)?;
+    reader.read_all()?;
+    assert_eq!(reader.get_column_header(1).unwrap().column_type, EnumDataType::Float64);
+    assert_eq!(reader.get_column_header(2).unwrap().column_type, EnumDataType::UInt64);
+    assert_eq!(reader.column_slice::<f64>(1).unwrap(), &prices[..]);
+    assert_eq!(reader.column_slice::<u64>(2).unwrap(), &counts[..]);
+
+    Ok(())
+  }
 }
@@ -0,0 +1,45 @@
+use std::{fs::{self, OpenOptions}, io, path::{Path, PathBuf}};
+
+use tracing::trace;
+
+use super::error::TsfError;
+
+// Advisory single writer lock, a <file>.lock sidecar created exclusively and removed on drop.
+// Readers never look at it. A writer that dies without dropping leaves the sidecar behind, and it
+// has to be removed by hand before the file can be appended to again
+pub struct WriteLock {
+  lock_path: PathBuf,
+}
+
+impl WriteLock {
+  pub fn acquire(file_path: &Path) -> io::Result<Self> {
+    trace!("WriteLock::acquire");
+
+    let lock_path: PathBuf = Self::lock_path(file_path);
+    match OpenOptions::new().write(true).create_new(true).open(&lock_path) {
+      Ok(_) => Ok(WriteLock { lock_path }),
+      Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Err(io::Error::new(
+        io::ErrorKind::WouldBlock,
+        TsfError::AlreadyLocked { path: file_path.display().to_string() },
+      )),
+      Err(e) => Err(e),
+    }
+  }
+
+  // Whether another writer currently holds the file, i.e. appending now would fail
+  pub fn is_locked(file_path: &Path) -> bool {
+    Self::lock_path(file_path).exists()
+  }
+
+  fn lock_path(file_path: &Path) -> PathBuf {
+    let mut lock_path: std::ffi::OsString = file_path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    PathBuf::from(lock_path)
+  }
+}
+
+impl Drop for WriteLock {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.lock_path);
+  }
+}